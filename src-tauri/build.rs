@@ -1,3 +1,28 @@
 fn main() {
+    export_dependency_versions(&["tauri", "reqwest", "cpal"]);
+
     tauri_build::build()
 }
+
+/// Expose resolved dependency versions from Cargo.lock as `DEP_VERSION_<NAME>` env vars
+/// for `get_version_info`
+fn export_dependency_versions(names: &[&str]) {
+    println!("cargo:rerun-if-changed=Cargo.lock");
+
+    let lock = std::fs::read_to_string("Cargo.lock").unwrap_or_default();
+    let mut current: Option<&str> = None;
+
+    for line in lock.lines() {
+        if let Some(name) = line.strip_prefix("name = ") {
+            current = Some(name.trim_matches('"'));
+        } else if let (Some(version), Some(name)) = (line.strip_prefix("version = "), current.take()) {
+            if names.contains(&name) {
+                println!(
+                    "cargo:rustc-env=DEP_VERSION_{}={}",
+                    name.to_uppercase(),
+                    version.trim_matches('"')
+                );
+            }
+        }
+    }
+}
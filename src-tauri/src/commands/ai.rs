@@ -1,130 +1,1691 @@
 use anyhow::{Context, Result};
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER,
+};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::commands::code;
+use crate::commands::context::CaptureContext;
+use crate::commands::duplicates;
+use crate::commands::history;
+use crate::commands::image::{
+    base64_mime, downscale_base64, exceeds_image_limit, strip_metadata_base64,
+};
+use crate::commands::logs;
+use crate::commands::memory;
+use crate::commands::models;
+use crate::commands::ocr;
+use crate::commands::privacy;
+use crate::commands::prompt::{self, PromptFragment};
+use crate::commands::requests::{InFlightRequests, SlowResponseWatch, TaskKind};
+use crate::commands::retry;
+use crate::commands::screenshot::{capture_screenshot, LastScreenshot};
+use crate::commands::session::SessionStore;
+use crate::commands::tokens;
+use crate::commands::transfer::{Delivery, Transfers};
+use crate::commands::usage;
+use crate::config::ConfigState;
+use crate::error::GhostError;
+use crate::settings::SettingsStore;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Message {
     pub role: String,
     pub content: MessageContent,
+    /// Kept as context by `send_message`'s `pinned_only` option
+    /// Saved with the conversation but never sent to the API
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum MessageContent {
     Text(String),
     Parts(Vec<ContentPart>),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl MessageContent {
+    /// Text of the message; for multi-part content the text parts joined by newlines
+    pub fn text(&self) -> String {
+        match self {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|p| match p {
+                    ContentPart::Text { text, .. } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ContentPart {
-    Text { text: String },
+    Text {
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
     ImageUrl { image_url: ImageUrl },
+    /// Audio for models that accept it as input (e.g. `gpt-4o-audio-preview`)
+    InputAudio { input_audio: InputAudio },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ImageUrl {
     pub url: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputAudio {
+    /// Base64-encoded audio file, without a data URL prefix
+    pub data: String,
+    /// `wav` or `mp3`
+    pub format: String,
+}
+
+/// Explicit prompt-caching marker (Anthropic-style `cache_control`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheControl {
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+impl CacheControl {
+    pub fn ephemeral() -> Self {
+        Self {
+            kind: String::from("ephemeral"),
+        }
+    }
+}
+
+/// Per-request options for `send_message_impl`
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ChatOptions {
+    /// Mark the system prompt as cacheable for models with explicit cache markers
+    /// (`ModelCapabilities::cache_markers`); OpenAI caches long prefixes automatically,
+    /// so this is a no-op there
+    pub cache_prompt: bool,
+    /// Used to notify the frontend about adjustments made while sending
+    pub app: Option<AppHandle>,
+    /// Text prepended to the user message when images are attached
+    pub app_context: Option<String>,
+    /// Per-call or per-conversation model; `None` uses the configured default
+    pub model: Option<String>,
+    /// Frontend id for the request, used for cancellation and `response-slow`
+    pub request_id: Option<String>,
+    /// Return token log probabilities with this many alternatives per token (0-20)
+    pub logprobs: Option<u8>,
+    /// Labels recorded in the usage log; never sent to the API
+    pub tags: Vec<String>,
+    /// Let the model search the web and cite its sources
+    /// Ignored for models that can't (see `ModelCapabilities::web_search`)
+    pub web_search: bool,
+    /// Send images even if the privacy guard finds sensitive text in them
+    pub allow_sensitive: bool,
+    /// Reply length limit for this request, overriding `max_tokens` / `auto_max_tokens`
+    pub max_tokens: Option<u32>,
+    /// System-prompt fragments and wrappers left out of this request
+    pub disabled_fragments: Vec<PromptFragment>,
+    /// Reasoning effort for this request; an error on non-reasoning models
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// A chat turn the user started, as opposed to internal requests like titles and
+    /// summaries; only these get the composed system prompt (persona, response
+    /// language) and `message_prefix` / `message_suffix`
+    pub user_turn: bool,
+}
+
+impl ChatOptions {
+    /// Model the request is sent to: the per-call choice, else GHOSTGPT_MODEL, else
+    /// `DEFAULT_MODEL`
+    pub fn model(&self) -> Cow<'_, str> {
+        match &self.model {
+            Some(model) => Cow::Borrowed(model),
+            None => Cow::Owned(crate::config::default_model()),
+        }
+    }
+
+    /// `max_tokens` for a request with `messages`: the per-call override, else
+    /// GHOSTGPT_MAX_TOKENS, else the remaining context with `auto_max_tokens`, else the
+    /// `max_tokens` setting
+    fn max_tokens(&self, messages: &[Message]) -> u32 {
+        if let Some(max_tokens) = self.max_tokens.or_else(crate::config::default_max_tokens) {
+            return max_tokens;
+        }
+        let Some(app) = &self.app else {
+            return DEFAULT_MAX_TOKENS;
+        };
+
+        let settings = app.state::<SettingsStore>().get();
+        if settings.auto_max_tokens {
+            tokens::output_budget(&self.model(), messages)
+        } else {
+            settings.max_tokens
+        }
+    }
+
+    /// `reasoning_effort` to send: the per-call value, else the configured one, and
+    /// only for reasoning models
+    fn reasoning_effort(&self) -> Option<ReasoningEffort> {
+        if !models::capabilities(&self.model()).reasoning {
+            return None;
+        }
+        self.reasoning_effort.or_else(|| {
+            let app = self.app.as_ref()?;
+            app.state::<SettingsStore>().get().reasoning_effort
+        })
+    }
+
+    /// Web search was asked for and the model supports it
+    fn searches_web(&self) -> bool {
+        self.web_search && models::capabilities(&self.model()).web_search
+    }
+}
+
+/// Non-success HTTP status from the OpenAI API
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: reqwest::StatusCode,
+    pub body: String,
+    /// Wait requested by the `Retry-After` header (seconds form only)
+    pub retry_after: Option<Duration>,
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "OpenAI API returned error {}: {}", self.status, self.body)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// Error codes meaning the account is out of credit rather than rate-limited
+const QUOTA_ERROR_CODES: [&str; 2] = ["insufficient_quota", "billing_hard_limit_reached"];
+
+/// The account has run out of credit or hit its billing limit
+/// Returned instead of `ApiError` so it is never mistaken for a transient 429
+#[derive(Debug)]
+pub struct QuotaExceeded {
+    pub message: String,
+}
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "OpenAI quota exceeded, check your plan and billing details: {}",
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+/// Body of an OpenAI error response
+#[derive(Debug, Deserialize)]
+struct ApiErrorBody {
+    error: ApiErrorDetail,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiErrorDetail {
+    #[serde(default)]
+    message: String,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    code: Option<String>,
+}
+
+/// Error for a non-success response: `QuotaExceeded` for billing errors,
+/// otherwise `ApiError`
+pub(crate) fn api_error(status: reqwest::StatusCode, body: String) -> anyhow::Error {
+    classify_api_error(ApiError {
+        status,
+        body,
+        retry_after: None,
+    })
+}
+
+/// Like `api_error`, reading the status, body and `Retry-After` from `response`
+pub(crate) async fn response_error(response: reqwest::Response) -> anyhow::Error {
+    let status = response.status();
+    let retry_after = response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(Duration::from_secs);
+    let body = response.text().await.unwrap_or_default();
+
+    classify_api_error(ApiError {
+        status,
+        body,
+        retry_after,
+    })
+}
+
+fn classify_api_error(error: ApiError) -> anyhow::Error {
+    match error.quota_exceeded() {
+        Some(quota) => quota.into(),
+        None => error.into(),
+    }
+}
+
+/// Roles the chat completions API accepts
+pub(crate) const CHAT_ROLES: [&str; 4] = ["system", "user", "assistant", "tool"];
+
+/// A message has a role the API would reject with an opaque 400
+#[derive(Debug)]
+pub struct InvalidRole {
+    pub index: usize,
+    pub role: String,
+}
+
+impl std::fmt::Display for InvalidRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Message {} has invalid role {:?}, expected one of {}",
+            self.index,
+            self.role,
+            CHAT_ROLES.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for InvalidRole {}
+
+/// `role` without surrounding whitespace, lowercased, if that is one of `CHAT_ROLES`
+pub(crate) fn normalize_role(role: &str) -> Option<String> {
+    let role = role.trim().to_ascii_lowercase();
+    CHAT_ROLES.contains(&role.as_str()).then_some(role)
+}
+
+/// Normalize roles (surrounding whitespace, case) and reject anything else
+fn sanitize_roles(messages: &mut [Message]) -> Result<(), InvalidRole> {
+    for (index, message) in messages.iter_mut().enumerate() {
+        let Some(role) = normalize_role(&message.role) else {
+            return Err(InvalidRole {
+                index,
+                role: message.role.clone(),
+            });
+        };
+        message.role = role;
+    }
+    Ok(())
+}
+
+/// The model doesn't return log probabilities, or rejected a request for them
+#[derive(Debug)]
+pub struct LogprobsUnsupported {
+    pub model: String,
+}
+
+impl std::fmt::Display for LogprobsUnsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Model {} does not support logprobs", self.model)
+    }
+}
+
+impl std::error::Error for LogprobsUnsupported {}
+
+/// How long a reasoning model thinks before answering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+/// `reasoning_effort` was asked for on a model that isn't a reasoning model
+#[derive(Debug)]
+pub struct ReasoningUnsupported {
+    pub model: String,
+}
+
+impl std::fmt::Display for ReasoningUnsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Model {} does not support reasoning effort", self.model)
+    }
+}
+
+impl std::error::Error for ReasoningUnsupported {}
+
+impl ApiError {
+    /// Parsed `error` object, if the body is OpenAI's JSON error shape
+    fn detail(&self) -> Option<ApiErrorDetail> {
+        serde_json::from_str::<ApiErrorBody>(&self.body)
+            .ok()
+            .map(|b| b.error)
+    }
+
+    /// The billing error this response represents, if any
+    /// OpenAI reports these as 429 too, distinguished by `code` / `type`
+    fn quota_exceeded(&self) -> Option<QuotaExceeded> {
+        let detail = self.detail()?;
+        let is_quota = [&detail.code, &detail.kind]
+            .into_iter()
+            .flatten()
+            .any(|code| QUOTA_ERROR_CODES.contains(&code.as_str()));
+
+        is_quota.then_some(QuotaExceeded {
+            message: detail.message,
+        })
+    }
+
+    /// Whether the API rejected the request's `logprobs` parameters
+    pub fn is_logprobs_unsupported(&self) -> bool {
+        self.status == reqwest::StatusCode::BAD_REQUEST
+            && self.body.to_lowercase().contains("logprobs")
+    }
+
+    /// Whether an OpenAI-compatible server rejected `stream: true`
+    pub fn is_stream_unsupported(&self) -> bool {
+        let rejected = matches!(self.status.as_u16(), 400 | 404 | 405 | 422 | 501);
+        let body = self.body.to_lowercase();
+        rejected
+            && body.contains("stream")
+            && ["not supported", "unsupported", "not implemented", "not allowed"]
+                .iter()
+                .any(|phrase| body.contains(phrase))
+    }
+
+    /// Whether the API rejected an attached image for its size
+    /// Only this error class triggers the downscale-and-retry fallback
+    pub fn is_image_too_large(&self) -> bool {
+        if self.status != reqwest::StatusCode::BAD_REQUEST {
+            return false;
+        }
+        let body = self.body.to_lowercase();
+        body.contains("image")
+            && (body.contains("too large") || body.contains("too big") || body.contains("exceeds"))
+    }
+}
+
+/// Assistant reply plus token usage reported by the API
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatResponse {
+    pub content: String,
+    pub usage: Option<Usage>,
+    /// Per-token log probabilities, when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<Vec<TokenLogprob>>,
+    /// The stream was cancelled; `content` is the partial text received
+    pub cancelled: bool,
+    /// Sources cited in `content`, for rendering footnotes
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub citations: Vec<Citation>,
+    /// Id of the attached image, for asking about it again with `image_id`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_id: Option<String>,
+}
+
+/// A web source the answer cites
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Citation {
+    pub url: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Range of `content` (in characters) backed by this source
+    pub start_index: usize,
+    pub end_index: usize,
+}
+
+/// Annotation attached to assistant text; only URL citations are kept
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum Annotation {
+    /// Chat Completions nests the citation, the Responses API flattens it
+    UrlCitation {
+        #[serde(default)]
+        url_citation: Option<Citation>,
+        #[serde(flatten)]
+        flat: Option<Citation>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+impl Annotation {
+    pub(crate) fn into_citation(self) -> Option<Citation> {
+        match self {
+            Annotation::UrlCitation { url_citation, flat } => url_citation.or(flat),
+            Annotation::Other => None,
+        }
+    }
+}
+
+/// Log probability of one generated token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenLogprob {
+    pub token: String,
+    pub logprob: f64,
+    /// Most likely alternatives at this position
+    #[serde(default)]
+    pub top_logprobs: Vec<TopLogprob>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopLogprob {
+    pub token: String,
+    pub logprob: f64,
+}
+
+/// `logprobs` object of a choice (or streamed chunk)
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChoiceLogprobs {
+    #[serde(default)]
+    pub content: Option<Vec<TokenLogprob>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+    /// Prompt tokens served from the provider's prompt cache
+    pub cached_tokens: Option<u32>,
+}
+
 #[derive(Debug, Serialize)]
-struct OpenAIRequest {
+pub(crate) struct OpenAIRequest {
     model: String,
     messages: Vec<Message>,
-    max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    /// Reasoning models reject `max_tokens` and take this instead
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_completion_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logprobs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_logprobs: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    web_search_options: Option<WebSearchOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning_effort: Option<ReasoningEffort>,
+}
+
+/// Enables search on search-capable models; defaults are fine, so it's sent empty
+#[derive(Debug, Serialize)]
+struct WebSearchOptions {}
+
+#[derive(Debug, Serialize)]
+struct StreamOptions {
+    /// Ask for a final chunk carrying token usage
+    include_usage: bool,
 }
 
 #[derive(Debug, Deserialize)]
 struct OpenAIResponse {
     choices: Vec<Choice>,
+    usage: Option<ApiUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ApiUsage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    total_tokens: u32,
+    /// OpenAI reports automatic cache hits here
+    prompt_tokens_details: Option<PromptTokensDetails>,
+    /// Anthropic-compatible backends report explicit cache hits here
+    cache_read_input_tokens: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PromptTokensDetails {
+    cached_tokens: Option<u32>,
+}
+
+impl From<ApiUsage> for Usage {
+    fn from(usage: ApiUsage) -> Self {
+        let cached_tokens = usage
+            .prompt_tokens_details
+            .and_then(|d| d.cached_tokens)
+            .or(usage.cache_read_input_tokens);
+
+        Self {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: usage.total_tokens,
+            cached_tokens,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct Choice {
     message: ResponseMessage,
+    #[serde(default)]
+    logprobs: Option<ChoiceLogprobs>,
 }
 
 #[derive(Debug, Deserialize)]
 struct ResponseMessage {
-    content: String,
+    #[serde(default)]
+    content: Option<ResponseContent>,
+    #[serde(default)]
+    refusal: Option<String>,
+    #[serde(default)]
+    annotations: Vec<Annotation>,
+}
+
+/// Assistant content: a bare string, or typed blocks in newer API shapes
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ResponseContent {
+    Text(String),
+    Blocks(Vec<ResponseBlock>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ResponseBlock {
+    Text { text: String },
+    OutputText {
+        text: String,
+        #[serde(default)]
+        annotations: Vec<Annotation>,
+    },
+    Refusal { refusal: String },
+    #[serde(other)]
+    Other,
+}
+
+/// The model declined to answer
+#[derive(Debug)]
+pub struct ModelRefusal(pub String);
+
+impl std::fmt::Display for ModelRefusal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The model refused to respond: {}", self.0)
+    }
+}
+
+impl std::error::Error for ModelRefusal {}
+
+impl ResponseMessage {
+    /// Answer text, concatenating text blocks, and its citations
+    /// Refusals become `ModelRefusal`
+    fn into_text(self) -> Result<(String, Vec<Citation>)> {
+        if let Some(refusal) = self.refusal {
+            return Err(ModelRefusal(refusal).into());
+        }
+
+        let mut citations: Vec<Citation> = self
+            .annotations
+            .into_iter()
+            .filter_map(Annotation::into_citation)
+            .collect();
+
+        match self.content.context("Response contained no content")? {
+            ResponseContent::Text(text) => Ok((text, citations)),
+            ResponseContent::Blocks(blocks) => {
+                let mut text = String::new();
+                for block in blocks {
+                    match block {
+                        ResponseBlock::Text { text: t } => text.push_str(&t),
+                        ResponseBlock::OutputText { text: t, annotations } => {
+                            // Block citations index into the block; shift them onto the whole text
+                            let offset = text.chars().count();
+                            citations.extend(annotations.into_iter().filter_map(|a| {
+                                let mut citation = a.into_citation()?;
+                                citation.start_index += offset;
+                                citation.end_index += offset;
+                                Some(citation)
+                            }));
+                            text.push_str(&t);
+                        }
+                        ResponseBlock::Refusal { refusal } => {
+                            return Err(ModelRefusal(refusal).into())
+                        }
+                        ResponseBlock::Other => {}
+                    }
+                }
+                Ok((text, citations))
+            }
+        }
+    }
+}
+
+/// Arguments of `send_message` and `send_message_stream`, passed as one `request` object
+/// Everything but `messages` is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SendMessageRequest {
+    /// Conversation history
+    pub messages: Vec<Message>,
+    /// Base64-encoded screenshot attached to the latest user message
+    pub screenshot_base64: Option<String>,
+    /// Attach the image sent earlier under this id (returned as the response's
+    /// `image_id`) instead of sending `screenshot_base64` again
+    pub image_id: Option<String>,
+    /// Mark the system prompt as cacheable where the backend supports it
+    pub cache_prompt: bool,
+    /// Model for this call only, overriding the default
+    pub model: Option<String>,
+    /// Reply length limit for this request only
+    pub max_tokens: Option<u32>,
+    /// Id for `cancel_request` and the `response-slow` event
+    pub request_id: Option<String>,
+    /// Return token log probabilities with this many alternatives (0-20)
+    pub logprobs: Option<u8>,
+    /// Labels recorded in the usage log (not sent to the API)
+    pub tags: Vec<String>,
+    /// Let search-capable models look things up; the reply then carries `citations`
+    /// A no-op for other models
+    pub web_search: bool,
+    /// Send the screenshot even though the privacy guard flagged it (after the user
+    /// confirmed a `sensitive-content-warning`)
+    pub allow_sensitive: bool,
+    /// Parts of the composed system prompt (persona, language, ...) or the message
+    /// prefix/suffix to leave out of this request
+    pub disabled_fragments: Vec<PromptFragment>,
+    /// `low`, `medium` or `high` for reasoning (o-series) models, overriding the
+    /// setting; rejected for other models
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// Send the screenshot's text (recognized locally) instead of the image when the
+    /// screen is mostly text; falls back to the image if recognition is unsure
+    /// `send_message` only
+    pub prefer_ocr: bool,
+    /// Few-shot `[user, assistant]` pairs inserted after the system prompt; sent with
+    /// this request only, never part of the returned or saved history
+    /// `send_message` only
+    pub examples: Vec<(String, String)>,
+    /// Send only the system prompt, the messages marked `pinned` and the latest turn
+    /// `send_message` only
+    pub pinned_only: bool,
+    /// Send even if the latest user message repeats a recent one; otherwise that fails
+    /// with a `duplicate-message` event the UI can confirm. `send_message` only
+    pub allow_duplicate: bool,
+}
+
+impl SendMessageRequest {
+    /// The image to attach, from `screenshot_base64` or `image_id`, and its id
+    pub(crate) fn take_image(
+        &mut self,
+        sessions: &SessionStore,
+    ) -> Result<Option<(String, String)>, String> {
+        sessions.attach_image(self.screenshot_base64.take(), self.image_id.take())
+    }
+
+    /// `ChatOptions` for this user turn
+    pub(crate) fn chat_options(
+        &self,
+        app: AppHandle,
+        app_context: Option<String>,
+    ) -> Result<ChatOptions, String> {
+        let (model, max_tokens) = request_model(self.model.clone(), self.max_tokens)?;
+        Ok(ChatOptions {
+            cache_prompt: self.cache_prompt,
+            app: Some(app),
+            app_context,
+            model,
+            request_id: self.request_id.clone(),
+            logprobs: self.logprobs.map(|n| n.min(MAX_TOP_LOGPROBS)),
+            tags: usage::normalize_tags(self.tags.clone()),
+            web_search: self.web_search,
+            allow_sensitive: self.allow_sensitive,
+            max_tokens,
+            disabled_fragments: self.disabled_fragments.clone(),
+            reasoning_effort: self.reasoning_effort,
+            user_turn: true,
+        })
+    }
 }
 
 /// Send message to OpenAI API with optional screenshot
-///
-/// # Arguments
-/// * `api_key` - OpenAI API key from state
-/// * `messages` - Conversation history
-/// * `screenshot_base64` - Optional base64-encoded screenshot
+/// See `SendMessageRequest` for the options
 ///
 /// # Returns
-/// Assistant's response text
+/// Assistant's response text and token usage, as a `Delivery`: replies too large
+/// for one IPC message are read with `read_transfer_chunk`
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn send_message(
-    api_key: tauri::State<'_, String>,
-    messages: Vec<Message>,
-    screenshot_base64: Option<String>,
-) -> Result<String, String> {
-    send_message_impl(&api_key, messages, screenshot_base64)
+    app: AppHandle,
+    config: tauri::State<'_, ConfigState>,
+    settings: tauri::State<'_, SettingsStore>,
+    capture_context: tauri::State<'_, CaptureContext>,
+    requests: tauri::State<'_, InFlightRequests>,
+    sessions: tauri::State<'_, SessionStore>,
+    transfers: tauri::State<'_, Transfers>,
+    mut request: SendMessageRequest,
+) -> Result<Delivery, GhostError> {
+    config.touch();
+
+    // A screenshot makes a repeated question a new one about what's on screen
+    let attached = request.screenshot_base64.is_some() || request.image_id.is_some();
+    let duplicate_check = settings.get().duplicate_check;
+    if duplicate_check.enabled && !request.allow_duplicate && !attached {
+        if let Some(duplicate) = duplicates::find_duplicate(&request.messages, &duplicate_check) {
+            app.emit("duplicate-message", &duplicate)
+                .map_err(|e| GhostError::Other(format!("Failed to emit event: {}", e)))?;
+            return Err(GhostError::DuplicateMessage(duplicate.to_string()));
+        }
+    }
+
+    let mut messages = std::mem::take(&mut request.messages);
+    if request.pinned_only {
+        messages = memory::context_from_pinned(messages);
+    }
+
+    let attached = request
+        .take_image(&sessions)
+        .map_err(GhostError::InvalidRequest)?;
+    let image_id = attached.as_ref().map(|(id, _)| id.clone());
+    let mut screenshot_base64 = attached.map(|(_, image)| image);
+
+    let app_context = screenshot_base64
+        .as_ref()
+        .and_then(|_| capture_context.get())
+        .map(|c| c.describe());
+    let options = request
+        .chat_options(app, app_context)
+        .map_err(GhostError::InvalidRequest)?;
+
+    if let Some(screenshot) = screenshot_base64.as_deref().filter(|_| request.prefer_ocr) {
+        let context = options.app_context.as_deref();
+        if ocr::prefer_text(&mut messages, screenshot, &settings.get().ocr, context).await {
+            screenshot_base64 = None;
+        }
+    }
+
+    insert_examples(&mut messages, request.examples);
+
+    let api_key = config.get().openai_api_key;
+    let images = screenshot_base64.into_iter().collect();
+    let mut response = requests
+        .run(
+            request.request_id,
+            TaskKind::Chat,
+            send_message_impl(&api_key, messages, images, &options),
+        )
+        .await
+        .map_err(|e| {
+            GhostError::classify(&e, format!("OpenAI API error: {}", e), GhostError::Api)
+        })?;
+
+    if let Some(app) = &options.app {
+        code::enrich_reply(app, &mut response.content);
+        code::auto_copy(app, &response.content);
+    }
+    response.image_id = image_id;
+    transfers
+        .deliver(&response)
+        .map_err(|e| GhostError::Other(format!("Failed to serialize response: {}", e)))
+}
+
+/// Result of `edit_and_resend`
+#[derive(Debug, Serialize)]
+pub struct ResendResult {
+    /// The edited message followed by the regenerated reply; replaces everything from `index` on
+    pub tail: Vec<Message>,
+    pub response: ChatResponse,
+}
+
+/// Replace the user message at `index`, drop everything after it and regenerate the reply
+/// Images and audio attached to the original message are kept unless `new_content`
+/// has attachments of its own
+/// The `ResendResult` comes back as a `Delivery`, chunked when very long
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn edit_and_resend(
+    app: AppHandle,
+    config: tauri::State<'_, ConfigState>,
+    requests: tauri::State<'_, InFlightRequests>,
+    transfers: tauri::State<'_, Transfers>,
+    mut messages: Vec<Message>,
+    index: usize,
+    new_content: MessageContent,
+    model: Option<String>,
+    request_id: Option<String>,
+) -> Result<Delivery, String> {
+    config.touch();
+
+    let target = messages.get(index).ok_or_else(|| {
+        format!("Message index {} out of range ({} messages)", index, messages.len())
+    })?;
+    if normalize_role(&target.role).as_deref() != Some("user") {
+        return Err(format!(
+            "Message {} is a {} message, only user messages can be edited",
+            index, target.role
+        ));
+    }
+
+    let edited = Message {
+        role: String::from("user"),
+        content: replace_content(&target.content, new_content),
+        pinned: false,
+    };
+    history::validate_message(&edited).map_err(|e| format!("Invalid message: {}", e))?;
+
+    messages.truncate(index);
+    messages.push(edited.clone());
+
+    let options = ChatOptions {
+        app: Some(app),
+        model: model.filter(|m| !m.trim().is_empty()),
+        request_id: request_id.clone(),
+        user_turn: true,
+        ..ChatOptions::default()
+    };
+
+    let api_key = config.get().openai_api_key;
+    let response = requests
+        .run(
+            request_id,
+            TaskKind::Chat,
+            send_message_impl(&api_key, messages, Vec::new(), &options),
+        )
+        .await
+        .map_err(|e| format!("OpenAI API error: {}", e))?;
+
+    let reply = Message {
+        role: String::from("assistant"),
+        content: MessageContent::Text(response.content.clone()),
+        pinned: false,
+    };
+    let result = ResendResult {
+        tail: vec![edited, reply],
+        response,
+    };
+    transfers
+        .deliver(&result)
+        .map_err(|e| format!("Failed to serialize response: {}", e))
+}
+
+/// New content for an edited message, carrying over the original's attachments
+/// when the edit is text only
+fn replace_content(original: &MessageContent, new_content: MessageContent) -> MessageContent {
+    let is_attachment = |part: &ContentPart| !matches!(part, ContentPart::Text { .. });
+
+    if let MessageContent::Parts(parts) = &new_content {
+        if parts.iter().any(is_attachment) {
+            return new_content;
+        }
+    }
+
+    let attachments: Vec<ContentPart> = match original {
+        MessageContent::Parts(parts) => {
+            parts.iter().filter(|p| is_attachment(p)).cloned().collect()
+        }
+        MessageContent::Text(_) => Vec::new(),
+    };
+    if attachments.is_empty() {
+        return new_content;
+    }
+
+    let mut parts = vec![ContentPart::Text {
+        text: new_content.text(),
+        cache_control: None,
+    }];
+    parts.extend(attachments);
+    MessageContent::Parts(parts)
+}
+
+/// Insert few-shot example turns between the leading system prompts and the conversation
+fn insert_examples(messages: &mut Vec<Message>, examples: Vec<(String, String)>) {
+    let at = messages.iter().take_while(|m| m.role == "system").count();
+    let turns = examples.into_iter().flat_map(|(user, assistant)| {
+        [
+            Message {
+                role: String::from("user"),
+                content: MessageContent::Text(user),
+                pinned: false,
+            },
+            Message {
+                role: String::from("assistant"),
+                content: MessageContent::Text(assistant),
+                pinned: false,
+            },
+        ]
+    });
+    messages.splice(at..at, turns);
+}
+
+/// One-shot question with an ephemeral system prompt
+/// Builds a fresh single-turn conversation; stored history is neither read nor changed
+///
+/// # Arguments
+/// * `prompt` - The question
+/// * `system` - System prompt used for this request only
+/// * `images` - Optional base64-encoded images attached to the prompt
+#[tauri::command]
+pub async fn quick_ask(
+    app: AppHandle,
+    config: tauri::State<'_, ConfigState>,
+    prompt: String,
+    system: Option<String>,
+    images: Option<Vec<String>>,
+) -> Result<ChatResponse, String> {
+    config.touch();
+
+    if prompt.trim().is_empty() {
+        return Err("Prompt is empty".to_string());
+    }
+
+    let mut messages = Vec::new();
+    if let Some(system) = system.filter(|s| !s.trim().is_empty()) {
+        messages.push(Message {
+            role: String::from("system"),
+            content: MessageContent::Text(system),
+            pinned: false,
+        });
+    }
+    messages.push(Message {
+        role: String::from("user"),
+        content: MessageContent::Text(prompt),
+        pinned: false,
+    });
+
+    let options = ChatOptions {
+        app: Some(app),
+        user_turn: true,
+        ..ChatOptions::default()
+    };
+
+    let api_key = config.get().openai_api_key;
+    send_message_impl(&api_key, messages, images.unwrap_or_default(), &options)
+        .await
+        .map_err(|e| format!("OpenAI API error: {}", e))
+}
+
+/// Prompt sent by `summarize_screen`
+const SUMMARIZE_SCREEN_PROMPT: &str = "Summarize what is shown on my screen in a few sentences. \
+Then list any action items, questions or deadlines it contains, or say there are none.";
+
+/// Time for the window server to remove the hidden overlay before capturing
+const HIDE_SETTLE: std::time::Duration = std::time::Duration::from_millis(150);
+
+/// One-click "what's on my screen": capture with the overlay hidden and summarize it
+/// With `prefer_ocr`, mostly-text screens are sent as locally recognized text to
+/// save tokens, falling back to the image when recognition is unsure
+/// `allow_sensitive` confirms sending past a `sensitive-content-warning`
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn summarize_screen(
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    config: tauri::State<'_, ConfigState>,
+    settings: tauri::State<'_, SettingsStore>,
+    capture_context: tauri::State<'_, CaptureContext>,
+    last: tauri::State<'_, LastScreenshot>,
+    prefer_ocr: Option<bool>,
+    model: Option<String>,
+    allow_sensitive: Option<bool>,
+) -> Result<ChatResponse, String> {
+    config.touch();
+
+    let was_visible = window.is_visible().unwrap_or(false);
+    if was_visible {
+        let _ = window.hide();
+        tokio::time::sleep(HIDE_SETTLE).await;
+    }
+    let captured = capture_screenshot(last, settings.clone(), capture_context.clone(), None).await;
+    if was_visible {
+        let _ = window.show();
+    }
+    let screenshot = captured.map_err(|e| e.to_string())?;
+
+    let mut messages = vec![Message {
+        role: String::from("user"),
+        content: MessageContent::Text(SUMMARIZE_SCREEN_PROMPT.to_string()),
+        pinned: false,
+    }];
+    let options = ChatOptions {
+        app: Some(app),
+        app_context: capture_context.get().map(|c| c.describe()),
+        model: model.filter(|m| !m.trim().is_empty()),
+        allow_sensitive: allow_sensitive.unwrap_or(false),
+        user_turn: true,
+        ..ChatOptions::default()
+    };
+
+    let mut images = vec![screenshot];
+    if prefer_ocr == Some(true) {
+        let context = options.app_context.as_deref();
+        if ocr::prefer_text(&mut messages, &images[0], &settings.get().ocr, context).await {
+            images.clear();
+        }
+    }
+
+    let api_key = config.get().openai_api_key;
+    send_message_impl(&api_key, messages, images, &options)
         .await
         .map_err(|e| format!("OpenAI API error: {}", e))
 }
 
-async fn send_message_impl(
+/// Largest `top_logprobs` the API accepts
+pub(crate) const MAX_TOP_LOGPROBS: u8 = 20;
+
+/// Chat model for requests that name none, unless GHOSTGPT_MODEL sets another
+pub(crate) const DEFAULT_MODEL: &str = "gpt-4o";
+
+/// Reply length limit when none is configured
+pub(crate) const DEFAULT_MAX_TOKENS: u32 = 1000;
+
+/// Per-call `model` and `max_tokens` of a chat command; `ChatOptions` fills in the
+/// configured defaults for what the call leaves out
+/// An empty model name counts as none; a zero `max_tokens` is rejected
+pub(crate) fn request_model(
+    model: Option<String>,
+    max_tokens: Option<u32>,
+) -> Result<(Option<String>, Option<u32>), String> {
+    if max_tokens == Some(0) {
+        return Err("max_tokens must be greater than 0".to_string());
+    }
+    Ok((model.filter(|m| !m.trim().is_empty()), max_tokens))
+}
+
+/// Default model for `send_audio_message`
+pub(crate) const AUDIO_MODEL: &str = "gpt-4o-audio-preview";
+
+/// Whether a model accepts `input_audio` content parts
+pub(crate) fn supports_audio_input(model: &str) -> bool {
+    models::capabilities(model).audio_input
+}
+
+pub(crate) fn chat_completions_url() -> String {
+    crate::config::api_url("chat/completions")
+}
+
+/// Cheap authenticated endpoint, used to open connections and check the key
+pub(crate) fn models_url() -> String {
+    crate::config::api_url("models")
+}
+
+/// Client shared by all API calls, so requests reuse pooled (already TLS-handshaken)
+/// connections instead of setting up a new one each time; clones share the same pool
+pub(crate) fn http_client() -> reqwest::Client {
+    static CLIENT: std::sync::OnceLock<reqwest::Client> = std::sync::OnceLock::new();
+    CLIENT.get_or_init(reqwest::Client::new).clone()
+}
+
+/// Headers from the `extra_headers` setting, set by `set_extra_headers`
+fn extra_header_map() -> &'static std::sync::RwLock<HeaderMap> {
+    static HEADERS: std::sync::OnceLock<std::sync::RwLock<HeaderMap>> =
+        std::sync::OnceLock::new();
+    HEADERS.get_or_init(|| std::sync::RwLock::new(HeaderMap::new()))
+}
+
+/// Headers to add to a request for an `api_url` endpoint (e.g. for Helicone or
+/// LangSmith proxies); never sent to Deepgram, Google or whisper.cpp
+pub(crate) fn extra_headers() -> HeaderMap {
+    extra_header_map().read().unwrap().clone()
+}
+
+/// Replace the headers `extra_headers` returns
+/// Fails without changing anything if a header is invalid or is one every request
+/// sets itself (`Authorization`, `Content-Type`)
+pub(crate) fn set_extra_headers(headers: &BTreeMap<String, String>) -> Result<()> {
+    let headers = parse_extra_headers(headers)?;
+    *extra_header_map().write().unwrap() = headers;
+    Ok(())
+}
+
+/// Check `headers` as `set_extra_headers` would, without applying them
+pub(crate) fn validate_extra_headers(headers: &BTreeMap<String, String>) -> Result<()> {
+    parse_extra_headers(headers).map(|_| ())
+}
+
+fn parse_extra_headers(headers: &BTreeMap<String, String>) -> Result<HeaderMap> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        let header_name = HeaderName::from_bytes(name.trim().as_bytes())
+            .with_context(|| format!("Invalid header name {:?}", name))?;
+        if header_name == AUTHORIZATION {
+            anyhow::bail!("Authorization can't be overridden; set the API key in .env instead");
+        }
+        if header_name == CONTENT_TYPE {
+            anyhow::bail!("Content-Type is set by each request and can't be overridden");
+        }
+        let header_value = HeaderValue::from_str(value.trim())
+            .with_context(|| format!("Invalid value for header {}", header_name))?;
+        map.insert(header_name, header_value);
+    }
+    Ok(map)
+}
+
+/// Open a connection to the API in the background so the first real request
+/// skips connection and TLS setup
+/// Best-effort: returns immediately and failures are only logged
+#[tauri::command]
+pub fn warmup(config: tauri::State<'_, ConfigState>) {
+    let api_key = config.get().openai_api_key;
+    tauri::async_runtime::spawn(async move {
+        // Any response (even 401 when locked) leaves the connection in the pool
+        let request = http_client().get(models_url()).headers(extra_headers());
+        let result = request.bearer_auth(api_key).send().await;
+        if let Err(e) = result {
+            eprintln!("Warning: API warmup failed: {}", e);
+        }
+    });
+}
+
+/// Downscale steps (scale factor, JPEG quality) tried when an image is rejected as too large,
+/// or is over `MAX_IMAGE_BYTES` to begin with
+/// Factors are relative to the original image, not the previous step
+const IMAGE_DOWNSCALE_STEPS: [(f32, u8); 2] = [(0.5, 85), (0.25, 70)];
+
+/// Payload of the `chat-retry` event
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatRetry {
+    pub model: String,
+    /// 1-based retry number
+    pub attempt: u32,
+    pub delay_ms: u64,
+    pub error: String,
+}
+
+/// Send a conversation to OpenAI, attaching `images` (base64 PNG) to the last user message
+pub(crate) async fn send_message_impl(
     api_key: &str,
+    messages: Vec<Message>,
+    images: Vec<String>,
+    options: &ChatOptions,
+) -> Result<ChatResponse> {
+    let _watch = SlowResponseWatch::start(options.app.as_ref(), options.request_id.clone());
+    let response = send_chat(api_key, messages, images, options, false).await?;
+
+    let openai_response: OpenAIResponse = response
+        .json()
+        .await
+        .context("Failed to parse OpenAI response")?;
+    let chat_response = openai_response.into_chat_response()?;
+
+    usage::record_chat(
+        options.app.as_ref(),
+        &options.model(),
+        chat_response.usage.as_ref(),
+        &options.tags,
+    );
+
+    Ok(chat_response)
+}
+
+/// Parse a chat completion body received other than from `post_chat` (e.g. batch output)
+pub(crate) fn parse_chat_response(body: serde_json::Value) -> Result<ChatResponse> {
+    serde_json::from_value::<OpenAIResponse>(body)
+        .context("Failed to parse OpenAI response")?
+        .into_chat_response()
+}
+
+impl OpenAIResponse {
+    /// The first choice's answer, with usage and log probabilities
+    fn into_chat_response(self) -> Result<ChatResponse> {
+        let choice = self
+            .choices
+            .into_iter()
+            .next()
+            .context("No response from OpenAI")?;
+        let logprobs = choice.logprobs.and_then(|l| l.content);
+        let (content, citations) = choice.message.into_text()?;
+
+        Ok(ChatResponse {
+            content,
+            usage: self.usage.map(Usage::from),
+            logprobs,
+            cancelled: false,
+            citations,
+            image_id: None,
+        })
+    }
+}
+
+/// Build and send a chat request, failing with `InvalidRole` before any network call
+/// 429 and 5xx responses are retried per the `chat_retry` setting, emitting `chat-retry`
+/// If the API rejects the images as too large they are re-encoded smaller and the
+/// request retried, emitting `image-downscaled` each time
+pub(crate) async fn send_chat(
+    api_key: &str,
+    mut messages: Vec<Message>,
+    mut images: Vec<String>,
+    options: &ChatOptions,
+    stream: bool,
+) -> Result<reqwest::Response> {
+    // Checked before anything else so the index still matches the caller's list
+    sanitize_roles(&mut messages)?;
+    if options.reasoning_effort.is_some() && !models::capabilities(&options.model()).reasoning {
+        return Err(ReasoningUnsupported {
+            model: options.model().to_string(),
+        }
+        .into());
+    }
+    if options.logprobs.is_some() && !models::capabilities(&options.model()).logprobs {
+        return Err(LogprobsUnsupported {
+            model: options.model().to_string(),
+        }
+        .into());
+    }
+
+    let settings = options.app.as_ref().map(|app| app.state::<SettingsStore>().get());
+    let strip_metadata = match &settings {
+        Some(settings) => settings.strip_image_metadata,
+        None => true,
+    };
+    let policy = settings.as_ref().map(|s| s.chat_retry).unwrap_or_default();
+    if let Some(settings) = settings.as_ref().filter(|_| options.user_turn) {
+        prompt::compose(&mut messages, settings, &options.disabled_fragments);
+    }
+    if let Some((app, settings)) = options.app.as_ref().zip(settings.as_ref()) {
+        if settings.privacy_guard.enabled && !options.allow_sensitive {
+            privacy::check_images(app, &settings.privacy_guard, &messages, &images).await?;
+        }
+    }
+    if strip_metadata {
+        images = images
+            .iter()
+            .map(|img| strip_metadata_base64(img))
+            .collect::<Result<_>>()
+            .context("Failed to strip image metadata")?;
+    }
+
+    // Each step scales the images as sent first, so its factor is the one reported
+    let original_images = images.clone();
+    let mut steps = IMAGE_DOWNSCALE_STEPS.iter();
+
+    // Sending an image over the limit would only get it rejected
+    if images.iter().any(|img| exceeds_image_limit(img)) {
+        if let Some(&(factor, quality)) = steps.next() {
+            images = downscale_images(&original_images, factor, quality, options)?;
+        }
+    }
+
+    loop {
+        let request = build_request(messages.clone(), images.clone(), options, stream);
+        let app = options.app.as_ref();
+        let on_retry = |attempt, delay: Duration, error: &anyhow::Error| {
+            eprintln!("Chat request failed ({}), retrying in {:?}", error, delay);
+            if let Some(app) = app {
+                let retrying = ChatRetry {
+                    model: options.model().to_string(),
+                    attempt,
+                    delay_ms: delay.as_millis() as u64,
+                    error: error.to_string(),
+                };
+                let _ = app.emit("chat-retry", retrying);
+            }
+        };
+        let result = retry::with_retry(&policy, on_retry, || async {
+            let started = std::time::Instant::now();
+            let result = post_chat(api_key, &request).await;
+            let elapsed = started.elapsed();
+            logs::log_request(app, "chat", &options.model(), elapsed, result.as_ref().err());
+            result
+        })
+        .await;
+        let error = match result {
+            Ok(response) => return Ok(response),
+            Err(e) => e,
+        };
+
+        let logprobs_rejected = options.logprobs.is_some()
+            && error
+                .downcast_ref::<ApiError>()
+                .is_some_and(ApiError::is_logprobs_unsupported);
+        if logprobs_rejected {
+            return Err(LogprobsUnsupported {
+                model: options.model().to_string(),
+            }
+            .into());
+        }
+
+        let too_large = !images.is_empty()
+            && error
+                .downcast_ref::<ApiError>()
+                .is_some_and(ApiError::is_image_too_large);
+        let Some(&(factor, quality)) = steps.next().filter(|_| too_large) else {
+            return Err(error);
+        };
+
+        images = downscale_images(&original_images, factor, quality, options)?;
+    }
+}
+
+/// `images` scaled by `factor` and re-encoded, emitting `image-downscaled`
+fn downscale_images(
+    images: &[String],
+    factor: f32,
+    quality: u8,
+    options: &ChatOptions,
+) -> Result<Vec<String>> {
+    let downscaled = images
+        .iter()
+        .map(|img| downscale_base64(img, factor, quality))
+        .collect::<Result<_>>()
+        .context("Failed to downscale image")?;
+
+    println!("Image too large, sending at {}x scale", factor);
+    if let Some(app) = &options.app {
+        let _ = app.emit("image-downscaled", factor);
+    }
+    Ok(downscaled)
+}
+
+/// Build the chat completion request body
+pub(crate) fn build_request(
     mut messages: Vec<Message>,
-    screenshot_base64: Option<String>,
-) -> Result<String> {
-    // If screenshot provided, append to last user message
-    if let Some(base64_data) = screenshot_base64 {
+    images: Vec<String>,
+    options: &ChatOptions,
+    stream: bool,
+) -> OpenAIRequest {
+    // Pins are app state; the API rejects fields it doesn't know
+    for message in &mut messages {
+        message.pinned = false;
+    }
+
+    // If images provided, append them to last user message
+    if !images.is_empty() {
         if let Some(last_msg) = messages.last_mut() {
             if last_msg.role == "user" {
-                // Convert to multi-part message with text + image
+                // Convert to multi-part message with text + images
                 let text = match &last_msg.content {
                     MessageContent::Text(t) => t.clone(),
                     MessageContent::Parts(_) => String::from("Analyze this screenshot"),
                 };
 
-                last_msg.content = MessageContent::Parts(vec![
-                    ContentPart::Text { text },
-                    ContentPart::ImageUrl {
-                        image_url: ImageUrl {
-                            url: format!("data:image/png;base64,{}", base64_data),
-                        },
+                let mut parts = Vec::new();
+                if let Some(context) = &options.app_context {
+                    parts.push(ContentPart::Text {
+                        text: context.clone(),
+                        cache_control: None,
+                    });
+                }
+                parts.push(ContentPart::Text {
+                    text,
+                    cache_control: None,
+                });
+                parts.extend(images.into_iter().map(|base64_data| ContentPart::ImageUrl {
+                    image_url: ImageUrl {
+                        url: format!("data:{};base64,{}", base64_mime(&base64_data), base64_data),
                     },
-                ]);
+                }));
+
+                last_msg.content = MessageContent::Parts(parts);
             }
         }
     }
 
-    let request = OpenAIRequest {
-        model: String::from("gpt-4o"),
+    if options.cache_prompt && models::capabilities(&options.model()).cache_markers {
+        mark_system_cacheable(&mut messages);
+    }
+
+    let max_tokens = options.max_tokens(&messages);
+    let reasoning = models::capabilities(&options.model()).reasoning;
+    OpenAIRequest {
+        model: options.model().to_string(),
+        max_tokens: (!reasoning).then_some(max_tokens),
+        max_completion_tokens: reasoning.then_some(max_tokens),
         messages,
-        max_tokens: 1000,
-    };
+        stream,
+        stream_options: stream.then_some(StreamOptions {
+            include_usage: true,
+        }),
+        logprobs: options.logprobs.map(|_| true),
+        top_logprobs: options.logprobs.filter(|n| *n > 0),
+        web_search_options: options.searches_web().then_some(WebSearchOptions {}),
+        reasoning_effort: options.reasoning_effort(),
+    }
+}
 
-    let client = reqwest::Client::new();
+/// POST a chat completion request, turning non-success statuses into errors
+pub(crate) async fn post_chat(api_key: &str, request: &OpenAIRequest) -> Result<reqwest::Response> {
+    let api_key = crate::config::require_key(api_key)?;
+    let client = http_client();
     let response = client
-        .post("https://api.openai.com/v1/chat/completions")
+        .post(chat_completions_url())
+        .headers(extra_headers())
         .header("Authorization", format!("Bearer {}", api_key))
         .header("Content-Type", "application/json")
-        .json(&request)
+        .json(request)
         .send()
         .await
         .context("Failed to send request to OpenAI")?;
 
     if !response.status().is_success() {
-        let status = response.status();
-        let error_text = response.text().await.unwrap_or_default();
-        anyhow::bail!("OpenAI API returned error {}: {}", status, error_text);
+        return Err(response_error(response).await);
     }
 
-    let openai_response: OpenAIResponse = response
-        .json()
-        .await
-        .context("Failed to parse OpenAI response")?;
+    Ok(response)
+}
+
+/// Attach a cache marker to the end of the system prompt so the whole prefix is cached
+fn mark_system_cacheable(messages: &mut [Message]) {
+    let Some(system) = messages.iter_mut().rev().find(|m| m.role == "system") else {
+        return;
+    };
 
-    openai_response
-        .choices
-        .first()
-        .map(|c| c.message.content.clone())
-        .context("No response from OpenAI")
+    match &mut system.content {
+        MessageContent::Text(text) => {
+            system.content = MessageContent::Parts(vec![ContentPart::Text {
+                text: std::mem::take(text),
+                cache_control: Some(CacheControl::ephemeral()),
+            }]);
+        }
+        MessageContent::Parts(parts) => {
+            if let Some(ContentPart::Text { cache_control, .. }) = parts
+                .iter_mut()
+                .rev()
+                .find(|p| matches!(p, ContentPart::Text { .. }))
+            {
+                *cache_control = Some(CacheControl::ephemeral());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(json: &str) -> ResponseMessage {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn string_content_is_returned_as_is() {
+        let (text, citations) = message(r#"{"content": "Hello"}"#).into_text().unwrap();
+        assert_eq!(text, "Hello");
+        assert!(citations.is_empty());
+    }
+
+    #[test]
+    fn text_blocks_are_concatenated() {
+        let json = r#"{"content": [
+            {"type": "text", "text": "Hello, "},
+            {"type": "image", "url": "ignored"},
+            {"type": "output_text", "text": "world"}
+        ]}"#;
+        let (text, _) = message(json).into_text().unwrap();
+        assert_eq!(text, "Hello, world");
+    }
+
+    #[test]
+    fn refusals_are_typed_errors() {
+        let block = r#"{"content": [{"type": "refusal", "refusal": "No"}]}"#;
+        let field = r#"{"content": null, "refusal": "No"}"#;
+        for json in [block, field] {
+            let error = message(json).into_text().unwrap_err();
+            assert_eq!(error.downcast_ref::<ModelRefusal>().unwrap().0, "No");
+        }
+    }
+
+    #[test]
+    fn logprobs_are_parsed_from_the_choice() {
+        let body = serde_json::json!({
+            "choices": [{
+                "message": {"content": "Hi"},
+                "logprobs": {"content": [{
+                    "token": "Hi",
+                    "logprob": -0.25,
+                    "top_logprobs": [
+                        {"token": "Hi", "logprob": -0.25},
+                        {"token": "Hello", "logprob": -1.5}
+                    ]
+                }]}
+            }]
+        });
+        let response = parse_chat_response(body).unwrap();
+        let logprobs = response.logprobs.unwrap();
+        assert_eq!(logprobs.len(), 1);
+        assert_eq!(logprobs[0].token, "Hi");
+        assert_eq!(logprobs[0].logprob, -0.25);
+        assert_eq!(logprobs[0].top_logprobs[1].token, "Hello");
+    }
+
+    #[test]
+    fn logprobs_are_absent_unless_returned() {
+        let body = serde_json::json!({"choices": [{"message": {"content": "Hi"}}]});
+        assert!(parse_chat_response(body).unwrap().logprobs.is_none());
+    }
+
+    fn too_many_requests(body: &str) -> anyhow::Error {
+        classify_api_error(ApiError {
+            status: reqwest::StatusCode::TOO_MANY_REQUESTS,
+            body: body.to_string(),
+            retry_after: None,
+        })
+    }
+
+    #[test]
+    fn quota_429s_are_not_retried() {
+        for code in QUOTA_ERROR_CODES {
+            let body = serde_json::json!({
+                "error": {"message": "You exceeded your quota", "type": code, "code": code}
+            });
+            let error = too_many_requests(&body.to_string());
+            assert!(error.is::<QuotaExceeded>(), "{}", code);
+            assert!(!retry::is_retryable(&error), "{}", code);
+        }
+    }
+
+    #[test]
+    fn rate_limit_429s_are_retried() {
+        let body = r#"{"error": {"message": "Rate limit reached", "type": "requests",
+            "code": "rate_limit_exceeded"}}"#;
+        let error = too_many_requests(body);
+        assert!(error.is::<ApiError>());
+        assert!(retry::is_retryable(&error));
+    }
+
+    fn chat(role: &str) -> Message {
+        Message {
+            role: role.to_string(),
+            content: MessageContent::Text(String::from("Hi")),
+            pinned: false,
+        }
+    }
+
+    #[test]
+    fn roles_are_trimmed_and_lowercased() {
+        let mut messages = vec![chat(" System "), chat("USER"), chat("assistant\n")];
+        sanitize_roles(&mut messages).unwrap();
+        let roles: Vec<&str> = messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["system", "user", "assistant"]);
+    }
+
+    #[test]
+    fn unknown_role_is_rejected_with_its_index() {
+        let mut messages = vec![chat("user"), chat("assistant"), chat("Bot")];
+        let error = sanitize_roles(&mut messages).unwrap_err();
+        assert_eq!(error.index, 2);
+        assert_eq!(error.role, "Bot");
+
+        let mut messages = vec![chat("")];
+        assert_eq!(sanitize_roles(&mut messages).unwrap_err().index, 0);
+    }
+
+    #[test]
+    fn extra_headers_reject_the_ones_requests_set() {
+        let headers = |name: &str| BTreeMap::from([(name.to_string(), String::from("x"))]);
+        assert!(validate_extra_headers(&headers("Helicone-Auth")).is_ok());
+        assert!(validate_extra_headers(&headers("authorization")).is_err());
+        assert!(validate_extra_headers(&headers("Content-Type")).is_err());
+        assert!(validate_extra_headers(&headers("bad header")).is_err());
+    }
+
+    #[test]
+    fn audio_input_follows_the_capability_table() {
+        assert!(supports_audio_input("gpt-4o-audio-preview"));
+        assert!(supports_audio_input("gpt-4o-mini-audio-preview"));
+        assert!(!supports_audio_input("gpt-4o"));
+        assert!(!supports_audio_input("my-audio-finetune"));
+    }
+
+    #[tokio::test]
+    async fn logprobs_for_a_model_without_them_fail_before_sending() {
+        let options = ChatOptions {
+            model: Some(String::from("o3-mini")),
+            logprobs: Some(3),
+            ..ChatOptions::default()
+        };
+        let error = send_chat("key", vec![chat("user")], Vec::new(), &options, false)
+            .await
+            .unwrap_err();
+        assert!(error.downcast_ref::<LogprobsUnsupported>().is_some());
+    }
+
+    fn with_effort(model: &str) -> ChatOptions {
+        ChatOptions {
+            model: Some(model.to_string()),
+            reasoning_effort: Some(ReasoningEffort::High),
+            ..ChatOptions::default()
+        }
+    }
+
+    #[test]
+    fn reasoning_effort_is_sent_only_to_o_series_models() {
+        for model in ["o1", "o3-mini", "o4-mini"] {
+            let request = build_request(vec![chat("user")], Vec::new(), &with_effort(model), false);
+            let body = serde_json::to_value(&request).unwrap();
+            assert_eq!(body["reasoning_effort"], "high", "{}", model);
+            assert!(body["max_completion_tokens"].is_u64(), "{}", model);
+            assert!(body.get("max_tokens").is_none(), "{}", model);
+        }
+        for model in ["gpt-4o", "gpt-4o-mini", "o1-mini"] {
+            let request = build_request(vec![chat("user")], Vec::new(), &with_effort(model), false);
+            let body = serde_json::to_value(&request).unwrap();
+            assert!(body.get("reasoning_effort").is_none(), "{}", model);
+            assert!(body["max_tokens"].is_u64(), "{}", model);
+            assert!(body.get("max_completion_tokens").is_none(), "{}", model);
+        }
+    }
+
+    #[tokio::test]
+    async fn reasoning_effort_for_other_models_fails_before_sending() {
+        let error = send_chat("key", vec![chat("user")], Vec::new(), &with_effort("gpt-4o"), false)
+            .await
+            .unwrap_err();
+        assert_eq!(error.downcast_ref::<ReasoningUnsupported>().unwrap().model, "gpt-4o");
+    }
+
+    #[test]
+    fn send_message_request_fills_in_defaults() {
+        let request: SendMessageRequest = serde_json::from_value(serde_json::json!({
+            "messages": [{"role": "user", "content": "Hi"}],
+            "screenshotBase64": "aGk=",
+            "reasoningEffort": "low",
+        }))
+        .unwrap();
+        assert_eq!(request.messages.len(), 1);
+        assert_eq!(request.screenshot_base64.as_deref(), Some("aGk="));
+        assert_eq!(request.reasoning_effort, Some(ReasoningEffort::Low));
+        assert!(!request.allow_duplicate && !request.prefer_ocr);
+        assert!(request.tags.is_empty() && request.model.is_none());
+    }
 }
@@ -0,0 +1,223 @@
+use anyhow::Result;
+use serde::Serialize;
+use tauri::State;
+
+use crate::commands::audio::AudioRecorder;
+#[cfg(target_os = "macos")]
+use crate::commands::audio::{StreamSource, STREAM_RELEASE_TIMEOUT};
+
+/// Format requested from ScreenCaptureKit; mono keeps the buffer layout the same
+/// as a single-channel microphone
+#[cfg(target_os = "macos")]
+const CAPTURE_SAMPLE_RATE: u32 = 48000;
+
+/// First macOS release whose ScreenCaptureKit can capture audio
+#[cfg(target_os = "macos")]
+const MIN_MACOS_MAJOR: u32 = 13;
+
+/// An application whose audio can be recorded
+#[derive(Debug, Clone, Serialize)]
+pub struct CapturableApp {
+    pub name: String,
+    pub bundle_id: String,
+    pub pid: i32,
+}
+
+/// List running applications that `start_app_audio_recording` can capture
+/// Requires the screen-recording permission; macOS 13 or later only
+#[tauri::command]
+pub async fn list_capturable_apps() -> Result<Vec<CapturableApp>, String> {
+    tauri::async_runtime::spawn_blocking(list_capturable_apps_impl)
+        .await
+        .map_err(|e| format!("Failed to list applications: {}", e))?
+        .map_err(|e| format!("Failed to list applications: {}", e))
+}
+
+/// Record the audio output of the application with `bundle_id` into the recording buffer
+/// Stop and transcribe it with the usual `stop_audio_recording` and `process_audio`
+/// An idle microphone stream is released first so the two sources never mix
+#[tauri::command]
+pub async fn start_app_audio_recording(
+    recorder: State<'_, AudioRecorder>,
+    bundle_id: String,
+) -> Result<(), String> {
+    let recorder = recorder.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || start_app_capture(&recorder, &bundle_id))
+        .await
+        .map_err(|e| format!("Failed to start app audio recording: {}", e))?
+        .map_err(|e| format!("Failed to start app audio recording: {}", e))
+}
+
+#[cfg(target_os = "macos")]
+fn list_capturable_apps_impl() -> Result<Vec<CapturableApp>> {
+    use screencapturekit::shareable_content::SCShareableContent;
+
+    ensure_supported()?;
+    let content = SCShareableContent::get()
+        .map_err(|e| anyhow::anyhow!("ScreenCaptureKit error: {:?}", e))?;
+
+    let mut apps: Vec<CapturableApp> = content
+        .applications()
+        .iter()
+        .filter(|app| !app.bundle_identifier().is_empty())
+        .map(|app| CapturableApp {
+            name: app.application_name(),
+            bundle_id: app.bundle_identifier(),
+            pid: app.process_id(),
+        })
+        .collect();
+    apps.sort_by_key(|app| app.name.to_lowercase());
+    Ok(apps)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn list_capturable_apps_impl() -> Result<Vec<CapturableApp>> {
+    anyhow::bail!("Per-app audio capture is only available on macOS")
+}
+
+/// Open an audio-only ScreenCaptureKit stream on the chosen app and feed the buffer
+/// The stream lives on its own thread until recording stops or the stream is released
+#[cfg(target_os = "macos")]
+fn start_app_capture(recorder: &AudioRecorder, bundle_id: &str) -> Result<()> {
+    use anyhow::Context;
+    use screencapturekit::shareable_content::SCShareableContent;
+    use screencapturekit::stream::configuration::SCStreamConfiguration;
+    use screencapturekit::stream::content_filter::SCContentFilter;
+    use screencapturekit::stream::output_type::SCStreamOutputType;
+    use screencapturekit::stream::SCStream;
+    use std::time::Duration;
+
+    let sck = |e| anyhow::anyhow!("ScreenCaptureKit error: {:?}", e);
+
+    ensure_supported()?;
+    let _startup = recorder.lock_startup();
+    if recorder.is_recording() {
+        anyhow::bail!("A recording is already in progress");
+    }
+    // Wait for the owner to drop the idle stream, so the two sources never overlap
+    if recorder.stream_source().is_some()
+        && !recorder.release_stream_and_wait(STREAM_RELEASE_TIMEOUT)
+    {
+        anyhow::bail!("The microphone stream did not close in time; try again");
+    }
+
+    let content = SCShareableContent::get().map_err(sck)?;
+    let app = content
+        .applications()
+        .into_iter()
+        .find(|app| app.bundle_identifier() == bundle_id)
+        .with_context(|| format!("{} is not running or can't be captured", bundle_id))?;
+    let display = content
+        .displays()
+        .into_iter()
+        .next()
+        .context("No display available to attach the capture to")?;
+
+    let filter = SCContentFilter::new()
+        .with_display_including_application_excluding_windows(&display, &[&app], &[]);
+    let config = SCStreamConfiguration::new()
+        .set_captures_audio(true)
+        .and_then(|c| c.set_sample_rate(CAPTURE_SAMPLE_RATE))
+        .and_then(|c| c.set_channel_count(1))
+        .and_then(|c| c.set_excludes_current_process_audio(true))
+        .map_err(sck)?;
+
+    recorder.set_stream_format(CAPTURE_SAMPLE_RATE, 1);
+    recorder.clear_buffer();
+    recorder.set_recording(true);
+
+    let mut stream = SCStream::new(&filter, &config);
+    stream.add_output_handler(AppAudioOutput(recorder.clone()), SCStreamOutputType::Audio);
+    if let Err(e) = stream.start_capture() {
+        recorder.set_recording(false);
+        return Err(sck(e).context("Check the screen-recording permission"));
+    }
+    let generation = recorder.claim_stream(StreamSource::App);
+    println!("Capturing audio from {} ({})", app.application_name(), bundle_id);
+
+    // Same ownership scheme as the microphone: a thread keeps the stream alive,
+    // here only for the length of one recording
+    let recorder = recorder.clone();
+    std::thread::spawn(move || {
+        while recorder.holds_stream(generation) && recorder.is_recording() {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        if let Err(e) = stream.stop_capture() {
+            eprintln!("Failed to stop app audio capture: {:?}", e);
+        }
+        drop(stream);
+        // Leaves a microphone stream opened since then running
+        recorder.stream_closed(generation);
+    });
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn start_app_capture(_recorder: &AudioRecorder, _bundle_id: &str) -> Result<()> {
+    anyhow::bail!("Per-app audio capture is only available on macOS")
+}
+
+/// Appends each audio sample buffer to the recording, with the input gain applied
+#[cfg(target_os = "macos")]
+struct AppAudioOutput(AudioRecorder);
+
+#[cfg(target_os = "macos")]
+impl screencapturekit::stream::output_trait::SCStreamOutputTrait for AppAudioOutput {
+    fn did_output_sample_buffer(
+        &self,
+        sample: core_media_rs::cm_sample_buffer::CMSampleBuffer,
+        of_type: screencapturekit::stream::output_type::SCStreamOutputType,
+    ) {
+        use crate::commands::audio::apply_gain;
+        use screencapturekit::stream::output_type::SCStreamOutputType;
+
+        let recorder = &self.0;
+        if of_type != SCStreamOutputType::Audio || !recorder.is_recording() {
+            return;
+        }
+        let Ok(buffers) = sample.get_audio_buffer_list() else {
+            return;
+        };
+
+        // Audio arrives as 32-bit float, one buffer per channel
+        for buffer in buffers.buffers() {
+            let mut samples: Vec<f32> = buffer
+                .data()
+                .chunks_exact(4)
+                .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            apply_gain(&mut samples, recorder.input_gain());
+            recorder.append_audio(samples);
+        }
+    }
+}
+
+/// Fail with a readable message instead of a ScreenCaptureKit crash on older systems
+/// or without the screen-recording permission
+#[cfg(target_os = "macos")]
+fn ensure_supported() -> Result<()> {
+    use crate::commands::permissions::{screen_recording_status, PermissionStatus};
+
+    let version = std::process::Command::new("sw_vers")
+        .arg("-productVersion")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default();
+    let major = version.split('.').next().and_then(|v| v.parse::<u32>().ok());
+    if major.is_some_and(|major| major < MIN_MACOS_MAJOR) {
+        anyhow::bail!(
+            "Per-app audio capture requires macOS {} or later (this is {})",
+            MIN_MACOS_MAJOR,
+            version
+        );
+    }
+
+    if screen_recording_status() != PermissionStatus::Granted {
+        anyhow::bail!(
+            "Per-app audio capture needs the screen-recording permission; allow it in \
+             System Settings > Privacy & Security"
+        );
+    }
+    Ok(())
+}
@@ -1,22 +1,197 @@
 use anyhow::{Context, Result};
-use std::sync::{Arc, Mutex};
-use tauri::State;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Condvar, Mutex, MutexGuard};
+use tauri::{AppHandle, Emitter, Manager, State};
 
-use std::sync::atomic::{AtomicBool, Ordering};
+use crate::commands::ai::{
+    send_message_impl, supports_audio_input, ChatOptions, ChatResponse, ContentPart, InputAudio,
+    Message, MessageContent, AUDIO_MODEL,
+};
+use crate::commands::history;
+use crate::commands::requests::{Cancelled, InFlightRequests, TaskKind};
+use crate::commands::transcription::{
+    self, AudioClip, EmptyTranscription, TranscriptionOptions, UploadDescription,
+};
+use crate::commands::transfer::Transfers;
+use crate::commands::usage;
+use crate::config::{AppConfig, ConfigState, TranscriptionProvider};
+use crate::error::GhostError;
+use crate::settings::SettingsStore;
+
+use std::sync::atomic::{
+    AtomicBool, AtomicU16, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering,
+};
+use std::time::{Duration, Instant};
 
 /// Global audio recorder state
 pub struct AudioRecorder {
     is_recording: Arc<AtomicBool>,
-    audio_buffer: Arc<Mutex<Vec<f32>>>,
+    audio_buffer: Arc<Mutex<VecDeque<f32>>>,
     stream_started: Arc<AtomicBool>,
+    /// Held while a stream is being opened, so concurrent starts can't build two
+    stream_startup: Arc<Mutex<()>>,
+    /// Which source owns the current stream; notified when an owner drops its stream
+    stream_owner: Arc<(Mutex<StreamOwner>, Condvar)>,
+    sample_rate: Arc<AtomicU32>,
+    channels: Arc<AtomicU16>,
+    /// Ring-buffer retention window in samples (0 = keep everything)
+    retention_samples: Arc<AtomicUsize>,
+    /// Requested retention window in milliseconds, re-applied when the stream format is known
+    retention_ms: Arc<AtomicU32>,
+    /// Language detected for the current recording, cleared with the buffer
+    detected_language: Arc<Mutex<Option<String>>>,
+    /// When recording last stopped; `None` while recording
+    idle_since: Arc<Mutex<Option<Instant>>>,
+    /// Release the input stream after this many idle seconds (0 = never)
+    idle_timeout_secs: Arc<AtomicU64>,
+    /// Fixed gain applied in the capture callback, stored as f32 bits
+    input_gain: Arc<AtomicU32>,
+    /// Typing-noise gate sensitivity as f32 bits; 0 disables the gate
+    typing_noise_gate: Arc<AtomicU32>,
+    /// Settings key of the device the stream was opened on
+    device_key: Arc<Mutex<Option<String>>>,
+    /// Last successfully transcribed recording, kept for `retranscribe`
+    last_transcribed: Arc<Mutex<Vec<f32>>>,
+    /// Wakes the thread owning the input stream when recording starts or stops, or
+    /// the stream is released; tagged with the generation of the stream it belongs to
+    stream_wake: Arc<Mutex<Option<(u64, mpsc::Sender<()>)>>>,
+    /// Longest recording in seconds (0 = unlimited)
+    max_recording_secs: Arc<AtomicU64>,
+    /// RMS level below which input counts as silence, as f32 bits; 0 disables auto-stop
+    silence_level: Arc<AtomicU32>,
+    /// Quiet time after which silence auto-stop ends the recording
+    silence_after_ms: Arc<AtomicU32>,
+    /// Consecutive quiet samples since the input was last above `silence_level`
+    silent_samples: Arc<AtomicUsize>,
+    /// Whether the current recording has had any sound yet; silence before it is ignored
+    heard_sound: Arc<AtomicBool>,
+    /// Why the recording stopped itself (`AutoStopReason::code`, 0 = it didn't),
+    /// until the stream thread reports it
+    auto_stop_reason: Arc<AtomicU8>,
+    /// Interleaved samples recorded when the recording stopped itself
+    auto_stop_samples: Arc<AtomicUsize>,
+}
+
+/// What a capture stream records from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StreamSource {
+    Microphone,
+    /// Another application's output, via ScreenCaptureKit
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    App,
+}
+
+/// The thread owning the current stream
+#[derive(Debug, Default)]
+struct StreamOwner {
+    /// Bumped for every stream opened, so an old owner can't tear down a newer stream
+    generation: u64,
+    /// Source of the stream an owner thread still holds; `None` once it's dropped
+    source: Option<StreamSource>,
+}
+
+/// Longest wait for a stream's owner thread to drop it when switching sources
+pub(crate) const STREAM_RELEASE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Why a recording stopped without `stop_audio_recording`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AutoStopReason {
+    /// `max_recording_secs` was reached
+    MaxDuration,
+    /// The input stayed quiet for `silence_auto_stop.after_secs`
+    Silence,
+}
+
+impl AutoStopReason {
+    /// Nonzero code stored in `AudioRecorder::auto_stop_reason`
+    fn code(self) -> u8 {
+        match self {
+            Self::MaxDuration => 1,
+            Self::Silence => 2,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(Self::MaxDuration),
+            2 => Some(Self::Silence),
+            _ => None,
+        }
+    }
+}
+
+/// Payload of the `recording-auto-stopped` event
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingAutoStopped {
+    pub reason: AutoStopReason,
+    /// Length of the recording when it stopped
+    pub duration_secs: f64,
+}
+
+/// End a recording once the input stays below `threshold_dbfs` for `after_secs`,
+/// counted from the first sound so a slow start isn't cut off
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SilenceAutoStop {
+    pub threshold_dbfs: f32,
+    pub after_secs: f32,
+}
+
+impl Default for SilenceAutoStop {
+    fn default() -> Self {
+        Self {
+            threshold_dbfs: -45.0,
+            after_secs: 2.0,
+        }
+    }
 }
 
+/// Largest `buckets` accepted by `get_waveform`
+const MAX_WAVEFORM_BUCKETS: usize = 4096;
+
+/// Most recent audio drawn by `get_waveform`, bounding the copy made under the lock
+const WAVEFORM_WINDOW_SECS: f32 = 120.0;
+
+/// Lowest and highest sample in one slice of the recording, in [-1, 1]
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct WaveformBucket {
+    pub min: f32,
+    pub max: f32,
+}
+
+/// Allowed range for `set_input_gain`
+const INPUT_GAIN_RANGE: (f32, f32) = (0.0, 8.0);
+
 impl AudioRecorder {
     pub fn new() -> Self {
         Self {
             is_recording: Arc::new(AtomicBool::new(false)),
-            audio_buffer: Arc::new(Mutex::new(Vec::new())),
+            audio_buffer: Arc::new(Mutex::new(VecDeque::new())),
             stream_started: Arc::new(AtomicBool::new(false)),
+            stream_startup: Arc::new(Mutex::new(())),
+            stream_owner: Arc::new((Mutex::new(StreamOwner::default()), Condvar::new())),
+            sample_rate: Arc::new(AtomicU32::new(44100)),
+            channels: Arc::new(AtomicU16::new(1)),
+            retention_samples: Arc::new(AtomicUsize::new(0)),
+            retention_ms: Arc::new(AtomicU32::new(0)),
+            detected_language: Arc::new(Mutex::new(None)),
+            idle_since: Arc::new(Mutex::new(None)),
+            idle_timeout_secs: Arc::new(AtomicU64::new(0)),
+            input_gain: Arc::new(AtomicU32::new(1.0f32.to_bits())),
+            typing_noise_gate: Arc::new(AtomicU32::new(0.0f32.to_bits())),
+            device_key: Arc::new(Mutex::new(None)),
+            last_transcribed: Arc::new(Mutex::new(Vec::new())),
+            stream_wake: Arc::new(Mutex::new(None)),
+            max_recording_secs: Arc::new(AtomicU64::new(0)),
+            silence_level: Arc::new(AtomicU32::new(0.0f32.to_bits())),
+            silence_after_ms: Arc::new(AtomicU32::new(0)),
+            silent_samples: Arc::new(AtomicUsize::new(0)),
+            heard_sound: Arc::new(AtomicBool::new(false)),
+            auto_stop_reason: Arc::new(AtomicU8::new(0)),
+            auto_stop_samples: Arc::new(AtomicUsize::new(0)),
         }
     }
 
@@ -25,20 +200,261 @@ impl AudioRecorder {
     }
 
     pub fn get_audio_buffer(&self) -> Vec<f32> {
-        self.audio_buffer.lock().unwrap().clone()
+        self.audio_buffer.lock().unwrap().iter().copied().collect()
+    }
+
+    /// Copy only the most recent `seconds` of audio from the buffer
+    pub fn get_recent_audio(&self, seconds: f32) -> Vec<f32> {
+        let wanted = self.seconds_to_samples(seconds);
+        let buffer = self.audio_buffer.lock().unwrap();
+        let skip = buffer.len().saturating_sub(wanted);
+        buffer.iter().skip(skip).copied().collect()
+    }
+
+    /// Copy the first `seconds` of audio from the buffer
+    pub fn get_audio_prefix(&self, seconds: f32) -> Vec<f32> {
+        let wanted = self.seconds_to_samples(seconds);
+        let buffer = self.audio_buffer.lock().unwrap();
+        buffer.iter().take(wanted).copied().collect()
+    }
+
+    /// Seconds of audio in the buffer, from its length and the stream format
+    pub fn buffered_seconds(&self) -> f64 {
+        let samples = self.audio_buffer.lock().unwrap().len();
+        let per_second = self.sample_rate() as f64 * self.channels().max(1) as f64;
+        if per_second == 0.0 {
+            return 0.0;
+        }
+        samples as f64 / per_second
+    }
+
+    /// Peak envelope of the last `WAVEFORM_WINDOW_SECS` in `buckets` equal slices
+    /// The window is copied out first so the capture callback isn't held up by the scan
+    /// Channels are interleaved, so each bucket covers all of them
+    pub fn waveform(&self, buckets: usize) -> Vec<WaveformBucket> {
+        let buffer = self.get_recent_audio(WAVEFORM_WINDOW_SECS);
+        let mut envelope = vec![WaveformBucket::default(); buckets];
+        if buffer.is_empty() || buckets == 0 {
+            return envelope;
+        }
+
+        let len = buffer.len();
+        for (i, &sample) in buffer.iter().enumerate() {
+            let bucket = &mut envelope[i * buckets / len];
+            let sample = sanitize_sample(sample);
+            bucket.min = bucket.min.min(sample);
+            bucket.max = bucket.max.max(sample);
+        }
+        envelope
+    }
+
+    /// Remove and return everything captured so far, for consumers that stream audio out
+    #[cfg_attr(not(feature = "realtime"), allow(dead_code))]
+    pub fn take_audio(&self) -> Vec<f32> {
+        self.audio_buffer.lock().unwrap().drain(..).collect()
+    }
+
+    /// Remember a transcribed recording after its buffer is cleared
+    pub fn keep_transcribed(&self, samples: Vec<f32>) {
+        *self.last_transcribed.lock().unwrap() = samples;
+    }
+
+    /// Clear the buffer once `samples`, a snapshot of it, were transcribed, keeping them
+    /// for `retranscribe`; only called on success so failures keep the recording
+    pub fn finish_transcription(&self, samples: Vec<f32>) {
+        self.keep_transcribed(samples);
+        self.clear_buffer();
+    }
+
+    pub fn last_transcribed(&self) -> Vec<f32> {
+        self.last_transcribed.lock().unwrap().clone()
     }
 
     pub fn clear_buffer(&self) {
-        self.audio_buffer.lock().unwrap().clear()
+        self.audio_buffer.lock().unwrap().clear();
+        *self.detected_language.lock().unwrap() = None;
+    }
+
+    pub fn detected_language(&self) -> Option<String> {
+        self.detected_language.lock().unwrap().clone()
+    }
+
+    pub fn set_detected_language(&self, language: String) {
+        *self.detected_language.lock().unwrap() = Some(language);
     }
 
+    /// Add captured samples, stopping the recording at `max_recording_secs` or when
+    /// silence auto-stop triggers
+    /// Called from the input callback, so a stop only sets atomics and never logs or
+    /// emits; the stream thread reports it as `recording-auto-stopped`
     pub fn append_audio(&self, samples: Vec<f32>) {
+        let silent = self.track_silence(&samples);
         let mut buffer = self.audio_buffer.lock().unwrap();
         buffer.extend(samples);
+
+        // In ring-buffer mode drop the oldest samples beyond the retention window
+        let retention = self.retention_samples.load(Ordering::Relaxed);
+        if retention > 0 && buffer.len() > retention {
+            let excess = buffer.len() - retention;
+            buffer.drain(..excess);
+        }
+
+        // A ring buffer is already bounded by its retention window
+        let max_samples = match retention {
+            0 => self.max_recording_samples(),
+            _ => None,
+        };
+        let full = max_samples.is_some_and(|max| buffer.len() >= max);
+        if let Some(max) = max_samples.filter(|_| full) {
+            buffer.truncate(max);
+        }
+        let recorded = buffer.len();
+        drop(buffer);
+
+        let reason = if full {
+            AutoStopReason::MaxDuration
+        } else if silent {
+            AutoStopReason::Silence
+        } else {
+            return;
+        };
+        if !self.is_recording() {
+            return;
+        }
+        self.auto_stop_samples.store(recorded, Ordering::Relaxed);
+        self.auto_stop_reason.store(reason.code(), Ordering::Release);
+        self.is_recording.store(false, Ordering::Relaxed);
+        // The owner thread also polls, so a contended wake only delays the report
+        if let Ok(current) = self.stream_wake.try_lock() {
+            if let Some((_, wake)) = current.as_ref() {
+                let _ = wake.send(());
+            }
+        }
+    }
+
+    /// Update the silence counters with one callback's samples
+    /// Returns true once the input has been quiet long enough after some sound
+    fn track_silence(&self, samples: &[f32]) -> bool {
+        let level = f32::from_bits(self.silence_level.load(Ordering::Relaxed));
+        if level <= 0.0 || samples.is_empty() {
+            return false;
+        }
+
+        let rms = (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt();
+        if rms >= level {
+            self.heard_sound.store(true, Ordering::Relaxed);
+            self.silent_samples.store(0, Ordering::Relaxed);
+            return false;
+        }
+        if !self.heard_sound.load(Ordering::Relaxed) {
+            return false;
+        }
+
+        let silent =
+            self.silent_samples.fetch_add(samples.len(), Ordering::Relaxed) + samples.len();
+        let after_ms = self.silence_after_ms.load(Ordering::Relaxed);
+        silent >= self.seconds_to_samples(after_ms as f32 / 1000.0)
+    }
+
+    /// Interleaved sample count of `max_recording_secs`, `None` when unlimited
+    fn max_recording_samples(&self) -> Option<usize> {
+        let seconds = self.max_recording_secs.load(Ordering::Relaxed);
+        (seconds > 0).then(|| self.seconds_to_samples(seconds as f32))
+    }
+
+    /// Set the longest recording; `None` (or 0) records until stopped
+    pub fn set_max_recording(&self, seconds: Option<u64>) {
+        self.max_recording_secs.store(seconds.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    /// Turn silence auto-stop on, or off with `None`
+    pub fn set_silence_auto_stop(&self, auto_stop: Option<SilenceAutoStop>) {
+        let auto_stop = auto_stop
+            .filter(|a| a.threshold_dbfs.is_finite() && a.after_secs.is_finite())
+            .filter(|a| a.after_secs > 0.0);
+        let level = auto_stop.map_or(0.0, |a| 10f32.powf(a.threshold_dbfs.min(0.0) / 20.0));
+        let after_ms = auto_stop.map_or(0, |a| (a.after_secs * 1000.0).round() as u32);
+        self.silence_level.store(level.to_bits(), Ordering::Relaxed);
+        self.silence_after_ms.store(after_ms, Ordering::Relaxed);
+    }
+
+    /// Why the recording last stopped itself, once
+    fn take_auto_stop(&self) -> Option<RecordingAutoStopped> {
+        let reason = AutoStopReason::from_code(self.auto_stop_reason.swap(0, Ordering::Acquire))?;
+        let recorded = self.auto_stop_samples.load(Ordering::Relaxed);
+        let per_second = self.sample_rate() as f64 * self.channels().max(1) as f64;
+        Some(RecordingAutoStopped {
+            reason,
+            duration_secs: recorded as f64 / per_second.max(1.0),
+        })
     }
 
     pub fn set_recording(&self, recording: bool) {
+        if recording {
+            self.silent_samples.store(0, Ordering::Relaxed);
+            self.heard_sound.store(false, Ordering::Relaxed);
+            self.auto_stop_reason.store(0, Ordering::Relaxed);
+        }
         self.is_recording.store(recording, Ordering::Relaxed);
+        *self.idle_since.lock().unwrap() = (!recording).then(Instant::now);
+        self.wake_stream();
+    }
+
+    /// Set how long the stream may sit unused before it is released; `None` keeps it open
+    pub fn set_idle_timeout(&self, seconds: Option<u64>) {
+        self.idle_timeout_secs.store(seconds.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    pub fn input_gain(&self) -> f32 {
+        f32::from_bits(self.input_gain.load(Ordering::Relaxed))
+    }
+
+    /// Set the capture gain, clamped to a sane range; returns the value applied
+    pub fn set_input_gain(&self, gain: f32) -> f32 {
+        let gain = if gain.is_finite() {
+            gain.clamp(INPUT_GAIN_RANGE.0, INPUT_GAIN_RANGE.1)
+        } else {
+            1.0
+        };
+        self.input_gain.store(gain.to_bits(), Ordering::Relaxed);
+        gain
+    }
+
+    /// Sensitivity of the typing-noise gate, `None` while it is off
+    pub fn typing_noise_gate(&self) -> Option<f32> {
+        let sensitivity = f32::from_bits(self.typing_noise_gate.load(Ordering::Relaxed));
+        (sensitivity > 0.0).then_some(sensitivity)
+    }
+
+    /// Turn the typing-noise gate on with a sensitivity clamped to 0-1, or off with `None`
+    /// (or 0); returns the value applied
+    pub fn set_typing_noise_gate(&self, sensitivity: Option<f32>) -> Option<f32> {
+        let sensitivity = sensitivity
+            .filter(|s| s.is_finite() && *s > 0.0)
+            .map(|s| s.min(1.0));
+        self.typing_noise_gate.store(sensitivity.unwrap_or(0.0).to_bits(), Ordering::Relaxed);
+        sensitivity
+    }
+
+    /// Settings key of the current input device, once a stream has been opened
+    pub fn device_key(&self) -> Option<String> {
+        self.device_key.lock().unwrap().clone()
+    }
+
+    fn set_device_key(&self, key: Option<String>) {
+        *self.device_key.lock().unwrap() = key;
+    }
+
+    /// Whether the stream has been idle longer than the configured timeout
+    fn idle_expired(&self) -> bool {
+        let timeout = self.idle_timeout_secs.load(Ordering::Relaxed);
+        if timeout == 0 || self.is_recording() {
+            return false;
+        }
+        self.idle_since
+            .lock()
+            .unwrap()
+            .is_some_and(|since| since.elapsed() >= Duration::from_secs(timeout))
     }
 
     pub fn is_stream_started(&self) -> bool {
@@ -47,16 +463,241 @@ impl AudioRecorder {
 
     pub fn set_stream_started(&self, started: bool) {
         self.stream_started.store(started, Ordering::Relaxed);
+        self.wake_stream();
+    }
+
+    /// Lock out other stream starts while opening one
+    pub(crate) fn lock_startup(&self) -> MutexGuard<'_, ()> {
+        self.stream_startup.lock().unwrap()
+    }
+
+    /// Record a newly opened stream from `source` and mark it started
+    ///
+    /// # Returns
+    /// The stream's generation, which its owner thread passes to `holds_stream` and
+    /// `stream_closed`
+    pub(crate) fn claim_stream(&self, source: StreamSource) -> u64 {
+        let mut owner = self.stream_owner.0.lock().unwrap();
+        owner.generation += 1;
+        owner.source = Some(source);
+        self.set_stream_started(true);
+        owner.generation
+    }
+
+    /// Whether the stream of `generation` should keep running
+    pub(crate) fn holds_stream(&self, generation: u64) -> bool {
+        self.is_stream_started() && self.stream_owner.0.lock().unwrap().generation == generation
+    }
+
+    /// Stop the stream of `generation`; does nothing if a newer stream replaced it
+    fn release_stream(&self, generation: u64) {
+        let owner = self.stream_owner.0.lock().unwrap();
+        if owner.generation == generation {
+            self.set_stream_started(false);
+        }
+    }
+
+    /// Called by the owner thread once it has dropped the stream of `generation`
+    pub(crate) fn stream_closed(&self, generation: u64) {
+        let (owner, closed) = &*self.stream_owner;
+        let mut owner = owner.lock().unwrap();
+        if owner.generation == generation {
+            owner.source = None;
+            self.set_stream_started(false);
+        }
+        closed.notify_all();
+    }
+
+    /// Source of the stream currently held, if any
+    pub(crate) fn stream_source(&self) -> Option<StreamSource> {
+        self.stream_owner.0.lock().unwrap().source
+    }
+
+    /// Release the current stream and wait until its owner thread has dropped it, so
+    /// the device is free for another source
+    ///
+    /// # Returns
+    /// `false` if the owner still held the stream after `timeout`
+    pub(crate) fn release_stream_and_wait(&self, timeout: Duration) -> bool {
+        let (owner, closed) = &*self.stream_owner;
+        let owner = owner.lock().unwrap();
+        self.set_stream_started(false);
+        let (owner, _) = closed
+            .wait_timeout_while(owner, timeout, |owner| owner.source.is_some())
+            .unwrap();
+        owner.source.is_none()
+    }
+
+    fn wake_stream(&self) {
+        if let Some((_, wake)) = self.stream_wake.lock().unwrap().as_ref() {
+            let _ = wake.send(());
+        }
+    }
+
+    /// Drop the wake sender of the stream of `generation`; a stream opened since (a
+    /// re-open or a quick stop and start) keeps its own
+    fn clear_stream_wake(&self, generation: u64) {
+        let mut current = self.stream_wake.lock().unwrap();
+        if current.as_ref().is_some_and(|(owner, _)| *owner == generation) {
+            *current = None;
+        }
+    }
+
+    /// Stop recording and release the input stream (used on app exit)
+    pub fn shutdown(&self) {
+        self.set_recording(false);
+        self.set_stream_started(false);
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate.load(Ordering::Relaxed)
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels.load(Ordering::Relaxed)
+    }
+
+    /// Record the format of the active input stream
+    pub fn set_stream_format(&self, sample_rate: u32, channels: u16) {
+        self.sample_rate.store(sample_rate, Ordering::Relaxed);
+        self.channels.store(channels, Ordering::Relaxed);
+        // Sample count of the retention window depends on the stream format
+        self.apply_retention();
+    }
+
+    /// Set the ring-buffer retention window; `None` disables ring-buffer mode
+    pub fn set_retention_window(&self, seconds: Option<f32>) {
+        let ms = seconds
+            .filter(|s| s.is_finite() && *s > 0.0)
+            .map(|s| (s * 1000.0).round() as u32)
+            .unwrap_or(0);
+        self.retention_ms.store(ms, Ordering::Relaxed);
+        self.apply_retention();
+    }
+
+    fn apply_retention(&self) {
+        let ms = self.retention_ms.load(Ordering::Relaxed);
+        let samples = if ms == 0 {
+            0
+        } else {
+            self.seconds_to_samples(ms as f32 / 1000.0)
+        };
+        self.retention_samples.store(samples, Ordering::Relaxed);
+    }
+
+    /// Convert a duration to an interleaved sample count for the current stream format
+    /// Rounded to whole frames so a cut never splits the channels of one frame
+    fn seconds_to_samples(&self, seconds: f32) -> usize {
+        let channels = self.channels().max(1) as usize;
+        let frames = (seconds.max(0.0) * self.sample_rate() as f32).round() as usize;
+        frames * channels
+    }
+}
+
+/// A range of capture formats supported by the input device
+#[derive(Debug, Clone, Serialize)]
+pub struct InputConfigRange {
+    pub sample_format: String,
+    pub channels: u16,
+    pub min_sample_rate: u32,
+    pub max_sample_rate: u32,
+}
+
+/// Capture format requested by the caller; unset fields accept any value
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InputConfigRequest {
+    pub sample_format: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+}
+
+/// Audio preferences remembered for one input device
+/// Unset fields fall back to the global settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceAudioSettings {
+    pub input_gain: Option<f32>,
+    /// Last capture format explicitly requested on this device
+    pub input_config: Option<InputConfigRequest>,
+}
+
+/// Send the transcript from `process_audio` straight to the AI (voice-assistant mode)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoSendSettings {
+    pub enabled: bool,
+    /// Time to cancel a wrong transcript (via `cancel_task`) before it is sent
+    pub delay_ms: u64,
+}
+
+impl Default for AutoSendSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            delay_ms: 1500,
+        }
     }
 }
 
+/// Payload of the `auto-send-pending` event
+#[derive(Debug, Clone, Serialize)]
+pub struct AutoSendPending {
+    /// Task id to pass to `cancel_task`
+    pub request_id: String,
+    pub transcript: String,
+    pub delay_ms: u64,
+}
+
+/// Payload of the `auto-send-failed` event
+#[derive(Debug, Clone, Serialize)]
+pub struct AutoSendFailed {
+    pub request_id: String,
+    pub error: String,
+}
+
+/// Payload of the `transcription-auto-retry` event
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptionAutoRetry {
+    /// Gain applied to the recording for the second attempt
+    pub gain: f32,
+}
+
+/// List the capture formats supported by the input device
+#[tauri::command]
+pub fn list_input_configs() -> Result<Vec<InputConfigRange>, String> {
+    list_input_configs_impl().map_err(|e| format!("Failed to query input device: {}", e))
+}
+
+fn list_input_configs_impl() -> Result<Vec<InputConfigRange>> {
+    use cpal::traits::DeviceTrait;
+
+    let device = input_device()?;
+    let configs = device
+        .supported_input_configs()?
+        .map(|range| InputConfigRange {
+            sample_format: format!("{:?}", range.sample_format()),
+            channels: range.channels(),
+            min_sample_rate: range.min_sample_rate().0,
+            max_sample_rate: range.max_sample_rate().0,
+        })
+        .collect();
+
+    Ok(configs)
+}
+
 /// Start recording audio from microphone
+/// `config` optionally requests a capture format; unsupported requests fall back to the default
+/// A stream released after inactivity is re-acquired here
 #[tauri::command]
 pub async fn start_audio_recording(
+    app: AppHandle,
     recorder: State<'_, AudioRecorder>,
-) -> Result<(), String> {
-    start_microphone_recording(recorder.inner())
-        .map_err(|e| format!("Failed to start recording: {}", e))
+    config: Option<InputConfigRequest>,
+) -> Result<(), GhostError> {
+    start_microphone_recording(recorder.inner(), config, Some(app)).map_err(|e| {
+        let message = format!("Failed to start recording: {}", e);
+        GhostError::classify(&e, message, GhostError::RecordingFailed)
+    })
 }
 
 /// Stop recording audio
@@ -68,222 +709,2655 @@ pub async fn stop_audio_recording(
     Ok(())
 }
 
-/// Get current recording status
-#[tauri::command]
-pub fn is_recording(recorder: State<'_, AudioRecorder>) -> bool {
-    recorder.is_recording()
+/// Result of `test_microphone`
+#[derive(Debug, Serialize)]
+pub struct MicrophoneTest {
+    /// Peak absolute sample level in [0, 1]
+    pub peak: f32,
+    pub duration_secs: f32,
+    /// False when no output device was available for playback
+    pub played_back: bool,
 }
 
-/// Process recorded audio: transcribe and return text
+/// Record a short clip through the normal capture pipeline and play it back
+/// Nothing is sent to the transcription API; the buffer is cleared afterwards
+/// Refused while an unprocessed recording is buffered, so the test can't discard it
 #[tauri::command]
-pub async fn process_audio(
+pub async fn test_microphone(
+    app: AppHandle,
     recorder: State<'_, AudioRecorder>,
-    api_key: State<'_, String>,
-) -> Result<String, String> {
-    // Get audio buffer
-    let audio_samples = recorder.get_audio_buffer();
-
-    if audio_samples.is_empty() {
-        return Err("No audio recorded".to_string());
+    seconds: Option<f32>,
+) -> Result<MicrophoneTest, String> {
+    let seconds = seconds.unwrap_or(3.0);
+    if !seconds.is_finite() || seconds <= 0.0 || seconds > 30.0 {
+        return Err("Test duration must be between 0 and 30 seconds".to_string());
+    }
+    if recorder.is_recording() {
+        return Err("Stop the current recording before testing the microphone".to_string());
+    }
+    // The test records into the same buffer, which would lose the user's audio
+    if recorder.buffered_seconds() > 0.0 {
+        return Err(
+            "Process or discard the current recording before testing the microphone".to_string(),
+        );
     }
 
-    println!("Processing {} audio samples", audio_samples.len());
-
-    // Convert to WAV format
-    let wav_data = samples_to_wav(&audio_samples, 44100, 1)
-        .map_err(|e| format!("Failed to encode audio: {}", e))?;
-
-    println!("Encoded {} bytes of WAV data", wav_data.len());
-
-    // Transcribe using Whisper
-    let transcription = transcribe_audio_impl(&api_key, wav_data)
+    let recorder = recorder.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || test_microphone_impl(&recorder, seconds, app))
         .await
-        .map_err(|e| format!("Transcription failed: {}", e))?;
+        .map_err(|e| format!("Microphone test failed: {}", e))?
+        .map_err(|e| format!("Microphone test failed: {}", e))
+}
 
-    // Clear buffer for next recording
+fn test_microphone_impl(
+    recorder: &AudioRecorder,
+    seconds: f32,
+    app: AppHandle,
+) -> Result<MicrophoneTest> {
+    start_microphone_recording(recorder, None, Some(app))?;
+    std::thread::sleep(Duration::from_secs_f32(seconds));
+    recorder.set_recording(false);
+
+    let samples = recorder.get_audio_buffer();
     recorder.clear_buffer();
 
-    Ok(transcription)
-}
+    if samples.is_empty() {
+        anyhow::bail!("No audio captured. Check the input device and microphone permission");
+    }
 
-fn start_microphone_recording(recorder: &AudioRecorder) -> Result<()> {
-    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    let sample_rate = recorder.sample_rate();
+    let channels = recorder.channels();
+    let peak = samples
+        .iter()
+        .map(|s| sanitize_sample(*s).abs())
+        .fold(0.0, f32::max);
+    let duration_secs = samples.len() as f32 / (sample_rate as f32 * channels.max(1) as f32);
 
-    // Check if stream already started
-    if recorder.is_stream_started() {
-        println!("Stream already started, just resuming recording");
-        recorder.set_recording(true);
-        return Ok(());
-    }
+    let played_back = match play_samples(samples, sample_rate, channels, 1.0, |_| {}) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Warning: Skipping playback: {}", e);
+            false
+        }
+    };
 
-    // Set recording flag
-    recorder.set_recording(true);
-    recorder.clear_buffer();
+    Ok(MicrophoneTest {
+        peak,
+        duration_secs,
+        played_back,
+    })
+}
 
-    let host = cpal::default_host();
-    let device = host
-        .default_input_device()
-        .context("No input device available")?;
+/// Play samples on the default output device at `speed`, blocking until done
+/// `started` gets the sink once playback is running, so another thread can control it
+fn play_samples(
+    samples: Vec<f32>,
+    sample_rate: u32,
+    channels: u16,
+    speed: f32,
+    started: impl FnOnce(Arc<rodio::Sink>),
+) -> Result<()> {
+    let (_stream, handle) =
+        rodio::OutputStream::try_default().context("No audio output device available")?;
+    let sink = Arc::new(rodio::Sink::try_new(&handle).context("Failed to open audio output")?);
 
-    println!("Using input device: {:?}", device.name());
+    sink.set_speed(speed);
+    sink.append(rodio::buffer::SamplesBuffer::new(channels, sample_rate, samples));
+    started(Arc::clone(&sink));
+    sink.sleep_until_end();
+    Ok(())
+}
 
-    let config = device.default_input_config()?;
-    println!("Input config: {:?}", config);
+/// Allowed range for `play_recording` speed
+const PLAYBACK_SPEED_RANGE: (f32, f32) = (0.5, 3.0);
 
-    let recorder_clone = Arc::new(AudioRecorder {
-        is_recording: Arc::clone(&recorder.is_recording),
-        audio_buffer: Arc::clone(&recorder.audio_buffer),
-        stream_started: Arc::clone(&recorder.stream_started),
-    });
+/// The sink of the recording being played back, if any
+#[derive(Default)]
+pub struct Playback(Mutex<Option<Arc<rodio::Sink>>>);
 
-    // Create stream based on sample format
-    let stream = match config.sample_format() {
-        cpal::SampleFormat::F32 => {
-            let config: cpal::StreamConfig = config.into();
-            device.build_input_stream(
-                &config,
-                move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                    if recorder_clone.is_recording() {
-                        recorder_clone.append_audio(data.to_vec());
-                    }
-                },
-                move |err| {
-                    eprintln!("Audio stream error: {}", err);
-                },
-                None,
-            )?
-        }
-        cpal::SampleFormat::I16 => {
-            let config: cpal::StreamConfig = config.into();
-            device.build_input_stream(
-                &config,
-                move |data: &[i16], _: &cpal::InputCallbackInfo| {
-                    if recorder_clone.is_recording() {
-                        // Convert i16 to f32
-                        let samples: Vec<f32> = data
-                            .iter()
-                            .map(|&sample| sample as f32 / i16::MAX as f32)
-                            .collect();
-                        recorder_clone.append_audio(samples);
-                    }
-                },
-                move |err| {
-                    eprintln!("Audio stream error: {}", err);
-                },
-                None,
-            )?
-        }
-        cpal::SampleFormat::U16 => {
-            let config: cpal::StreamConfig = config.into();
-            device.build_input_stream(
-                &config,
-                move |data: &[u16], _: &cpal::InputCallbackInfo| {
-                    if recorder_clone.is_recording() {
-                        // Convert u16 to f32
-                        let samples: Vec<f32> = data
-                            .iter()
-                            .map(|&sample| (sample as f32 / u16::MAX as f32) * 2.0 - 1.0)
-                            .collect();
-                        recorder_clone.append_audio(samples);
-                    }
-                },
-                move |err| {
-                    eprintln!("Audio stream error: {}", err);
-                },
-                None,
-            )?
-        }
-        _ => anyhow::bail!("Unsupported sample format: {:?}", config.sample_format()),
+impl Playback {
+    fn stop(&self) {
+        if let Some(sink) = self.0.lock().unwrap().take() {
+            sink.stop();
+        }
+    }
+}
+
+/// Play the current recording (or the last transcribed one, or the WAV at `path`)
+/// at `speed` times normal, clamped to 0.5-3; any playback already running is stopped
+/// Returns once playback has started; `playback-finished` is emitted when it ends
+#[tauri::command]
+pub fn play_recording(
+    app: AppHandle,
+    recorder: State<'_, AudioRecorder>,
+    playback: State<'_, Playback>,
+    speed: Option<f32>,
+    path: Option<PathBuf>,
+) -> Result<(), String> {
+    let (samples, sample_rate, channels) = match path {
+        Some(path) => {
+            let bytes = std::fs::read(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            wav_to_samples(&bytes).map_err(|e| format!("Invalid recording: {}", e))?
+        }
+        None => {
+            let mut samples = recorder.get_audio_buffer();
+            if samples.is_empty() {
+                samples = recorder.last_transcribed();
+            }
+            (samples, recorder.sample_rate(), recorder.channels())
+        }
     };
+    if samples.is_empty() {
+        return Err("No audio recorded".to_string());
+    }
 
-    stream.play()?;
+    let speed = clamp_playback_speed(speed.unwrap_or(1.0));
 
-    // Mark stream as started
-    recorder.set_stream_started(true);
+    // Held until the new sink is stored, so a concurrent call can't start a second
+    // playback between stopping the old one and recording the new one
+    let mut current = playback.0.lock().unwrap();
+    if let Some(sink) = current.take() {
+        sink.stop();
+    }
 
-    // Store stream in a thread so it doesn't get dropped
+    // The output stream can't leave the thread that opened it, so it lives on one
+    // for as long as the recording plays
+    let (started_tx, started_rx) = std::sync::mpsc::channel();
     std::thread::spawn(move || {
-        loop {
-            std::thread::sleep(std::time::Duration::from_millis(100));
+        let on_started = started_tx.clone();
+        let played = play_samples(samples, sample_rate, channels, speed, move |sink| {
+            let _ = on_started.send(Ok(sink));
+        });
+        match played {
+            Ok(()) => {
+                let _ = app.emit("playback-finished", ());
+            }
+            Err(e) => {
+                let _ = started_tx.send(Err(e));
+            }
         }
     });
 
+    let sink = started_rx
+        .recv()
+        .map_err(|_| "Playback thread exited".to_string())?
+        .map_err(|e| format!("Playback failed: {}", e))?;
+    *current = Some(sink);
     Ok(())
 }
 
-/// Convert audio samples to WAV format
-fn samples_to_wav(samples: &[f32], sample_rate: u32, channels: u16) -> Result<Vec<u8>> {
-    use hound::{WavSpec, WavWriter};
-    use std::io::Cursor;
+/// Change the speed of the running playback, clamped to 0.5-3
+///
+/// # Returns
+/// The speed applied, or `None` if nothing is playing
+#[tauri::command]
+pub fn set_playback_speed(playback: State<'_, Playback>, speed: f32) -> Option<f32> {
+    let speed = clamp_playback_speed(speed);
+    let sink = playback.0.lock().unwrap().clone()?;
+    sink.set_speed(speed);
+    Some(speed)
+}
 
-    let spec = WavSpec {
-        channels,
-        sample_rate,
-        bits_per_sample: 32,
-        sample_format: hound::SampleFormat::Float,
-    };
+/// Stop playback started by `play_recording`; safe to call when nothing is playing
+#[tauri::command]
+pub fn stop_playback(playback: State<'_, Playback>) {
+    playback.stop();
+}
 
-    let mut cursor = Cursor::new(Vec::new());
-    {
-        let mut writer = WavWriter::new(&mut cursor, spec)?;
-        for &sample in samples {
-            writer.write_sample(sample)?;
-        }
-        writer.finalize()?;
+fn clamp_playback_speed(speed: f32) -> f32 {
+    if speed.is_finite() {
+        speed.clamp(PLAYBACK_SPEED_RANGE.0, PLAYBACK_SPEED_RANGE.1)
+    } else {
+        1.0
     }
+}
 
-    Ok(cursor.into_inner())
+/// Abort the current recording and throw away the captured audio
+/// Safe to call when not recording
+#[tauri::command]
+pub fn discard_recording(
+    app: AppHandle,
+    recorder: State<'_, AudioRecorder>,
+) -> Result<(), String> {
+    recorder.set_recording(false);
+    recorder.clear_buffer();
+
+    app.emit("recording-discarded", ())
+        .map_err(|e| format!("Failed to emit event: {}", e))
 }
 
-/// Transcribe audio using OpenAI Whisper API
-async fn transcribe_audio_impl(api_key: &str, audio_data: Vec<u8>) -> Result<String> {
-    use reqwest::multipart;
+/// Output format for `save_recording`
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFileFormat {
+    Wav,
+    Mp3,
+}
 
-    let client = reqwest::Client::new();
+impl AudioFileFormat {
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            Self::Wav => "wav",
+            Self::Mp3 => "mp3",
+        }
+    }
+}
 
-    // Create multipart form with audio file
-    let part = multipart::Part::bytes(audio_data)
-        .file_name("audio.wav")
-        .mime_str("audio/wav")?;
+/// Why saving a recording failed
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", content = "message")]
+pub enum SaveRecordingError {
+    NoRecording,
+    PermissionDenied(String),
+    Io(String),
+    Encode(String),
+}
 
-    let form = multipart::Form::new()
-        .part("file", part)
-        .text("model", "whisper-1");
+impl std::fmt::Display for SaveRecordingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoRecording => write!(f, "No audio recorded"),
+            Self::PermissionDenied(path) => write!(f, "Permission denied writing {}", path),
+            Self::Io(message) => write!(f, "Failed to write recording: {}", message),
+            Self::Encode(message) => write!(f, "Failed to encode recording: {}", message),
+        }
+    }
+}
 
-    let response = client
-        .post("https://api.openai.com/v1/audio/transcriptions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .multipart(form)
-        .send()
-        .await
-        .context("Failed to send request to Whisper API")?;
+impl std::error::Error for SaveRecordingError {}
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let error_text = response.text().await.unwrap_or_default();
-        anyhow::bail!("Whisper API returned error {}: {}", status, error_text);
+/// Save the current buffer to `path` as WAV (16-bit PCM) or MP3
+/// The extension matching `format` is added if missing; the buffer is left intact
+///
+/// # Returns
+/// The path actually written
+/// Audio captured at a different rate than `preferred_sample_rate` is resampled to it
+#[tauri::command]
+pub fn save_recording(
+    recorder: State<'_, AudioRecorder>,
+    settings: State<'_, SettingsStore>,
+    path: PathBuf,
+    format: Option<AudioFileFormat>,
+) -> Result<PathBuf, SaveRecordingError> {
+    let samples = recorder.get_audio_buffer();
+    if samples.is_empty() {
+        return Err(SaveRecordingError::NoRecording);
     }
 
-    #[derive(serde::Deserialize)]
-    struct WhisperResponse {
-        text: String,
+    let format = format.unwrap_or(AudioFileFormat::Wav);
+    let extension = format.extension();
+
+    let mut path = path;
+    let has_extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case(extension));
+    if !has_extension {
+        path.set_extension(extension);
     }
 
-    let whisper_response: WhisperResponse = response
-        .json()
-        .await
-        .context("Failed to parse Whisper response")?;
+    let preferred_rate = settings.get().preferred_sample_rate;
+    let bytes = encode_recording(&recorder, samples, format, preferred_rate)
+        .map_err(|e| SaveRecordingError::Encode(e.to_string()))?;
+
+    std::fs::write(&path, bytes).map_err(|e| match e.kind() {
+        std::io::ErrorKind::PermissionDenied => {
+            SaveRecordingError::PermissionDenied(path.display().to_string())
+        }
+        _ => SaveRecordingError::Io(e.to_string()),
+    })?;
 
-    Ok(whisper_response.text)
+    Ok(path)
 }
 
-// Implement Clone for AudioRecorder
-impl Clone for AudioRecorder {
-    fn clone(&self) -> Self {
+/// Encode `samples` captured by `recorder` as a WAV (16-bit PCM) or MP3 file
+/// Audio captured at a different rate than `preferred_rate` is resampled to it
+pub(crate) fn encode_recording(
+    recorder: &AudioRecorder,
+    samples: Vec<f32>,
+    format: AudioFileFormat,
+    preferred_rate: Option<u32>,
+) -> Result<Vec<u8>> {
+    let (mut sample_rate, channels) = (recorder.sample_rate(), recorder.channels());
+    let mut samples = samples;
+    // The device fell back to its default rate; archive at the rate that was asked for
+    if let Some(preferred) = preferred_rate.filter(|&r| r != sample_rate) {
+        samples = resample_interleaved(&samples, channels, sample_rate, preferred);
+        sample_rate = preferred;
+    }
+    match format {
+        AudioFileFormat::Wav => samples_to_wav_i16(&samples, sample_rate, channels),
+        AudioFileFormat::Mp3 => samples_to_mp3(&samples, sample_rate, channels),
+    }
+}
+
+/// Get current recording status
+#[tauri::command]
+pub fn is_recording(recorder: State<'_, AudioRecorder>) -> bool {
+    recorder.is_recording()
+}
+
+/// Length of the current recording in seconds, based on the captured samples
+/// Returns 0 when not recording
+#[tauri::command]
+pub fn get_recording_duration(recorder: State<'_, AudioRecorder>) -> f64 {
+    if !recorder.is_recording() {
+        return 0.0;
+    }
+    recorder.buffered_seconds()
+}
+
+/// Peak envelope of the current recording (its last two minutes) for drawing a waveform
+/// `buckets` is clamped to 1-4096; an empty buffer yields all-zero buckets
+#[tauri::command]
+pub fn get_waveform(recorder: State<'_, AudioRecorder>, buckets: usize) -> Vec<WaveformBucket> {
+    recorder.waveform(buckets.clamp(1, MAX_WAVEFORM_BUCKETS))
+}
+
+/// Frame length for level analysis
+const ANALYSIS_FRAME_SECS: f32 = 0.02;
+
+/// Frames quieter than this count as silence
+const SILENCE_DBFS: f32 = -50.0;
+
+/// Samples at or above this magnitude count as clipped
+const CLIP_LEVEL: f32 = 0.999;
+
+/// Reported instead of -inf for digital silence
+const MIN_DBFS: f32 = -120.0;
+
+/// Why a recording is unlikely to transcribe well
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AudioWarning {
+    Empty,
+    TooQuiet,
+    Clipped,
+    MostlySilence,
+    Noisy,
+}
+
+/// Levels of the current recording; all levels in dBFS
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioQuality {
+    pub duration_secs: f32,
+    pub peak_dbfs: f32,
+    pub rms_dbfs: f32,
+    /// Loud frames against the noise floor of the quietest frames
+    pub snr_db: f32,
+    /// Share of samples at full scale, 0-100
+    pub clipping_percent: f32,
+    /// Share of 20ms frames below the silence level, 0-1
+    pub silence_ratio: f32,
+    pub warnings: Vec<AudioWarning>,
+}
+
+/// Measure the current recording so the UI can warn before spending a transcription request
+/// An empty buffer yields zeroed levels and the `empty` warning
+#[tauri::command]
+pub fn analyze_audio(recorder: State<'_, AudioRecorder>) -> AudioQuality {
+    let samples = recorder.get_audio_buffer();
+    analyze_samples(&samples, recorder.sample_rate(), recorder.channels())
+}
+
+fn analyze_samples(samples: &[f32], sample_rate: u32, channels: u16) -> AudioQuality {
+    if samples.is_empty() || sample_rate == 0 {
+        return AudioQuality {
+            duration_secs: 0.0,
+            peak_dbfs: MIN_DBFS,
+            rms_dbfs: MIN_DBFS,
+            snr_db: 0.0,
+            clipping_percent: 0.0,
+            silence_ratio: 1.0,
+            warnings: vec![AudioWarning::Empty],
+        };
+    }
+
+    let samples: Vec<f32> = samples.iter().map(|&s| sanitize_sample(s)).collect();
+    let to_dbfs = |level: f32| (20.0 * level.log10()).max(MIN_DBFS);
+    let rms = |samples: &[f32]| {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    };
+
+    let peak = samples.iter().map(|s| s.abs()).fold(0.0, f32::max);
+    let clipped = samples.iter().filter(|s| s.abs() >= CLIP_LEVEL).count();
+    let clipping_percent = clipped as f32 / samples.len() as f32 * 100.0;
+
+    let mono = downmix_to_mono(&samples, channels);
+    let frame_len = ((sample_rate as f32 * ANALYSIS_FRAME_SECS) as usize).max(1);
+    let mut frames: Vec<f32> = mono.chunks(frame_len).map(|f| to_dbfs(rms(f))).collect();
+    frames.sort_by(f32::total_cmp);
+
+    let silent = frames.iter().filter(|&&db| db < SILENCE_DBFS).count();
+    let silence_ratio = silent as f32 / frames.len() as f32;
+
+    // Noise floor from the quietest tenth of frames, signal from the loudest tenth
+    let tenth = (frames.len() / 10).max(1);
+    let mean = |frames: &[f32]| frames.iter().sum::<f32>() / frames.len() as f32;
+    let snr_db = mean(&frames[frames.len() - tenth..]) - mean(&frames[..tenth]);
+
+    let quality = AudioQuality {
+        duration_secs: mono.len() as f32 / sample_rate as f32,
+        peak_dbfs: to_dbfs(peak),
+        rms_dbfs: to_dbfs(rms(&samples)),
+        snr_db,
+        clipping_percent,
+        silence_ratio,
+        warnings: Vec::new(),
+    };
+    let warnings = [
+        (quality.peak_dbfs < -30.0, AudioWarning::TooQuiet),
+        (quality.clipping_percent > 0.1, AudioWarning::Clipped),
+        (quality.silence_ratio > 0.8, AudioWarning::MostlySilence),
+        (silence_ratio < 0.8 && quality.snr_db < 10.0, AudioWarning::Noisy),
+    ];
+    AudioQuality {
+        warnings: warnings.into_iter().filter(|(hit, _)| *hit).map(|(_, w)| w).collect(),
+        ..quality
+    }
+}
+
+/// Process recorded audio: transcribe and return text
+/// `language` is an ISO-639-1 hint such as `de`, in place of the `transcription_language`
+/// setting; without either the provider detects the language itself
+/// Long recordings are transcribed in chunks, emitting `transcription-progress`
+/// If transcription fails the recording is kept for `retry_transcription`
+///
+/// With auto-send enabled the transcript is also appended to `messages` and sent
+/// after the configured delay: `auto-send-pending` is emitted first, then `ai-done`
+/// with the reply (or `auto-send-cancelled` / `auto-send-failed`)
+#[tauri::command]
+pub async fn process_audio(
+    app: AppHandle,
+    recorder: State<'_, AudioRecorder>,
+    config: State<'_, ConfigState>,
+    settings: State<'_, SettingsStore>,
+    requests: State<'_, InFlightRequests>,
+    messages: Option<Vec<Message>>,
+    language: Option<String>,
+) -> Result<String, GhostError> {
+    config.touch();
+
+    let defer = settings.get().defer_failed_transcriptions;
+    let options = TranscriptionOptions {
+        language,
+        ..TranscriptionOptions::default()
+    };
+    let transcript = transcribe_buffer(
+        &app,
+        &recorder,
+        &config,
+        &requests,
+        &options,
+        "No audio recorded",
+        defer,
+    )
+    .await?;
+
+    let auto_send = settings.get().auto_send;
+    if auto_send.enabled && !transcript.trim().is_empty() {
+        spawn_auto_send(&app, messages.unwrap_or_default(), &transcript, auto_send.delay_ms)
+            .map_err(GhostError::Other)?;
+    }
+
+    Ok(transcript)
+}
+
+/// Send `transcript` as a new user message once `delay_ms` has passed
+/// Runs as a chat task, so cancelling it during the delay means nothing is sent
+pub(crate) fn spawn_auto_send(
+    app: &AppHandle,
+    mut messages: Vec<Message>,
+    transcript: &str,
+    delay_ms: u64,
+) -> Result<(), String> {
+    let request_id = history::new_id();
+    let pending = AutoSendPending {
+        request_id: request_id.clone(),
+        transcript: transcript.to_string(),
+        delay_ms,
+    };
+    app.emit("auto-send-pending", pending)
+        .map_err(|e| format!("Failed to emit event: {}", e))?;
+
+    messages.push(Message {
+        role: String::from("user"),
+        content: MessageContent::Text(transcript.to_string()),
+        pinned: false,
+    });
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let options = ChatOptions {
+            app: Some(app.clone()),
+            request_id: Some(request_id.clone()),
+            user_turn: true,
+            ..ChatOptions::default()
+        };
+        let api_key = app.state::<ConfigState>().get().openai_api_key;
+
+        let requests = app.state::<InFlightRequests>();
+        let result = requests
+            .run(Some(request_id.clone()), TaskKind::Chat, async {
+                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                send_message_impl(&api_key, messages, Vec::new(), &options).await
+            })
+            .await;
+
+        match result {
+            Ok(response) => match app.state::<Transfers>().deliver(&response) {
+                Ok(delivery) => {
+                    let _ = app.emit("ai-done", delivery);
+                }
+                Err(e) => eprintln!("Failed to serialize auto-sent reply: {}", e),
+            },
+            Err(e) if e.is::<Cancelled>() => {
+                let _ = app.emit("auto-send-cancelled", &request_id);
+            }
+            Err(e) => {
+                let failed = AutoSendFailed {
+                    request_id,
+                    error: format!("OpenAI API error: {}", e),
+                };
+                let _ = app.emit("auto-send-failed", failed);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Resend the recording kept after a failed `process_audio`, without re-recording
+/// The recording is cleared once a retry succeeds
+#[tauri::command]
+pub async fn retry_transcription(
+    app: AppHandle,
+    recorder: State<'_, AudioRecorder>,
+    config: State<'_, ConfigState>,
+    requests: State<'_, InFlightRequests>,
+) -> Result<String, String> {
+    config.touch();
+
+    if recorder.is_recording() {
+        return Err("Recording in progress; stop it before retrying".to_string());
+    }
+    let options = TranscriptionOptions::default();
+    let empty_error = "No recording to retry";
+    transcribe_buffer(&app, &recorder, &config, &requests, &options, empty_error, false)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// A transcription failed even after retries, so the recording was saved to disk
+/// Returned (as the error message) by `process_audio` and emitted as `transcription-deferred`
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptionDeferred {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+impl std::fmt::Display for TranscriptionDeferred {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Transcription failed ({}); the recording was saved to {} to transcribe later",
+            self.error,
+            self.path.display()
+        )
+    }
+}
+
+impl std::error::Error for TranscriptionDeferred {}
+
+/// Transcribe a recording saved by a deferred `process_audio` (or any WAV file)
+/// A deferred recording is deleted once it has been transcribed
+#[tauri::command]
+pub async fn transcribe_saved(
+    app: AppHandle,
+    config: State<'_, ConfigState>,
+    requests: State<'_, InFlightRequests>,
+    path: PathBuf,
+) -> Result<String, String> {
+    config.touch();
+
+    let bytes = std::fs::read(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let (samples, sample_rate, channels) =
+        wav_to_samples(&bytes).map_err(|e| format!("Invalid recording: {}", e))?;
+    let clip = AudioClip {
+        samples: &samples,
+        sample_rate,
+        channels,
+    };
+
+    let config = config.get();
+    let transcription = requests
+        .run(
+            None,
+            TaskKind::Transcription,
+            transcription::transcribe(&config, &clip, Some(&app)),
+        )
+        .await
+        .map_err(|e| format!("Transcription failed: {}", e))?;
+    usage::record_transcription(&app, transcription::provider_model(&config), clip.duration_secs());
+
+    if deferred_dir(&app).is_ok_and(|dir| path.starts_with(dir)) {
+        if let Err(e) = std::fs::remove_file(&path) {
+            eprintln!("Warning: Could not delete transcribed recording: {}", e);
+        }
+    }
+    Ok(transcription)
+}
+
+/// Where deferred recordings are kept
+fn deferred_dir(app: &AppHandle) -> Result<PathBuf> {
+    Ok(app.path().app_data_dir()?.join("deferred"))
+}
+
+/// Save `samples` as WAV to the deferred directory and emit `transcription-deferred`
+fn defer_recording(
+    app: &AppHandle,
+    recorder: &AudioRecorder,
+    samples: &[f32],
+    error: &anyhow::Error,
+) -> Result<TranscriptionDeferred> {
+    let deferred = TranscriptionDeferred {
+        path: write_deferred(app, recorder, samples)?,
+        error: error.to_string(),
+    };
+    let _ = app.emit("transcription-deferred", &deferred);
+    Ok(deferred)
+}
+
+/// Save the unprocessed recording to the deferred directory so quitting mid-recording
+/// doesn't lose it; `transcribe_saved` picks it up later
+///
+/// # Returns
+/// The saved file, or `None` when nothing was recorded
+pub(crate) fn save_unprocessed(
+    app: &AppHandle,
+    recorder: &AudioRecorder,
+) -> Result<Option<PathBuf>> {
+    let samples = recorder.get_audio_buffer();
+    if samples.is_empty() {
+        return Ok(None);
+    }
+    write_deferred(app, recorder, &samples).map(Some)
+}
+
+fn write_deferred(app: &AppHandle, recorder: &AudioRecorder, samples: &[f32]) -> Result<PathBuf> {
+    let dir = deferred_dir(app)?;
+    std::fs::create_dir_all(&dir).context("Failed to create deferred recordings directory")?;
+    // Saves within the same second get distinct names, and `create_new` refuses to
+    // overwrite a kept recording if one still collides
+    let path = dir.join(format!(
+        "recording-{}-{}.wav",
+        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+        history::new_id()
+    ));
+
+    let preferred_rate = app.state::<SettingsStore>().get().preferred_sample_rate;
+    let bytes = encode_recording(recorder, samples.to_vec(), AudioFileFormat::Wav, preferred_rate)?;
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .context("Failed to save recording")?;
+    std::io::Write::write_all(&mut file, &bytes).context("Failed to save recording")?;
+    Ok(path)
+}
+
+/// Transcribe the whole buffer, clearing it only on success
+/// Registered as a task, so `cancel_task` can abort it (the buffer is then kept)
+/// With `retry_empty_transcriptions` an empty result is retried once with the
+/// recording boosted (see `retry_boosted`)
+async fn transcribe_buffer(
+    app: &AppHandle,
+    recorder: &AudioRecorder,
+    config: &ConfigState,
+    requests: &InFlightRequests,
+    options: &TranscriptionOptions,
+    empty_error: &str,
+    defer: bool,
+) -> Result<String, GhostError> {
+    let config = config.get();
+    let result = transcribe_recorded(Some(app), recorder, &config, requests, options).await;
+    match result {
+        Ok(Some(transcription)) => Ok(transcription),
+        Ok(None) => Err(GhostError::NoAudio(empty_error.to_string())),
+        // Silence or a cancel isn't worth keeping; anything else (offline, quota) is
+        Err((e, audio_samples))
+            if defer && !e.is::<EmptyTranscription>() && !e.is::<Cancelled>() =>
+        {
+            Err(match defer_recording(app, recorder, &audio_samples, &e) {
+                Ok(deferred) => GhostError::TranscriptionDeferred(deferred.to_string()),
+                Err(save_error) => {
+                    let message = format!(
+                        "Transcription failed: {} (saving the recording also failed: {})",
+                        e, save_error
+                    );
+                    GhostError::classify(&e, message, GhostError::TranscriptionFailed)
+                }
+            })
+        }
+        Err((e, _)) => {
+            let message = format!("Transcription failed: {}", e);
+            Err(GhostError::classify(&e, message, GhostError::TranscriptionFailed))
+        }
+    }
+}
+
+/// Transcribe the buffered recording as a `Transcription` task with the configured
+/// provider, clearing the buffer (keeping a copy for `retranscribe`) only on success
+/// `Ok(None)` when nothing is buffered; on failure the samples stay in the buffer and
+/// come back with the error
+async fn transcribe_recorded(
+    app: Option<&AppHandle>,
+    recorder: &AudioRecorder,
+    config: &AppConfig,
+    requests: &InFlightRequests,
+    options: &TranscriptionOptions,
+) -> Result<Option<String>, (anyhow::Error, Vec<f32>)> {
+    let audio_samples = recorder.get_audio_buffer();
+    if audio_samples.is_empty() {
+        return Ok(None);
+    }
+
+    println!("Processing {} audio samples", audio_samples.len());
+
+    let clip = AudioClip {
+        samples: &audio_samples,
+        sample_rate: recorder.sample_rate(),
+        channels: recorder.channels(),
+    };
+    let result = run_transcription(app, config, requests, &clip, options).await;
+    let duration_secs = clip.duration_secs();
+
+    match result {
+        Ok(transcription) => {
+            if let Some(app) = app {
+                let model = transcription::provider_model(config);
+                usage::record_transcription(app, model, duration_secs);
+            }
+            recorder.finish_transcription(audio_samples);
+            Ok(Some(transcription))
+        }
+        Err(e) => Err((e, audio_samples)),
+    }
+}
+
+/// Transcribe `clip` as a `Transcription` task, so it can be listed and cancelled
+/// With `retry_empty_transcriptions` an empty result is retried once boosted
+async fn run_transcription(
+    app: Option<&AppHandle>,
+    config: &AppConfig,
+    requests: &InFlightRequests,
+    clip: &AudioClip<'_>,
+    options: &TranscriptionOptions,
+) -> Result<String> {
+    let result = requests
+        .run(
+            None,
+            TaskKind::Transcription,
+            transcription::transcribe_with(config, clip, app, options),
+        )
+        .await;
+    let retry_empty =
+        app.filter(|app| app.state::<SettingsStore>().get().retry_empty_transcriptions);
+    match (result, retry_empty) {
+        (Err(e), Some(app)) if e.is::<EmptyTranscription>() => {
+            retry_boosted(app, config, requests, clip, options, e).await
+        }
+        (result, _) => result,
+    }
+}
+
+/// Dry run of `process_audio` / `retranscribe`: the multipart uploads the configured
+/// backend would be sent for the current recording, without sending anything
+/// Uses the pending buffer if there is one, otherwise the last transcribed recording
+/// Field names (including the `transcription_language` fallback), file name, MIME type,
+/// size and format of the audio are reported; the audio itself and the API key are not
+/// Only the OpenAI and whisper.cpp backends upload a multipart form
+#[tauri::command]
+pub async fn describe_transcription_upload(
+    app: AppHandle,
+    recorder: State<'_, AudioRecorder>,
+    config: State<'_, ConfigState>,
+    options: Option<TranscriptionOptions>,
+) -> Result<Vec<UploadDescription>, String> {
+    let mut samples = recorder.get_audio_buffer();
+    if samples.is_empty() {
+        samples = recorder.last_transcribed();
+    }
+    if samples.is_empty() {
+        return Err("No recording to describe".to_string());
+    }
+
+    let clip = AudioClip {
+        samples: &samples,
+        sample_rate: recorder.sample_rate(),
+        channels: recorder.channels(),
+    };
+    transcription::transcriber_with(&config.get(), Some(&app), &options.unwrap_or_default())
+        .and_then(|transcriber| transcriber.describe_upload(&clip))
+        .map_err(|e| format!("Failed to build the upload: {}", e))
+}
+
+/// Peak level a quiet recording is raised to before retrying an empty transcription
+const RETRY_PEAK: f32 = 0.9;
+
+/// Largest boost for the retry (about +26 dB); more only amplifies noise
+const MAX_RETRY_GAIN: f32 = 20.0;
+
+/// Second and last attempt at a recording that came back empty: its peak is raised
+/// to `RETRY_PEAK` and `transcription-auto-retry` is emitted
+/// Records usage for the empty first attempt, and for the retry unless it succeeds
+/// (the caller records a transcript)
+/// Returns `error` without a request when boosting can't help (silent or already loud)
+async fn retry_boosted(
+    app: &AppHandle,
+    config: &AppConfig,
+    requests: &InFlightRequests,
+    clip: &AudioClip<'_>,
+    options: &TranscriptionOptions,
+    error: anyhow::Error,
+) -> Result<String> {
+    let peak = clip.samples.iter().map(|s| s.abs()).fold(0.0, f32::max);
+    if peak == 0.0 || peak >= RETRY_PEAK {
+        return Err(error);
+    }
+    let gain = (RETRY_PEAK / peak).min(MAX_RETRY_GAIN);
+    let mut boosted = clip.samples.to_vec();
+    apply_gain(&mut boosted, gain);
+    println!("Empty transcription; retrying with {:.1}x gain", gain);
+    let _ = app.emit("transcription-auto-retry", TranscriptionAutoRetry { gain });
+
+    // An empty transcript is billed like any other
+    let model = transcription::provider_model(config);
+    usage::record_transcription(app, model, clip.duration_secs());
+
+    let boosted = AudioClip {
+        samples: &boosted,
+        sample_rate: clip.sample_rate,
+        channels: clip.channels,
+    };
+    let result = requests
+        .run(
+            None,
+            TaskKind::Transcription,
+            transcription::transcribe_with(config, &boosted, Some(app), options),
+        )
+        .await;
+    if result.as_ref().is_err_and(|e| e.is::<EmptyTranscription>()) {
+        usage::record_transcription(app, model, clip.duration_secs());
+    }
+    result
+}
+
+/// Transcribe the recording again with different options, e.g. a language hint or
+/// prompt after a poor result, without re-recording
+/// Uses the pending buffer if there is one (e.g. after a failed `process_audio`),
+/// otherwise the last transcribed recording; neither is cleared
+///
+/// # Returns
+/// The new transcript, for comparison with the previous one
+#[tauri::command]
+pub async fn retranscribe(
+    app: AppHandle,
+    recorder: State<'_, AudioRecorder>,
+    config: State<'_, ConfigState>,
+    requests: State<'_, InFlightRequests>,
+    options: TranscriptionOptions,
+) -> Result<String, String> {
+    config.touch();
+
+    if recorder.is_recording() {
+        return Err("Recording in progress; stop it before retranscribing".to_string());
+    }
+
+    let mut samples = recorder.get_audio_buffer();
+    if samples.is_empty() {
+        samples = recorder.last_transcribed();
+    }
+    if samples.is_empty() {
+        return Err("No recording to transcribe".to_string());
+    }
+
+    let clip = AudioClip {
+        samples: &samples,
+        sample_rate: recorder.sample_rate(),
+        channels: recorder.channels(),
+    };
+
+    let config = config.get();
+    let transcription = requests
+        .run(
+            None,
+            TaskKind::Transcription,
+            transcription::transcribe_with(&config, &clip, Some(&app), &options),
+        )
+        .await
+        .map_err(|e| format!("Transcription failed: {}", e))?;
+    let model = options.model.as_deref().unwrap_or(transcription::provider_model(&config));
+    usage::record_transcription(&app, model, clip.duration_secs());
+
+    Ok(transcription)
+}
+
+/// One backend's run in `benchmark_transcription`
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum BenchmarkResult {
+    Ok {
+        provider: &'static str,
+        transcript: String,
+        elapsed_ms: u64,
+        /// Processing time over audio length; below 1 is faster than real time
+        realtime_factor: f64,
+    },
+    /// Not configured (e.g. no API key) or not running (no whisper.cpp server), so not tried
+    Skipped {
+        provider: &'static str,
+        reason: String,
+    },
+    Failed {
+        provider: &'static str,
+        error: String,
+    },
+}
+
+/// Transcribe the same audio with every backend and time each, to compare speed and output
+/// `sample` is a base64 WAV file; without it the current (or last transcribed) recording is used
+/// Backends run one after another so their timings don't affect each other, and without
+/// retries so a slow backend isn't hidden
+/// Runs on paid backends are recorded in the usage log like any other transcription
+#[tauri::command]
+pub async fn benchmark_transcription(
+    app: AppHandle,
+    recorder: State<'_, AudioRecorder>,
+    config: State<'_, ConfigState>,
+    sample: Option<String>,
+) -> Result<Vec<BenchmarkResult>, String> {
+    let (samples, sample_rate, channels) = match sample {
+        Some(base64_wav) => {
+            use base64::Engine;
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(base64_wav.trim())
+                .map_err(|e| format!("Invalid base64 audio: {}", e))?;
+            wav_to_samples(&bytes).map_err(|e| format!("Invalid WAV sample: {}", e))?
+        }
+        None => {
+            let mut samples = recorder.get_audio_buffer();
+            if samples.is_empty() {
+                samples = recorder.last_transcribed();
+            }
+            (samples, recorder.sample_rate(), recorder.channels())
+        }
+    };
+    if samples.is_empty() {
+        return Err("No audio to benchmark".to_string());
+    }
+
+    let clip = AudioClip {
+        samples: &samples,
+        sample_rate,
+        channels,
+    };
+    let base = config.get();
+    let mut results = Vec::new();
+
+    for provider in TranscriptionProvider::ALL {
+        let config = AppConfig {
+            transcription_provider: provider,
+            ..base.clone()
+        };
+        let name = transcription::provider_model(&config);
+        if provider == TranscriptionProvider::OpenAi && config.openai_api_key.is_empty() {
+            results.push(BenchmarkResult::Skipped {
+                provider: name,
+                reason: String::from("OPENAI_API_KEY is not set (or the app is locked)"),
+            });
+            continue;
+        }
+        if provider == TranscriptionProvider::WhisperCpp
+            && !transcription::whisper_cpp_reachable(&config).await
+        {
+            results.push(BenchmarkResult::Skipped {
+                provider: name,
+                reason: format!("No whisper.cpp server running at {}", config.whisper_cpp_url),
+            });
+            continue;
+        }
+        let transcriber = match transcription::transcriber(&config, None) {
+            Ok(transcriber) => transcriber,
+            Err(e) => {
+                results.push(BenchmarkResult::Skipped {
+                    provider: name,
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+
+        let started = Instant::now();
+        let result = match transcriber.transcribe(&clip).await {
+            Ok(transcript) => {
+                let elapsed = started.elapsed();
+                if provider != TranscriptionProvider::WhisperCpp {
+                    usage::record_transcription(&app, name, clip.duration_secs());
+                }
+                BenchmarkResult::Ok {
+                    provider: name,
+                    transcript: transcript.trim().to_string(),
+                    elapsed_ms: elapsed.as_millis() as u64,
+                    realtime_factor: elapsed.as_secs_f64() / clip.duration_secs().max(f64::EPSILON),
+                }
+            }
+            Err(e) => BenchmarkResult::Failed {
+                provider: name,
+                error: e.to_string(),
+            },
+        };
+        results.push(result);
+    }
+
+    Ok(results)
+}
+
+/// Set the idle timeout after which the microphone is released, and persist it
+/// `None` keeps the stream open until exit
+#[tauri::command]
+pub fn set_mic_idle_timeout(
+    recorder: State<'_, AudioRecorder>,
+    settings: State<'_, SettingsStore>,
+    seconds: Option<u64>,
+) -> Result<(), String> {
+    let seconds = seconds.filter(|s| *s > 0);
+    recorder.set_idle_timeout(seconds);
+    settings
+        .update(|s| s.mic_idle_timeout_secs = seconds)
+        .map_err(|e| format!("Failed to save settings: {}", e))
+}
+
+/// Set a fixed capture gain (1.0 = unity) and persist it
+/// Values are clamped to 0-8; amplified samples are clipped at full scale
+/// The gain is remembered for the current input device; before any device has
+/// been opened it sets the global default used for unseen devices
+///
+/// # Returns
+/// The gain actually applied
+#[tauri::command]
+pub fn set_input_gain(
+    recorder: State<'_, AudioRecorder>,
+    settings: State<'_, SettingsStore>,
+    factor: f32,
+) -> Result<f32, String> {
+    let gain = recorder.set_input_gain(factor);
+    settings
+        .update(|s| match recorder.device_key() {
+            Some(key) => s.device_audio.entry(key).or_default().input_gain = Some(gain),
+            None => s.input_gain = gain,
+        })
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(gain)
+}
+
+/// Turn the typing-noise gate on (sensitivity 0-1, higher catches softer clicks) or
+/// off with `None`, and persist it
+/// Takes effect immediately, also on a recording in progress
+///
+/// # Returns
+/// The sensitivity actually applied
+#[tauri::command]
+pub fn set_typing_noise_gate(
+    recorder: State<'_, AudioRecorder>,
+    settings: State<'_, SettingsStore>,
+    sensitivity: Option<f32>,
+) -> Result<Option<f32>, String> {
+    let sensitivity = recorder.set_typing_noise_gate(sensitivity);
+    settings
+        .update(|s| s.typing_noise_gate = sensitivity)
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(sensitivity)
+}
+
+/// Move remembered preferences to a device's new name, e.g. after it was renamed
+/// in Audio MIDI Setup; settings already stored under `to` are replaced
+#[tauri::command]
+pub fn rename_device_settings(
+    settings: State<'_, SettingsStore>,
+    from: String,
+    to: String,
+) -> Result<(), String> {
+    settings
+        .update(|s| {
+            let remembered = s.device_audio.remove(&from)?;
+            s.device_audio.insert(to, remembered);
+            Some(())
+        })
+        .map_err(|e| format!("Failed to save settings: {}", e))?
+        .ok_or_else(|| format!("No settings remembered for device {:?}", from))
+}
+
+/// Configure ring-buffer mode: keep only the last `seconds` of audio while recording
+/// Pass `None` to keep the full recording
+#[tauri::command]
+pub fn set_retention_window(recorder: State<'_, AudioRecorder>, seconds: Option<f32>) {
+    recorder.set_retention_window(seconds);
+}
+
+/// Transcribe only the most recent `seconds` of captured audio
+/// Recording continues; the buffer is left intact
+/// Registered as a task like `process_audio`, so `cancel_task` can abort it
+#[tauri::command]
+pub async fn process_recent(
+    app: AppHandle,
+    recorder: State<'_, AudioRecorder>,
+    config: State<'_, ConfigState>,
+    requests: State<'_, InFlightRequests>,
+    seconds: f32,
+) -> Result<String, String> {
+    config.touch();
+
+    if !seconds.is_finite() || seconds <= 0.0 {
+        return Err("Duration must be a positive number of seconds".to_string());
+    }
+
+    let audio_samples = recorder.get_recent_audio(seconds);
+
+    if audio_samples.is_empty() {
+        return Err("No audio recorded".to_string());
+    }
+
+    println!("Processing last {}s ({} samples)", seconds, audio_samples.len());
+
+    let clip = AudioClip {
+        samples: &audio_samples,
+        sample_rate: recorder.sample_rate(),
+        channels: recorder.channels(),
+    };
+
+    let config = config.get();
+    let options = TranscriptionOptions::default();
+    let transcription = run_transcription(Some(&app), &config, &requests, &clip, &options)
+        .await
+        .map_err(|e| format!("Transcription failed: {}", e))?;
+    usage::record_transcription(&app, transcription::provider_model(&config), clip.duration_secs());
+
+    Ok(transcription)
+}
+
+/// Send the recording directly to an audio-capable model, skipping transcription
+/// The model hears the audio and replies in one call; `process_audio` remains the default flow
+///
+/// # Arguments
+/// * `messages` - Conversation so far; the audio is appended as a new user message
+/// * `prompt` - Optional text sent alongside the audio
+/// * `model` - Audio-capable model, defaults to `gpt-4o-audio-preview`
+#[tauri::command]
+pub async fn send_audio_message(
+    app: AppHandle,
+    recorder: State<'_, AudioRecorder>,
+    config: State<'_, ConfigState>,
+    mut messages: Vec<Message>,
+    prompt: Option<String>,
+    model: Option<String>,
+) -> Result<ChatResponse, String> {
+    use base64::Engine;
+
+    config.touch();
+
+    let model = model.unwrap_or_else(|| AUDIO_MODEL.to_string());
+    if !supports_audio_input(&model) {
+        return Err(format!("Model {} does not accept audio input", model));
+    }
+
+    let audio_samples = recorder.get_audio_buffer();
+    if audio_samples.is_empty() {
+        return Err("No audio recorded".to_string());
+    }
+
+    // Audio input expects integer PCM
+    let wav = samples_to_wav_i16(&audio_samples, recorder.sample_rate(), recorder.channels())
+        .map_err(|e| format!("Failed to encode audio: {}", e))?;
+
+    let mut parts = Vec::new();
+    if let Some(prompt) = prompt.filter(|p| !p.trim().is_empty()) {
+        parts.push(ContentPart::Text {
+            text: prompt,
+            cache_control: None,
+        });
+    }
+    parts.push(ContentPart::InputAudio {
+        input_audio: InputAudio {
+            data: base64::engine::general_purpose::STANDARD.encode(wav),
+            format: String::from("wav"),
+        },
+    });
+    messages.push(Message {
+        role: String::from("user"),
+        content: MessageContent::Parts(parts),
+        pinned: false,
+    });
+
+    let options = ChatOptions {
+        app: Some(app),
+        model: Some(model),
+        user_turn: true,
+        ..ChatOptions::default()
+    };
+
+    let api_key = config.get().openai_api_key;
+    let response = send_message_impl(&api_key, messages, Vec::new(), &options)
+        .await
+        .map_err(|e| format!("OpenAI API error: {}", e))?;
+
+    recorder.clear_buffer();
+
+    Ok(response)
+}
+
+/// The system has no input device to record from
+#[derive(Debug)]
+pub struct NoInputDevice;
+
+impl std::fmt::Display for NoInputDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No input device available")
+    }
+}
+
+impl std::error::Error for NoInputDevice {}
+
+/// Input device used for capture
+pub(crate) fn input_device() -> Result<cpal::Device> {
+    use cpal::traits::HostTrait;
+
+    cpal::default_host().default_input_device().ok_or_else(|| NoInputDevice.into())
+}
+
+/// Key for remembering settings per input device: its name, plus its default format
+/// when another input device has the same name
+/// Identical devices (same name and format) can't be told apart and share settings
+fn device_key(device: &cpal::Device) -> Option<String> {
+    use cpal::traits::{DeviceTrait, HostTrait};
+
+    let name = device.name().ok()?;
+    let same_name = cpal::default_host()
+        .input_devices()
+        .map(|devices| {
+            devices
+                .filter(|d| d.name().is_ok_and(|n| n == name))
+                .count()
+        })
+        .unwrap_or(1);
+    if same_name <= 1 {
+        return Some(name);
+    }
+
+    match device.default_input_config() {
+        Ok(config) => Some(format!(
+            "{} ({}ch {}Hz)",
+            name,
+            config.channels(),
+            config.sample_rate().0
+        )),
+        Err(_) => Some(name),
+    }
+}
+
+/// Update the remembered settings for an input device and persist them
+fn remember_device_settings(
+    app: &AppHandle,
+    key: &str,
+    f: impl FnOnce(&mut DeviceAudioSettings),
+) {
+    let result = app
+        .state::<SettingsStore>()
+        .update(|s| f(s.device_audio.entry(key.to_string()).or_default()));
+    if let Err(e) = result {
+        eprintln!("Warning: Could not save device settings: {}", e);
+    }
+}
+
+/// Pick the supported config matching `request`, or `None` if the device can't honor it
+fn find_input_config(
+    device: &cpal::Device,
+    request: &InputConfigRequest,
+) -> Result<Option<cpal::SupportedStreamConfig>> {
+    use cpal::traits::DeviceTrait;
+
+    let default_rate = device.default_input_config()?.sample_rate().0;
+    let wanted_rate = request.sample_rate.unwrap_or(default_rate);
+
+    let config = device.supported_input_configs()?.find_map(|range| {
+        let format_ok = request
+            .sample_format
+            .as_ref()
+            .map_or(true, |f| format!("{:?}", range.sample_format()).eq_ignore_ascii_case(f));
+        let channels_ok = request.channels.map_or(true, |c| range.channels() == c);
+        let rate_ok = (range.min_sample_rate().0..=range.max_sample_rate().0).contains(&wanted_rate);
+
+        (format_ok && channels_ok && rate_ok)
+            .then(|| range.with_sample_rate(cpal::SampleRate(wanted_rate)))
+    });
+
+    Ok(config)
+}
+
+/// Detect the spoken language from the start of the current recording
+/// The result is cached until the buffer is cleared, so repeated calls don't re-upload
+///
+/// # Arguments
+/// * `seconds` - Length of the prefix to analyze (default 5s)
+///
+/// # Returns
+/// ISO-639-1 language code, usable as the transcription language hint
+#[tauri::command]
+pub async fn detect_language(
+    app: AppHandle,
+    recorder: State<'_, AudioRecorder>,
+    config: State<'_, ConfigState>,
+    seconds: Option<f32>,
+) -> Result<String, String> {
+    if let Some(language) = recorder.detected_language() {
+        return Ok(language);
+    }
+
+    let audio_samples = recorder.get_audio_prefix(seconds.unwrap_or(5.0));
+    if audio_samples.is_empty() {
+        return Err("No audio recorded".to_string());
+    }
+
+    let clip = AudioClip {
+        samples: &audio_samples,
+        sample_rate: recorder.sample_rate(),
+        channels: recorder.channels(),
+    };
+
+    let config = config.get();
+    let language = transcription::detect_language(&config, &clip)
+        .await
+        .map_err(|e| format!("Language detection failed: {}", e))?;
+    // Always Whisper, whatever the configured provider
+    usage::record_transcription(&app, "whisper-1", clip.duration_secs());
+
+    recorder.set_detected_language(language.clone());
+    Ok(language)
+}
+
+/// Start (or resume) capturing
+/// Concurrent calls (double hotkey press, UI race) are serialized: the first opens the
+/// stream and the rest find it running, so the second call is a no-op
+pub(crate) fn start_microphone_recording(
+    recorder: &AudioRecorder,
+    requested: Option<InputConfigRequest>,
+    app: Option<AppHandle>,
+) -> Result<()> {
+    start_capture(recorder, requested, |recorder, requested| {
+        open_input_stream(recorder, requested, app, None)
+    })
+}
+
+/// `start_microphone_recording` with the stream opened by `open`, which must have
+/// marked the stream started by the time it returns `Ok`
+fn start_capture(
+    recorder: &AudioRecorder,
+    requested: Option<InputConfigRequest>,
+    open: impl FnOnce(&AudioRecorder, Option<InputConfigRequest>) -> Result<()>,
+) -> Result<()> {
+    let _startup = recorder.lock_startup();
+
+    if recorder.is_recording() && recorder.is_stream_started() {
+        println!("Already recording, ignoring start request");
+        return Ok(());
+    }
+
+    // A finished app-audio capture may still be stopping; it can't be resumed as a mic
+    if recorder.stream_source() == Some(StreamSource::App)
+        && !recorder.release_stream_and_wait(STREAM_RELEASE_TIMEOUT)
+    {
+        anyhow::bail!("App audio capture did not stop in time; try again");
+    }
+
+    // Check if stream already started
+    if recorder.is_stream_started() {
+        if requested.is_some() {
+            eprintln!("Warning: Stream already running, ignoring requested input config");
+        }
+        println!("Stream already started, just resuming recording");
+        recorder.set_recording(true);
+        return Ok(());
+    }
+
+    // Set recording flag
+    recorder.set_recording(true);
+    recorder.clear_buffer();
+
+    let result = open(recorder, requested);
+    if result.is_err() {
+        recorder.set_recording(false);
+    }
+    result
+}
+
+/// Payload of the `recording-error` event
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingError {
+    pub message: String,
+    /// Recording carried on with the new default input device
+    pub recovered: bool,
+}
+
+/// An input configuration that failed to open, and why
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamAttempt {
+    /// e.g. `48000Hz/2ch F32`
+    pub config: String,
+    pub error: String,
+}
+
+impl StreamAttempt {
+    fn new(config: &cpal::SupportedStreamConfig, error: &anyhow::Error) -> Self {
+        Self {
+            config: describe_config(config),
+            error: error.to_string(),
+        }
+    }
+}
+
+/// No input configuration of the device produced a working stream
+#[derive(Debug)]
+pub struct InputStreamFailed {
+    pub device: String,
+    pub attempts: Vec<StreamAttempt>,
+}
+
+impl std::fmt::Display for InputStreamFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let attempts: Vec<String> = self
+            .attempts
+            .iter()
+            .map(|a| format!("{}: {}", a.config, a.error))
+            .collect();
+        write!(f, "Could not open {} (tried {})", self.device, attempts.join("; "))
+    }
+}
+
+impl std::error::Error for InputStreamFailed {}
+
+fn describe_config(config: &cpal::SupportedStreamConfig) -> String {
+    format!(
+        "{}Hz/{}ch {:?}",
+        config.sample_rate().0,
+        config.channels(),
+        config.sample_format()
+    )
+}
+
+/// Open the input stream on the current default device and start capturing into the buffer
+/// If the chosen format won't open, the device's other supported formats are tried
+/// (unless `input_config_fallback` is off) before failing with `InputStreamFailed`
+/// With `required_format` set (after a device was lost mid-recording) the stream is
+/// only opened if its sample rate and channel count match what is already buffered
+fn open_input_stream(
+    recorder: &AudioRecorder,
+    requested: Option<InputConfigRequest>,
+    app: Option<AppHandle>,
+    required_format: Option<(u32, u16)>,
+) -> Result<()> {
+    use cpal::traits::DeviceTrait;
+
+    let device = input_device()?;
+
+    println!("Using input device: {:?}", device.name());
+
+    // Restore what was last used on this device; unseen devices get the global defaults
+    let key = device_key(&device);
+    let (remembered, global_gain, preferred_rate, fallback) = match &app {
+        Some(app) => {
+            let settings = app.state::<SettingsStore>().get();
+            let remembered = key
+                .as_ref()
+                .and_then(|k| settings.device_audio.get(k).cloned())
+                .unwrap_or_default();
+            recorder.set_typing_noise_gate(settings.typing_noise_gate);
+            (
+                remembered,
+                Some(settings.input_gain),
+                settings.preferred_sample_rate,
+                settings.input_config_fallback,
+            )
+        }
+        None => (DeviceAudioSettings::default(), None, None, true),
+    };
+    if let Some(gain) = remembered.input_gain.or(global_gain) {
+        recorder.set_input_gain(gain);
+    }
+    recorder.set_device_key(key.clone());
+
+    let explicit = requested.clone();
+    let preferred = preferred_rate.map(|rate| InputConfigRequest {
+        sample_rate: Some(rate),
+        ..Default::default()
+    });
+    let config = match requested.or(remembered.input_config).or(preferred) {
+        Some(request) => match find_input_config(&device, &request)? {
+            Some(config) => {
+                // Remember an explicitly requested format the device accepted
+                if let (Some(app), Some(key), Some(explicit)) = (&app, &key, explicit) {
+                    remember_device_settings(app, key, |d| d.input_config = Some(explicit));
+                }
+                config
+            }
+            None => {
+                eprintln!(
+                    "Warning: Input config {:?} not supported, using device default",
+                    request
+                );
+                device.default_input_config()?
+            }
+        },
+        None => device.default_input_config()?,
+    };
+    println!("Input config: {:?}", config);
+
+    let format = (config.sample_rate().0, config.channels());
+    if let Some(required) = required_format.filter(|required| *required != format) {
+        anyhow::bail!(
+            "{} captures at {}Hz/{}ch but the recording so far is {}Hz/{}ch",
+            device.name().unwrap_or_else(|_| String::from("Input device")),
+            format.0,
+            format.1,
+            required.0,
+            required.1
+        );
+    }
+
+    // cpal streams can't move between threads, so one thread builds the stream and
+    // owns it until it is released; dropping it closes the device
+    let (opened_tx, opened_rx) = mpsc::channel();
+    let owner = recorder.clone();
+    std::thread::spawn(move || {
+        let opened = open_with_fallback(&device, &config, &owner, &app, fallback, required_format);
+        match opened {
+            Ok(stream) => {
+                let (wake_tx, wake_rx) = mpsc::channel();
+                let generation = owner.claim_stream(StreamSource::Microphone);
+                *owner.stream_wake.lock().unwrap() = Some((generation, wake_tx));
+                let _ = opened_tx.send(Ok(()));
+                hold_stream(stream, &owner, &app, wake_rx, generation);
+                owner.stream_closed(generation);
+            }
+            Err(e) => {
+                let _ = opened_tx.send(Err(e));
+            }
+        }
+    });
+
+    opened_rx
+        .recv()
+        .context("Audio thread exited before opening the stream")?
+}
+
+/// Build and start a stream with `config`, or with the device's other supported
+/// configurations if `fallback` is set
+fn open_with_fallback(
+    device: &cpal::Device,
+    config: &cpal::SupportedStreamConfig,
+    recorder: &AudioRecorder,
+    app: &Option<AppHandle>,
+    fallback: bool,
+    required_format: Option<(u32, u16)>,
+) -> Result<cpal::Stream> {
+    use cpal::traits::DeviceTrait;
+
+    let error = match start_stream(device, config, recorder, app) {
+        Ok(stream) => return Ok(stream),
+        Err(e) => e,
+    };
+    eprintln!("Warning: Could not open input stream ({}): {}", describe_config(config), error);
+
+    let mut attempts = vec![StreamAttempt::new(config, &error)];
+    let candidates = if fallback {
+        fallback_configs(device, config, required_format)
+    } else {
+        Vec::new()
+    };
+    let (opened, failures) =
+        try_configs(candidates, |candidate| start_stream(device, candidate, recorder, app));
+    attempts.extend(failures);
+    if let Some((candidate, stream)) = opened {
+        println!("Input config fallback: {:?}", candidate);
+        return Ok(stream);
+    }
+    Err(InputStreamFailed {
+        device: device.name().unwrap_or_else(|_| String::from("Input device")),
+        attempts,
+    }
+    .into())
+}
+
+/// How often the stream owner checks the idle timeout when nothing wakes it
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Keep `stream` alive until the recorder releases it: paused between recordings,
+/// playing while recording, and released once it has been idle past the timeout
+/// The stream is dropped, closing the device, when this returns
+fn hold_stream(
+    stream: cpal::Stream,
+    recorder: &AudioRecorder,
+    app: &Option<AppHandle>,
+    wake: mpsc::Receiver<()>,
+    generation: u64,
+) {
+    use cpal::traits::StreamTrait;
+
+    let mut playing = true;
+    while recorder.holds_stream(generation) {
+        let recording = recorder.is_recording();
+        if recording != playing {
+            let result = if recording { stream.play() } else { stream.pause() };
+            match result {
+                Ok(()) => playing = recording,
+                // Some backends can't pause; the callback ignores input while not
+                // recording, so the stream just keeps running
+                Err(e) if !recording => {
+                    eprintln!("Warning: Could not pause input stream: {}", e);
+                    playing = false;
+                }
+                Err(e) => {
+                    eprintln!("Could not resume input stream, releasing it: {}", e);
+                    recorder.release_stream(generation);
+                    break;
+                }
+            }
+        }
+
+        if let Some(stopped) = recorder.take_auto_stop() {
+            println!("Recording stopped automatically ({:?})", stopped.reason);
+            // The callback only cleared the flag; this starts the idle timer too
+            recorder.set_recording(false);
+            if let Some(app) = app {
+                let _ = app.emit("recording-auto-stopped", stopped);
+            }
+        }
+
+        if recorder.idle_expired() {
+            println!("Microphone idle, releasing input stream");
+            recorder.release_stream(generation);
+            if let Some(app) = app {
+                let _ = app.emit("mic-released", ());
+            }
+            break;
+        }
+
+        match wake.recv_timeout(IDLE_CHECK_INTERVAL) {
+            Ok(()) | Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    recorder.clear_stream_wake(generation);
+}
+
+/// Build and start a stream with `config`, capturing into `recorder`
+fn start_stream(
+    device: &cpal::Device,
+    config: &cpal::SupportedStreamConfig,
+    recorder: &AudioRecorder,
+    app: &Option<AppHandle>,
+) -> Result<cpal::Stream> {
+    use cpal::traits::StreamTrait;
+
+    recorder.set_stream_format(config.sample_rate().0, config.channels());
+    let capture = Arc::new(recorder.clone());
+
+    // Create stream based on sample format
+    let sample_format = config.sample_format();
+    let config: cpal::StreamConfig = config.clone().into();
+    let stream = match sample_format {
+        cpal::SampleFormat::F32 => build_input_stream::<f32>(device, &config, capture, app)?,
+        cpal::SampleFormat::F64 => build_input_stream::<f64>(device, &config, capture, app)?,
+        cpal::SampleFormat::I8 => build_input_stream::<i8>(device, &config, capture, app)?,
+        cpal::SampleFormat::I16 => build_input_stream::<i16>(device, &config, capture, app)?,
+        cpal::SampleFormat::I32 => build_input_stream::<i32>(device, &config, capture, app)?,
+        cpal::SampleFormat::I64 => build_input_stream::<i64>(device, &config, capture, app)?,
+        cpal::SampleFormat::U8 => build_input_stream::<u8>(device, &config, capture, app)?,
+        cpal::SampleFormat::U16 => build_input_stream::<u16>(device, &config, capture, app)?,
+        cpal::SampleFormat::U32 => build_input_stream::<u32>(device, &config, capture, app)?,
+        cpal::SampleFormat::U64 => build_input_stream::<u64>(device, &config, capture, app)?,
+        _ => anyhow::bail!("Unsupported sample format: {:?}", sample_format),
+    };
+
+    stream.play()?;
+    Ok(stream)
+}
+
+/// The first of `candidates` that `open` succeeds with, and the failures before it
+fn try_configs<S>(
+    candidates: Vec<cpal::SupportedStreamConfig>,
+    mut open: impl FnMut(&cpal::SupportedStreamConfig) -> Result<S>,
+) -> (Option<(cpal::SupportedStreamConfig, S)>, Vec<StreamAttempt>) {
+    let mut attempts = Vec::new();
+    for candidate in candidates {
+        match open(&candidate) {
+            Ok(opened) => return (Some((candidate, opened)), attempts),
+            Err(e) => attempts.push(StreamAttempt::new(&candidate, &e)),
+        }
+    }
+    (None, attempts)
+}
+
+/// Configurations to try after `failed` wouldn't open, from the device's supported ranges
+/// With `required_format` set only configurations matching it are returned
+fn fallback_configs(
+    device: &cpal::Device,
+    failed: &cpal::SupportedStreamConfig,
+    required_format: Option<(u32, u16)>,
+) -> Vec<cpal::SupportedStreamConfig> {
+    use cpal::traits::DeviceTrait;
+
+    match device.supported_input_configs() {
+        Ok(ranges) => candidate_configs(ranges, failed, required_format),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// One configuration per range, at the failed rate or the nearest the range allows,
+/// same channel count first; `failed` itself and duplicates are left out
+fn candidate_configs(
+    ranges: impl IntoIterator<Item = cpal::SupportedStreamConfigRange>,
+    failed: &cpal::SupportedStreamConfig,
+    required_format: Option<(u32, u16)>,
+) -> Vec<cpal::SupportedStreamConfig> {
+    let wanted_rate = failed.sample_rate().0;
+
+    let mut configs: Vec<cpal::SupportedStreamConfig> = Vec::new();
+    for range in ranges {
+        let rate = wanted_rate.clamp(range.min_sample_rate().0, range.max_sample_rate().0);
+        let config = range.with_sample_rate(cpal::SampleRate(rate));
+        if required_format.is_some_and(|required| required != (rate, config.channels())) {
+            continue;
+        }
+        if config != *failed && !configs.contains(&config) {
+            configs.push(config);
+        }
+    }
+    configs.sort_by_key(|c| {
+        (c.channels() != failed.channels(), c.sample_rate() != failed.sample_rate())
+    });
+    configs
+}
+
+/// Build an input stream that converts samples of type `T` to f32 in [-1, 1]
+/// Stream errors are reported as `recording-error`; see `handle_stream_error`
+fn build_input_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    recorder: Arc<AudioRecorder>,
+    app: &Option<AppHandle>,
+) -> Result<cpal::Stream>
+where
+    T: cpal::SizedSample,
+    f32: cpal::FromSample<T>,
+{
+    use cpal::traits::DeviceTrait;
+
+    let error_recorder = AudioRecorder::clone(&recorder);
+    let app = app.clone();
+    let mut gate = TypingNoiseGate::new(config.sample_rate.0, config.channels);
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            if recorder.is_recording() {
+                let mut samples = convert_samples(data);
+                if let Some(sensitivity) = recorder.typing_noise_gate() {
+                    gate.process(&mut samples, sensitivity);
+                }
+                apply_gain(&mut samples, recorder.input_gain());
+                recorder.append_audio(samples);
+            }
+        },
+        move |err| handle_stream_error(&error_recorder, app.as_ref(), err),
+        None,
+    )?;
+
+    Ok(stream)
+}
+
+/// React to an input stream failure so the UI is never left in a recording state
+/// that captures nothing
+///
+/// A lost device (e.g. an unplugged USB mic) closes the stream; if a recording was
+/// in progress it continues on the new default device when that device has the same
+/// format, otherwise it is stopped with the audio so far kept for `process_audio`
+/// Other errors are reported and the stream left running
+fn handle_stream_error(recorder: &AudioRecorder, app: Option<&AppHandle>, err: cpal::StreamError) {
+    eprintln!("Audio stream error: {}", err);
+
+    let emit = |error: RecordingError| {
+        if let Some(app) = app {
+            let _ = app.emit("recording-error", error);
+        }
+    };
+
+    if !matches!(err, cpal::StreamError::DeviceNotAvailable) {
+        emit(RecordingError {
+            message: err.to_string(),
+            recovered: false,
+        });
+        return;
+    }
+
+    // The callback can fire more than once for the same loss; only the first recovers
+    if !recorder.stream_started.swap(false, Ordering::Relaxed) {
+        return;
+    }
+
+    if !recorder.is_recording() {
+        // Nothing in progress; the next recording opens whichever device is available
+        emit(RecordingError {
+            message: err.to_string(),
+            recovered: false,
+        });
+        return;
+    }
+
+    // Streams can't be rebuilt from inside their own callback
+    let recorder = recorder.clone();
+    let app = app.cloned();
+    std::thread::spawn(move || {
+        let _startup = recorder.lock_startup();
+        let format = (recorder.sample_rate(), recorder.channels());
+        let result = open_input_stream(&recorder, None, app.clone(), Some(format));
+
+        let error = match result {
+            Ok(()) => {
+                println!("Input device lost, continuing on the default device");
+                RecordingError {
+                    message: err.to_string(),
+                    recovered: true,
+                }
+            }
+            Err(e) => {
+                eprintln!("Could not switch input device: {}", e);
+                recorder.set_recording(false);
+                RecordingError {
+                    message: format!("{} ({})", err, e),
+                    recovered: false,
+                }
+            }
+        };
+        if let Some(app) = &app {
+            let _ = app.emit("recording-error", error);
+        }
+    });
+}
+
+/// Normalize raw device samples to f32 in [-1, 1]
+fn convert_samples<T>(data: &[T]) -> Vec<f32>
+where
+    T: cpal::Sample,
+    f32: cpal::FromSample<T>,
+{
+    use cpal::Sample;
+
+    data.iter().map(|&sample| sample.to_sample::<f32>()).collect()
+}
+
+/// Average interleaved channels into a single mono channel
+#[cfg_attr(not(feature = "realtime"), allow(dead_code))]
+pub(crate) fn downmix_to_mono(samples: &[f32], channels: u16) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    if channels == 1 {
+        return samples.to_vec();
+    }
+    samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect()
+}
+
+/// Resample mono audio with linear interpolation
+#[cfg_attr(not(feature = "realtime"), allow(dead_code))]
+pub(crate) fn resample_linear(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    if from_rate == to_rate || samples.is_empty() || from_rate == 0 {
+        return samples.to_vec();
+    }
+
+    let ratio = from_rate as f64 / to_rate as f64;
+    let out_len = (samples.len() as f64 / ratio).round() as usize;
+    (0..out_len)
+        .map(|i| {
+            let position = i as f64 * ratio;
+            let index = position.floor() as usize;
+            let fraction = (position - index as f64) as f32;
+            let current = samples[index.min(samples.len() - 1)];
+            let next = samples[(index + 1).min(samples.len() - 1)];
+            current + (next - current) * fraction
+        })
+        .collect()
+}
+
+/// Resample interleaved audio channel by channel
+pub(crate) fn resample_interleaved(
+    samples: &[f32],
+    channels: u16,
+    from_rate: u32,
+    to_rate: u32,
+) -> Vec<f32> {
+    let channels = channels.max(1) as usize;
+    if channels == 1 {
+        return resample_linear(samples, from_rate, to_rate);
+    }
+
+    let resampled: Vec<Vec<f32>> = (0..channels)
+        .map(|c| {
+            let channel: Vec<f32> = samples.iter().skip(c).step_by(channels).copied().collect();
+            resample_linear(&channel, from_rate, to_rate)
+        })
+        .collect();
+    let frames = resampled.iter().map(Vec::len).min().unwrap_or(0);
+    (0..frames)
+        .flat_map(|i| resampled.iter().map(move |channel| channel[i]))
+        .collect()
+}
+
+/// Multiply samples by `gain`, clipping to [-1, 1] instead of wrapping
+pub(crate) fn apply_gain(samples: &mut [f32], gain: f32) {
+    if gain == 1.0 {
+        return;
+    }
+    for sample in samples {
+        *sample = (*sample * gain).clamp(-1.0, 1.0);
+    }
+}
+
+/// Key clicks quieter than this peak level are left alone
+const GATE_FLOOR: f32 = 0.02;
+
+/// Gain applied to a detected click (about -20 dB)
+const GATE_ATTENUATION: f32 = 0.1;
+
+/// Attenuates short, sharp spikes such as key presses while passing speech
+///
+/// A fast peak follower is compared with a slow level that tracks the speech (or room
+/// noise) around it; a jump far above that level is gated for a few milliseconds
+/// A spike still loud once the hold ends is sustained sound, i.e. speech starting,
+/// so the slow level jumps to it and the gate stays open
+pub(crate) struct TypingNoiseGate {
+    channels: usize,
+    fast_decay: f32,
+    slow_smoothing: f32,
+    hold_frames: usize,
+    release_step: f32,
+    fast: f32,
+    slow: f32,
+    gain: f32,
+    hold: usize,
+}
+
+impl TypingNoiseGate {
+    pub(crate) fn new(sample_rate: u32, channels: u16) -> Self {
+        let rate = sample_rate.max(1) as f32;
+        Self {
+            channels: usize::from(channels.max(1)),
+            // Time constants: 2 ms peak decay, 150 ms level, 8 ms hold, 10 ms release
+            fast_decay: (-1.0 / (0.002 * rate)).exp(),
+            slow_smoothing: (-1.0 / (0.150 * rate)).exp(),
+            hold_frames: (0.008 * rate) as usize,
+            release_step: (1.0 - GATE_ATTENUATION) / (0.010 * rate).max(1.0),
+            fast: 0.0,
+            slow: 0.0,
+            gain: 1.0,
+            hold: 0,
+        }
+    }
+
+    /// Gate interleaved `samples` in place
+    /// `sensitivity` 0-1 sets how far above the surrounding level a spike must rise:
+    /// 10x at 0 down to 2x at 1
+    pub(crate) fn process(&mut self, samples: &mut [f32], sensitivity: f32) {
+        let ratio = 10.0 - 8.0 * sensitivity.clamp(0.0, 1.0);
+
+        for frame in samples.chunks_mut(self.channels) {
+            let peak = frame.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+            self.fast = peak.max(self.fast * self.fast_decay);
+            let spiking = self.fast > GATE_FLOOR && self.fast > ratio * self.slow.max(1e-4);
+
+            if self.hold > 0 {
+                self.hold -= 1;
+                if self.hold == 0 && spiking {
+                    // Still loud after the hold: speech, not a click
+                    self.slow = self.fast;
+                }
+            } else if spiking {
+                self.hold = self.hold_frames;
+                self.gain = GATE_ATTENUATION;
+            } else {
+                self.slow = self.slow * self.slow_smoothing + peak * (1.0 - self.slow_smoothing);
+                self.gain = (self.gain + self.release_step).min(1.0);
+            }
+
+            for sample in frame {
+                *sample *= self.gain;
+            }
+        }
+    }
+}
+
+/// Writing a sample to the WAV encoder failed
+#[derive(Debug)]
+pub struct AudioEncodingError {
+    pub index: usize,
+    pub source: hound::Error,
+}
+
+impl std::fmt::Display for AudioEncodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to encode audio sample {}: {}", self.index, self.source)
+    }
+}
+
+impl std::error::Error for AudioEncodingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Replace NaN/inf from misbehaving devices with silence and clamp to [-1, 1]
+fn sanitize_sample(sample: f32) -> f32 {
+    if sample.is_finite() {
+        sample.clamp(-1.0, 1.0)
+    } else {
+        0.0
+    }
+}
+
+/// Upper bound on the header `samples_to_wav` writes (float WAV uses an extended fmt chunk)
+const WAV_HEADER_BYTES: usize = 80;
+
+/// Size in bytes of the float WAV `samples_to_wav` produces for `sample_count` samples
+/// Computed without encoding, so oversized recordings can be split up front
+pub(crate) fn estimated_wav_size(sample_count: usize) -> usize {
+    WAV_HEADER_BYTES + sample_count * std::mem::size_of::<f32>()
+}
+
+/// Convert audio samples to WAV format
+pub(crate) fn samples_to_wav(samples: &[f32], sample_rate: u32, channels: u16) -> Result<Vec<u8>> {
+    use hound::{WavSpec, WavWriter};
+    use std::io::Cursor;
+
+    let spec = WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let mut writer = WavWriter::new(&mut cursor, spec)?;
+        for (index, &sample) in samples.iter().enumerate() {
+            writer
+                .write_sample(sanitize_sample(sample))
+                .map_err(|source| AudioEncodingError { index, source })?;
+        }
+        writer.finalize()?;
+    }
+
+    Ok(cursor.into_inner())
+}
+
+/// Convert audio samples to 16-bit PCM WAV, for backends that reject float WAV
+pub(crate) fn samples_to_wav_i16(samples: &[f32], sample_rate: u32, channels: u16) -> Result<Vec<u8>> {
+    use hound::{WavSpec, WavWriter};
+    use std::io::Cursor;
+
+    let spec = WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut cursor = Cursor::new(Vec::new());
+    {
+        let mut writer = WavWriter::new(&mut cursor, spec)?;
+        for (index, &sample) in samples.iter().enumerate() {
+            writer
+                .write_sample((sanitize_sample(sample) * i16::MAX as f32) as i16)
+                .map_err(|source| AudioEncodingError { index, source })?;
+        }
+        writer.finalize()?;
+    }
+
+    Ok(cursor.into_inner())
+}
+
+/// Decode a WAV file into interleaved f32 samples, its sample rate and channel count
+pub(crate) fn wav_to_samples(bytes: &[u8]) -> Result<(Vec<f32>, u32, u16)> {
+    let mut reader = hound::WavReader::new(std::io::Cursor::new(bytes))?;
+    let spec = reader.spec();
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = (1_i64 << (spec.bits_per_sample.clamp(1, 32) - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+    Ok((samples, spec.sample_rate, spec.channels))
+}
+
+/// Encode audio samples as a 128 kbps MP3
+/// LAME only handles mono and stereo input
+pub(crate) fn samples_to_mp3(samples: &[f32], sample_rate: u32, channels: u16) -> Result<Vec<u8>> {
+    use mp3lame_encoder::{Bitrate, Builder, FlushNoGap, InterleavedPcm, MonoPcm, Quality};
+
+    if !matches!(channels, 1 | 2) {
+        anyhow::bail!("MP3 supports mono or stereo audio, got {} channels", channels);
+    }
+
+    let mut builder = Builder::new().context("Failed to create MP3 encoder")?;
+    builder
+        .set_num_channels(channels as u8)
+        .map_err(|e| anyhow::anyhow!("Unsupported channel count: {}", e))?;
+    builder
+        .set_sample_rate(sample_rate)
+        .map_err(|e| anyhow::anyhow!("Unsupported sample rate {}: {}", sample_rate, e))?;
+    builder
+        .set_brate(Bitrate::Kbps128)
+        .map_err(|e| anyhow::anyhow!("Unsupported bitrate: {}", e))?;
+    builder
+        .set_quality(Quality::Good)
+        .map_err(|e| anyhow::anyhow!("Unsupported quality: {}", e))?;
+    let mut encoder = builder
+        .build()
+        .map_err(|e| anyhow::anyhow!("Failed to initialize MP3 encoder: {}", e))?;
+
+    let pcm: Vec<i16> = samples
+        .iter()
+        .map(|&s| (sanitize_sample(s) * i16::MAX as f32) as i16)
+        .collect();
+
+    let frames = pcm.len() / channels as usize;
+    let mut mp3 = Vec::with_capacity(mp3lame_encoder::max_required_buffer_size(frames));
+    let encoded = if channels == 1 {
+        encoder.encode_to_vec(MonoPcm(&pcm), &mut mp3)
+    } else {
+        encoder.encode_to_vec(InterleavedPcm(&pcm), &mut mp3)
+    };
+    encoded.map_err(|e| anyhow::anyhow!("Failed to encode MP3: {}", e))?;
+    encoder
+        .flush_to_vec::<FlushNoGap>(&mut mp3)
+        .map_err(|e| anyhow::anyhow!("Failed to flush MP3 encoder: {}", e))?;
+
+    Ok(mp3)
+}
+
+// Implement Clone for AudioRecorder
+impl Clone for AudioRecorder {
+    fn clone(&self) -> Self {
         Self {
             is_recording: Arc::clone(&self.is_recording),
             audio_buffer: Arc::clone(&self.audio_buffer),
             stream_started: Arc::clone(&self.stream_started),
+            stream_startup: Arc::clone(&self.stream_startup),
+            stream_owner: Arc::clone(&self.stream_owner),
+            sample_rate: Arc::clone(&self.sample_rate),
+            channels: Arc::clone(&self.channels),
+            retention_samples: Arc::clone(&self.retention_samples),
+            retention_ms: Arc::clone(&self.retention_ms),
+            detected_language: Arc::clone(&self.detected_language),
+            idle_since: Arc::clone(&self.idle_since),
+            idle_timeout_secs: Arc::clone(&self.idle_timeout_secs),
+            input_gain: Arc::clone(&self.input_gain),
+            typing_noise_gate: Arc::clone(&self.typing_noise_gate),
+            device_key: Arc::clone(&self.device_key),
+            last_transcribed: Arc::clone(&self.last_transcribed),
+            stream_wake: Arc::clone(&self.stream_wake),
+            max_recording_secs: Arc::clone(&self.max_recording_secs),
+            silence_level: Arc::clone(&self.silence_level),
+            silence_after_ms: Arc::clone(&self.silence_after_ms),
+            silent_samples: Arc::clone(&self.silent_samples),
+            heard_sound: Arc::clone(&self.heard_sound),
+            auto_stop_reason: Arc::clone(&self.auto_stop_reason),
+            auto_stop_samples: Arc::clone(&self.auto_stop_samples),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::test_server::MockServer;
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-3, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn converts_signed_integer_formats() {
+        assert_close(&convert_samples(&[i8::MIN, 0, 64]), &[-1.0, 0.0, 0.5]);
+        assert_close(&convert_samples(&[i32::MIN, 0, i32::MAX]), &[-1.0, 0.0, 1.0]);
+        assert_close(&convert_samples(&[i64::MIN, 0, i64::MAX]), &[-1.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn converts_unsigned_integer_formats() {
+        assert_close(&convert_samples(&[0_u8, 128, 192]), &[-1.0, 0.0, 0.5]);
+        assert_close(&convert_samples(&[0_u32, 1 << 31, u32::MAX]), &[-1.0, 0.0, 1.0]);
+        assert_close(&convert_samples(&[0_u64, 1 << 63, u64::MAX]), &[-1.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn converts_f64() {
+        assert_close(&convert_samples(&[-1.0_f64, 0.25, 1.0]), &[-1.0, 0.25, 1.0]);
+    }
+
+    #[test]
+    fn non_finite_samples_encode_cleanly() {
+        let samples = [0.5, f32::NAN, f32::INFINITY, f32::NEG_INFINITY, 1.5];
+        for wav in [
+            samples_to_wav(&samples, 16_000, 1).unwrap(),
+            samples_to_wav_i16(&samples, 16_000, 1).unwrap(),
+        ] {
+            let (decoded, sample_rate, channels) = wav_to_samples(&wav).unwrap();
+            assert_eq!((sample_rate, channels), (16_000, 1));
+            assert_close(&decoded, &[0.5, 0.0, 0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn gain_doubles_and_clamps() {
+        let mut samples = [0.1, -0.25, 0.4, 0.6, -0.9];
+        apply_gain(&mut samples, 2.0);
+        assert_close(&samples, &[0.2, -0.5, 0.8, 1.0, -1.0]);
+    }
+
+    #[test]
+    fn backend_stream_error_leaves_the_stream_running() {
+        let recorder = AudioRecorder::new();
+        recorder.set_stream_started(true);
+        recorder.set_recording(true);
+
+        let err = cpal::StreamError::BackendSpecific {
+            err: cpal::BackendSpecificError {
+                description: String::from("buffer overrun"),
+            },
+        };
+        handle_stream_error(&recorder, None, err);
+        assert!(recorder.is_stream_started());
+        assert!(recorder.is_recording());
+    }
+
+    #[test]
+    fn device_lost_while_idle_closes_the_stream_once() {
+        let recorder = AudioRecorder::new();
+        recorder.set_stream_started(true);
+
+        handle_stream_error(&recorder, None, cpal::StreamError::DeviceNotAvailable);
+        assert!(!recorder.is_stream_started());
+        assert!(!recorder.is_recording());
+
+        // A repeated callback for the same loss must not reopen anything
+        handle_stream_error(&recorder, None, cpal::StreamError::DeviceNotAvailable);
+        assert!(!recorder.is_stream_started());
+    }
+
+    #[test]
+    fn waveform_covers_only_the_recent_window() {
+        let recorder = AudioRecorder::new();
+        recorder.set_stream_format(1_000, 1);
+        let window = WAVEFORM_WINDOW_SECS as usize * 1_000;
+        recorder.append_audio(vec![0.9; 10_000]);
+        recorder.append_audio(vec![-0.5; window / 2]);
+        recorder.append_audio(vec![0.5; window / 2]);
+
+        let envelope = recorder.waveform(2);
+        assert_eq!(envelope[0].min, -0.5);
+        assert_eq!(envelope[0].max, -0.5);
+        assert_eq!(envelope[1].min, 0.5);
+        assert_eq!(envelope[1].max, 0.5);
+    }
+
+    #[test]
+    fn concurrent_starts_open_one_stream() {
+        let recorder = AudioRecorder::new();
+        let opened = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(std::sync::Barrier::new(2));
+
+        let starts: Vec<_> = (0..2)
+            .map(|_| {
+                let (recorder, opened, barrier) =
+                    (recorder.clone(), Arc::clone(&opened), Arc::clone(&barrier));
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    start_capture(&recorder, None, |recorder, _| {
+                        opened.fetch_add(1, Ordering::SeqCst);
+                        // Slow device: the other start arrives while this one is opening
+                        std::thread::sleep(Duration::from_millis(50));
+                        recorder.set_stream_started(true);
+                        Ok(())
+                    })
+                })
+            })
+            .collect();
+        for start in starts {
+            assert!(start.join().unwrap().is_ok());
+        }
+
+        assert_eq!(opened.load(Ordering::SeqCst), 1);
+        assert!(recorder.is_recording());
+        assert!(recorder.is_stream_started());
+    }
+
+    #[test]
+    fn release_waits_for_the_owner_to_drop_the_stream() {
+        let recorder = AudioRecorder::new();
+        let generation = recorder.claim_stream(StreamSource::Microphone);
+        let dropped = Arc::new(AtomicBool::new(false));
+
+        let (owner, owner_dropped) = (recorder.clone(), Arc::clone(&dropped));
+        std::thread::spawn(move || {
+            while owner.holds_stream(generation) {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            // Closing a device can take a while
+            std::thread::sleep(Duration::from_millis(50));
+            owner_dropped.store(true, Ordering::SeqCst);
+            owner.stream_closed(generation);
+        });
+
+        assert!(recorder.release_stream_and_wait(Duration::from_secs(5)));
+        assert!(dropped.load(Ordering::SeqCst));
+        assert_eq!(recorder.stream_source(), None);
+    }
+
+    #[test]
+    fn stale_owner_leaves_a_newer_stream_running() {
+        let recorder = AudioRecorder::new();
+        let app = recorder.claim_stream(StreamSource::App);
+        let microphone = recorder.claim_stream(StreamSource::Microphone);
+
+        recorder.stream_closed(app);
+        assert!(recorder.is_stream_started());
+        assert!(recorder.holds_stream(microphone));
+        assert!(!recorder.holds_stream(app));
+        assert_eq!(recorder.stream_source(), Some(StreamSource::Microphone));
+    }
+
+    #[test]
+    fn stale_owner_keeps_the_newer_wake_sender() {
+        let recorder = AudioRecorder::new();
+        let old = recorder.claim_stream(StreamSource::Microphone);
+        let new = recorder.claim_stream(StreamSource::Microphone);
+        let (wake_tx, wake_rx) = mpsc::channel();
+        *recorder.stream_wake.lock().unwrap() = Some((new, wake_tx));
+
+        recorder.clear_stream_wake(old);
+        recorder.wake_stream();
+        assert!(wake_rx.try_recv().is_ok());
+
+        recorder.clear_stream_wake(new);
+        assert!(recorder.stream_wake.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn typing_gate_attenuates_clicks_and_passes_speech() {
+        const RATE: usize = 16_000;
+        let tone = |i: usize, hz: f32, level: f32| {
+            level * (i as f32 / RATE as f32 * hz * std::f32::consts::TAU).sin()
+        };
+        // Half a second of faint room noise with a key click, then half a second of speech
+        let mut input: Vec<f32> = (0..RATE).map(|i| tone(i, 1_000.0, 0.005)).collect();
+        let click = RATE / 4;
+        for n in 0..32 {
+            input[click + n] += 0.8 * (-(n as f32) / 8.0).exp();
+        }
+        for (i, sample) in input.iter_mut().enumerate().skip(RATE / 2) {
+            *sample = tone(i, 220.0, 0.3);
+        }
+
+        let mut output = input.clone();
+        TypingNoiseGate::new(RATE as u32, 1).process(&mut output, 0.5);
+
+        assert_eq!(output[..click], input[..click]);
+        let click_peak = output[click..click + 32].iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!(click_peak < 0.1, "click peak {}", click_peak);
+
+        // Speech onset is gated for the hold, then passes untouched
+        let settled = RATE / 2 + RATE / 20;
+        assert_eq!(output[settled..], input[settled..]);
+        let energy = |samples: &[f32]| samples.iter().map(|s| s * s).sum::<f32>();
+        let speech = RATE / 2..;
+        assert!(energy(&output[speech.clone()]) > 0.95 * energy(&input[speech]));
+    }
+
+    fn range(
+        channels: u16,
+        min: u32,
+        max: u32,
+        format: cpal::SampleFormat,
+    ) -> cpal::SupportedStreamConfigRange {
+        cpal::SupportedStreamConfigRange::new(
+            channels,
+            cpal::SampleRate(min),
+            cpal::SampleRate(max),
+            cpal::SupportedBufferSize::Unknown,
+            format,
+        )
+    }
+
+    fn stream_config(
+        channels: u16,
+        rate: u32,
+        format: cpal::SampleFormat,
+    ) -> cpal::SupportedStreamConfig {
+        cpal::SupportedStreamConfig::new(
+            channels,
+            cpal::SampleRate(rate),
+            cpal::SupportedBufferSize::Unknown,
+            format,
+        )
+    }
+
+    #[test]
+    fn fallback_candidates_keep_the_channel_count_first() {
+        use cpal::SampleFormat::{F32, I16};
+        let failed = stream_config(2, 48_000, F32);
+        let ranges = vec![
+            range(2, 44_100, 48_000, F32),
+            range(1, 8_000, 96_000, I16),
+            range(2, 16_000, 16_000, I16),
+            range(1, 8_000, 96_000, I16),
+        ];
+
+        let candidates = candidate_configs(ranges.clone(), &failed, None);
+        assert_eq!(candidates, [stream_config(2, 16_000, I16), stream_config(1, 48_000, I16)]);
+
+        let matching = candidate_configs(ranges, &failed, Some((48_000, 1)));
+        assert_eq!(matching, [stream_config(1, 48_000, I16)]);
+    }
+
+    #[test]
+    fn fallback_stops_at_the_first_config_that_opens() {
+        use cpal::SampleFormat::{F32, I16};
+        let candidates = vec![
+            stream_config(2, 44_100, F32),
+            stream_config(1, 48_000, I16),
+            stream_config(1, 16_000, I16),
+            stream_config(1, 8_000, I16),
+        ];
+
+        let mut tried = Vec::new();
+        let (opened, attempts) = try_configs(candidates.clone(), |config| {
+            tried.push(config.clone());
+            if config.sample_rate().0 == 16_000 {
+                Ok("stream")
+            } else {
+                anyhow::bail!("device busy")
+            }
+        });
+        assert_eq!(opened, Some((candidates[2].clone(), "stream")));
+        assert_eq!(tried, candidates[..3]);
+        let described: Vec<&str> = attempts.iter().map(|a| a.config.as_str()).collect();
+        assert_eq!(described, ["44100Hz/2ch F32", "48000Hz/1ch I16"]);
+        assert!(attempts.iter().all(|a| a.error == "device busy"));
+
+        let (opened, attempts) =
+            try_configs::<()>(candidates, |_| anyhow::bail!("format not supported"));
+        assert!(opened.is_none());
+        assert_eq!(attempts.len(), 4);
+    }
+
+    #[test]
+    fn max_duration_stop_is_reported_once_by_the_owner() {
+        let recorder = AudioRecorder::new();
+        recorder.set_stream_format(16_000, 1);
+        recorder.set_max_recording(Some(1));
+        recorder.set_recording(true);
+
+        recorder.append_audio(vec![0.1; 12_000]);
+        assert!(recorder.is_recording());
+        assert!(recorder.take_auto_stop().is_none());
+
+        recorder.append_audio(vec![0.1; 12_000]);
+        assert!(!recorder.is_recording());
+        let stopped = recorder.take_auto_stop().unwrap();
+        assert_eq!(stopped.reason, AutoStopReason::MaxDuration);
+        assert_eq!(stopped.duration_secs, 1.0);
+        assert!(recorder.take_auto_stop().is_none());
+    }
+
+    /// One second of a 440 Hz sine at `rate`
+    fn sine(rate: u32) -> Vec<f32> {
+        (0..rate)
+            .map(|i| (i as f32 / rate as f32 * 440.0 * std::f32::consts::TAU).sin())
+            .collect()
+    }
+
+    #[test]
+    fn resampling_to_16k_keeps_the_duration() {
+        for rate in [8_000, 22_050, 44_100, 48_000, 96_000, 192_000] {
+            let resampled = resample_linear(&sine(rate), rate, 16_000);
+            assert_eq!(resampled.len(), 16_000, "from {} Hz", rate);
+        }
+        assert_eq!(resample_linear(&sine(48_000), 48_000, 48_000).len(), 48_000);
+    }
+
+    #[test]
+    fn resampling_keeps_the_waveform() {
+        // 48 kHz to 16 kHz takes every third sample exactly
+        let source = sine(48_000);
+        let resampled = resample_linear(&source, 48_000, 16_000);
+        for (i, sample) in resampled.iter().enumerate() {
+            assert!((sample - source[i * 3]).abs() < 1e-6);
+        }
+
+        // An uneven ratio interpolates, staying close to the true signal
+        let resampled = resample_linear(&sine(44_100), 44_100, 16_000);
+        let expected = sine(16_000);
+        let worst = resampled
+            .iter()
+            .zip(&expected)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max);
+        assert!(worst < 0.05, "max deviation {}", worst);
+    }
+
+    #[test]
+    fn interleaved_resampling_keeps_channels_apart() {
+        let left = sine(48_000);
+        let stereo: Vec<f32> = left.iter().flat_map(|&l| [l, -l]).collect();
+        let resampled = resample_interleaved(&stereo, 2, 48_000, 16_000);
+        assert_eq!(resampled.len(), 2 * 16_000);
+        for frame in resampled.chunks(2) {
+            assert!((frame[0] + frame[1]).abs() < 1e-6);
+        }
+    }
+
+    #[tokio::test]
+    async fn whisper_cpp_probe_detects_a_running_server() {
+        let server = MockServer::start(vec![(404, "")]);
+        assert!(transcription::whisper_cpp_reachable(&whisper_cpp_config(&server.url)).await);
+
+        // Nothing listens on a port that was just released
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/inference", listener.local_addr().unwrap());
+        drop(listener);
+        assert!(!transcription::whisper_cpp_reachable(&whisper_cpp_config(&url)).await);
+    }
+
+    /// Configuration sending transcriptions to a whisper.cpp server at `url`
+    fn whisper_cpp_config(url: &str) -> AppConfig {
+        AppConfig {
+            openai_api_key: String::from("sk-test"),
+            transcription_provider: TranscriptionProvider::WhisperCpp,
+            deepgram_api_key: None,
+            google_speech_api_key: None,
+            google_speech_language: String::from("en-US"),
+            whisper_cpp_url: url.to_string(),
+            base_url: url.to_string(),
+            model: None,
+            max_tokens: None,
+            hotkey: None,
         }
     }
+
+    #[tokio::test]
+    async fn failed_transcription_keeps_the_recording_for_retry() {
+        let server = MockServer::start(vec![
+            (400, r#"{"error": "model not loaded"}"#),
+            (200, r#"{"text": " Hello there "}"#),
+        ]);
+        let config = whisper_cpp_config(&server.url);
+        let requests = InFlightRequests::default();
+        let options = TranscriptionOptions::default();
+        let recorder = AudioRecorder::new();
+        recorder.set_stream_format(16_000, 1);
+        recorder.append_audio(vec![0.1; 1_600]);
+        let samples = recorder.get_audio_buffer();
+
+        let (_, kept) = transcribe_recorded(None, &recorder, &config, &requests, &options)
+            .await
+            .unwrap_err();
+        assert_eq!(kept, samples);
+        assert_eq!(recorder.get_audio_buffer(), samples);
+
+        let text = transcribe_recorded(None, &recorder, &config, &requests, &options)
+            .await
+            .unwrap();
+        assert_eq!(text.as_deref(), Some("Hello there"));
+        assert!(recorder.get_audio_buffer().is_empty());
+        assert_eq!(recorder.last_transcribed(), samples);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn whisper_cpp_server_errors_are_retried() {
+        let server = MockServer::start(vec![
+            (503, "loading model"),
+            (200, r#"{"text": "Back up"}"#),
+        ]);
+        let samples = vec![0.1; 1_600];
+        let clip = AudioClip {
+            samples: &samples,
+            sample_rate: 16_000,
+            channels: 1,
+        };
+
+        let config = whisper_cpp_config(&server.url);
+        let text = transcription::transcribe(&config, &clip, None).await.unwrap();
+        assert_eq!(text, "Back up");
+        assert_eq!(server.requests().len(), 2);
+    }
 }
@@ -0,0 +1,314 @@
+use anyhow::{Context, Result};
+use reqwest::multipart;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::State;
+
+use crate::commands::ai::{
+    api_error, build_request, extra_headers, http_client, parse_chat_response, ChatOptions,
+    Message, Usage,
+};
+use crate::config::{api_url, require_key, ConfigState};
+
+/// Endpoint every batched request is sent to
+const BATCH_ENDPOINT: &str = "/v1/chat/completions";
+
+/// One conversation in a `submit_batch` job
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchRequest {
+    /// Echoed back in the results; defaults to the request's position
+    pub custom_id: Option<String>,
+    pub messages: Vec<Message>,
+    pub model: Option<String>,
+}
+
+/// A created batch job
+#[derive(Debug, Serialize)]
+pub struct BatchSubmitted {
+    pub batch_id: String,
+    pub input_file_id: String,
+    pub request_count: usize,
+}
+
+/// State of a batch job, with results once it has finished
+#[derive(Debug, Serialize)]
+pub struct BatchStatus {
+    pub id: String,
+    /// `validating`, `in_progress`, `finalizing`, `completed`, `failed`, `expired`,
+    /// `cancelling` or `cancelled`
+    pub status: String,
+    pub total: u32,
+    pub completed: u32,
+    pub failed: u32,
+    /// Per-request results; present once the job has completed
+    pub results: Option<Vec<BatchResult>>,
+    /// Why the whole job failed (e.g. an invalid input file)
+    pub errors: Vec<String>,
+}
+
+/// Outcome of one request in a batch
+#[derive(Debug, Serialize)]
+pub struct BatchResult {
+    pub custom_id: String,
+    pub content: Option<String>,
+    pub usage: Option<Usage>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileObject {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchObject {
+    id: String,
+    status: String,
+    output_file_id: Option<String>,
+    error_file_id: Option<String>,
+    #[serde(default)]
+    request_counts: RequestCounts,
+    errors: Option<BatchErrors>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RequestCounts {
+    total: u32,
+    completed: u32,
+    failed: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchErrors {
+    #[serde(default)]
+    data: Vec<BatchErrorDetail>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchErrorDetail {
+    message: String,
+}
+
+/// One line of a batch output or error file
+#[derive(Debug, Deserialize)]
+struct BatchOutputLine {
+    custom_id: String,
+    response: Option<BatchOutputResponse>,
+    error: Option<BatchErrorDetail>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchOutputResponse {
+    status_code: u16,
+    body: serde_json::Value,
+}
+
+/// Queue chat requests on the OpenAI Batch API, which is cheaper but answers
+/// within 24 hours instead of immediately
+/// The requests are uploaded as a JSONL file and a batch job created from it;
+/// check on it with `poll_batch`
+#[tauri::command]
+pub async fn submit_batch(
+    config: State<'_, ConfigState>,
+    requests: Vec<BatchRequest>,
+) -> Result<BatchSubmitted, String> {
+    config.touch();
+
+    let api_key = config.get().openai_api_key;
+    submit_batch_impl(&api_key, requests)
+        .await
+        .map_err(|e| format!("Batch submission failed: {}", e))
+}
+
+async fn submit_batch_impl(api_key: &str, requests: Vec<BatchRequest>) -> Result<BatchSubmitted> {
+    let api_key = require_key(api_key)?;
+    if requests.is_empty() {
+        anyhow::bail!("No requests to submit");
+    }
+
+    let request_count = requests.len();
+    let jsonl = batch_jsonl(requests)?;
+    let client = http_client();
+
+    // Step 1: upload the input file
+    let part = multipart::Part::bytes(jsonl.into_bytes())
+        .file_name("batch.jsonl")
+        .mime_str("application/jsonl")?;
+    let form = multipart::Form::new().text("purpose", "batch").part("file", part);
+    let response = client
+        .post(api_url("files"))
+        .headers(extra_headers())
+        .header("Authorization", format!("Bearer {}", api_key))
+        .multipart(form)
+        .send()
+        .await
+        .context("Failed to upload batch file")?;
+    let file: FileObject = read_json(response).await.context("Batch file upload failed")?;
+
+    // Step 2: create the job from it
+    let response = client
+        .post(api_url("batches"))
+        .headers(extra_headers())
+        .header("Authorization", format!("Bearer {}", api_key))
+        .json(&json!({
+            "input_file_id": file.id,
+            "endpoint": BATCH_ENDPOINT,
+            "completion_window": "24h",
+        }))
+        .send()
+        .await
+        .context("Failed to create batch")?;
+    let batch: BatchObject = read_json(response).await.context("Batch creation failed")?;
+
+    Ok(BatchSubmitted {
+        batch_id: batch.id,
+        input_file_id: file.id,
+        request_count,
+    })
+}
+
+/// One chat completion request per line, in the Batch API's input format
+fn batch_jsonl(requests: Vec<BatchRequest>) -> Result<String> {
+    let mut jsonl = String::new();
+    let mut seen = std::collections::HashSet::new();
+
+    for (index, request) in requests.into_iter().enumerate() {
+        let custom_id = request
+            .custom_id
+            .filter(|id| !id.trim().is_empty())
+            .unwrap_or_else(|| format!("request-{}", index));
+        if !seen.insert(custom_id.clone()) {
+            anyhow::bail!("Duplicate custom_id {:?}", custom_id);
+        }
+        if request.messages.is_empty() {
+            anyhow::bail!("Request {:?} has no messages", custom_id);
+        }
+
+        let options = ChatOptions {
+            model: request.model.filter(|m| !m.trim().is_empty()),
+            ..ChatOptions::default()
+        };
+        let line = json!({
+            "custom_id": custom_id,
+            "method": "POST",
+            "url": BATCH_ENDPOINT,
+            "body": build_request(request.messages, Vec::new(), &options, false),
+        });
+        jsonl.push_str(&serde_json::to_string(&line)?);
+        jsonl.push('\n');
+    }
+
+    Ok(jsonl)
+}
+
+/// Check a batch job and, once it has completed, fetch its results
+#[tauri::command]
+pub async fn poll_batch(config: State<'_, ConfigState>, id: String) -> Result<BatchStatus, String> {
+    let api_key = config.get().openai_api_key;
+    poll_batch_impl(&api_key, &id)
+        .await
+        .map_err(|e| format!("Failed to check batch: {}", e))
+}
+
+async fn poll_batch_impl(api_key: &str, id: &str) -> Result<BatchStatus> {
+    let api_key = require_key(api_key)?;
+    let client = http_client();
+
+    let response = client
+        .get(api_url(&format!("batches/{}", id)))
+        .headers(extra_headers())
+        .header("Authorization", format!("Bearer {}", api_key))
+        .send()
+        .await
+        .context("Failed to fetch batch")?;
+    let batch: BatchObject = read_json(response).await?;
+
+    let results = match batch.status.as_str() {
+        "completed" => {
+            let mut results = Vec::new();
+            // Successful requests go to the output file, failed ones to the error file
+            for file_id in [&batch.output_file_id, &batch.error_file_id].into_iter().flatten() {
+                let jsonl = download_file(&client, api_key, file_id).await?;
+                results.extend(parse_batch_output(&jsonl)?);
+            }
+            Some(results)
+        }
+        _ => None,
+    };
+
+    Ok(BatchStatus {
+        id: batch.id,
+        status: batch.status,
+        total: batch.request_counts.total,
+        completed: batch.request_counts.completed,
+        failed: batch.request_counts.failed,
+        results,
+        errors: batch
+            .errors
+            .map(|e| e.data.into_iter().map(|d| d.message).collect())
+            .unwrap_or_default(),
+    })
+}
+
+async fn download_file(client: &reqwest::Client, api_key: &str, file_id: &str) -> Result<String> {
+    let response = client
+        .get(api_url(&format!("files/{}/content", file_id)))
+        .headers(extra_headers())
+        .header("Authorization", format!("Bearer {}", api_key))
+        .send()
+        .await
+        .context("Failed to download batch results")?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(api_error(status, error_text));
+    }
+    response.text().await.context("Failed to read batch results")
+}
+
+fn parse_batch_output(jsonl: &str) -> Result<Vec<BatchResult>> {
+    jsonl
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let line: BatchOutputLine =
+                serde_json::from_str(line).context("Invalid batch result line")?;
+
+            let (content, usage, error) = match (line.response, line.error) {
+                (_, Some(error)) => (None, None, Some(error.message)),
+                (Some(response), None) if response.status_code == 200 => {
+                    match parse_chat_response(response.body) {
+                        Ok(chat) => (Some(chat.content), chat.usage, None),
+                        Err(e) => (None, None, Some(e.to_string())),
+                    }
+                }
+                (Some(response), None) => {
+                    let message = response.body["error"]["message"]
+                        .as_str()
+                        .map(String::from)
+                        .unwrap_or_else(|| format!("HTTP {}", response.status_code));
+                    (None, None, Some(message))
+                }
+                (None, None) => (None, None, Some(String::from("No response"))),
+            };
+
+            Ok(BatchResult {
+                custom_id: line.custom_id,
+                content,
+                usage,
+                error,
+            })
+        })
+        .collect()
+}
+
+/// Deserialize a success body, turning error statuses into `api_error`s
+async fn read_json<T: serde::de::DeserializeOwned>(response: reqwest::Response) -> Result<T> {
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(api_error(status, error_text));
+    }
+    response.json().await.context("Unexpected response from OpenAI")
+}
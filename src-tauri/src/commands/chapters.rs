@@ -0,0 +1,213 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::commands::ai::{send_message_impl, ChatOptions, Message, MessageContent};
+use crate::commands::audio::AudioRecorder;
+use crate::commands::transcription::{self, AudioClip, TranscriptSegment};
+use crate::commands::usage;
+use crate::config::ConfigState;
+use crate::settings::SettingsStore;
+
+/// Transcript characters sent per request; longer transcripts are chapterized in parts
+const MAX_CHUNK_CHARS: usize = 24_000;
+
+/// Usage log tag for chapterizing requests
+const CHAPTERS_TAG: &str = "chapters";
+
+/// A topic section of a recording
+#[derive(Debug, Clone, Serialize)]
+pub struct Chapter {
+    /// Seconds from the start of the recording
+    pub start: f64,
+    pub end: f64,
+    pub title: String,
+    /// One sentence on what the chapter covers
+    pub summary: String,
+}
+
+/// Chapter as written by the model; times are snapped to segment boundaries afterwards
+#[derive(Debug, Deserialize)]
+struct ProposedChapter {
+    start: f64,
+    title: String,
+    #[serde(default)]
+    summary: String,
+}
+
+/// Split a transcript into titled chapters at topic changes
+/// `segments` are timestamped transcript pieces; without them the last transcribed
+/// recording is transcribed again with Whisper's segment timestamps, in the
+/// `transcription_language` if set, and logged as usage
+///
+/// # Returns
+/// Chapters in order, covering the whole transcript
+#[tauri::command]
+pub async fn chapterize_transcript(
+    app: AppHandle,
+    config: State<'_, ConfigState>,
+    recorder: State<'_, AudioRecorder>,
+    segments: Option<Vec<TranscriptSegment>>,
+    model: Option<String>,
+) -> Result<Vec<Chapter>, String> {
+    config.touch();
+
+    let config = config.get();
+    let segments = match segments.filter(|s| !s.is_empty()) {
+        Some(segments) => segments,
+        None => {
+            let samples = recorder.last_transcribed();
+            if samples.is_empty() {
+                return Err("No transcript or transcribed recording to chapterize".to_string());
+            }
+            let clip = AudioClip {
+                samples: &samples,
+                sample_rate: recorder.sample_rate(),
+                channels: recorder.channels(),
+            };
+            let language = app.state::<SettingsStore>().get().transcription_language;
+            let segments = transcription::transcribe_segments(&config, &clip, language.as_deref())
+                .await
+                .map_err(|e| format!("Transcription failed: {}", e))?;
+            usage::record_transcription(&app, "whisper-1", clip.duration_secs());
+            segments
+        }
+    };
+
+    let options = ChatOptions {
+        app: Some(app),
+        model: model.filter(|m| !m.trim().is_empty()),
+        tags: vec![CHAPTERS_TAG.to_string()],
+        ..ChatOptions::default()
+    };
+    chapterize(&config.openai_api_key, &segments, &options)
+        .await
+        .map_err(|e| format!("Chapterizing failed: {}", e))
+}
+
+async fn chapterize(
+    api_key: &str,
+    segments: &[TranscriptSegment],
+    options: &ChatOptions,
+) -> Result<Vec<Chapter>> {
+    let mut chapters: Vec<Chapter> = Vec::new();
+    for chunk in chunk_segments(segments) {
+        let proposed = propose_chapters(api_key, chunk, options).await?;
+        for chapter in snap_to_segments(proposed, chunk) {
+            // A topic running across a chunk boundary comes back twice under one title
+            match chapters.last_mut() {
+                Some(last) if last.title.eq_ignore_ascii_case(&chapter.title) => {
+                    last.end = chapter.end;
+                }
+                _ => chapters.push(chapter),
+            }
+        }
+    }
+    Ok(chapters)
+}
+
+/// Consecutive runs of segments whose text stays under `MAX_CHUNK_CHARS`
+fn chunk_segments(segments: &[TranscriptSegment]) -> Vec<&[TranscriptSegment]> {
+    let mut chunks = Vec::new();
+    let (mut start, mut chars) = (0, 0);
+    for (i, segment) in segments.iter().enumerate() {
+        if chars > 0 && chars + segment.text.len() > MAX_CHUNK_CHARS {
+            chunks.push(&segments[start..i]);
+            (start, chars) = (i, 0);
+        }
+        chars += segment.text.len();
+    }
+    if start < segments.len() {
+        chunks.push(&segments[start..]);
+    }
+    chunks
+}
+
+async fn propose_chapters(
+    api_key: &str,
+    segments: &[TranscriptSegment],
+    options: &ChatOptions,
+) -> Result<Vec<ProposedChapter>> {
+    let transcript = segments
+        .iter()
+        .map(|s| format!("[{:.1}] {}", s.start, s.text))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let request = vec![
+        Message {
+            role: String::from("system"),
+            content: MessageContent::Text(String::from(
+                "Divide the timestamped transcript into chapters where the topic changes. \
+                 Each line starts with its time in seconds. Reply with only a JSON array of \
+                 objects with \"start\" (the time of the chapter's first line), \"title\" \
+                 (a few words) and \"summary\" (one sentence). Keep chapters at least a \
+                 minute long where possible and write in the transcript's language.",
+            )),
+            pinned: false,
+        },
+        Message {
+            role: String::from("user"),
+            content: MessageContent::Text(transcript),
+            pinned: false,
+        },
+    ];
+
+    let response = send_message_impl(api_key, request, Vec::new(), options).await?;
+    parse_chapters(&response.content)
+}
+
+/// The JSON array in the reply, tolerating code fences or text around it
+fn parse_chapters(reply: &str) -> Result<Vec<ProposedChapter>> {
+    let start = reply.find('[').context("Reply contained no chapter list")?;
+    let end = reply.rfind(']').filter(|&end| end > start).context("Reply was cut off")?;
+    serde_json::from_str(&reply[start..=end]).context("Reply was not a valid chapter list")
+}
+
+/// Move each chapter start onto the segment it falls in, drop duplicates and fill in
+/// end times; the first chapter always starts with the first segment
+fn snap_to_segments(
+    mut proposed: Vec<ProposedChapter>,
+    segments: &[TranscriptSegment],
+) -> Vec<Chapter> {
+    let (Some(first), Some(last)) = (segments.first(), segments.last()) else {
+        return Vec::new();
+    };
+    proposed.retain(|c| c.start.is_finite() && !c.title.trim().is_empty());
+    proposed.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    let mut chapters: Vec<Chapter> = Vec::new();
+    for chapter in proposed {
+        let start = segments
+            .iter()
+            .rev()
+            .find(|s| s.start <= chapter.start)
+            .map_or(first.start, |s| s.start);
+        if chapters.last().is_some_and(|last| last.start == start) {
+            continue;
+        }
+        chapters.push(Chapter {
+            start,
+            end: last.end,
+            title: chapter.title.trim().to_string(),
+            summary: chapter.summary.trim().to_string(),
+        });
+    }
+
+    match chapters.first_mut() {
+        Some(chapter) => chapter.start = first.start,
+        None => {
+            return vec![Chapter {
+                start: first.start,
+                end: last.end,
+                title: String::from("Transcript"),
+                summary: String::new(),
+            }]
+        }
+    }
+    let next_starts: Vec<f64> = chapters.iter().skip(1).map(|c| c.start).collect();
+    for (chapter, next_start) in chapters.iter_mut().zip(next_starts) {
+        chapter.end = next_start;
+    }
+    chapters
+}
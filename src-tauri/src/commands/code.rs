@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+use crate::settings::SettingsStore;
+
+/// A fenced code block found in a response
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CodeBlock {
+    /// First word of the fence's info string (` ```rust ` gives `rust`)
+    pub language: Option<String>,
+    pub content: String,
+}
+
+/// Which block to copy to the clipboard when a response completes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoCopyCode {
+    First,
+    Last,
+}
+
+/// Fenced code blocks in `text`, in order, for per-block copy buttons
+#[tauri::command]
+pub fn extract_code_blocks(text: String) -> Vec<CodeBlock> {
+    parse_code_blocks(&text)
+}
+
+/// Add a detected language to fences that have none, so they can be highlighted
+/// Tagged blocks and blocks the heuristics aren't sure about are left as they are
+#[tauri::command]
+pub fn tag_code_blocks(text: String) -> String {
+    tag_code_languages(&text)
+}
+
+/// Opening fence being read: its character, length, line and the block so far
+struct OpenFence {
+    marker: char,
+    len: usize,
+    line: usize,
+    block: CodeBlock,
+}
+
+/// Parse fences the CommonMark way: a fence is 3+ backticks or tildes, and only
+/// a line of at least as many of the same character closes it, so a shorter fence
+/// quoted inside a longer one stays part of the code
+/// An unterminated fence runs to the end of the text
+pub(crate) fn parse_code_blocks(text: &str) -> Vec<CodeBlock> {
+    parse_fences(text).into_iter().map(|(_, block)| block).collect()
+}
+
+/// Blocks with the index of their opening fence line (in `split_inclusive('\n')` lines)
+fn parse_fences(text: &str) -> Vec<(usize, CodeBlock)> {
+    let mut blocks = Vec::new();
+    let mut open: Option<OpenFence> = None;
+
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        let trimmed = line.trim_start();
+        let marker = match trimmed.chars().next() {
+            Some(c @ ('`' | '~')) => c,
+            _ => ' ',
+        };
+        let len = trimmed.chars().take_while(|&c| c == marker).count();
+
+        match &mut open {
+            Some(fence) => {
+                let closes = marker == fence.marker
+                    && len >= fence.len
+                    && trimmed[len..].trim().is_empty();
+                if closes {
+                    let fence = open.take().unwrap();
+                    blocks.push((fence.line, fence.block));
+                } else {
+                    fence.block.content.push_str(line);
+                }
+            }
+            None if len >= 3 => {
+                let info = trimmed[len..].trim();
+                // A backtick fence's info string can't contain backticks (inline code)
+                if marker == '`' && info.contains('`') {
+                    continue;
+                }
+                open = Some(OpenFence {
+                    marker,
+                    len,
+                    line: index,
+                    block: CodeBlock {
+                        language: info.split_whitespace().next().map(str::to_string),
+                        content: String::new(),
+                    },
+                });
+            }
+            None => {}
+        }
+    }
+
+    blocks.extend(open.map(|fence| (fence.line, fence.block)));
+    blocks
+}
+
+/// `tag_code_blocks` for `text`
+pub(crate) fn tag_code_languages(text: &str) -> String {
+    let mut lines: Vec<String> = text.split_inclusive('\n').map(str::to_string).collect();
+
+    for (index, block) in parse_fences(text) {
+        if block.language.is_some() {
+            continue;
+        }
+        let Some(language) = detect_language(&block.content) else {
+            continue;
+        };
+
+        // Insert right after the fence characters, keeping indentation and line ending
+        let line = &mut lines[index];
+        let indent = line.len() - line.trim_start().len();
+        let marker = line[indent..].chars().next().unwrap_or('`');
+        let fence_end = indent + line[indent..].chars().take_while(|&c| c == marker).count();
+        let ending = &line[line.trim_end().len()..];
+        *line = format!("{}{}{}", &line[..fence_end], language, ending);
+    }
+
+    lines.concat()
+}
+
+/// Tells that a snippet is in a language, with how strongly each one counts
+const LANGUAGE_HINTS: &[(&str, &[(&str, u32)])] = &[
+    (
+        "rust",
+        &[("fn ", 2), ("let mut ", 3), ("impl ", 3), ("->", 1), ("::", 1), ("println!", 3)],
+    ),
+    (
+        "python",
+        &[("def ", 2), ("import ", 1), ("self.", 1), ("elif ", 3), ("print(", 1), ("):\n", 2)],
+    ),
+    ("typescript", &[("interface ", 2), (": string", 3), (": number", 3), ("=>", 1)]),
+    (
+        "javascript",
+        &[("const ", 1), ("=>", 1), ("function ", 2), ("console.log", 3), ("require(", 3)],
+    ),
+    ("go", &[("package ", 3), ("func ", 2), (":=", 2), ("fmt.", 3)]),
+    ("java", &[("public class", 3), ("System.out", 3), ("private ", 1), ("void ", 1)]),
+    ("cpp", &[("#include", 3), ("std::", 3), ("int main", 2)]),
+    ("bash", &[("#!/bin/", 4), ("$ ", 1), ("sudo ", 3), ("echo ", 2), ("export ", 2)]),
+    ("sql", &[("SELECT ", 3), (" FROM ", 1), ("INSERT INTO", 4), ("CREATE TABLE", 4)]),
+    ("html", &[("</div>", 3), ("<html", 4), ("</", 1), ("class=\"", 1)]),
+];
+
+/// Guess the language of an unlabeled snippet from keyword hints
+/// Returns `None` unless one language clearly scores highest
+fn detect_language(code: &str) -> Option<&'static str> {
+    let trimmed = code.trim();
+    let is_json = trimmed.starts_with(['{', '['])
+        && serde_json::from_str::<serde_json::Value>(trimmed).is_ok();
+    if is_json {
+        return Some("json");
+    }
+
+    let mut scores: Vec<(&'static str, u32)> = LANGUAGE_HINTS
+        .iter()
+        .map(|(language, hints)| {
+            let score = hints
+                .iter()
+                .filter(|(hint, _)| code.contains(hint))
+                .map(|(_, weight)| weight)
+                .sum();
+            (*language, score)
+        })
+        .collect();
+    scores.sort_by(|a, b| b.1.cmp(&a.1));
+
+    match scores.as_slice() {
+        [(language, best), (_, second), ..] if *best >= 3 && best > second => Some(*language),
+        _ => None,
+    }
+}
+
+/// Tag unlabeled code blocks in a finished reply when `tag_code_languages` is on
+pub(crate) fn enrich_reply(app: &AppHandle, reply: &mut String) {
+    if app.state::<SettingsStore>().get().tag_code_languages {
+        *reply = tag_code_languages(reply);
+    }
+}
+
+/// Copy the configured code block of a finished reply, emitting `code-copied`
+/// Does nothing when the option is off or the reply has no code
+pub(crate) fn auto_copy(app: &AppHandle, reply: &str) {
+    let Some(pick) = app.state::<SettingsStore>().get().auto_copy_code else {
+        return;
+    };
+
+    let blocks = parse_code_blocks(reply);
+    let block = match pick {
+        AutoCopyCode::First => blocks.into_iter().next(),
+        AutoCopyCode::Last => blocks.into_iter().last(),
+    };
+    let Some(block) = block else {
+        return;
+    };
+
+    match app.clipboard().write_text(block.content.clone()) {
+        Ok(()) => {
+            let _ = app.emit("code-copied", block);
+        }
+        Err(e) => eprintln!("Warning: Could not copy code block: {}", e),
+    }
+}
@@ -0,0 +1,103 @@
+use futures_util::{stream, StreamExt};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Instant;
+use tauri::{AppHandle, State};
+
+use crate::commands::ai::{send_message_impl, ChatOptions, ChatResponse, Message};
+use crate::commands::usage;
+use crate::config::ConfigState;
+
+/// Models queried at the same time by `compare_models`
+const MAX_CONCURRENT_MODELS: usize = 3;
+
+/// Most models one comparison may include
+const MAX_COMPARED_MODELS: usize = 8;
+
+/// Usage log tag for comparison requests, so they can be told apart from normal chat
+const COMPARE_TAG: &str = "compare";
+
+/// One model's answer in a comparison
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum ModelAnswer {
+    Ok {
+        response: ChatResponse,
+        /// 0 for models without known pricing
+        cost_usd: f64,
+        elapsed_ms: u64,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+/// Send the same conversation to each of `models` and collect the answers side by side
+/// Requests run a few at a time and fail independently; transient errors are retried
+///
+/// # Returns
+/// Each model's answer or error, keyed by model name
+#[tauri::command]
+pub async fn compare_models(
+    app: AppHandle,
+    config: State<'_, ConfigState>,
+    messages: Vec<Message>,
+    models: Vec<String>,
+) -> Result<BTreeMap<String, ModelAnswer>, String> {
+    config.touch();
+
+    let mut unique: Vec<String> = Vec::new();
+    for model in models.iter().map(|m| m.trim()).filter(|m| !m.is_empty()) {
+        if !unique.iter().any(|u| u == model) {
+            unique.push(model.to_string());
+        }
+    }
+    if unique.is_empty() {
+        return Err("No models to compare".to_string());
+    }
+    if unique.len() > MAX_COMPARED_MODELS {
+        return Err(format!("At most {} models can be compared", MAX_COMPARED_MODELS));
+    }
+
+    let api_key = config.get().openai_api_key;
+    let answers = stream::iter(unique)
+        .map(|model| {
+            let options = ChatOptions {
+                app: Some(app.clone()),
+                model: Some(model.clone()),
+                tags: vec![COMPARE_TAG.to_string()],
+                user_turn: true,
+                ..ChatOptions::default()
+            };
+            let (api_key, messages) = (&api_key, &messages);
+            async move {
+                let answer = ask_model(api_key, messages, &options).await;
+                (model, answer)
+            }
+        })
+        .buffer_unordered(MAX_CONCURRENT_MODELS)
+        .collect()
+        .await;
+
+    Ok(answers)
+}
+
+async fn ask_model(api_key: &str, messages: &[Message], options: &ChatOptions) -> ModelAnswer {
+    let started = Instant::now();
+    // Transient failures are already retried per `chat_retry` inside the request
+    let result = send_message_impl(api_key, messages.to_vec(), Vec::new(), options).await;
+
+    match result {
+        Ok(response) => ModelAnswer::Ok {
+            cost_usd: response
+                .usage
+                .as_ref()
+                .map_or(0.0, |u| usage::chat_cost(&options.model(), u)),
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            response,
+        },
+        Err(e) => ModelAnswer::Failed {
+            error: e.to_string(),
+        },
+    }
+}
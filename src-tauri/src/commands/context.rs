@@ -0,0 +1,194 @@
+use serde::Serialize;
+use std::sync::Mutex;
+
+/// Application in front when a screenshot was taken
+#[derive(Debug, Clone, Serialize)]
+pub struct AppContext {
+    pub app_name: String,
+    pub window_title: Option<String>,
+}
+
+impl AppContext {
+    /// Short text block prepended to the message sent with the screenshot
+    pub fn describe(&self) -> String {
+        match &self.window_title {
+            Some(title) => format!(
+                "Context: the screenshot shows the application \"{}\", window \"{}\".",
+                self.app_name, title
+            ),
+            None => format!(
+                "Context: the screenshot shows the application \"{}\".",
+                self.app_name
+            ),
+        }
+    }
+}
+
+/// Context recorded with the most recent screenshot
+/// Only filled when `include_app_context` is enabled in settings
+#[derive(Default)]
+pub struct CaptureContext(Mutex<Option<AppContext>>);
+
+impl CaptureContext {
+    pub fn set(&self, context: Option<AppContext>) {
+        *self.0.lock().unwrap() = context;
+    }
+
+    pub fn get(&self) -> Option<AppContext> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// An on-screen application window
+#[derive(Debug, Clone, Serialize)]
+pub struct WindowInfo {
+    /// CoreGraphics window number
+    pub id: u32,
+    pub pid: i32,
+    pub app_name: String,
+    /// Only readable with the Screen Recording permission
+    pub title: Option<String>,
+}
+
+/// On-screen application windows, front to back
+/// Our own overlay windows are left out unless `include_own` is set (for debugging),
+/// so they can't be picked and captured by accident
+#[tauri::command]
+pub fn list_windows(include_own: Option<bool>) -> Vec<WindowInfo> {
+    let include_own = include_own.unwrap_or(false);
+    let own_pid = std::process::id() as i32;
+    app_windows()
+        .into_iter()
+        .filter(|window| include_own || window.pid != own_pid)
+        .collect()
+}
+
+/// Frontmost application name and window title
+/// Our own overlay is skipped so the context describes what the user is looking at
+#[cfg(target_os = "macos")]
+pub fn frontmost_app() -> Option<AppContext> {
+    use cocoa::base::{id, nil};
+    use objc::{class, msg_send, sel, sel_impl};
+
+    let own_pid = std::process::id() as i32;
+
+    // Prefer NSWorkspace's notion of the active application
+    let frontmost_pid: Option<i32> = unsafe {
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let frontmost: id = msg_send![workspace, frontmostApplication];
+        if frontmost == nil {
+            None
+        } else {
+            let pid: i32 = msg_send![frontmost, processIdentifier];
+            Some(pid).filter(|&pid| pid != own_pid)
+        }
+    };
+
+    app_windows()
+        .into_iter()
+        .filter(|window| window.pid != own_pid)
+        .find(|window| !frontmost_pid.is_some_and(|front| front != window.pid))
+        .map(|window| AppContext {
+            app_name: window.app_name,
+            window_title: window.title,
+        })
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn frontmost_app() -> Option<AppContext> {
+    None
+}
+
+/// Normal application windows currently on screen, ordered front to back
+#[cfg(target_os = "macos")]
+fn app_windows() -> Vec<WindowInfo> {
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::NSString;
+    use objc::{msg_send, sel, sel_impl};
+    use std::ffi::{c_void, CStr};
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGWindowListCopyWindowInfo(option: u32, relative_to_window: u32) -> id;
+    }
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFRelease(cf: *const c_void);
+    }
+
+    // kCGWindowListOptionOnScreenOnly | kCGWindowListExcludeDesktopElements
+    const WINDOW_LIST_OPTIONS: u32 = (1 << 0) | (1 << 4);
+    const NULL_WINDOW_ID: u32 = 0;
+
+    unsafe fn string_value(value: id) -> Option<String> {
+        if value == nil {
+            return None;
+        }
+        let utf8: *const std::os::raw::c_char = msg_send![value, UTF8String];
+        if utf8.is_null() {
+            return None;
+        }
+        let text = CStr::from_ptr(utf8).to_string_lossy().trim().to_string();
+        (!text.is_empty()).then_some(text)
+    }
+
+    unsafe fn integer_value(value: id) -> Option<i64> {
+        if value == nil {
+            return None;
+        }
+        Some(msg_send![value, longLongValue])
+    }
+
+    unsafe {
+        // Window list is ordered front to back; toll-free bridged to NSArray
+        let windows = CGWindowListCopyWindowInfo(WINDOW_LIST_OPTIONS, NULL_WINDOW_ID);
+        if windows == nil {
+            return Vec::new();
+        }
+
+        let key_number = NSString::alloc(nil).init_str("kCGWindowNumber");
+        let key_pid = NSString::alloc(nil).init_str("kCGWindowOwnerPID");
+        let key_owner = NSString::alloc(nil).init_str("kCGWindowOwnerName");
+        let key_name = NSString::alloc(nil).init_str("kCGWindowName");
+        let key_layer = NSString::alloc(nil).init_str("kCGWindowLayer");
+
+        let mut list = Vec::new();
+        let count: usize = msg_send![windows, count];
+        for i in 0..count {
+            let info: id = msg_send![windows, objectAtIndex: i];
+
+            // Layer 0 holds normal application windows (no menu bar, dock, overlays)
+            let layer = integer_value(msg_send![info, objectForKey: key_layer]);
+            let number = integer_value(msg_send![info, objectForKey: key_number]);
+            let pid = integer_value(msg_send![info, objectForKey: key_pid]);
+            let (Some(number), Some(pid)) = (number, pid) else {
+                continue;
+            };
+            if layer != Some(0) {
+                continue;
+            }
+            let Some(app_name) = string_value(msg_send![info, objectForKey: key_owner]) else {
+                continue;
+            };
+
+            list.push(WindowInfo {
+                id: number as u32,
+                pid: pid as i32,
+                app_name,
+                title: string_value(msg_send![info, objectForKey: key_name]),
+            });
+        }
+
+        for key in [key_number, key_pid, key_owner, key_name, key_layer] {
+            let _: () = msg_send![key, release];
+        }
+        CFRelease(windows as *const c_void);
+
+        list
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn app_windows() -> Vec<WindowInfo> {
+    Vec::new()
+}
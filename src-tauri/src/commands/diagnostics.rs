@@ -0,0 +1,229 @@
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+
+use crate::commands::ai::{extra_headers, http_client, models_url};
+use crate::commands::audio;
+use crate::commands::permissions::{self, PermissionStatus};
+use crate::config::ConfigState;
+
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    /// Couldn't be checked because an earlier check failed
+    Skipped,
+}
+
+/// Outcome of one diagnostic check
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// What the user can do about a failure
+    pub hint: Option<String>,
+}
+
+impl DiagnosticCheck {
+    fn pass(name: &str, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn fail(name: &str, detail: impl Into<String>, hint: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            status: CheckStatus::Fail,
+            detail: detail.into(),
+            hint: Some(hint.to_string()),
+        }
+    }
+
+    fn skipped(name: &str, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: CheckStatus::Skipped,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticReport {
+    /// False if any check failed
+    pub passed: bool,
+    pub checks: Vec<DiagnosticCheck>,
+}
+
+/// Check the API key, network, microphone, permissions and writable directories
+/// Each check runs independently, so one failure doesn't hide the others
+#[tauri::command]
+pub async fn run_diagnostics(
+    app: AppHandle,
+    config: State<'_, ConfigState>,
+) -> Result<DiagnosticReport, String> {
+    let mut checks = Vec::new();
+
+    checks.extend(check_api(&config).await);
+
+    let permissions = permissions::check_permissions();
+    checks.push(check_permission("microphone-permission", permissions.microphone));
+    checks.push(check_permission("screen-recording-permission", permissions.screen_recording));
+    checks.push(
+        tauri::async_runtime::spawn_blocking(check_input_device)
+            .await
+            .map_err(|e| format!("Diagnostics failed: {}", e))?,
+    );
+
+    let dirs = [
+        ("data-dir", app.path().app_data_dir()),
+        ("log-dir", app.path().app_log_dir()),
+        ("temp-dir", Ok(std::env::temp_dir())),
+    ];
+    for (name, dir) in dirs {
+        checks.push(match dir {
+            Ok(dir) => check_writable(name, &dir),
+            Err(e) => DiagnosticCheck::fail(
+                name,
+                format!("Could not resolve directory: {}", e),
+                "Report this as a bug, including your OS version",
+            ),
+        });
+    }
+
+    Ok(DiagnosticReport {
+        passed: checks.iter().all(|c| c.status != CheckStatus::Fail),
+        checks,
+    })
+}
+
+/// Network reachability and API key validity, from one request to the models endpoint
+async fn check_api(config: &ConfigState) -> [DiagnosticCheck; 2] {
+    if config.is_locked() {
+        return [
+            DiagnosticCheck::skipped("network", "App is locked"),
+            DiagnosticCheck::fail("api-key", "App is locked", "Unlock the app and run again"),
+        ];
+    }
+
+    let api_key = config.get().openai_api_key;
+    let url = models_url();
+    let response = http_client()
+        .get(&url)
+        .headers(extra_headers())
+        .bearer_auth(&api_key)
+        .timeout(NETWORK_TIMEOUT)
+        .send()
+        .await;
+
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            return [
+                DiagnosticCheck::fail(
+                    "network",
+                    format!("Could not reach {}: {}", url, e),
+                    "Check your internet connection, proxy and firewall settings",
+                ),
+                DiagnosticCheck::skipped("api-key", "Network unavailable"),
+            ]
+        }
+    };
+
+    let network = DiagnosticCheck::pass("network", format!("Reached {}", url));
+    let status = response.status();
+    let api_key = if status.is_success() {
+        DiagnosticCheck::pass("api-key", "Key accepted")
+    } else if status == reqwest::StatusCode::UNAUTHORIZED {
+        DiagnosticCheck::fail(
+            "api-key",
+            "Key rejected (401)",
+            "Set a valid OPENAI_API_KEY in .env, then reload the config",
+        )
+    } else {
+        DiagnosticCheck::fail(
+            "api-key",
+            format!("Unexpected response: {}", status),
+            "Check https://status.openai.com and your account's API access",
+        )
+    };
+    [network, api_key]
+}
+
+fn check_permission(name: &str, status: PermissionStatus) -> DiagnosticCheck {
+    let hint = "Allow the app in System Settings > Privacy & Security, then restart it";
+    match status {
+        PermissionStatus::Granted => DiagnosticCheck::pass(name, "Granted"),
+        PermissionStatus::Unsupported => {
+            DiagnosticCheck::skipped(name, "Not applicable on this platform")
+        }
+        PermissionStatus::NotDetermined => DiagnosticCheck::fail(
+            name,
+            "Not requested yet",
+            "Grant it from the onboarding checklist (request_permissions)",
+        ),
+        PermissionStatus::Denied => DiagnosticCheck::fail(name, "Denied", hint),
+        PermissionStatus::Restricted => DiagnosticCheck::fail(
+            name,
+            "Restricted by a device-management policy",
+            "Ask your administrator to allow it",
+        ),
+    }
+}
+
+/// Default input device and the format it would record in
+fn check_input_device() -> DiagnosticCheck {
+    use cpal::traits::DeviceTrait;
+
+    let hint = "Connect a microphone or pick an input device in the system sound settings";
+    let device = match audio::input_device() {
+        Ok(device) => device,
+        Err(e) => return DiagnosticCheck::fail("input-device", e.to_string(), hint),
+    };
+    let name = device.name().unwrap_or_else(|_| String::from("Unknown device"));
+
+    match device.default_input_config() {
+        Ok(config) => DiagnosticCheck::pass(
+            "input-device",
+            format!(
+                "{}: {} Hz, {} channel(s), {:?}",
+                name,
+                config.sample_rate().0,
+                config.channels(),
+                config.sample_format()
+            ),
+        ),
+        Err(e) => DiagnosticCheck::fail(
+            "input-device",
+            format!("{} has no usable input format: {}", name, e),
+            hint,
+        ),
+    }
+}
+
+/// Create `dir` if needed and write (then remove) a probe file in it
+fn check_writable(name: &str, dir: &Path) -> DiagnosticCheck {
+    let probe = dir.join(".diagnostics-probe");
+    let result = std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::write(&probe, b"ok"))
+        .and_then(|_| std::fs::remove_file(&probe));
+
+    match result {
+        Ok(()) => DiagnosticCheck::pass(name, format!("{} is writable", dir.display())),
+        Err(e) => DiagnosticCheck::fail(
+            name,
+            format!("{} is not writable: {}", dir.display(), e),
+            "Fix the folder's permissions or free up disk space",
+        ),
+    }
+}
@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::commands::ai::{ContentPart, Message, MessageContent};
+use crate::settings::SettingsStore;
+
+/// Messages longer than this (after normalizing) are only compared exactly,
+/// since edit distance is quadratic in length
+const MAX_FUZZY_CHARS: usize = 2000;
+
+/// Warn before resending a question that was just asked
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DuplicateCheck {
+    pub enabled: bool,
+    /// Similarity from 0 to 1 at which messages count as duplicates; 1 means identical
+    pub threshold: f32,
+    /// How many earlier user messages to compare against
+    pub lookback: usize,
+    /// Messages shorter than this (after normalizing) are never flagged, so replies
+    /// like "yes" or "continue" can be repeated
+    pub min_chars: usize,
+}
+
+impl Default for DuplicateCheck {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold: 0.9,
+            lookback: 10,
+            min_chars: 20,
+        }
+    }
+}
+
+/// The latest user message repeats an earlier one
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateMessage {
+    /// Index in `messages` of the earlier message
+    pub index: usize,
+    pub similarity: f32,
+}
+
+impl std::fmt::Display for DuplicateMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.similarity >= 1.0 {
+            write!(f, "This message was already sent (message {})", self.index + 1)
+        } else {
+            write!(
+                f,
+                "This message is {:.0}% similar to message {}",
+                self.similarity * 100.0,
+                self.index + 1
+            )
+        }
+    }
+}
+
+impl std::error::Error for DuplicateMessage {}
+
+/// Check whether the last user message in `messages` repeats a recent one
+///
+/// # Returns
+/// The closest earlier match at or above the threshold, or `None`
+/// (also when the check is turned off in settings)
+#[tauri::command]
+pub fn check_duplicate(
+    settings: State<'_, SettingsStore>,
+    messages: Vec<Message>,
+) -> Option<DuplicateMessage> {
+    let check = settings.get().duplicate_check;
+    if !check.enabled {
+        return None;
+    }
+    find_duplicate(&messages, &check)
+}
+
+/// Compare the last user message against the `lookback` user messages before it
+/// Multimodal messages are compared by their text parts only; a latest message with
+/// an image or audio attached is a new question about it and never a duplicate
+pub(crate) fn find_duplicate(
+    messages: &[Message],
+    check: &DuplicateCheck,
+) -> Option<DuplicateMessage> {
+    let mut user_messages = messages
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, m)| m.role == "user");
+
+    let (_, latest) = user_messages.next()?;
+    if has_attachments(latest) {
+        return None;
+    }
+    let latest = normalize(&latest.content.text());
+    if latest.is_empty() || latest.chars().count() < check.min_chars {
+        return None;
+    }
+
+    user_messages
+        .take(check.lookback)
+        .map(|(index, message)| DuplicateMessage {
+            index,
+            similarity: similarity(&latest, &normalize(&message.content.text())),
+        })
+        .filter(|d| d.similarity >= check.threshold)
+        .max_by(|a, b| a.similarity.total_cmp(&b.similarity))
+}
+
+fn has_attachments(message: &Message) -> bool {
+    match &message.content {
+        MessageContent::Text(_) => false,
+        MessageContent::Parts(parts) => {
+            parts.iter().any(|part| !matches!(part, ContentPart::Text { .. }))
+        }
+    }
+}
+
+/// Lowercase, drop punctuation and collapse whitespace, so "What's X?" matches "whats x"
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 1 minus the edit distance relative to the longer text
+fn similarity(a: &str, b: &str) -> f32 {
+    if a == b {
+        return 1.0;
+    }
+
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 || longest > MAX_FUZZY_CHARS {
+        return 0.0;
+    }
+    1.0 - levenshtein(&a, &b) as f32 / longest as f32
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::ai::ImageUrl;
+
+    fn user(text: &str) -> Message {
+        Message {
+            role: String::from("user"),
+            content: MessageContent::Text(text.to_string()),
+            pinned: false,
+        }
+    }
+
+    #[test]
+    fn repeated_question_is_flagged() {
+        let messages = [
+            user("What is the capital of Australia?"),
+            user("Thanks!"),
+            user("what is the capital of  australia"),
+        ];
+        let duplicate = find_duplicate(&messages, &DuplicateCheck::default()).unwrap();
+        assert_eq!(duplicate.index, 0);
+        assert!(duplicate.similarity >= 1.0);
+    }
+
+    #[test]
+    fn short_replies_can_be_repeated() {
+        let messages = [user("continue"), user("Continue.")];
+        assert!(find_duplicate(&messages, &DuplicateCheck::default()).is_none());
+    }
+
+    #[test]
+    fn message_with_an_image_is_not_a_duplicate() {
+        let question = "What is wrong with this code snippet?";
+        let with_image = Message {
+            content: MessageContent::Parts(vec![
+                ContentPart::Text {
+                    text: question.to_string(),
+                    cache_control: None,
+                },
+                ContentPart::ImageUrl {
+                    image_url: ImageUrl {
+                        url: String::from("data:image/png;base64,iVBORw0KGgo="),
+                    },
+                },
+            ]),
+            ..user(question)
+        };
+        let messages = [user(question), with_image];
+        assert!(find_duplicate(&messages, &DuplicateCheck::default()).is_none());
+    }
+}
@@ -0,0 +1,296 @@
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::PathBuf;
+use syntect::highlighting::ThemeSet;
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::SyntaxSet;
+
+use tauri::State;
+
+use crate::commands::ai::{ContentPart, Message, MessageContent};
+use crate::commands::audio::{self, AudioFileFormat, AudioRecorder};
+use crate::commands::history::HistoryStore;
+use crate::settings::SettingsStore;
+
+const HTML_STYLE: &str = r#"
+body { font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", sans-serif; max-width: 48rem; margin: 2rem auto; padding: 0 1rem; color: #1d1d1f; background: #fff; line-height: 1.5; }
+.message { margin: 1.5rem 0; padding: 1rem 1.25rem; border-radius: 12px; }
+.user { background: #eef4ff; }
+.assistant { background: #f5f5f7; }
+.system { background: #fff8e6; font-size: 0.9em; }
+.role { font-weight: 600; font-size: 0.8em; text-transform: uppercase; letter-spacing: 0.05em; color: #6e6e73; margin-bottom: 0.5rem; }
+pre { padding: 0.75rem 1rem; border-radius: 8px; overflow-x: auto; font-size: 0.85em; }
+img { max-width: 100%; border-radius: 8px; margin: 0.5rem 0; }
+.attachment { color: #6e6e73; font-style: italic; }
+"#;
+
+/// Render the conversation to a standalone HTML file
+/// Code blocks are syntax-highlighted and images embedded inline, so the file
+/// opens correctly on its own in any browser
+///
+/// # Returns
+/// The path written (`.html` is added if missing)
+#[tauri::command]
+pub fn export_html(messages: Vec<Message>, path: PathBuf) -> Result<PathBuf, String> {
+    export_html_impl(&messages, path).map_err(|e| format!("Export failed: {}", e))
+}
+
+fn export_html_impl(messages: &[Message], mut path: PathBuf) -> Result<PathBuf> {
+    let has_extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("html") || e.eq_ignore_ascii_case("htm"));
+    if !has_extension {
+        path.set_extension("html");
+    }
+
+    let html = render_html(messages)?;
+    std::fs::write(&path, html).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Write one zip holding everything from a voice session: the recording, its
+/// transcript and the conversation as Markdown and JSON
+/// `messages` defaults to the current saved conversation; the recording is the
+/// current buffer, or the last transcribed one once the buffer was taken
+/// Missing parts are left out, but at least one must be present
+///
+/// # Returns
+/// The path written (`.zip` is added if missing)
+#[tauri::command]
+pub fn export_session_bundle(
+    recorder: State<'_, AudioRecorder>,
+    settings: State<'_, SettingsStore>,
+    history: State<'_, HistoryStore>,
+    path: PathBuf,
+    messages: Option<Vec<Message>>,
+    transcript: Option<String>,
+    format: Option<AudioFileFormat>,
+) -> Result<PathBuf, String> {
+    let messages = messages
+        .or_else(|| history.current().map(|c| c.messages))
+        .unwrap_or_default();
+    let transcript = transcript.filter(|t| !t.trim().is_empty());
+
+    let mut samples = recorder.get_audio_buffer();
+    if samples.is_empty() {
+        samples = recorder.last_transcribed();
+    }
+    let format = format.unwrap_or(AudioFileFormat::Wav);
+    let recording = if samples.is_empty() {
+        None
+    } else {
+        let preferred_rate = settings.get().preferred_sample_rate;
+        let bytes = audio::encode_recording(&recorder, samples, format, preferred_rate)
+            .map_err(|e| format!("Failed to encode recording: {}", e))?;
+        Some((format!("recording.{}", format.extension()), bytes))
+    };
+
+    if recording.is_none() && transcript.is_none() && messages.is_empty() {
+        return Err("Nothing to export: no recording, transcript or conversation".to_string());
+    }
+
+    export_bundle_impl(path, recording, transcript.as_deref(), &messages)
+        .map_err(|e| format!("Export failed: {}", e))
+}
+
+fn export_bundle_impl(
+    mut path: PathBuf,
+    recording: Option<(String, Vec<u8>)>,
+    transcript: Option<&str>,
+    messages: &[Message],
+) -> Result<PathBuf> {
+    use zip::write::SimpleFileOptions;
+    use zip::CompressionMethod;
+
+    let has_extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("zip"));
+    if !has_extension {
+        path.set_extension("zip");
+    }
+
+    let file = std::fs::File::create(&path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let text = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    // Audio is either already compressed or barely compressible
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    let mut add = |name: &str, options: SimpleFileOptions, bytes: &[u8]| -> Result<()> {
+        zip.start_file(name, options)
+            .with_context(|| format!("Failed to add {}", name))?;
+        zip.write_all(bytes).with_context(|| format!("Failed to write {}", name))
+    };
+
+    if let Some((name, bytes)) = &recording {
+        add(name, stored, bytes)?;
+    }
+    if let Some(transcript) = transcript {
+        add("transcript.txt", text, transcript.as_bytes())?;
+    }
+    if !messages.is_empty() {
+        add("conversation.md", text, render_markdown(messages).as_bytes())?;
+        let json = serde_json::to_vec_pretty(messages).context("Failed to serialize messages")?;
+        add("conversation.json", text, &json)?;
+    }
+
+    zip.finish().context("Failed to finish zip file")?;
+    Ok(path)
+}
+
+/// Render the conversation as Markdown, one heading per message
+/// Images and audio are noted but not embedded
+pub(crate) fn render_markdown(messages: &[Message]) -> String {
+    let mut markdown = String::from("# Phantom conversation\n");
+    for message in messages {
+        let mut role = message.role.clone();
+        if let Some(first) = role.get_mut(..1) {
+            first.make_ascii_uppercase();
+        }
+        markdown.push_str(&format!("\n## {}\n\n", role));
+
+        match &message.content {
+            MessageContent::Text(text) => markdown.push_str(text.trim_end()),
+            MessageContent::Parts(parts) => {
+                let rendered: Vec<String> = parts
+                    .iter()
+                    .map(|part| match part {
+                        ContentPart::Text { text, .. } => text.trim_end().to_string(),
+                        ContentPart::ImageUrl { .. } => String::from("*[Image attachment]*"),
+                        ContentPart::InputAudio { .. } => String::from("*[Audio attachment]*"),
+                    })
+                    .collect();
+                markdown.push_str(&rendered.join("\n\n"));
+            }
+        }
+        markdown.push('\n');
+    }
+    markdown
+}
+
+fn render_html(messages: &[Message]) -> Result<String> {
+    let highlighter = Highlighter::new();
+
+    let mut body = String::new();
+    for message in messages {
+        let role = match message.role.as_str() {
+            "user" | "assistant" | "system" => message.role.as_str(),
+            _ => "system",
+        };
+        body.push_str(&format!(
+            "<div class=\"message {}\">\n<div class=\"role\">{}</div>\n",
+            role,
+            escape_html(&message.role)
+        ));
+
+        match &message.content {
+            MessageContent::Text(text) => body.push_str(&highlighter.render_text(text)?),
+            MessageContent::Parts(parts) => {
+                for part in parts {
+                    match part {
+                        ContentPart::Text { text, .. } => {
+                            body.push_str(&highlighter.render_text(text)?)
+                        }
+                        ContentPart::ImageUrl { image_url } => body.push_str(&format!(
+                            "<img src=\"{}\" alt=\"Attached image\">\n",
+                            escape_html(&image_url.url)
+                        )),
+                        ContentPart::InputAudio { .. } => body
+                            .push_str("<p class=\"attachment\">[Audio attachment]</p>\n"),
+                    }
+                }
+            }
+        }
+
+        body.push_str("</div>\n");
+    }
+
+    Ok(format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Phantom conversation</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        HTML_STYLE, body
+    ))
+}
+
+struct Highlighter {
+    syntaxes: SyntaxSet,
+    themes: ThemeSet,
+}
+
+impl Highlighter {
+    fn new() -> Self {
+        Self {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            themes: ThemeSet::load_defaults(),
+        }
+    }
+
+    /// Render message text: fenced code blocks are highlighted, the rest escaped
+    fn render_text(&self, text: &str) -> Result<String> {
+        let mut html = String::new();
+        let mut prose = String::new();
+        let mut code: Option<(String, String)> = None;
+
+        for line in text.split_inclusive('\n') {
+            match line.trim_start().strip_prefix("```") {
+                Some(fence) => match code.take() {
+                    // Closing fence
+                    Some((lang, source)) => html.push_str(&self.render_code(&lang, &source)?),
+                    // Opening fence, optionally naming the language
+                    None => {
+                        html.push_str(&render_prose(&std::mem::take(&mut prose)));
+                        code = Some((fence.trim().to_string(), String::new()));
+                    }
+                },
+                None => match &mut code {
+                    Some((_, source)) => source.push_str(line),
+                    None => prose.push_str(line),
+                },
+            }
+        }
+
+        // An unterminated fence still renders as code
+        if let Some((lang, source)) = code {
+            html.push_str(&self.render_code(&lang, &source)?);
+        }
+        html.push_str(&render_prose(&prose));
+        Ok(html)
+    }
+
+    fn render_code(&self, lang: &str, source: &str) -> Result<String> {
+        let syntax = self
+            .syntaxes
+            .find_syntax_by_token(lang)
+            .unwrap_or_else(|| self.syntaxes.find_syntax_plain_text());
+        let theme = &self.themes.themes["InspiredGitHub"];
+
+        highlighted_html_for_string(source, &self.syntaxes, syntax, theme)
+            .context("Failed to highlight code block")
+    }
+}
+
+/// Escaped paragraphs, single newlines kept as line breaks
+fn render_prose(text: &str) -> String {
+    text.split("\n\n")
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| format!("<p>{}</p>\n", escape_html(p).replace('\n', "<br>\n")))
+        .collect()
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
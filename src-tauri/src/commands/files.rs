@@ -0,0 +1,167 @@
+use base64::Engine;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::commands::image::{supported_mime, MAX_IMAGE_BYTES};
+
+/// Most dropped paths remembered for `read_dropped_files`, newest kept
+const MAX_DROPPED_PATHS: usize = 64;
+
+/// Larger text files would crowd out the conversation (~50k tokens)
+const MAX_TEXT_BYTES: u64 = 200 * 1024;
+
+/// Bytes checked for NULs when deciding whether a file is text
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+
+/// A dropped file, ready to attach or preview
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum DroppedFile {
+    /// Send as an image attachment (`images` / `screenshot_base64`)
+    Image {
+        name: String,
+        mime: String,
+        base64: String,
+    },
+    /// Send as context; `context` is the file fenced and labeled for the prompt
+    Text {
+        name: String,
+        language: Option<String>,
+        content: String,
+        context: String,
+    },
+    /// Not attachable, with the reason for the preview
+    Rejected { name: String, reason: String },
+}
+
+/// Paths the user dropped on the window
+/// `read_dropped_files` reads only these, so the webview can't use it to read any file
+#[derive(Default)]
+pub struct DroppedPaths(Mutex<VecDeque<PathBuf>>);
+
+impl DroppedPaths {
+    fn record(&self, paths: &[PathBuf]) {
+        let mut dropped = self.0.lock().unwrap();
+        dropped.extend(paths.iter().cloned());
+        let excess = dropped.len().saturating_sub(MAX_DROPPED_PATHS);
+        dropped.drain(..excess);
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        self.0.lock().unwrap().iter().any(|dropped| dropped == path)
+    }
+}
+
+/// Read dropped files again as attachments and context
+/// Only paths from a drop on the window are read; anything else, and files that are
+/// too large, unreadable or binary, come back as `rejected`
+#[tauri::command]
+pub async fn read_dropped_files(
+    dropped: State<'_, DroppedPaths>,
+    paths: Vec<PathBuf>,
+) -> Result<Vec<DroppedFile>, String> {
+    let allowed: Vec<bool> = paths.iter().map(|path| dropped.contains(path)).collect();
+    tauri::async_runtime::spawn_blocking(move || {
+        paths
+            .iter()
+            .zip(allowed)
+            .map(|(path, allowed)| {
+                if allowed {
+                    return read_file(path);
+                }
+                DroppedFile::Rejected {
+                    name: file_name(path),
+                    reason: String::from("Only files dropped on the window can be read"),
+                }
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| format!("Failed to read files: {}", e))
+}
+
+/// Handle a drop on the window: remember the paths, read the files and emit
+/// `files-dropped`
+pub(crate) fn handle_drop(app: &AppHandle, paths: Vec<PathBuf>) {
+    app.state::<DroppedPaths>().record(&paths);
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let files: Vec<DroppedFile> = paths.iter().map(|p| read_file(p)).collect();
+        let _ = app.emit("files-dropped", files);
+    });
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+fn read_file(path: &Path) -> DroppedFile {
+    let name = file_name(path);
+    let rejected = |reason: String| DroppedFile::Rejected {
+        name: name.clone(),
+        reason,
+    };
+
+    let size = match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => return rejected("Folders can't be attached".into()),
+        Ok(metadata) => metadata.len(),
+        Err(e) => return rejected(format!("Could not read file: {}", e)),
+    };
+    if size > (MAX_IMAGE_BYTES as u64).max(MAX_TEXT_BYTES) {
+        return rejected(format!("File is too large ({} MB)", size / (1024 * 1024)));
+    }
+
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => return rejected(format!("Could not read file: {}", e)),
+    };
+
+    // Detect images by content, not extension, accepting what the send path accepts
+    if let Ok(format) = image::guess_format(&bytes) {
+        let base64 = base64::engine::general_purpose::STANDARD.encode(&bytes);
+        let Some(mime) = supported_mime(&base64) else {
+            return rejected(format!("Unsupported image format {:?}", format));
+        };
+        return DroppedFile::Image {
+            name,
+            mime: mime.to_string(),
+            base64,
+        };
+    }
+
+    if size > MAX_TEXT_BYTES {
+        return rejected(format!(
+            "Text file is too large ({} KB, limit {} KB)",
+            size / 1024,
+            MAX_TEXT_BYTES / 1024
+        ));
+    }
+    if bytes.iter().take(BINARY_SNIFF_BYTES).any(|&b| b == 0) {
+        return rejected("Binary files can't be attached".into());
+    }
+    let Ok(content) = String::from_utf8(bytes) else {
+        return rejected("File is not UTF-8 text".into());
+    };
+
+    let language = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    let context = format!(
+        "File: {}\n```{}\n{}\n```",
+        name,
+        language.as_deref().unwrap_or(""),
+        content.trim_end()
+    );
+    DroppedFile::Text {
+        name,
+        language,
+        content,
+        context,
+    }
+}
@@ -0,0 +1,1405 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::commands::ai::{send_message_impl, ChatOptions, ContentPart, Message, MessageContent};
+use crate::commands::memory;
+use crate::commands::transfer::{Delivery, Transfers};
+use crate::commands::usage;
+use crate::config::ConfigState;
+use crate::settings::SettingsStore;
+
+/// Length of the title derived from the first user message
+const TITLE_CHARS: usize = 60;
+
+/// Roles accepted by `append_message`
+const MESSAGE_ROLES: [&str; 3] = ["system", "user", "assistant"];
+
+/// Search index file; the leading dot keeps it from ever matching a conversation id
+pub(crate) const INDEX_FILE: &str = ".index.json";
+
+/// Directory in the history directory holding the images of saved conversations
+/// Images are stored once each, by content, instead of as base64 inside the JSON
+pub(crate) const IMAGES_DIR: &str = "images";
+
+/// URL that stands in for an image in `IMAGES_DIR` in a conversation file
+const IMAGE_REF_PREFIX: &str = "ghostgpt-image:";
+
+/// Characters of each opening message shown to the model when generating a title
+const TITLE_CONTEXT_CHARS: usize = 1000;
+
+const TITLE_PROMPT: &str = "Write a short title (at most six words) for the conversation \
+below. Reply with the title only, without quotes or a trailing period.";
+
+/// Characters of context shown either side of a search match
+const SNIPPET_CONTEXT_CHARS: usize = 40;
+
+/// Snippets returned per matching conversation
+const SNIPPETS_PER_RESULT: usize = 3;
+
+/// Global shortcuts for flipping between saved conversations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConversationHotkeys {
+    pub next: Option<String>,
+    pub previous: Option<String>,
+}
+
+impl Default for ConversationHotkeys {
+    fn default() -> Self {
+        Self {
+            next: Some(String::from("CommandOrControl+Option+]")),
+            previous: Some(String::from("CommandOrControl+Option+[")),
+        }
+    }
+}
+
+/// When a conversation is archived and replaced by a fresh one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArchiveSettings {
+    /// Archive once a saved conversation has more than this many messages
+    /// `None` (the default) lets conversations grow without limit
+    pub max_messages: Option<usize>,
+    /// Start the new conversation with a summary of the archived one
+    pub carry_summary: bool,
+}
+
+impl Default for ArchiveSettings {
+    fn default() -> Self {
+        Self {
+            max_messages: None,
+            carry_summary: true,
+        }
+    }
+}
+
+/// Payload of the `conversation-archived` event
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationArchived {
+    pub old_id: String,
+    pub new_id: String,
+    /// Opening messages of the new conversation (system prompts and summary)
+    pub messages: Vec<Message>,
+}
+
+/// A conversation saved to disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedConversation {
+    pub id: String,
+    pub title: String,
+    /// Seconds since the UNIX epoch
+    pub updated_at: u64,
+    /// Model last used in this conversation, restored when it is reloaded
+    #[serde(default)]
+    pub model: Option<String>,
+    /// User-defined labels, e.g. "work" or "personal"
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The title was written by the model and is kept on later saves
+    #[serde(default)]
+    pub generated_title: bool,
+    pub messages: Vec<Message>,
+}
+
+/// A conversation that could not be deleted, and why
+#[derive(Debug, Clone, Serialize)]
+pub struct DeleteFailure {
+    pub id: String,
+    pub error: String,
+}
+
+/// Result of `merge_conversations`
+#[derive(Debug, Clone, Serialize)]
+pub struct MergedConversation {
+    pub id: String,
+    /// Originals that could not be deleted; the merged conversation was still saved
+    pub delete_failures: Vec<DeleteFailure>,
+}
+
+/// Result of `cleanup_old_conversations`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Cleanup {
+    /// Ids of the deleted conversations
+    pub deleted: Vec<String>,
+    pub failed: Vec<DeleteFailure>,
+}
+
+/// Payload of the `conversation-titled` event
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationTitled {
+    pub id: String,
+    pub title: String,
+}
+
+/// Listing entry, without the messages
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationSummary {
+    pub id: String,
+    pub title: String,
+    pub updated_at: u64,
+    pub model: Option<String>,
+    pub tags: Vec<String>,
+    pub message_count: usize,
+}
+
+/// A conversation matching `search_conversations`
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResult {
+    pub id: String,
+    pub title: String,
+    pub updated_at: u64,
+    pub score: usize,
+    pub snippets: Vec<SearchSnippet>,
+}
+
+/// A match with surrounding text
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchSnippet {
+    /// Index into the conversation's messages; `None` for a title match
+    pub message_index: Option<usize>,
+    pub role: String,
+    pub text: String,
+}
+
+/// Text of every conversation, so searches don't parse each conversation file
+/// and its inline images
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SearchIndex {
+    entries: BTreeMap<String, IndexEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexEntry {
+    title: String,
+    updated_at: u64,
+    /// Modification time of the file when indexed, to detect changes
+    modified: u64,
+    /// Role and text of each message, attachments omitted
+    messages: Vec<(String, String)>,
+}
+
+impl IndexEntry {
+    fn new(conversation: &SavedConversation, modified: u64) -> Self {
+        Self {
+            title: conversation.title.clone(),
+            updated_at: conversation.updated_at,
+            modified,
+            messages: conversation
+                .messages
+                .iter()
+                .map(|m| (m.role.clone(), m.content.text()))
+                .collect(),
+        }
+    }
+}
+
+/// Saved conversations, one JSON file each in the app data directory
+pub struct HistoryStore {
+    dir: PathBuf,
+    /// Conversation last loaded or saved, the starting point for cycling
+    current: Mutex<Option<String>>,
+    /// Loaded on the first search and reconciled with the directory on each one
+    index: Mutex<Option<SearchIndex>>,
+    /// Conversations with a title request in flight
+    titling: Mutex<HashSet<String>>,
+    /// Held while a conversation and its images are written, and while unused images
+    /// are pruned, so a prune can't delete an image whose conversation isn't written yet
+    images: Mutex<()>,
+}
+
+impl HistoryStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            current: Mutex::new(None),
+            index: Mutex::new(None),
+            titling: Mutex::new(HashSet::new()),
+            images: Mutex::new(()),
+        }
+    }
+
+    /// Save `messages`, creating a new conversation when `id` is `None`
+    /// Without a `model` or `tags` an existing conversation keeps the ones it had
+    pub fn save(
+        &self,
+        id: Option<String>,
+        messages: Vec<Message>,
+        model: Option<String>,
+        tags: Option<Vec<String>>,
+    ) -> Result<String> {
+        let (id, previous) = match id {
+            Some(id) => {
+                validate_id(&id)?;
+                let previous = self.read(&id).ok();
+                (id, previous)
+            }
+            None => (new_id(), None),
+        };
+        let (previous_model, previous_tags, generated_title) = previous
+            .map(|c| (c.model, c.tags, c.generated_title.then_some(c.title)))
+            .unwrap_or_default();
+
+        let conversation = SavedConversation {
+            generated_title: generated_title.is_some(),
+            title: generated_title.unwrap_or_else(|| title_for(&messages)),
+            id: id.clone(),
+            updated_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            model: model.or(previous_model),
+            tags: tags.unwrap_or(previous_tags),
+            messages,
+        };
+        self.write(&conversation)?;
+
+        *self.current.lock().unwrap() = Some(id.clone());
+        Ok(id)
+    }
+
+    /// Replace the derived title with a generated one
+    fn set_generated_title(&self, id: &str, title: String) -> Result<()> {
+        let mut conversation = self.read(id)?;
+        conversation.title = title;
+        conversation.generated_title = true;
+        self.write(&conversation)
+    }
+
+    /// Pin or unpin the message at `index`, returning the pinned indexes
+    /// The pin is stored on the message, so it follows it through later edits and saves
+    pub fn set_pinned(&self, id: &str, index: usize, pinned: bool) -> Result<Vec<usize>> {
+        let mut conversation = self.read(id)?;
+        let count = conversation.messages.len();
+        let message = conversation.messages.get_mut(index).with_context(|| {
+            format!("Message index {} out of range ({} messages)", index, count)
+        })?;
+
+        message.pinned = pinned;
+        self.write(&conversation)?;
+        Ok(pinned_indexes(&conversation.messages))
+    }
+
+    /// Change the model stored for a conversation without touching its messages
+    pub fn set_model(&self, id: &str, model: Option<String>) -> Result<()> {
+        let mut conversation = self.read(id)?;
+        conversation.model = model;
+        self.write(&conversation)
+    }
+
+    /// Write the conversation, moving its images into `IMAGES_DIR`
+    /// Images an earlier version referred to and this one doesn't are pruned
+    fn write(&self, conversation: &SavedConversation) -> Result<()> {
+        let _images = self.images.lock().unwrap();
+        std::fs::create_dir_all(&self.dir).context("Failed to create history directory")?;
+        let previous = self
+            .read_stored(&conversation.id)
+            .map(|mut previous| image_refs(&mut previous.messages))
+            .unwrap_or_default();
+
+        let mut stored = conversation.clone();
+        for url in image_urls(&mut stored.messages) {
+            self.store_image(url)?;
+        }
+        let json = serde_json::to_string(&stored)?;
+        std::fs::write(self.path(&conversation.id), json)
+            .context("Failed to write conversation")?;
+
+        if !previous.is_subset(&image_refs(&mut stored.messages)) {
+            self.prune_unreferenced();
+        }
+        Ok(())
+    }
+
+    /// The conversation with its images inlined again as data URLs
+    fn read(&self, id: &str) -> Result<SavedConversation> {
+        let mut conversation = self.read_stored(id)?;
+        for url in image_urls(&mut conversation.messages) {
+            self.restore_image(url);
+        }
+        Ok(conversation)
+    }
+
+    /// The conversation as stored, images left as `IMAGE_REF_PREFIX` references
+    fn read_stored(&self, id: &str) -> Result<SavedConversation> {
+        validate_id(id)?;
+        let json = std::fs::read_to_string(self.path(id))
+            .with_context(|| format!("Conversation not found: {}", id))?;
+        serde_json::from_str(&json).context("Corrupt conversation file")
+    }
+
+    /// Move a base64 `data:image/` URL into `IMAGES_DIR`, replacing it with a reference
+    /// Files are named by a hash of the image, so re-saving a conversation (or the same
+    /// screenshot in two conversations) writes it only once
+    fn store_image(&self, url: &mut String) -> Result<()> {
+        use base64::Engine;
+        use std::hash::{Hash, Hasher};
+
+        let Some((extension, data)) = url
+            .strip_prefix("data:image/")
+            .and_then(|rest| rest.split_once(";base64,"))
+            .filter(|(extension, _)| extension.chars().all(|c| c.is_ascii_alphanumeric()))
+        else {
+            return Ok(());
+        };
+        let Ok(bytes) = base64::engine::general_purpose::STANDARD.decode(data.trim()) else {
+            return Ok(());
+        };
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        let name = format!("{:016x}-{}.{}", hasher.finish(), bytes.len(), extension);
+
+        let dir = self.dir.join(IMAGES_DIR);
+        let path = dir.join(&name);
+        if !path.exists() {
+            std::fs::create_dir_all(&dir).context("Failed to create images directory")?;
+            std::fs::write(&path, &bytes).context("Failed to write conversation image")?;
+        }
+        *url = format!("{}{}", IMAGE_REF_PREFIX, name);
+        Ok(())
+    }
+
+    /// Turn an `IMAGE_REF_PREFIX` reference back into a data URL
+    /// A missing image file leaves the reference in place
+    fn restore_image(&self, url: &mut String) {
+        use base64::Engine;
+
+        let Some(name) = url.strip_prefix(IMAGE_REF_PREFIX) else {
+            return;
+        };
+        let Some((_, extension)) = name.rsplit_once('.').filter(|_| is_image_name(name)) else {
+            return;
+        };
+        match std::fs::read(self.dir.join(IMAGES_DIR).join(name)) {
+            Ok(bytes) => {
+                *url = format!(
+                    "data:image/{};base64,{}",
+                    extension,
+                    base64::engine::general_purpose::STANDARD.encode(bytes)
+                );
+            }
+            Err(e) => eprintln!("Warning: Could not read conversation image {}: {}", name, e),
+        }
+    }
+
+    /// Delete images no saved conversation refers to any more
+    fn prune_images(&self) {
+        let _images = self.images.lock().unwrap();
+        self.prune_unreferenced();
+    }
+
+    /// `prune_images` for a caller already holding `images`
+    fn prune_unreferenced(&self) {
+        let Ok(entries) = std::fs::read_dir(self.dir.join(IMAGES_DIR)) else {
+            return;
+        };
+        let mut referenced: HashSet<String> = HashSet::new();
+        for mut conversation in self.list().unwrap_or_default() {
+            referenced.extend(image_refs(&mut conversation.messages));
+        }
+
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if is_image_name(&name) && !referenced.contains(&name) {
+                if let Err(e) = std::fs::remove_file(entry.path()) {
+                    eprintln!("Warning: Could not delete unused image {}: {}", name, e);
+                }
+            }
+        }
+    }
+
+    /// A missing or corrupt conversation file loads as an empty conversation
+    pub fn load(&self, id: &str) -> Result<SavedConversation> {
+        let conversation = match self.read(id) {
+            Ok(conversation) => conversation,
+            Err(e) if is_missing(&e) => return Ok(empty_conversation(id)),
+            Err(e) if e.is::<serde_json::Error>() => {
+                eprintln!("Warning: Corrupt conversation file {}, loading it empty: {:#}", id, e);
+                return Ok(empty_conversation(id));
+            }
+            Err(e) => return Err(e),
+        };
+
+        *self.current.lock().unwrap() = Some(conversation.id.clone());
+        Ok(conversation)
+    }
+
+    /// The conversation last loaded or saved, if it still exists
+    pub fn current(&self) -> Option<SavedConversation> {
+        let id = self.current.lock().unwrap().clone()?;
+        self.read(&id).ok()
+    }
+
+    /// All saved conversations, most recent first
+    /// Unreadable files are skipped so one bad file doesn't hide the rest
+    /// Images stay as references; `load` a conversation to get them inlined
+    pub fn list(&self) -> Result<Vec<SavedConversation>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).context("Failed to read history directory"),
+        };
+
+        let mut conversations: Vec<SavedConversation> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| is_conversation_file(path))
+            .filter_map(|path| std::fs::read_to_string(path).ok())
+            .filter_map(|json| serde_json::from_str(&json).ok())
+            .collect();
+
+        // Ids start with a millisecond timestamp, breaking ties within a second
+        conversations.sort_by(|a, b| {
+            b.updated_at
+                .cmp(&a.updated_at)
+                .then_with(|| b.id.cmp(&a.id))
+        });
+        Ok(conversations)
+    }
+
+    /// Load the conversation `offset` places away from the current one by recency,
+    /// wrapping around at either end
+    /// Positive offsets move to older conversations
+    pub fn step(&self, offset: isize) -> Result<SavedConversation> {
+        let conversations = self.list()?;
+        if conversations.is_empty() {
+            anyhow::bail!("No saved conversations");
+        }
+
+        let len = conversations.len() as isize;
+        let current = self.current.lock().unwrap().clone();
+        let index = match current.and_then(|id| conversations.iter().position(|c| c.id == id)) {
+            Some(index) => (index as isize + offset).rem_euclid(len),
+            // Nothing loaded yet: start from the newest (or oldest going back)
+            None if offset >= 0 => 0,
+            None => len - 1,
+        };
+
+        self.load(&conversations[index as usize].id)
+    }
+
+    /// Add a message to the end of the current conversation, starting a new one
+    /// if none is loaded
+    pub fn append(&self, message: Message) -> Result<SavedConversation> {
+        validate_message(&message)?;
+
+        let current = self.current.lock().unwrap().clone();
+        let (id, mut messages, model) = match current {
+            Some(id) => {
+                let conversation = self.read(&id)?;
+                (Some(conversation.id), conversation.messages, conversation.model)
+            }
+            None => (None, Vec::new(), None),
+        };
+        messages.push(message);
+
+        let id = self.save(id, messages, model, None)?;
+        self.read(&id)
+    }
+
+    /// Combine two conversations into a new one, older conversation first
+    /// Identical adjacent messages (e.g. a shared opening prompt) are kept once
+    /// Both originals are attempted when deleting; failures are reported, not returned
+    /// as an error, since the merged conversation has already been written
+    pub fn merge(
+        &self,
+        id_a: &str,
+        id_b: &str,
+        delete_originals: bool,
+    ) -> Result<MergedConversation> {
+        if id_a == id_b {
+            anyhow::bail!("Cannot merge a conversation with itself");
+        }
+        let a = self.read(id_a)?;
+        let b = self.read(id_b)?;
+
+        let (first, second) = if (a.updated_at, &a.id) <= (b.updated_at, &b.id) {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        let model = second.model.clone().or(first.model.clone());
+        let mut tags = first.tags.clone();
+        tags.extend(second.tags.iter().filter(|t| !first.tags.contains(t)).cloned());
+
+        let mut messages: Vec<Message> = Vec::new();
+        for message in first.messages.into_iter().chain(second.messages) {
+            if messages.last() != Some(&message) {
+                messages.push(message);
+            }
+        }
+
+        let id = self.save(None, messages, model, Some(tags))?;
+
+        let mut delete_failures = Vec::new();
+        if delete_originals {
+            for original in [id_a, id_b] {
+                if let Err(e) = self.remove(original) {
+                    delete_failures.push(DeleteFailure {
+                        id: original.to_string(),
+                        error: format!("{:#}", e),
+                    });
+                }
+            }
+            self.prune_images();
+        }
+        Ok(MergedConversation {
+            id,
+            delete_failures,
+        })
+    }
+
+    pub fn delete(&self, id: &str) -> Result<()> {
+        self.remove(id)?;
+        self.prune_images();
+        Ok(())
+    }
+
+    /// Delete the conversation file, leaving its images for `prune_images`
+    fn remove(&self, id: &str) -> Result<()> {
+        validate_id(id)?;
+        std::fs::remove_file(self.path(id))
+            .with_context(|| format!("Failed to delete conversation {}", id))?;
+
+        let mut current = self.current.lock().unwrap();
+        if current.as_deref() == Some(id) {
+            *current = None;
+        }
+        Ok(())
+    }
+
+    /// Delete every conversation last updated before `cutoff` (seconds since the epoch)
+    /// A conversation that can't be deleted is reported and the rest still attempted
+    pub fn delete_older_than(&self, cutoff: u64) -> Result<Cleanup> {
+        let mut cleanup = Cleanup::default();
+        for conversation in self.list()? {
+            if conversation.updated_at >= cutoff {
+                continue;
+            }
+            match self.remove(&conversation.id) {
+                Ok(()) => cleanup.deleted.push(conversation.id),
+                Err(e) => cleanup.failed.push(DeleteFailure {
+                    id: conversation.id,
+                    error: format!("{:#}", e),
+                }),
+            }
+        }
+        if !cleanup.deleted.is_empty() {
+            self.prune_images();
+        }
+        Ok(cleanup)
+    }
+
+    /// Conversations containing every word of `query`, best match first
+    /// Matches in the title count extra when `include_titles` is set
+    pub fn search(
+        &self,
+        query: &str,
+        include_titles: bool,
+        limit: usize,
+    ) -> Result<Vec<SearchResult>> {
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut guard = self.index.lock().unwrap();
+        let index = self.synced_index(&mut guard)?;
+
+        let mut results: Vec<SearchResult> = index
+            .entries
+            .iter()
+            .filter_map(|(id, entry)| score_entry(id, entry, &terms, include_titles))
+            .collect();
+
+        results.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| b.updated_at.cmp(&a.updated_at))
+        });
+        results.truncate(limit);
+        Ok(results)
+    }
+
+    /// Load the index if needed and reconcile it with the files on disk: new or
+    /// modified conversations are (re)indexed and deleted ones dropped
+    /// Only file metadata is read for conversations that haven't changed
+    fn synced_index<'a>(&self, slot: &'a mut Option<SearchIndex>) -> Result<&'a SearchIndex> {
+        let index = slot.get_or_insert_with(|| {
+            std::fs::read_to_string(self.dir.join(INDEX_FILE))
+                .ok()
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or_default()
+        });
+
+        // Id and modification time of every conversation file
+        let files: BTreeMap<String, u64> = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| is_conversation_file(&entry.path()))
+                .filter_map(|entry| {
+                    let id = entry.path().file_stem()?.to_str()?.to_string();
+                    Some((id, modified_nanos(&entry.metadata().ok()?)))
+                })
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e).context("Failed to read history directory"),
+        };
+
+        let before = index.entries.len();
+        index.entries.retain(|id, _| files.contains_key(id));
+        let mut changed = index.entries.len() != before;
+
+        for (id, modified) in files {
+            if index.entries.get(&id).is_some_and(|e| e.modified == modified) {
+                continue;
+            }
+            if let Ok(conversation) = self.read_stored(&id) {
+                index.entries.insert(id, IndexEntry::new(&conversation, modified));
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.write_index(index);
+        }
+        Ok(index)
+    }
+
+    /// The index is only a cache, so failing to write it is not an error
+    fn write_index(&self, index: &SearchIndex) {
+        let path = self.dir.join(INDEX_FILE);
+        let result = serde_json::to_string(index)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| std::fs::write(path, json).map_err(Into::into));
+        if let Err(e) = result {
+            eprintln!("Warning: Could not write search index: {}", e);
+        }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+}
+
+/// File modification time in nanoseconds since the UNIX epoch, 0 if unavailable
+fn modified_nanos(metadata: &std::fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+/// Names of the stored images `messages` refer to
+fn image_refs(messages: &mut [Message]) -> HashSet<String> {
+    image_urls(messages)
+        .filter_map(|url| url.strip_prefix(IMAGE_REF_PREFIX).map(str::to_string))
+        .collect()
+}
+
+/// URLs of every image part in `messages`
+fn image_urls(messages: &mut [Message]) -> impl Iterator<Item = &mut String> {
+    messages
+        .iter_mut()
+        .filter_map(|message| match &mut message.content {
+            MessageContent::Parts(parts) => Some(parts),
+            MessageContent::Text(_) => None,
+        })
+        .flatten()
+        .filter_map(|part| match part {
+            ContentPart::ImageUrl { image_url } => Some(&mut image_url.url),
+            _ => None,
+        })
+}
+
+/// File names written by `store_image`: hash, size and an alphanumeric extension
+fn is_image_name(name: &str) -> bool {
+    name.split_once('.').is_some_and(|(stem, extension)| {
+        !stem.is_empty()
+            && stem.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+            && !extension.is_empty()
+            && extension.chars().all(|c| c.is_ascii_alphanumeric())
+    })
+}
+
+/// Conversation files, excluding the search index
+fn is_conversation_file(path: &std::path::Path) -> bool {
+    path.extension().is_some_and(|e| e == "json")
+        && path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| !n.starts_with('.'))
+}
+
+/// Rank a conversation by how often the terms occur; `None` unless every term matches
+fn score_entry(
+    id: &str,
+    entry: &IndexEntry,
+    terms: &[String],
+    include_titles: bool,
+) -> Option<SearchResult> {
+    let title = include_titles.then(|| entry.title.to_lowercase());
+    let messages: Vec<String> = entry.messages.iter().map(|(_, t)| t.to_lowercase()).collect();
+
+    let mut score = 0;
+    for term in terms {
+        let in_title = title.as_ref().map_or(0, |t| t.matches(term.as_str()).count());
+        let in_messages: usize = messages.iter().map(|m| m.matches(term.as_str()).count()).sum();
+        if in_title + in_messages == 0 {
+            return None;
+        }
+        score += in_title * 3 + in_messages;
+    }
+
+    let mut snippets = Vec::new();
+    if let Some(title) = &title {
+        if terms.iter().any(|t| title.contains(t.as_str())) {
+            snippets.push(SearchSnippet {
+                message_index: None,
+                role: String::from("title"),
+                text: entry.title.clone(),
+            });
+        }
+    }
+    for (i, lowered) in messages.iter().enumerate() {
+        if snippets.len() >= SNIPPETS_PER_RESULT {
+            break;
+        }
+        let Some(start) = terms.iter().filter_map(|t| lowered.find(t.as_str())).min() else {
+            continue;
+        };
+        let (role, text) = &entry.messages[i];
+        snippets.push(SearchSnippet {
+            message_index: Some(i),
+            role: role.clone(),
+            text: snippet(text, lowered, start),
+        });
+    }
+
+    Some(SearchResult {
+        id: id.to_string(),
+        title: entry.title.clone(),
+        updated_at: entry.updated_at,
+        score,
+        snippets,
+    })
+}
+
+/// Text around the match at byte `start` of `lowered`, with ellipses where cut
+/// Offsets are mapped by character so case changes that alter byte lengths are safe
+fn snippet(text: &str, lowered: &str, start: usize) -> String {
+    let match_char = lowered[..start].chars().count();
+    let chars: Vec<char> = text.chars().collect();
+
+    let from = match_char.saturating_sub(SNIPPET_CONTEXT_CHARS).min(chars.len());
+    let to = (match_char + SNIPPET_CONTEXT_CHARS * 2).min(chars.len());
+
+    let body: String = chars[from..to].iter().collect();
+    let body = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    format!(
+        "{}{}{}",
+        if from > 0 { "…" } else { "" },
+        body,
+        if to < chars.len() { "…" } else { "" }
+    )
+}
+
+/// Ids become file names, so only allow characters that can't escape the directory
+fn validate_id(id: &str) -> Result<()> {
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        anyhow::bail!("Invalid conversation id: {:?}", id);
+    }
+    Ok(())
+}
+
+/// Reject roles the API doesn't accept and content it would refuse
+/// Images and audio are only valid in user messages
+pub(crate) fn validate_message(message: &Message) -> Result<()> {
+    if !MESSAGE_ROLES.contains(&message.role.as_str()) {
+        anyhow::bail!(
+            "Invalid role {:?}, expected one of {}",
+            message.role,
+            MESSAGE_ROLES.join(", ")
+        );
+    }
+
+    match &message.content {
+        MessageContent::Text(text) if text.trim().is_empty() => {
+            anyhow::bail!("Message content is empty")
+        }
+        MessageContent::Text(_) => {}
+        MessageContent::Parts(parts) => {
+            if parts.is_empty() {
+                anyhow::bail!("Message has no content parts");
+            }
+            for part in parts {
+                match part {
+                    ContentPart::Text { text, .. } if text.trim().is_empty() => {
+                        anyhow::bail!("Text part is empty")
+                    }
+                    ContentPart::Text { .. } => {}
+                    ContentPart::ImageUrl { image_url } => {
+                        if message.role != "user" {
+                            anyhow::bail!("Only user messages can contain images");
+                        }
+                        let url = image_url.url.as_str();
+                        if !(url.starts_with("data:image/") || url.starts_with("https://")) {
+                            anyhow::bail!("Image must be a data:image/ or https:// URL");
+                        }
+                    }
+                    ContentPart::InputAudio { input_audio } => {
+                        if message.role != "user" {
+                            anyhow::bail!("Only user messages can contain audio");
+                        }
+                        if !matches!(input_audio.format.as_str(), "wav" | "mp3") {
+                            anyhow::bail!("Unsupported audio format {:?}", input_audio.format);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Millisecond timestamp plus a per-process counter for saves within the same millisecond
+pub(crate) fn new_id() -> String {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let seq = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    format!("{}-{}", millis, seq)
+}
+
+/// The error is a conversation file that doesn't exist
+fn is_missing(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<std::io::Error>()
+        .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound)
+}
+
+/// What `load` returns for a conversation whose file is missing or unreadable
+fn empty_conversation(id: &str) -> SavedConversation {
+    SavedConversation {
+        id: id.to_string(),
+        title: title_for(&[]),
+        updated_at: 0,
+        model: None,
+        tags: Vec::new(),
+        generated_title: false,
+        messages: Vec::new(),
+    }
+}
+
+fn title_for(messages: &[Message]) -> String {
+    let text = messages
+        .iter()
+        .find(|m| m.role == "user")
+        .map(|m| m.content.text())
+        .unwrap_or_default();
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if text.is_empty() {
+        return String::from("Untitled conversation");
+    }
+    if text.chars().count() <= TITLE_CHARS {
+        return text;
+    }
+    let truncated: String = text.chars().take(TITLE_CHARS).collect();
+    format!("{}…", truncated.trim_end())
+}
+
+/// Save a conversation
+///
+/// Past the `auto_archive` limit the conversation is saved as-is and a fresh one
+/// is started, optionally carrying a summary; `conversation-archived` tells the
+/// frontend to switch to it
+///
+/// # Arguments
+/// * `id` - Existing conversation to overwrite; omit to create a new one
+/// * `model` - Model last used, restored on reload
+/// * `tags` - Labels for the conversation; omit to keep the existing ones
+///
+/// # Returns
+/// The conversation id (the new conversation's, if it was archived)
+#[tauri::command]
+pub async fn save_conversation(
+    app: AppHandle,
+    history: State<'_, HistoryStore>,
+    id: Option<String>,
+    messages: Vec<Message>,
+    model: Option<String>,
+    tags: Option<Vec<String>>,
+) -> Result<String, String> {
+    let tags = tags.map(usage::normalize_tags);
+    let archive = app.state::<SettingsStore>().get().auto_archive;
+    if !archive.max_messages.is_some_and(|max| messages.len() > max) {
+        let id = history
+            .save(id, messages, model, tags)
+            .map_err(|e| format!("Failed to save conversation: {}", e))?;
+        spawn_auto_title(&app, &id);
+        return Ok(id);
+    }
+
+    archive_conversation(&app, &history, id, messages, model, tags, archive.carry_summary)
+        .await
+        .map_err(|e| format!("Failed to archive conversation: {}", e))
+}
+
+/// With `auto_title` on, name the conversation from its opening exchange in the
+/// background once the first assistant reply is saved
+/// Runs at most once per conversation and emits `conversation-titled`; on failure
+/// the title stays the truncated first message
+fn spawn_auto_title(app: &AppHandle, id: &str) {
+    if !app.state::<SettingsStore>().get().auto_title {
+        return;
+    }
+
+    let history = app.state::<HistoryStore>();
+    let Ok(conversation) = history.read(id) else {
+        return;
+    };
+    let has_reply = conversation.messages.iter().any(|m| m.role == "assistant");
+    if conversation.generated_title || !has_reply {
+        return;
+    }
+    if !history.titling.lock().unwrap().insert(id.to_string()) {
+        return;
+    }
+
+    let app = app.clone();
+    let id = id.to_string();
+    tauri::async_runtime::spawn(async move {
+        let options = ChatOptions {
+            app: Some(app.clone()),
+            model: conversation.model.clone(),
+            ..ChatOptions::default()
+        };
+        let api_key = app.state::<ConfigState>().get().openai_api_key;
+        let result = generate_title(&api_key, &conversation.messages, &options).await;
+
+        let history = app.state::<HistoryStore>();
+        match result.and_then(|title| {
+            history.set_generated_title(&id, title.clone())?;
+            Ok(title)
+        }) {
+            Ok(title) => {
+                let titled = ConversationTitled {
+                    id: id.clone(),
+                    title,
+                };
+                let _ = app.emit("conversation-titled", titled);
+            }
+            Err(e) => eprintln!("Warning: Could not generate a conversation title: {}", e),
+        }
+        history.titling.lock().unwrap().remove(&id);
+    });
+}
+
+/// Ask the model for a short title for the first user message and reply
+async fn generate_title(
+    api_key: &str,
+    messages: &[Message],
+    options: &ChatOptions,
+) -> Result<String> {
+    let opening = |role: &str| {
+        messages
+            .iter()
+            .find(|m| m.role == role)
+            .map(|m| m.content.text().chars().take(TITLE_CONTEXT_CHARS).collect::<String>())
+            .unwrap_or_default()
+    };
+    let prompt = vec![
+        Message {
+            role: String::from("system"),
+            content: MessageContent::Text(String::from(TITLE_PROMPT)),
+            pinned: false,
+        },
+        Message {
+            role: String::from("user"),
+            content: MessageContent::Text(format!(
+                "User: {}\n\nAssistant: {}",
+                opening("user"),
+                opening("assistant")
+            )),
+            pinned: false,
+        },
+    ];
+
+    let response = send_message_impl(api_key, prompt, Vec::new(), options).await?;
+    let title = response
+        .content
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'' || c == '.')
+        .trim();
+    if title.is_empty() {
+        anyhow::bail!("Model returned an empty title");
+    }
+    Ok(title.chars().take(TITLE_CHARS).collect())
+}
+
+async fn archive_conversation(
+    app: &AppHandle,
+    history: &HistoryStore,
+    id: Option<String>,
+    messages: Vec<Message>,
+    model: Option<String>,
+    tags: Option<Vec<String>>,
+    carry_summary: bool,
+) -> Result<String> {
+    let summary_words =
+        carry_summary.then(|| app.state::<SettingsStore>().get().memory.summary_words);
+    let options = ChatOptions {
+        app: Some(app.clone()),
+        model: model.clone(),
+        ..ChatOptions::default()
+    };
+    let api_key = app.state::<ConfigState>().get().openai_api_key;
+    let carried = memory::carry_over(&api_key, &messages, summary_words, &options).await;
+
+    let old_id = history.save(id, messages, model.clone(), tags)?;
+    // The continuation keeps the archived conversation's tags
+    let old_tags = history.read(&old_id)?.tags;
+    let new_id = history.save(None, carried.clone(), model, Some(old_tags))?;
+
+    app.emit(
+        "conversation-archived",
+        ConversationArchived {
+            old_id,
+            new_id: new_id.clone(),
+            messages: carried,
+        },
+    )?;
+    Ok(new_id)
+}
+
+/// Load a saved conversation by id; a missing or corrupt one comes back empty
+/// Conversations too large for one IPC message (e.g. many screenshots) come back
+/// chunked, to be read with `read_transfer_chunk`
+#[tauri::command]
+pub fn load_conversation(
+    history: State<'_, HistoryStore>,
+    transfers: State<'_, Transfers>,
+    id: String,
+) -> Result<Delivery, String> {
+    let conversation = history
+        .load(&id)
+        .map_err(|e| format!("Failed to load conversation: {}", e))?;
+    transfers
+        .deliver(&conversation)
+        .map_err(|e| format!("Failed to serialize conversation: {}", e))
+}
+
+/// List saved conversations, most recent first
+#[tauri::command]
+pub fn list_conversations(
+    history: State<'_, HistoryStore>,
+) -> Result<Vec<ConversationSummary>, String> {
+    let conversations = history
+        .list()
+        .map_err(|e| format!("Failed to list conversations: {}", e))?;
+
+    Ok(conversations
+        .into_iter()
+        .map(|c| ConversationSummary {
+            message_count: c.messages.len(),
+            id: c.id,
+            title: c.title,
+            updated_at: c.updated_at,
+            model: c.model,
+            tags: c.tags,
+        })
+        .collect())
+}
+
+/// Find saved conversations containing every word of `query`
+/// Message text (including the text parts of multimodal messages) is searched
+/// through an on-disk index, so large image-heavy histories stay fast
+///
+/// # Arguments
+/// * `include_titles` - Also match conversation titles, ranked higher (default true)
+/// * `limit` - Maximum results (default 20)
+///
+/// # Returns
+/// Matches ranked by relevance, each with context snippets
+#[tauri::command]
+pub fn search_conversations(
+    history: State<'_, HistoryStore>,
+    query: String,
+    include_titles: Option<bool>,
+    limit: Option<usize>,
+) -> Result<Vec<SearchResult>, String> {
+    history
+        .search(&query, include_titles.unwrap_or(true), limit.unwrap_or(20))
+        .map_err(|e| format!("Search failed: {}", e))
+}
+
+/// Model stored for a conversation; `None` means the global default
+#[tauri::command]
+pub fn get_conversation_model(
+    history: State<'_, HistoryStore>,
+    id: String,
+) -> Result<Option<String>, String> {
+    history
+        .read(&id)
+        .map(|c| c.model)
+        .map_err(|e| format!("Failed to load conversation: {}", e))
+}
+
+/// Set the model for a conversation; pass `None` to fall back to the global default
+#[tauri::command]
+pub fn set_conversation_model(
+    history: State<'_, HistoryStore>,
+    id: String,
+    model: Option<String>,
+) -> Result<(), String> {
+    history
+        .set_model(&id, model.filter(|m| !m.trim().is_empty()))
+        .map_err(|e| format!("Failed to save conversation: {}", e))
+}
+
+/// Indexes of the pinned messages in `messages`
+fn pinned_indexes(messages: &[Message]) -> Vec<usize> {
+    messages
+        .iter()
+        .enumerate()
+        .filter(|(_, message)| message.pinned)
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Keep the message at `index` as context when sending with `pinned_only`
+///
+/// # Returns
+/// All pinned message indexes in the conversation
+#[tauri::command]
+pub fn pin_message(
+    history: State<'_, HistoryStore>,
+    id: String,
+    index: usize,
+) -> Result<Vec<usize>, String> {
+    history
+        .set_pinned(&id, index, true)
+        .map_err(|e| format!("Failed to pin message: {}", e))
+}
+
+/// Remove the pin from the message at `index`
+///
+/// # Returns
+/// The remaining pinned message indexes
+#[tauri::command]
+pub fn unpin_message(
+    history: State<'_, HistoryStore>,
+    id: String,
+    index: usize,
+) -> Result<Vec<usize>, String> {
+    history
+        .set_pinned(&id, index, false)
+        .map_err(|e| format!("Failed to unpin message: {}", e))
+}
+
+/// Load the next older saved conversation, emitting `conversation-loaded`
+#[tauri::command]
+pub fn next_conversation(app: AppHandle) -> Result<Delivery, String> {
+    cycle_conversation(&app, 1)
+}
+
+/// Load the next newer saved conversation, emitting `conversation-loaded`
+#[tauri::command]
+pub fn previous_conversation(app: AppHandle) -> Result<Delivery, String> {
+    cycle_conversation(&app, -1)
+}
+
+/// Append a message to the current conversation without calling the API,
+/// e.g. to inject a canned reply or seed context
+/// Starts a new conversation if none is loaded
+///
+/// # Arguments
+/// * `role` - `system`, `user` or `assistant`
+/// * `content` - Plain text or content parts
+///
+/// # Returns
+/// The updated conversation
+#[tauri::command]
+pub fn append_message(
+    history: State<'_, HistoryStore>,
+    role: String,
+    content: MessageContent,
+) -> Result<SavedConversation, String> {
+    history
+        .append(Message {
+            role,
+            content,
+            pinned: false,
+        })
+        .map_err(|e| format!("Failed to append message: {}", e))
+}
+
+/// Merge two saved conversations into a new one
+/// Images are stored once by content hash, so the merged copy shares their files
+///
+/// # Arguments
+/// * `delete_originals` - Remove both source conversations after merging
+///
+/// # Returns
+/// The new conversation id and any original that could not be deleted
+#[tauri::command]
+pub fn merge_conversations(
+    history: State<'_, HistoryStore>,
+    id_a: String,
+    id_b: String,
+    delete_originals: Option<bool>,
+) -> Result<MergedConversation, String> {
+    history
+        .merge(&id_a, &id_b, delete_originals.unwrap_or(false))
+        .map_err(|e| format!("Failed to merge conversations: {}", e))
+}
+
+/// Step through conversations by recency and tell the frontend
+/// Shared by the commands and the global shortcuts
+/// The conversation is emitted and returned as a `Delivery`, chunked when too large
+/// for one IPC message; both share the same transfer
+pub fn cycle_conversation(app: &AppHandle, offset: isize) -> Result<Delivery, String> {
+    let conversation = app
+        .state::<HistoryStore>()
+        .step(offset)
+        .map_err(|e| format!("Failed to load conversation: {}", e))?;
+
+    let delivery = app
+        .state::<Transfers>()
+        .deliver(&conversation)
+        .map_err(|e| format!("Failed to serialize conversation: {}", e))?;
+    app.emit("conversation-loaded", &delivery)
+        .map_err(|e| format!("Failed to emit event: {}", e))?;
+
+    Ok(delivery)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::ai::ImageUrl;
+
+    /// A store in a fresh temporary directory, removed when dropped
+    struct TempStore(HistoryStore);
+
+    impl TempStore {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "phantom_history_{}_{}",
+                std::process::id(),
+                new_id()
+            ));
+            Self(HistoryStore::new(dir))
+        }
+
+        fn images(&self) -> Vec<String> {
+            std::fs::read_dir(self.0.dir.join(IMAGES_DIR))
+                .map(|entries| {
+                    entries
+                        .filter_map(|entry| entry.ok())
+                        .map(|entry| entry.file_name().to_string_lossy().to_string())
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+    }
+
+    impl Drop for TempStore {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0.dir);
+        }
+    }
+
+    const IMAGE_URL: &str = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAAB";
+
+    fn text(role: &str, text: &str) -> Message {
+        Message {
+            role: role.to_string(),
+            content: MessageContent::Text(text.to_string()),
+            pinned: false,
+        }
+    }
+
+    fn with_image(text: &str) -> Message {
+        Message {
+            role: String::from("user"),
+            content: MessageContent::Parts(vec![
+                ContentPart::Text {
+                    text: text.to_string(),
+                    cache_control: None,
+                },
+                ContentPart::ImageUrl {
+                    image_url: ImageUrl {
+                        url: IMAGE_URL.to_string(),
+                    },
+                },
+            ]),
+            pinned: false,
+        }
+    }
+
+    #[test]
+    fn images_are_saved_outside_the_conversation_file() {
+        let store = TempStore::new();
+        let messages = vec![with_image("first"), with_image("second")];
+        let id = store.0.save(None, messages.clone(), None, None).unwrap();
+
+        let json = std::fs::read_to_string(store.0.path(&id)).unwrap();
+        assert!(!json.contains("base64"));
+        assert!(json.contains(IMAGE_REF_PREFIX));
+        // The same screenshot twice is stored once
+        assert_eq!(store.images().len(), 1);
+
+        assert_eq!(store.0.load(&id).unwrap().messages, messages);
+    }
+
+    #[test]
+    fn re_save_without_an_image_prunes_it() {
+        let store = TempStore::new();
+        let id = store.0.save(None, vec![with_image("look")], None, None).unwrap();
+        let kept = store.0.save(None, vec![with_image("also")], None, None).unwrap();
+        assert_eq!(store.images().len(), 1);
+
+        // Still used by the other conversation
+        store.0.save(Some(id.clone()), vec![text("user", "look")], None, None).unwrap();
+        assert_eq!(store.images().len(), 1);
+
+        store.0.save(Some(kept), vec![text("user", "also")], None, None).unwrap();
+        assert!(store.images().is_empty());
+    }
+
+    #[test]
+    fn missing_or_corrupt_conversation_loads_empty() {
+        let store = TempStore::new();
+        let missing = store.0.load("1-0").unwrap();
+        assert_eq!(missing.id, "1-0");
+        assert!(missing.messages.is_empty());
+
+        std::fs::create_dir_all(&store.0.dir).unwrap();
+        std::fs::write(store.0.path("2-0"), "{ not json").unwrap();
+        assert!(store.0.load("2-0").unwrap().messages.is_empty());
+
+        assert!(store.0.load("../escape").is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use anyhow::{Context, Result};
+use chrono::{Local, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+use crate::commands::window;
+use crate::config::ConfigState;
+use crate::settings::SettingsStore;
+
+/// Show/hide shortcuts tried in order, until one registers, when none is configured
+/// or the configured one can't be registered
+const TOGGLE_HOTKEYS: [&str; 2] = ["CommandOrControl+Shift+Space", "CommandOrControl+Option+G"];
+
+/// How often quiet hours are re-evaluated
+const QUIET_HOURS_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Daily window, in local time, during which the show/hide hotkey is off
+/// An `end` before `start` spans midnight (e.g. 22:00-07:00)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuietHours {
+    /// `HH:MM`, 24-hour
+    pub start: String,
+    /// `HH:MM`, 24-hour
+    pub end: String,
+}
+
+impl QuietHours {
+    fn parse(&self) -> Result<(NaiveTime, NaiveTime)> {
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .with_context(|| format!("Invalid time {:?}, expected HH:MM", time))
+        };
+        Ok((parse(&self.start)?, parse(&self.end)?))
+    }
+
+    fn contains(&self, now: NaiveTime) -> bool {
+        let Ok((start, end)) = self.parse() else {
+            return false;
+        };
+        if start <= end {
+            start <= now && now < end
+        } else {
+            now >= start || now < end
+        }
+    }
+}
+
+/// The show/hide shortcut, so it can be turned off and back on at runtime
+#[derive(Default)]
+pub struct ToggleHotkey {
+    /// Combo that registered at startup; re-enabling registers exactly this one
+    shortcut: Mutex<Option<Shortcut>>,
+    active: AtomicBool,
+}
+
+/// Register the show/hide shortcut from the `toggle_hotkey` setting or GHOSTGPT_HOTKEY,
+/// else the first available default, unless the saved settings (or current quiet
+/// hours) have it disabled
+pub(crate) fn register_toggle_hotkey(app: &AppHandle) {
+    let state = app.state::<ToggleHotkey>();
+    let configured = app
+        .state::<SettingsStore>()
+        .get()
+        .toggle_hotkey
+        .or_else(|| app.state::<ConfigState>().get().hotkey)
+        .filter(|hotkey| !hotkey.trim().is_empty());
+
+    let candidates = configured.iter().map(String::as_str).chain(TOGGLE_HOTKEYS);
+    for hotkey_str in candidates {
+        let Ok(shortcut) = hotkey_str.trim().parse::<Shortcut>() else {
+            eprintln!("Warning: Invalid global hotkey {:?}", hotkey_str);
+            continue;
+        };
+        if register(app, shortcut).is_ok() {
+            println!("✓ Registered global hotkey: {}", hotkey_str);
+            *state.shortcut.lock().unwrap() = Some(shortcut);
+            state.active.store(true, Ordering::Relaxed);
+            sync_hotkey(app);
+            return;
+        }
+    }
+
+    eprintln!("Warning: Could not register any global hotkey");
+}
+
+fn register(app: &AppHandle, shortcut: Shortcut) -> Result<()> {
+    let cooldown = Duration::from_millis(app.state::<SettingsStore>().get().hotkey_cooldown_ms);
+    // Each registration tracks its own last toggle
+    let last_toggle = Mutex::new(None::<Instant>);
+
+    app.global_shortcut()
+        .on_shortcut(shortcut, move |app, _shortcut, event| {
+            if event.state != ShortcutState::Pressed {
+                return;
+            }
+
+            // Ignore key repeat and double-taps so the window doesn't flicker
+            let mut last_toggle = last_toggle.lock().unwrap();
+            if last_toggle.is_some_and(|t| t.elapsed() < cooldown) {
+                return;
+            }
+            *last_toggle = Some(Instant::now());
+
+            let Some(window) = app.get_webview_window("main") else {
+                return;
+            };
+            if window.is_visible().unwrap_or(false) {
+                window::save_geometry(&window);
+                let _ = window.hide();
+            } else {
+                let summon_position = app.state::<SettingsStore>().get().summon_position;
+                if let Err(e) = window::position_for_summon(&window, summon_position) {
+                    eprintln!("Warning: Could not position window: {}", e);
+                }
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+        })
+        .context("Failed to register shortcut")
+}
+
+/// Register or unregister the shortcut to match `hotkey_enabled` and quiet hours,
+/// emitting `hotkey-enabled` / `hotkey-disabled` when that changes
+pub(crate) fn sync_hotkey(app: &AppHandle) {
+    let settings = app.state::<SettingsStore>().get();
+    let quiet = settings
+        .quiet_hours
+        .as_ref()
+        .is_some_and(|q| q.contains(Local::now().time()));
+    let wanted = settings.hotkey_enabled && !quiet;
+
+    let state = app.state::<ToggleHotkey>();
+    let Some(shortcut) = *state.shortcut.lock().unwrap() else {
+        return;
+    };
+    if state.active.load(Ordering::Relaxed) == wanted {
+        return;
+    }
+
+    let result = if wanted {
+        register(app, shortcut)
+    } else {
+        app.global_shortcut()
+            .unregister(shortcut)
+            .context("Failed to unregister shortcut")
+    };
+    if let Err(e) = result {
+        eprintln!("Warning: Could not update global hotkey: {}", e);
+        return;
+    }
+
+    state.active.store(wanted, Ordering::Relaxed);
+    let event = if wanted {
+        "hotkey-enabled"
+    } else {
+        "hotkey-disabled"
+    };
+    let _ = app.emit(event, ());
+}
+
+/// Re-check quiet hours periodically so the hotkey turns off and on by itself
+pub fn spawn_quiet_hours(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(QUIET_HOURS_CHECK_INTERVAL);
+        sync_hotkey(&app);
+    });
+}
+
+/// Turn the show/hide hotkey off or on, and persist the choice
+/// While quiet hours are active it stays off until they end
+#[tauri::command]
+pub fn set_hotkey_enabled(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    enabled: bool,
+) -> Result<(), String> {
+    settings
+        .update(|s| s.hotkey_enabled = enabled)
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    sync_hotkey(&app);
+    Ok(())
+}
+
+/// Replace the show/hide hotkey with `shortcut`, e.g. `CommandOrControl+Shift+K`, and
+/// persist it as the `toggle_hotkey` setting
+/// The old hotkey stays registered if the new one is invalid or taken by another app
+/// While disabled or in quiet hours the new hotkey is kept but not registered
+#[tauri::command]
+pub fn set_global_hotkey(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    hotkey: State<'_, ToggleHotkey>,
+    shortcut: String,
+) -> Result<(), String> {
+    let shortcut_str = shortcut.trim().to_string();
+    let shortcut = shortcut_str
+        .parse::<Shortcut>()
+        .map_err(|e| format!("Invalid shortcut {:?}: {}", shortcut_str, e))?;
+
+    let previous = *hotkey.shortcut.lock().unwrap();
+    if previous != Some(shortcut) {
+        let was_active = hotkey.active.load(Ordering::Relaxed);
+        if let Some(previous) = previous.filter(|_| was_active) {
+            app.global_shortcut()
+                .unregister(previous)
+                .map_err(|e| format!("Failed to unregister the current hotkey: {}", e))?;
+            hotkey.active.store(false, Ordering::Relaxed);
+        }
+
+        // Registering is the only way to find out whether the OS or another app has it
+        if let Err(e) = register(&app, shortcut) {
+            if let Some(previous) = previous.filter(|_| was_active) {
+                if register(&app, previous).is_ok() {
+                    hotkey.active.store(true, Ordering::Relaxed);
+                }
+            }
+            return Err(format!(
+                "{} is already in use by the system or another app: {}",
+                shortcut_str, e
+            ));
+        }
+        *hotkey.shortcut.lock().unwrap() = Some(shortcut);
+        hotkey.active.store(true, Ordering::Relaxed);
+        println!("✓ Registered global hotkey: {}", shortcut_str);
+    }
+
+    settings
+        .update(|s| s.toggle_hotkey = Some(shortcut_str))
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    // Drops the registration again if the hotkey is meant to be off right now
+    sync_hotkey(&app);
+    Ok(())
+}
+
+/// Set (or with `None` clear) the daily window during which the hotkey is off
+#[tauri::command]
+pub fn set_quiet_hours(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    quiet_hours: Option<QuietHours>,
+) -> Result<(), String> {
+    if let Some(quiet_hours) = &quiet_hours {
+        quiet_hours.parse().map_err(|e| e.to_string())?;
+    }
+
+    settings
+        .update(|s| s.quiet_hours = quiet_hours)
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    sync_hotkey(&app);
+    Ok(())
+}
+
+/// Whether the show/hide hotkey is currently registered
+#[tauri::command]
+pub fn is_hotkey_active(hotkey: State<'_, ToggleHotkey>) -> bool {
+    hotkey.active.load(Ordering::Relaxed)
+}
@@ -0,0 +1,640 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+use imageproc::point::Point;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::settings::SettingsStore;
+
+/// Annotation color when `annotate_image` isn't given one
+const DEFAULT_ANNOTATION_COLOR: Rgba<u8> = Rgba([255, 59, 48, 255]);
+
+/// Opacity of `Shape::Highlight` fills, 0-1
+const HIGHLIGHT_OPACITY: f32 = 0.35;
+
+/// Markup drawn by `annotate_image`, in image pixel coordinates
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Shape {
+    /// Rectangle outline
+    Rect {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    /// Line from `from` with an arrowhead at `to`
+    Arrow { from: (u32, u32), to: (u32, u32) },
+    /// Translucent filled rectangle, like a highlighter pen
+    Highlight {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+}
+
+/// Crop a base64-encoded image to the given rectangle
+///
+/// # Arguments
+/// * `base64` - Source image (PNG, JPEG)
+/// * `x`, `y` - Top-left corner of the crop in pixels
+/// * `width`, `height` - Size of the crop in pixels
+///
+/// # Returns
+/// Cropped image as base64 PNG
+#[tauri::command]
+pub fn crop_image(
+    base64: String,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) -> Result<String, String> {
+    crop_image_impl(&base64, x, y, width, height).map_err(|e| format!("Crop failed: {}", e))
+}
+
+fn crop_image_impl(base64_data: &str, x: u32, y: u32, width: u32, height: u32) -> Result<String> {
+    let image = decode_base64_image(base64_data)?;
+
+    if width == 0 || height == 0 {
+        anyhow::bail!("Crop size must be non-zero, got {}x{}", width, height);
+    }
+
+    let fits_x = x.checked_add(width).is_some_and(|right| right <= image.width());
+    let fits_y = y.checked_add(height).is_some_and(|bottom| bottom <= image.height());
+    if !fits_x || !fits_y {
+        anyhow::bail!(
+            "Crop rectangle {}x{} at ({}, {}) is outside the {}x{} image",
+            width,
+            height,
+            x,
+            y,
+            image.width(),
+            image.height()
+        );
+    }
+
+    encode_base64_png(&image.crop_imm(x, y, width, height))
+}
+
+/// Draw rectangles, arrows and highlights on a base64-encoded image
+/// Used to point the model at part of a screenshot before sending it
+///
+/// # Arguments
+/// * `base64` - Source image (PNG, JPEG)
+/// * `shapes` - Markup from the drawing overlay, in image pixels
+/// * `color` - Hex color such as `#ff3b30` (defaults to red)
+/// * `thickness` - Outline and arrow width in pixels (defaults to 4)
+///
+/// # Returns
+/// Annotated image as base64 PNG
+#[tauri::command]
+pub fn annotate_image(
+    base64: String,
+    shapes: Vec<Shape>,
+    color: Option<String>,
+    thickness: Option<u32>,
+) -> Result<String, String> {
+    annotate_image_impl(&base64, &shapes, color.as_deref(), thickness.unwrap_or(4))
+        .map_err(|e| format!("Annotation failed: {}", e))
+}
+
+fn annotate_image_impl(
+    base64_data: &str,
+    shapes: &[Shape],
+    color: Option<&str>,
+    thickness: u32,
+) -> Result<String> {
+    let mut image = decode_base64_image(base64_data)?.to_rgba8();
+    let color = match color {
+        Some(hex) => parse_hex_color(hex)?,
+        None => DEFAULT_ANNOTATION_COLOR,
+    };
+    let thickness = thickness.clamp(1, 64);
+
+    // Check everything first so a bad shape never yields a half-annotated image
+    for shape in shapes {
+        validate_shape(shape, image.width(), image.height())?;
+    }
+
+    for shape in shapes {
+        match *shape {
+            Shape::Rect {
+                x,
+                y,
+                width,
+                height,
+            } => draw_rect_outline(&mut image, x, y, width, height, thickness, color),
+            Shape::Arrow { from, to } => draw_arrow(&mut image, from, to, thickness, color),
+            Shape::Highlight {
+                x,
+                y,
+                width,
+                height,
+            } => highlight(&mut image, x, y, width, height, color),
+        }
+    }
+
+    encode_base64_png(&DynamicImage::ImageRgba8(image))
+}
+
+fn validate_shape(shape: &Shape, image_width: u32, image_height: u32) -> Result<()> {
+    let in_bounds = |(x, y): (u32, u32)| x < image_width && y < image_height;
+
+    match *shape {
+        Shape::Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+        | Shape::Highlight {
+            x,
+            y,
+            width,
+            height,
+        } => {
+            let fits_x = x.checked_add(width).is_some_and(|right| right <= image_width);
+            let fits_y = y.checked_add(height).is_some_and(|bottom| bottom <= image_height);
+            if width == 0 || height == 0 || !fits_x || !fits_y {
+                anyhow::bail!(
+                    "Rectangle {}x{} at ({}, {}) is outside the {}x{} image",
+                    width,
+                    height,
+                    x,
+                    y,
+                    image_width,
+                    image_height
+                );
+            }
+        }
+        Shape::Arrow { from, to } => {
+            if !in_bounds(from) || !in_bounds(to) {
+                anyhow::bail!(
+                    "Arrow from {:?} to {:?} is outside the {}x{} image",
+                    from,
+                    to,
+                    image_width,
+                    image_height
+                );
+            }
+            if from == to {
+                anyhow::bail!("Arrow at {:?} has no length", from);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parse `#rrggbb` (the `#` is optional)
+fn parse_hex_color(hex: &str) -> Result<Rgba<u8>> {
+    let digits = hex.trim().trim_start_matches('#');
+    if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("Invalid color {:?}, expected #rrggbb", hex);
+    }
+    let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).unwrap_or(0);
+    Ok(Rgba([channel(0), channel(2), channel(4), 255]))
+}
+
+/// Outline drawn inwards, so it stays within the validated rectangle
+fn draw_rect_outline(
+    image: &mut RgbaImage,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    thickness: u32,
+    color: Rgba<u8>,
+) {
+    use imageproc::rect::Rect;
+
+    for inset in 0..thickness.min(width.div_ceil(2)).min(height.div_ceil(2)) {
+        let rect = Rect::at((x + inset) as i32, (y + inset) as i32)
+            .of_size(width - 2 * inset, height - 2 * inset);
+        imageproc::drawing::draw_hollow_rect_mut(image, rect, color);
+    }
+}
+
+fn draw_arrow(
+    image: &mut RgbaImage,
+    from: (u32, u32),
+    to: (u32, u32),
+    thickness: u32,
+    color: Rgba<u8>,
+) {
+    let (x0, y0) = (from.0 as f32, from.1 as f32);
+    let (x1, y1) = (to.0 as f32, to.1 as f32);
+    let length = ((x1 - x0).powi(2) + (y1 - y0).powi(2)).sqrt();
+    // Unit vectors along and across the shaft
+    let (dx, dy) = ((x1 - x0) / length, (y1 - y0) / length);
+    let (nx, ny) = (-dy, dx);
+
+    let head_length = (thickness as f32 * 4.0).min(length);
+    let head_width = thickness as f32 * 3.0;
+    let half = thickness as f32 / 2.0;
+    let point = |x: f32, y: f32| Point::new(x.round() as i32, y.round() as i32);
+
+    // Shaft stops at the base of the head so the tip stays sharp
+    let (bx, by) = (x1 - dx * head_length, y1 - dy * head_length);
+    let shaft = [
+        point(x0 + nx * half, y0 + ny * half),
+        point(bx + nx * half, by + ny * half),
+        point(bx - nx * half, by - ny * half),
+        point(x0 - nx * half, y0 - ny * half),
+    ];
+    if shaft[0] != shaft[3] && shaft[1] != shaft[2] && shaft[0] != shaft[1] {
+        imageproc::drawing::draw_polygon_mut(image, &shaft, color);
+    }
+
+    let head = [
+        point(x1, y1),
+        point(bx + nx * head_width / 2.0, by + ny * head_width / 2.0),
+        point(bx - nx * head_width / 2.0, by - ny * head_width / 2.0),
+    ];
+    if head[0] != head[2] && head[1] != head[2] {
+        imageproc::drawing::draw_polygon_mut(image, &head, color);
+    }
+}
+
+/// Blend `color` over the rectangle at `HIGHLIGHT_OPACITY`
+fn highlight(image: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32, color: Rgba<u8>) {
+    for py in y..y + height {
+        for px in x..x + width {
+            let pixel = image.get_pixel_mut(px, py);
+            for channel in 0..3 {
+                let blended = pixel[channel] as f32 * (1.0 - HIGHLIGHT_OPACITY)
+                    + color[channel] as f32 * HIGHLIGHT_OPACITY;
+                pixel[channel] = blended.round() as u8;
+            }
+        }
+    }
+}
+
+/// Channel difference below which a pixel counts as unchanged, absorbing
+/// compression noise and subpixel anti-aliasing
+const DEFAULT_DIFF_THRESHOLD: u8 = 16;
+
+/// Result of `diff_images`
+#[derive(Debug, Serialize)]
+pub struct ImageDiff {
+    /// The second image, faded, with changed pixels in red (base64 PNG)
+    pub diff_base64: String,
+    /// Share of pixels that changed, 0-100
+    pub changed_percent: f64,
+    /// Smallest rectangle containing every change; `None` if nothing changed
+    pub changed_bounds: Option<ChangedBounds>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ChangedBounds {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Compare two same-size images pixel by pixel and highlight what changed
+/// Sending the diff answers "what changed on screen" better than two full screenshots
+///
+/// # Arguments
+/// * `base64_a`, `base64_b` - Before and after images (PNG, JPEG)
+/// * `threshold` - Largest per-channel difference still treated as unchanged (default 16)
+#[tauri::command]
+pub fn diff_images(
+    base64_a: String,
+    base64_b: String,
+    threshold: Option<u8>,
+) -> Result<ImageDiff, String> {
+    diff_images_impl(&base64_a, &base64_b, threshold.unwrap_or(DEFAULT_DIFF_THRESHOLD))
+        .map_err(|e| format!("Diff failed: {}", e))
+}
+
+fn diff_images_impl(base64_a: &str, base64_b: &str, threshold: u8) -> Result<ImageDiff> {
+    let a = decode_base64_image(base64_a)?.to_rgba8();
+    let b = decode_base64_image(base64_b)?.to_rgba8();
+    if a.dimensions() != b.dimensions() {
+        anyhow::bail!(
+            "Images differ in size ({}x{} vs {}x{}); capture both from the same display",
+            a.width(),
+            a.height(),
+            b.width(),
+            b.height()
+        );
+    }
+
+    let mut diff = RgbaImage::new(b.width(), b.height());
+    let mut changed: u64 = 0;
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+
+    for (x, y, after) in b.enumerate_pixels() {
+        let before = a.get_pixel(x, y);
+        let is_changed = (0..3).any(|c| before[c].abs_diff(after[c]) > threshold);
+
+        let pixel = if is_changed {
+            changed += 1;
+            bounds = Some(match bounds {
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                None => (x, y, x, y),
+            });
+            DEFAULT_ANNOTATION_COLOR
+        } else {
+            // Fade unchanged content towards white so the changes stand out
+            let fade = |v: u8| ((v as u16 + 3 * 255) / 4) as u8;
+            Rgba([fade(after[0]), fade(after[1]), fade(after[2]), 255])
+        };
+        diff.put_pixel(x, y, pixel);
+    }
+
+    let total = b.width() as u64 * b.height() as u64;
+    Ok(ImageDiff {
+        diff_base64: encode_base64_png(&DynamicImage::ImageRgba8(diff))?,
+        changed_percent: if total == 0 {
+            0.0
+        } else {
+            changed as f64 * 100.0 / total as f64
+        },
+        changed_bounds: bounds.map(|(x0, y0, x1, y1)| ChangedBounds {
+            x: x0,
+            y: y0,
+            width: x1 - x0 + 1,
+            height: y1 - y0 + 1,
+        }),
+    })
+}
+
+/// How `prepare_image` fits an image within the size limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrepareMode {
+    /// Downscale only, keeping everything
+    Fit,
+    /// Center-crop to the target aspect ratio, then downscale
+    Crop,
+    /// Trim uniform margins (blank page around a document, empty panes), then downscale
+    Smart,
+}
+
+/// Limits `prepare_image` enforces
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImagePrepSettings {
+    /// Longest side in pixels after processing
+    pub max_dimension: u32,
+    /// Width / height that `crop` mode crops to
+    pub crop_aspect: f32,
+    /// Largest per-channel difference from the border color still counted as margin
+    pub margin_tolerance: u8,
+}
+
+impl Default for ImagePrepSettings {
+    fn default() -> Self {
+        Self {
+            max_dimension: 2048,
+            crop_aspect: 16.0 / 9.0,
+            margin_tolerance: 8,
+        }
+    }
+}
+
+/// Shrink an image before sending it, to stay under model size limits and save tokens
+///
+/// # Arguments
+/// * `base64` - Source image (PNG, JPEG)
+/// * `mode` - `fit`, `crop` or `smart`
+/// * `max_dimension` - Longest side in pixels, overriding `image_prep` for this call
+/// * `aspect` - Width / height for `crop`, overriding `image_prep`
+///
+/// # Returns
+/// Processed image as base64 PNG
+#[tauri::command]
+pub fn prepare_image(
+    settings: State<'_, SettingsStore>,
+    base64: String,
+    mode: PrepareMode,
+    max_dimension: Option<u32>,
+    aspect: Option<f32>,
+) -> Result<String, String> {
+    let mut prep = settings.get().image_prep;
+    if let Some(max_dimension) = max_dimension {
+        prep.max_dimension = max_dimension;
+    }
+    if let Some(aspect) = aspect {
+        prep.crop_aspect = aspect;
+    }
+    prepare_image_impl(&base64, mode, &prep).map_err(|e| format!("Failed to prepare image: {}", e))
+}
+
+fn prepare_image_impl(
+    base64_data: &str,
+    mode: PrepareMode,
+    prep: &ImagePrepSettings,
+) -> Result<String> {
+    if prep.max_dimension == 0 {
+        anyhow::bail!("Maximum dimension must be non-zero");
+    }
+    if !(prep.crop_aspect.is_finite() && prep.crop_aspect > 0.0) {
+        anyhow::bail!("Aspect ratio must be positive, got {}", prep.crop_aspect);
+    }
+
+    let image = decode_base64_image(base64_data)?;
+    let image = match mode {
+        PrepareMode::Fit => image,
+        PrepareMode::Crop => crop_to_aspect(&image, prep.crop_aspect),
+        PrepareMode::Smart => trim_margins(&image, prep.margin_tolerance),
+    };
+
+    let image = if image.width().max(image.height()) > prep.max_dimension {
+        image.resize(prep.max_dimension, prep.max_dimension, image::imageops::FilterType::Triangle)
+    } else {
+        image
+    };
+    encode_base64_png(&image)
+}
+
+/// Largest centered region with the given width / height ratio
+fn crop_to_aspect(image: &DynamicImage, aspect: f32) -> DynamicImage {
+    let (width, height) = (image.width(), image.height());
+    let current = width as f32 / height.max(1) as f32;
+
+    if current > aspect {
+        let new_width = ((height as f32 * aspect).round() as u32).clamp(1, width);
+        image.crop_imm((width - new_width) / 2, 0, new_width, height)
+    } else {
+        let new_height = ((width as f32 / aspect).round() as u32).clamp(1, height);
+        image.crop_imm(0, (height - new_height) / 2, width, new_height)
+    }
+}
+
+/// Crop away borders matching the top-left pixel's color
+/// An image that is uniform throughout is returned unchanged
+fn trim_margins(image: &DynamicImage, tolerance: u8) -> DynamicImage {
+    let rgba = image.to_rgba8();
+    let border = *rgba.get_pixel(0, 0);
+    let is_margin = |pixel: &Rgba<u8>| (0..4).all(|c| pixel[c].abs_diff(border[c]) <= tolerance);
+
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for (x, y, pixel) in rgba.enumerate_pixels() {
+        if !is_margin(pixel) {
+            bounds = Some(match bounds {
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                None => (x, y, x, y),
+            });
+        }
+    }
+
+    match bounds {
+        Some((x0, y0, x1, y1)) => image.crop_imm(x0, y0, x1 - x0 + 1, y1 - y0 + 1),
+        None => image.clone(),
+    }
+}
+
+/// Decode a base64-encoded image
+pub(crate) fn decode_base64_image(base64_data: &str) -> Result<DynamicImage> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(base64_data.trim())
+        .context("Invalid base64 image data")?;
+    image::load_from_memory(&bytes).context("Unsupported or corrupt image")
+}
+
+/// Encode an image as base64 PNG
+pub(crate) fn encode_base64_png(image: &DynamicImage) -> Result<String> {
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    image
+        .write_to(&mut bytes, ImageFormat::Png)
+        .context("Failed to encode PNG")?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(bytes.into_inner()))
+}
+
+/// Encode an image as base64 JPEG with the given quality (1-100)
+pub(crate) fn encode_base64_jpeg(image: &DynamicImage, quality: u8) -> Result<String> {
+    use image::codecs::jpeg::JpegEncoder;
+
+    let mut bytes = Vec::new();
+    // JPEG has no alpha channel
+    JpegEncoder::new_with_quality(&mut bytes, quality)
+        .encode_image(&image.to_rgb8())
+        .context("Failed to encode JPEG")?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
+/// Scale an image by `factor` (keeping aspect ratio) and re-encode it as JPEG
+pub(crate) fn downscale_base64(base64_data: &str, factor: f32, quality: u8) -> Result<String> {
+    let image = decode_base64_image(base64_data)?;
+
+    let width = ((image.width() as f32 * factor).round() as u32).max(1);
+    let height = ((image.height() as f32 * factor).round() as u32).max(1);
+    let resized = image.resize(width, height, image::imageops::FilterType::Triangle);
+
+    encode_base64_jpeg(&resized, quality)
+}
+
+/// Drop EXIF, GPS and other metadata by re-encoding the image
+/// EXIF orientation is applied to the pixels first so photos stay upright
+/// Images without metadata, and formats that can't be decoded here, are returned unchanged
+pub(crate) fn strip_metadata_base64(base64_data: &str) -> Result<String> {
+    use image::{ImageDecoder, ImageReader};
+
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(base64_data.trim())
+        .context("Invalid base64 image data")?;
+    let format = match image::guess_format(&bytes) {
+        Ok(format @ (ImageFormat::Png | ImageFormat::Jpeg)) => format,
+        _ => return Ok(base64_data.to_string()),
+    };
+    if !has_metadata(&bytes, format) {
+        return Ok(base64_data.to_string());
+    }
+
+    let mut decoder = ImageReader::with_format(std::io::Cursor::new(&bytes), format)
+        .into_decoder()
+        .context("Unsupported or corrupt image")?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder).context("Unsupported or corrupt image")?;
+    image.apply_orientation(orientation);
+
+    let stripped = match format {
+        ImageFormat::Jpeg => encode_base64_jpeg(&image, 92)?,
+        _ => encode_base64_png(&image)?,
+    };
+
+    // The encoders never write metadata; make sure that stays true
+    let check = base64::engine::general_purpose::STANDARD.decode(&stripped)?;
+    if has_metadata(&check, format) {
+        anyhow::bail!("Re-encoded image still contains metadata");
+    }
+    Ok(stripped)
+}
+
+/// Whether a PNG or JPEG carries EXIF, text or other metadata beyond the pixels
+fn has_metadata(bytes: &[u8], format: ImageFormat) -> bool {
+    match format {
+        ImageFormat::Png => {
+            const METADATA_CHUNKS: [&[u8]; 5] = [b"eXIf", b"tEXt", b"iTXt", b"zTXt", b"tIME"];
+
+            // Skip the 8-byte signature, then walk length / type / data / CRC chunks
+            let mut pos = 8;
+            while let Some(&[a, b, c, d, ref kind @ ..]) = bytes.get(pos..pos + 8) {
+                if METADATA_CHUNKS.contains(&kind) {
+                    return true;
+                }
+                if kind == b"IEND" {
+                    break;
+                }
+                pos += 12 + u32::from_be_bytes([a, b, c, d]) as usize;
+            }
+            false
+        }
+        ImageFormat::Jpeg => {
+            // Segments up to start-of-scan; APP1-APP15 hold EXIF/XMP/etc, 0xFE is a comment
+            let mut pos = 2;
+            while let Some(&[0xFF, marker, hi, lo]) = bytes.get(pos..pos + 4) {
+                if marker == 0xDA {
+                    break;
+                }
+                if (0xE1..=0xEF).contains(&marker) || marker == 0xFE {
+                    return true;
+                }
+                pos += 2 + u16::from_be_bytes([hi, lo]) as usize;
+            }
+            false
+        }
+        _ => false,
+    }
+}
+
+/// Largest image the API accepts, decoded
+pub(crate) const MAX_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+
+/// Decoded size of base64 image data, estimated from its length
+pub(crate) fn decoded_len(base64_data: &str) -> usize {
+    base64_data.trim().len() / 4 * 3
+}
+
+/// Over `MAX_IMAGE_BYTES`, so the API would reject it
+pub(crate) fn exceeds_image_limit(base64_data: &str) -> bool {
+    decoded_len(base64_data) > MAX_IMAGE_BYTES
+}
+
+/// Formats the API accepts, by the base64 encoding of their magic bytes
+const SUPPORTED_IMAGE_FORMATS: [(&str, &str); 4] = [
+    ("iVBORw0KGgo", "image/png"),
+    ("/9j/", "image/jpeg"),
+    ("R0lGOD", "image/gif"),
+    ("UklGR", "image/webp"),
+];
+
+/// MIME type of base64-encoded image data, or `None` if it isn't a format the API accepts
+pub(crate) fn supported_mime(base64_data: &str) -> Option<&'static str> {
+    let data = base64_data.trim_start();
+    SUPPORTED_IMAGE_FORMATS
+        .iter()
+        .find(|(magic, _)| data.starts_with(magic))
+        .map(|(_, mime)| *mime)
+}
+
+/// MIME type of base64-encoded image data, from its magic bytes; PNG if unrecognized
+pub(crate) fn base64_mime(base64_data: &str) -> &'static str {
+    supported_mime(base64_data).unwrap_or("image/png")
+}
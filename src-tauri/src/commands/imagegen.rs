@@ -0,0 +1,218 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::State;
+
+use crate::commands::ai::{extra_headers, http_client, response_error};
+use crate::commands::image::base64_mime;
+use crate::commands::screenshot::{save_image_file, ImageFileFormat, SaveScreenshotError};
+use crate::config::{api_url, require_key, ConfigState};
+use crate::settings::SettingsStore;
+
+/// Defaults for `generate_image`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageGenerationSettings {
+    /// `gpt-image-1`, `dall-e-3` or `dall-e-2`
+    pub model: String,
+    /// `WIDTHxHEIGHT` as accepted by the model, e.g. `1024x1024`
+    pub size: String,
+}
+
+impl Default for ImageGenerationSettings {
+    fn default() -> Self {
+        Self {
+            model: String::from("gpt-image-1"),
+            size: String::from("1024x1024"),
+        }
+    }
+}
+
+/// Most recent generated image, kept as raw bytes and their MIME type for
+/// `save_generated_image`
+#[derive(Default)]
+pub struct LastGeneratedImage(Mutex<Option<(Vec<u8>, &'static str)>>);
+
+/// Result of `generate_image`
+#[derive(Debug, Clone, Serialize)]
+pub struct GeneratedImage {
+    pub base64: String,
+    pub mime: &'static str,
+    /// The prompt as rewritten by the model (DALL·E 3 only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revised_prompt: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ImageRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+    size: &'a str,
+    n: u8,
+    /// DALL·E returns a URL by default; gpt-image models always return base64 and
+    /// reject this field
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<&'a str>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImageResponse {
+    data: Vec<ImageData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImageData {
+    b64_json: Option<String>,
+    url: Option<String>,
+    revised_prompt: Option<String>,
+}
+
+/// Generate an image from `prompt`
+/// `size` and `model` override the `image_generation` settings for this call
+///
+/// # Returns
+/// The image as base64, also kept for `save_generated_image`
+#[tauri::command]
+pub async fn generate_image(
+    config: State<'_, ConfigState>,
+    settings: State<'_, SettingsStore>,
+    last: State<'_, LastGeneratedImage>,
+    prompt: String,
+    size: Option<String>,
+    model: Option<String>,
+) -> Result<GeneratedImage, String> {
+    config.touch();
+
+    let prompt = prompt.trim();
+    if prompt.is_empty() {
+        return Err("Prompt is empty".to_string());
+    }
+
+    let defaults = settings.get().image_generation;
+    let model = model.filter(|m| !m.trim().is_empty()).unwrap_or(defaults.model);
+    let size = size.filter(|s| !s.trim().is_empty()).unwrap_or(defaults.size);
+    let api_key = config.get().openai_api_key;
+
+    let (bytes, revised_prompt) = generate_image_impl(&api_key, prompt, &size, &model)
+        .await
+        .map_err(|e| format!("Image generation failed: {}", e))?;
+
+    let base64 = base64::engine::general_purpose::STANDARD.encode(&bytes);
+    let mime = base64_mime(&base64);
+    *last.0.lock().unwrap() = Some((bytes, mime));
+
+    Ok(GeneratedImage {
+        mime,
+        base64,
+        revised_prompt,
+    })
+}
+
+/// Save the last generated image to `path` as PNG or JPEG
+/// Without `format` a PNG or JPEG keeps its own format; the extension matching the
+/// format is added if missing
+///
+/// # Returns
+/// The path actually written
+#[tauri::command]
+pub fn save_generated_image(
+    last: State<'_, LastGeneratedImage>,
+    path: PathBuf,
+    format: Option<ImageFileFormat>,
+) -> Result<PathBuf, SaveScreenshotError> {
+    let (bytes, mime) = last
+        .0
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or(SaveScreenshotError::NoGeneratedImage)?;
+
+    save_image_file(&bytes, mime, path, format)
+}
+
+async fn generate_image_impl(
+    api_key: &str,
+    prompt: &str,
+    size: &str,
+    model: &str,
+) -> Result<(Vec<u8>, Option<String>)> {
+    let api_key = require_key(api_key)?;
+    let request = ImageRequest {
+        model,
+        prompt,
+        size,
+        n: 1,
+        response_format: model.starts_with("dall-e").then_some("b64_json"),
+    };
+
+    let client = http_client();
+    let response = client
+        .post(api_url("images/generations"))
+        .headers(extra_headers())
+        .header("Authorization", format!("Bearer {}", api_key))
+        .json(&request)
+        .send()
+        .await
+        .context("Failed to send request to OpenAI")?;
+    if !response.status().is_success() {
+        return Err(response_error(response).await);
+    }
+
+    let image = response
+        .json::<ImageResponse>()
+        .await
+        .context("Failed to parse image response")?
+        .data
+        .into_iter()
+        .next()
+        .context("Response contained no image")?;
+
+    let bytes = match (image.b64_json, image.url) {
+        (Some(b64), _) => base64::engine::general_purpose::STANDARD
+            .decode(b64.trim())
+            .context("Invalid base64 image data")?,
+        (None, Some(url)) => client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .context("Failed to download generated image")?
+            .bytes()
+            .await
+            .context("Failed to download generated image")?
+            .to_vec(),
+        (None, None) => anyhow::bail!("Response contained no image"),
+    };
+
+    Ok((bytes, image.revised_prompt))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_jpeg_keeps_or_converts_its_format() {
+        let mut jpeg = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new(&mut jpeg)
+            .encode_image(&image::RgbImage::new(4, 4))
+            .unwrap();
+        let base64 = base64::engine::general_purpose::STANDARD.encode(&jpeg);
+        let mime = base64_mime(&base64);
+        let path = std::env::temp_dir().join(format!("phantom_generated_{}", std::process::id()));
+
+        let kept = save_image_file(&jpeg, mime, path.clone(), None).unwrap();
+        assert_eq!(kept.extension().unwrap(), "jpg");
+        assert_eq!(std::fs::read(&kept).unwrap(), jpeg);
+
+        let converted = save_image_file(&jpeg, mime, path, Some(ImageFileFormat::Png)).unwrap();
+        assert_eq!(converted.extension().unwrap(), "png");
+        let written = std::fs::read(&converted).unwrap();
+        assert_eq!(image::guess_format(&written).unwrap(), image::ImageFormat::Png);
+
+        let _ = std::fs::remove_file(kept);
+        let _ = std::fs::remove_file(converted);
+    }
+}
@@ -0,0 +1,106 @@
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::config::{AppConfig, ConfigState};
+use crate::settings::SettingsStore;
+
+/// How often the idle timer checks for inactivity
+const AUTO_LOCK_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// `.env` entry holding the passphrase `unlock_app` asks for
+/// Without one the OpenAI API key itself has to be entered
+const UNLOCK_PASSPHRASE_VAR: &str = "GHOSTGPT_UNLOCK_PASSPHRASE";
+
+/// Lock the app after `auto_lock_minutes` without activity
+/// Commands the user invokes and `report_activity` count as activity
+pub fn spawn_auto_lock(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(AUTO_LOCK_CHECK_INTERVAL);
+
+        let Some(minutes) = app.state::<SettingsStore>().get().auto_lock_minutes else {
+            continue;
+        };
+        let config = app.state::<ConfigState>();
+        if config.idle_for() >= Duration::from_secs(minutes * 60) {
+            lock(&app, &config);
+        }
+    });
+}
+
+/// Drop the API keys from memory and emit `locked`
+/// Requests fail with `Locked` until `unlock_app`
+#[tauri::command]
+pub fn lock_app(app: AppHandle, config: State<'_, ConfigState>) {
+    lock(&app, &config);
+}
+
+/// Re-read the API keys from `.env` and allow requests again; emits `unlocked`
+/// `passphrase` must match GHOSTGPT_UNLOCK_PASSPHRASE, or the OpenAI API key when
+/// none is set, so an unattended machine can't be unlocked with a click
+#[tauri::command]
+pub fn unlock_app(
+    app: AppHandle,
+    config: State<'_, ConfigState>,
+    passphrase: String,
+) -> Result<(), String> {
+    if !config.is_locked() {
+        return Ok(());
+    }
+
+    let new_config = AppConfig::reload().map_err(|e| format!("Failed to unlock: {}", e))?;
+    let expected = std::env::var(UNLOCK_PASSPHRASE_VAR)
+        .ok()
+        .filter(|passphrase| !passphrase.trim().is_empty())
+        .unwrap_or_else(|| new_config.openai_api_key.clone());
+    if !passphrase_matches(&passphrase, &expected) {
+        return Err(String::from("Incorrect passphrase"));
+    }
+    config.unlock(new_config);
+
+    app.emit("unlocked", ())
+        .map_err(|e| format!("Failed to emit event: {}", e))
+}
+
+/// Whether the app is locked, e.g. to show the lock screen on startup of the UI
+#[tauri::command]
+pub fn is_locked(config: State<'_, ConfigState>) -> bool {
+    config.is_locked()
+}
+
+/// Reset the auto-lock timer on user input that doesn't send a request
+#[tauri::command]
+pub fn report_activity(config: State<'_, ConfigState>) {
+    config.touch();
+}
+
+/// Compare without stopping at the first differing byte, so timing doesn't reveal
+/// how much of the passphrase was right
+fn passphrase_matches(entered: &str, expected: &str) -> bool {
+    let (entered, expected) = (entered.trim().as_bytes(), expected.trim().as_bytes());
+    let differing = entered
+        .iter()
+        .zip(expected)
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    !expected.is_empty() && entered.len() == expected.len() && differing == 0
+}
+
+fn lock(app: &AppHandle, config: &ConfigState) {
+    if config.lock() {
+        println!("Locked, API keys cleared from memory");
+        let _ = app.emit("locked", ());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passphrase_must_match_exactly() {
+        assert!(passphrase_matches("correct horse", "correct horse"));
+        assert!(passphrase_matches(" correct horse\n", "correct horse"));
+        assert!(!passphrase_matches("correct hors", "correct horse"));
+        assert!(!passphrase_matches("correct horsf", "correct horse"));
+        assert!(!passphrase_matches("", ""));
+    }
+}
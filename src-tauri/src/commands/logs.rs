@@ -0,0 +1,180 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+
+use crate::settings::SettingsStore;
+
+/// Name of the file currently written to; rotated files get a number before the extension
+const ACTIVE_LOG: &str = "requests.log";
+
+/// Request log rotation and retention
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestLogSettings {
+    /// Log one line per API request (model, outcome, duration); never prompts or replies
+    pub enabled: bool,
+    /// Rotate once the active file reaches this size
+    pub max_file_kb: u64,
+    /// Also rotate when the day changes
+    pub rotate_daily: bool,
+    /// Rotated files kept besides the active one; older ones are deleted
+    pub retention: usize,
+}
+
+impl Default for RequestLogSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_file_kb: 1024,
+            rotate_daily: true,
+            retention: 5,
+        }
+    }
+}
+
+/// Managed state for the request log in the app log directory
+pub struct RequestLog {
+    dir: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl RequestLog {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn active_path(&self) -> PathBuf {
+        self.dir.join(ACTIVE_LOG)
+    }
+
+    /// `requests.<n>.log`; 1 is the most recent
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        self.dir.join(format!("requests.{}.log", n))
+    }
+
+    fn append(&self, settings: &RequestLogSettings, line: &str) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+
+        std::fs::create_dir_all(&self.dir).context("Failed to create log directory")?;
+        let path = self.active_path();
+        if needs_rotation(&path, settings) {
+            self.rotate(settings.retention)?;
+        }
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .context("Failed to open request log")?;
+        writeln!(file, "{}", line).context("Failed to write request log")?;
+        Ok(())
+    }
+
+    /// Shift `requests.log` to `requests.1.log`, `.1` to `.2` and so on,
+    /// dropping whatever falls past `retention`
+    fn rotate(&self, retention: usize) -> Result<()> {
+        let _ = std::fs::remove_file(self.rotated_path(retention.max(1)));
+        for n in (1..retention).rev() {
+            let from = self.rotated_path(n);
+            if from.exists() {
+                std::fs::rename(&from, self.rotated_path(n + 1))
+                    .context("Failed to rotate request log")?;
+            }
+        }
+
+        if retention == 0 {
+            std::fs::remove_file(self.active_path()).context("Failed to rotate request log")?;
+        } else {
+            std::fs::rename(self.active_path(), self.rotated_path(1))
+                .context("Failed to rotate request log")?;
+        }
+        Ok(())
+    }
+
+    /// Delete the active and all rotated log files
+    fn clear(&self) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Ok(());
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with("requests.") && name.ends_with(".log") {
+                std::fs::remove_file(entry.path())
+                    .with_context(|| format!("Failed to delete {}", name))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether the active file is over the size limit or (with daily rotation) from an earlier day
+fn needs_rotation(path: &Path, settings: &RequestLogSettings) -> bool {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return false;
+    };
+    if metadata.len() >= settings.max_file_kb.saturating_mul(1024) {
+        return true;
+    }
+
+    settings.rotate_daily
+        && metadata.modified().is_ok_and(|modified| {
+            DateTime::<Local>::from(modified).date_naive() < Local::now().date_naive()
+        })
+}
+
+/// Record one API request if request logging is enabled
+pub(crate) fn log_request(
+    app: Option<&AppHandle>,
+    kind: &str,
+    model: &str,
+    elapsed: Duration,
+    error: Option<&anyhow::Error>,
+) {
+    let Some(app) = app else {
+        return;
+    };
+    let settings = app.state::<SettingsStore>().get().request_log;
+    if !settings.enabled {
+        return;
+    }
+
+    let outcome = match error {
+        Some(e) => format!("error {}", e.to_string().replace('\n', " ")),
+        None => String::from("ok"),
+    };
+    let line = format!(
+        "{} {} {} {}ms {}",
+        Local::now().to_rfc3339(),
+        kind,
+        model,
+        elapsed.as_millis(),
+        outcome
+    );
+
+    if let Err(e) = app.state::<RequestLog>().append(&settings, &line) {
+        eprintln!("Failed to write request log: {}", e);
+    }
+}
+
+/// Path of the log file currently being written (it may not exist yet)
+#[tauri::command]
+pub fn get_log_path(log: State<'_, RequestLog>) -> PathBuf {
+    log.active_path()
+}
+
+/// Delete all request logs, including rotated ones
+#[tauri::command]
+pub fn clear_logs(log: State<'_, RequestLog>) -> Result<(), String> {
+    log.clear().map_err(|e| format!("Failed to clear logs: {}", e))
+}
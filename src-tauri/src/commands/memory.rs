@@ -0,0 +1,271 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+
+use crate::commands::ai::{
+    normalize_role, send_message_impl, ChatOptions, ContentPart, Message, MessageContent,
+};
+use crate::config::ConfigState;
+use crate::settings::SettingsStore;
+
+/// Prefix identifying the rolling summary message
+const SUMMARY_PREFIX: &str = "Summary of the earlier conversation:";
+
+/// When and how older turns are folded into a rolling summary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemorySettings {
+    /// Compress once the conversation has more than this many non-system messages
+    pub threshold: usize,
+    /// Number of oldest messages replaced by the summary
+    pub summarize_count: usize,
+    /// Target summary length in words
+    pub summary_words: usize,
+}
+
+impl Default for MemorySettings {
+    fn default() -> Self {
+        Self {
+            threshold: 40,
+            summarize_count: 20,
+            summary_words: 200,
+        }
+    }
+}
+
+/// Context made of the system prompts, the pinned messages and the latest turn
+/// (everything from the last user message on), in their original order
+pub(crate) fn context_from_pinned(messages: Vec<Message>) -> Vec<Message> {
+    // Roles are normalized when sent, so " User " counts as a user message here too
+    let is_role = |message: &Message, role: &str| {
+        normalize_role(&message.role).as_deref() == Some(role)
+    };
+    let latest_turn = messages
+        .iter()
+        .rposition(|m| is_role(m, "user"))
+        .unwrap_or(messages.len());
+
+    messages
+        .into_iter()
+        .enumerate()
+        .filter(|(index, message)| {
+            is_role(message, "system") || message.pinned || *index >= latest_turn
+        })
+        .map(|(_, message)| message)
+        .collect()
+}
+
+/// Full history for display alongside the compressed history to send
+#[derive(Debug, Serialize)]
+pub struct CompressedHistory {
+    pub raw: Vec<Message>,
+    pub compressed: Vec<Message>,
+    /// How many messages were folded into the summary (0 if under the threshold)
+    pub summarized: usize,
+}
+
+/// Replace the oldest messages with a model-written summary once the
+/// conversation grows past the configured threshold
+///
+/// # Arguments
+/// * `messages` - History to send (may already contain an earlier summary)
+/// * `raw` - Full uncompressed history for the UI; defaults to `messages`
+#[tauri::command]
+pub async fn compress_conversation(
+    app: AppHandle,
+    config: State<'_, ConfigState>,
+    settings: State<'_, SettingsStore>,
+    messages: Vec<Message>,
+    raw: Option<Vec<Message>>,
+) -> Result<CompressedHistory, String> {
+    config.touch();
+
+    let memory = settings.get().memory;
+    let raw = raw.unwrap_or_else(|| messages.clone());
+
+    let options = ChatOptions {
+        app: Some(app),
+        ..ChatOptions::default()
+    };
+
+    let api_key = config.get().openai_api_key;
+    let (compressed, summarized) = compress_messages(&api_key, messages, &memory, &options)
+        .await
+        .map_err(|e| format!("Failed to summarize conversation: {}", e))?;
+
+    Ok(CompressedHistory {
+        raw,
+        compressed,
+        summarized,
+    })
+}
+
+async fn compress_messages(
+    api_key: &str,
+    messages: Vec<Message>,
+    memory: &MemorySettings,
+    options: &ChatOptions,
+) -> Result<(Vec<Message>, usize)> {
+    // Leading system prompts are kept verbatim
+    let prompt_len = prompt_len(&messages);
+    let conversation_len = messages.len() - prompt_len;
+
+    if conversation_len <= memory.threshold || memory.summarize_count == 0 {
+        return Ok((messages, 0));
+    }
+
+    // Always leave at least the latest message untouched
+    let count = memory.summarize_count.min(conversation_len - 1);
+    if count == 0 {
+        return Ok((messages, 0));
+    }
+    let mut messages = messages;
+    let oldest: Vec<Message> = messages.drain(prompt_len..prompt_len + count).collect();
+
+    let summary = summarize(api_key, &oldest, memory.summary_words, options).await?;
+    messages.insert(prompt_len, summary_message(&summary));
+
+    Ok((messages, count))
+}
+
+/// Start of a fresh conversation continuing `messages`: its leading system prompts,
+/// followed by a summary of the rest when `summary_words` is set
+/// If summarizing fails the prompts are carried over alone
+pub(crate) async fn carry_over(
+    api_key: &str,
+    messages: &[Message],
+    summary_words: Option<usize>,
+    options: &ChatOptions,
+) -> Vec<Message> {
+    let (prompts, rest) = messages.split_at(prompt_len(messages));
+    let mut carried = prompts.to_vec();
+
+    if let Some(words) = summary_words.filter(|_| !rest.is_empty()) {
+        match summarize(api_key, rest, words, options).await {
+            Ok(summary) => carried.push(summary_message(&summary)),
+            Err(e) => eprintln!("Warning: Could not summarize archived conversation: {}", e),
+        }
+    }
+    carried
+}
+
+/// Number of leading system prompts (an earlier summary is not a prompt)
+fn prompt_len(messages: &[Message]) -> usize {
+    messages
+        .iter()
+        .take_while(|m| m.role == "system" && !is_summary(m))
+        .count()
+}
+
+fn summary_message(summary: &str) -> Message {
+    Message {
+        role: String::from("system"),
+        content: MessageContent::Text(format!("{} {}", SUMMARY_PREFIX, summary)),
+        pinned: false,
+    }
+}
+
+async fn summarize(
+    api_key: &str,
+    messages: &[Message],
+    words: usize,
+    options: &ChatOptions,
+) -> Result<String> {
+    let transcript = messages
+        .iter()
+        .map(|m| format!("{}: {}", m.role, transcript_text(m)))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let request = vec![
+        Message {
+            role: String::from("system"),
+            content: MessageContent::Text(format!(
+                "Summarize the following conversation in at most {} words. \
+                 Keep facts, decisions, names and open questions needed to continue it.",
+                words
+            )),
+            pinned: false,
+        },
+        Message {
+            role: String::from("user"),
+            content: MessageContent::Text(transcript),
+            pinned: false,
+        },
+    ];
+
+    let response = send_message_impl(api_key, request, Vec::new(), options).await?;
+    Ok(response.content.trim().to_string())
+}
+
+/// Message text with attachments replaced by placeholders
+fn transcript_text(message: &Message) -> String {
+    let count = |kind: fn(&ContentPart) -> bool| match &message.content {
+        MessageContent::Parts(parts) => parts.iter().filter(|p| kind(p)).count(),
+        MessageContent::Text(_) => 0,
+    };
+    let images = count(|p| matches!(p, ContentPart::ImageUrl { .. }));
+    let audio = count(|p| matches!(p, ContentPart::InputAudio { .. }));
+
+    let mut text = message.content.text();
+    if images > 0 {
+        text.push_str(&format!(" [{} image(s)]", images));
+    }
+    if audio > 0 {
+        text.push_str(&format!(" [{} audio clip(s)]", audio));
+    }
+    text
+}
+
+pub(crate) fn is_summary(message: &Message) -> bool {
+    matches!(&message.content, MessageContent::Text(t) if t.starts_with(SUMMARY_PREFIX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, text: &str, pinned: bool) -> Message {
+        Message {
+            role: role.to_string(),
+            content: MessageContent::Text(text.to_string()),
+            pinned,
+        }
+    }
+
+    #[test]
+    fn pins_follow_their_message_when_earlier_ones_are_removed() {
+        let mut messages = vec![
+            message("system", "Be brief", false),
+            message("user", "Old question", false),
+            message("assistant", "Old answer", false),
+            message("user", "Key fact", true),
+            message("assistant", "Noted", false),
+            message("user", "Latest", false),
+        ];
+        messages.remove(1);
+
+        let texts: Vec<String> = context_from_pinned(messages)
+            .iter()
+            .map(|m| m.content.text())
+            .collect();
+        assert_eq!(texts, ["Be brief", "Key fact", "Latest"]);
+    }
+
+    #[test]
+    fn unnormalized_roles_still_mark_the_latest_turn() {
+        let messages = vec![
+            message(" System", "Be brief", false),
+            message("user", "Old question", false),
+            message("assistant", "Old answer", false),
+            message(" User ", "Latest", false),
+            message("assistant", "Reply", false),
+        ];
+
+        let texts: Vec<String> = context_from_pinned(messages)
+            .iter()
+            .map(|m| m.content.text())
+            .collect();
+        assert_eq!(texts, ["Be brief", "Latest", "Reply"]);
+    }
+}
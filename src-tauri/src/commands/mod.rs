@@ -1,3 +1,44 @@
 pub mod screenshot;
 pub mod ai;
+pub mod app_audio;
 pub mod audio;
+pub mod batch;
+pub mod chapters;
+pub mod code;
+pub mod compare;
+pub mod context;
+pub mod diagnostics;
+pub mod duplicates;
+pub mod export;
+pub mod files;
+pub mod history;
+pub mod hotkeys;
+pub mod image;
+pub mod imagegen;
+pub mod lock;
+pub mod logs;
+pub mod memory;
+pub mod models;
+pub mod ocr;
+pub mod permissions;
+pub mod privacy;
+pub mod prompt;
+pub mod realtime;
+pub mod recovery;
+pub mod requests;
+pub mod retry;
+pub mod scrolling;
+pub mod session;
+pub mod templates;
+#[cfg(test)]
+mod test_server;
+pub mod tokens;
+pub mod transfer;
+pub mod transcription;
+pub mod usage;
+pub mod validate;
+pub mod settings;
+pub mod storage;
+pub mod stream;
+pub mod system;
+pub mod window;
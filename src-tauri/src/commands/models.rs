@@ -0,0 +1,186 @@
+use serde::Serialize;
+
+/// What a model accepts and how much, for enabling UI features
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ModelCapabilities {
+    /// Accepts image inputs (screenshots)
+    pub vision: bool,
+    /// Accepts `input_audio` parts
+    pub audio_input: bool,
+    pub tools: bool,
+    /// Searches the web when sent `web_search_options`, citing sources in `annotations`
+    pub web_search: bool,
+    /// Supports `response_format` JSON mode
+    pub json_mode: bool,
+    /// Reasoning model that accepts `reasoning_effort`
+    pub reasoning: bool,
+    /// Returns token log probabilities when asked for `logprobs`
+    pub logprobs: bool,
+    pub streaming: bool,
+    /// Understands explicit `cache_control` markers on content parts (Claude models
+    /// behind an OpenAI-compatible endpoint); OpenAI caches automatically instead
+    pub cache_markers: bool,
+    /// Context window in tokens
+    pub max_context: usize,
+    /// Most tokens a single reply can have
+    pub max_output: usize,
+}
+
+/// Assumed for models not in `MODEL_CAPABILITIES`: a current general-purpose chat model
+const DEFAULT_CAPABILITIES: ModelCapabilities = ModelCapabilities {
+    vision: true,
+    audio_input: false,
+    tools: true,
+    web_search: false,
+    json_mode: true,
+    reasoning: false,
+    logprobs: true,
+    streaming: true,
+    cache_markers: false,
+    max_context: 128_000,
+    max_output: 16_384,
+};
+
+/// Capabilities by model prefix; the first matching prefix wins, so list more
+/// specific names (`gpt-4o-audio`) before the families they belong to (`gpt-4o`)
+const MODEL_CAPABILITIES: &[(&str, ModelCapabilities)] = &[
+    (
+        "gpt-4o-audio",
+        ModelCapabilities {
+            vision: false,
+            audio_input: true,
+            ..DEFAULT_CAPABILITIES
+        },
+    ),
+    (
+        "gpt-4o-mini-audio",
+        ModelCapabilities {
+            vision: false,
+            audio_input: true,
+            ..DEFAULT_CAPABILITIES
+        },
+    ),
+    (
+        "gpt-4o-search",
+        ModelCapabilities {
+            vision: false,
+            tools: false,
+            web_search: true,
+            logprobs: false,
+            ..DEFAULT_CAPABILITIES
+        },
+    ),
+    (
+        "gpt-4o-mini-search",
+        ModelCapabilities {
+            vision: false,
+            tools: false,
+            web_search: true,
+            logprobs: false,
+            ..DEFAULT_CAPABILITIES
+        },
+    ),
+    ("gpt-4o", DEFAULT_CAPABILITIES),
+    (
+        "gpt-4.1",
+        ModelCapabilities {
+            max_context: 1_047_576,
+            max_output: 32_768,
+            ..DEFAULT_CAPABILITIES
+        },
+    ),
+    (
+        "gpt-4-turbo",
+        ModelCapabilities {
+            max_output: 4_096,
+            ..DEFAULT_CAPABILITIES
+        },
+    ),
+    (
+        "gpt-4",
+        ModelCapabilities {
+            vision: false,
+            json_mode: false,
+            max_context: 8_192,
+            max_output: 8_192,
+            ..DEFAULT_CAPABILITIES
+        },
+    ),
+    (
+        "gpt-3.5-turbo",
+        ModelCapabilities {
+            vision: false,
+            max_context: 16_385,
+            max_output: 4_096,
+            ..DEFAULT_CAPABILITIES
+        },
+    ),
+    (
+        "o1-mini",
+        ModelCapabilities {
+            vision: false,
+            tools: false,
+            json_mode: false,
+            logprobs: false,
+            max_context: 128_000,
+            max_output: 65_536,
+            ..DEFAULT_CAPABILITIES
+        },
+    ),
+    (
+        "o1",
+        ModelCapabilities {
+            reasoning: true,
+            logprobs: false,
+            max_context: 200_000,
+            max_output: 100_000,
+            ..DEFAULT_CAPABILITIES
+        },
+    ),
+    (
+        "o3",
+        ModelCapabilities {
+            reasoning: true,
+            logprobs: false,
+            max_context: 200_000,
+            max_output: 100_000,
+            ..DEFAULT_CAPABILITIES
+        },
+    ),
+    (
+        "o4",
+        ModelCapabilities {
+            reasoning: true,
+            logprobs: false,
+            max_context: 200_000,
+            max_output: 100_000,
+            ..DEFAULT_CAPABILITIES
+        },
+    ),
+    (
+        "claude",
+        ModelCapabilities {
+            json_mode: false,
+            logprobs: false,
+            cache_markers: true,
+            max_context: 200_000,
+            max_output: 8_192,
+            ..DEFAULT_CAPABILITIES
+        },
+    ),
+];
+
+/// Capabilities of `model`, e.g. to hide the screenshot button for text-only models
+/// Unknown models get the defaults of a current general-purpose chat model
+#[tauri::command]
+pub fn get_model_capabilities(model: String) -> ModelCapabilities {
+    capabilities(model.trim())
+}
+
+pub(crate) fn capabilities(model: &str) -> ModelCapabilities {
+    MODEL_CAPABILITIES
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, capabilities)| *capabilities)
+        .unwrap_or(DEFAULT_CAPABILITIES)
+}
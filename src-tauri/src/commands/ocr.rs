@@ -0,0 +1,271 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::commands::ai::{ContentPart, Message, MessageContent};
+use crate::commands::context::CaptureContext;
+use crate::commands::screenshot::{capture_screenshot, LastScreenshot};
+use crate::settings::SettingsStore;
+
+/// When recognized text is trusted enough to replace the screenshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OcrSettings {
+    /// Minimum average recognition confidence, 0-1
+    pub min_confidence: f32,
+    /// Minimum fraction of the screen covered by text for it to count as mostly text
+    pub min_coverage: f32,
+}
+
+impl Default for OcrSettings {
+    fn default() -> Self {
+        Self {
+            min_confidence: 0.8,
+            min_coverage: 0.2,
+        }
+    }
+}
+
+/// Text recognized in an image
+#[derive(Debug, Clone, Serialize)]
+pub struct OcrText {
+    /// Lines in reading order
+    pub text: String,
+    /// Average confidence of the recognized lines, weighted by length
+    pub confidence: f32,
+    /// Fraction of the image area covered by text
+    pub coverage: f32,
+}
+
+impl OcrText {
+    /// Whether the text can stand in for the image under `settings`
+    pub fn is_readable(&self, settings: &OcrSettings) -> bool {
+        !self.text.trim().is_empty()
+            && self.confidence >= settings.min_confidence
+            && self.coverage >= settings.min_coverage
+    }
+}
+
+/// Result of `capture_and_ocr`
+#[derive(Debug, Serialize)]
+pub struct CaptureOcr {
+    pub ocr: OcrText,
+    /// The screenshot itself, for sending instead of the text
+    pub screenshot_base64: String,
+    /// Whether the text passes the configured thresholds
+    pub readable: bool,
+}
+
+/// Capture the screen and recognize its text locally with the macOS Vision framework
+/// Both the text and the image are returned so the UI can choose what to send
+#[tauri::command]
+pub async fn capture_and_ocr(
+    last: State<'_, LastScreenshot>,
+    settings: State<'_, SettingsStore>,
+    capture_context: State<'_, CaptureContext>,
+) -> Result<CaptureOcr, String> {
+    let screenshot_base64 = capture_screenshot(last, settings.clone(), capture_context, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let ocr = recognize_base64(screenshot_base64.clone())
+        .await
+        .map_err(|e| format!("OCR failed: {}", e))?;
+    let readable = ocr.is_readable(&settings.get().ocr);
+
+    Ok(CaptureOcr {
+        ocr,
+        screenshot_base64,
+        readable,
+    })
+}
+
+/// Recognize text in a base64-encoded image off the async runtime
+pub(crate) async fn recognize_base64(base64_data: String) -> Result<OcrText> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(base64_data.trim())
+            .context("Invalid base64 image data")?;
+        recognize_text(&bytes)
+    })
+    .await
+    .context("OCR task failed")?
+}
+
+/// Recognize `screenshot` and, if the text is readable under `settings`, attach it to
+/// the last user message; true when the text replaces the screenshot
+/// Low confidence or a failed recognition keeps the screenshot
+pub(crate) async fn prefer_text(
+    messages: &mut [Message],
+    screenshot: &str,
+    settings: &OcrSettings,
+    app_context: Option<&str>,
+) -> bool {
+    match recognize_base64(screenshot.to_string()).await {
+        Ok(text) if text.is_readable(settings) => {
+            attach_screen_text(messages, &text.text, app_context);
+            true
+        }
+        Ok(_) => {
+            println!("OCR confidence too low, sending the screenshot");
+            false
+        }
+        Err(e) => {
+            eprintln!("Warning: OCR failed, sending the screenshot: {}", e);
+            false
+        }
+    }
+}
+
+/// Add recognized screen text to the last user message in place of a screenshot
+pub(crate) fn attach_screen_text(
+    messages: &mut [Message],
+    text: &str,
+    app_context: Option<&str>,
+) {
+    let Some(last) = messages.last_mut().filter(|m| m.role == "user") else {
+        return;
+    };
+
+    let mut parts = Vec::new();
+    if let Some(context) = app_context {
+        parts.push(ContentPart::Text {
+            text: context.to_string(),
+            cache_control: None,
+        });
+    }
+    match std::mem::replace(&mut last.content, MessageContent::Parts(Vec::new())) {
+        MessageContent::Text(text) => parts.push(ContentPart::Text {
+            text,
+            cache_control: None,
+        }),
+        MessageContent::Parts(existing) => parts.extend(existing),
+    }
+    parts.push(ContentPart::Text {
+        text: format!("Text captured from the screen:\n{}", text),
+        cache_control: None,
+    });
+
+    last.content = MessageContent::Parts(parts);
+}
+
+#[cfg(target_os = "macos")]
+fn recognize_text(image: &[u8]) -> Result<OcrText> {
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::NSAutoreleasePool;
+    use objc::runtime::{BOOL, NO, YES};
+    use objc::{class, msg_send, sel, sel_impl};
+    use std::ffi::CStr;
+
+    #[link(name = "Vision", kind = "framework")]
+    extern "C" {}
+
+    /// Normalized bounding box, origin at the bottom left
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Rect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    }
+
+    // VNRequestTextRecognitionLevelAccurate
+    const RECOGNITION_LEVEL_ACCURATE: isize = 0;
+
+    unsafe {
+        // Runs on a blocking worker thread with no pool of its own, so the autoreleased
+        // NSData, arrays, results and strings would otherwise leak on every call
+        let pool: id = NSAutoreleasePool::new(nil);
+
+        let data: id = msg_send![class!(NSData), dataWithBytes: image.as_ptr() length: image.len()];
+        let options: id = msg_send![class!(NSDictionary), dictionary];
+        let handler: id = msg_send![class!(VNImageRequestHandler), alloc];
+        let handler: id = msg_send![handler, initWithData: data options: options];
+
+        let request: id = msg_send![class!(VNRecognizeTextRequest), alloc];
+        let request: id = msg_send![request, init];
+        let _: () = msg_send![request, setRecognitionLevel: RECOGNITION_LEVEL_ACCURATE];
+        let _: () = msg_send![request, setUsesLanguageCorrection: YES];
+
+        let requests: id = msg_send![class!(NSArray), arrayWithObject: request];
+        let mut error: id = nil;
+        let ok: BOOL = msg_send![handler, performRequests: requests error: &mut error];
+
+        let mut lines = Vec::new();
+        if ok != NO {
+            let results: id = msg_send![request, results];
+            let count: usize = if results == nil { 0 } else { msg_send![results, count] };
+
+            for i in 0..count {
+                let observation: id = msg_send![results, objectAtIndex: i];
+                let candidates: id = msg_send![observation, topCandidates: 1usize];
+                let candidate_count: usize = msg_send![candidates, count];
+                if candidate_count == 0 {
+                    continue;
+                }
+                let candidate: id = msg_send![candidates, objectAtIndex: 0usize];
+
+                let string: id = msg_send![candidate, string];
+                let utf8: *const std::os::raw::c_char = msg_send![string, UTF8String];
+                if utf8.is_null() {
+                    continue;
+                }
+                let text = CStr::from_ptr(utf8).to_string_lossy().to_string();
+                let confidence: f32 = msg_send![candidate, confidence];
+                let rect: Rect = msg_send![observation, boundingBox];
+
+                lines.push((text, confidence, rect));
+            }
+        }
+
+        let _: () = msg_send![request, release];
+        let _: () = msg_send![handler, release];
+        // Every line was copied into Rust strings above
+        pool.drain();
+
+        if ok == NO {
+            anyhow::bail!("Text recognition failed");
+        }
+
+        // Top to bottom, then left to right
+        lines.sort_by(|a, b| {
+            let top = |r: &Rect| r.y + r.height;
+            top(&b.2)
+                .total_cmp(&top(&a.2))
+                .then_with(|| a.2.x.total_cmp(&b.2.x))
+        });
+
+        let chars: usize = lines.iter().map(|(text, _, _)| text.chars().count()).sum();
+        let confidence = if chars == 0 {
+            0.0
+        } else {
+            lines
+                .iter()
+                .map(|(text, confidence, _)| confidence * text.chars().count() as f32)
+                .sum::<f32>()
+                / chars as f32
+        };
+        let coverage = lines
+            .iter()
+            .map(|(_, _, r)| (r.width * r.height) as f32)
+            .sum::<f32>()
+            .min(1.0);
+
+        Ok(OcrText {
+            text: lines
+                .into_iter()
+                .map(|(text, _, _)| text)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            confidence,
+            coverage,
+        })
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn recognize_text(_image: &[u8]) -> Result<OcrText> {
+    anyhow::bail!("Local OCR is only supported on macOS")
+}
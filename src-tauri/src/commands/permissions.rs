@@ -0,0 +1,145 @@
+use serde::Serialize;
+
+/// Grant state of an OS permission
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PermissionStatus {
+    Granted,
+    Denied,
+    /// The user hasn't been asked yet; `request_permissions` shows the prompt
+    NotDetermined,
+    /// Blocked by a device-management policy; the user can't change it
+    Restricted,
+    /// The feature needing it isn't available on this platform
+    Unsupported,
+}
+
+/// Permissions the app depends on
+/// Missing ones fail silently (empty transcriptions, black screenshots), so the
+/// onboarding checklist shows them up front
+#[derive(Debug, Clone, Serialize)]
+pub struct Permissions {
+    pub microphone: PermissionStatus,
+    pub screen_recording: PermissionStatus,
+}
+
+/// Current grant status of the microphone and screen-recording permissions
+#[tauri::command]
+pub fn check_permissions() -> Permissions {
+    Permissions {
+        microphone: microphone_status(),
+        screen_recording: screen_recording_status(),
+    }
+}
+
+/// Show the native prompts for permissions that haven't been asked for yet
+/// Once denied, macOS won't prompt again; the user has to allow the app in
+/// System Settings > Privacy & Security
+///
+/// # Returns
+/// Status after asking; prompts still on screen report `not-determined`
+#[tauri::command]
+pub async fn request_permissions() -> Result<Permissions, String> {
+    if microphone_status() == PermissionStatus::NotDetermined {
+        tauri::async_runtime::spawn_blocking(prompt_microphone)
+            .await
+            .map_err(|e| format!("Permission request failed: {}", e))?;
+    }
+    if screen_recording_status() != PermissionStatus::Granted {
+        request_screen_recording();
+    }
+
+    Ok(check_permissions())
+}
+
+#[cfg(target_os = "macos")]
+fn microphone_status() -> PermissionStatus {
+    use cocoa::base::id;
+    use objc::{class, msg_send, sel, sel_impl};
+
+    #[link(name = "AVFoundation", kind = "framework")]
+    extern "C" {
+        static AVMediaTypeAudio: id;
+    }
+
+    // AVAuthorizationStatus
+    let status: isize = unsafe {
+        msg_send![class!(AVCaptureDevice), authorizationStatusForMediaType: AVMediaTypeAudio]
+    };
+    match status {
+        0 => PermissionStatus::NotDetermined,
+        1 => PermissionStatus::Restricted,
+        2 => PermissionStatus::Denied,
+        _ => PermissionStatus::Granted,
+    }
+}
+
+/// Screen recording can only be checked as granted or not; macOS doesn't say
+/// whether the user was already asked
+#[cfg(target_os = "macos")]
+pub(crate) fn screen_recording_status() -> PermissionStatus {
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGPreflightScreenCaptureAccess() -> bool;
+    }
+
+    if unsafe { CGPreflightScreenCaptureAccess() } {
+        PermissionStatus::Granted
+    } else {
+        PermissionStatus::Denied
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn request_screen_recording() {
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGRequestScreenCaptureAccess() -> bool;
+    }
+
+    unsafe {
+        CGRequestScreenCaptureAccess();
+    }
+}
+
+/// Opening an input stream is what makes macOS show the microphone prompt
+#[cfg(target_os = "macos")]
+fn prompt_microphone() {
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+    let open = || -> anyhow::Result<()> {
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or_else(|| anyhow::anyhow!("No input device available"))?;
+        let config = device.default_input_config()?;
+        let stream = device.build_input_stream_raw(
+            &config.config(),
+            config.sample_format(),
+            |_: &cpal::Data, _: &cpal::InputCallbackInfo| {},
+            |err| eprintln!("Audio stream error: {}", err),
+            None,
+        )?;
+        stream.play()?;
+        Ok(())
+    };
+
+    if let Err(e) = open() {
+        eprintln!("Warning: Could not open microphone to request access: {}", e);
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn microphone_status() -> PermissionStatus {
+    PermissionStatus::Granted
+}
+
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn screen_recording_status() -> PermissionStatus {
+    PermissionStatus::Unsupported
+}
+
+#[cfg(not(target_os = "macos"))]
+fn request_screen_recording() {}
+
+#[cfg(not(target_os = "macos"))]
+fn prompt_microphone() {}
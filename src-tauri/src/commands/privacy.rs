@@ -0,0 +1,225 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+
+use crate::commands::ai::{ContentPart, Message, MessageContent};
+use crate::commands::ocr;
+
+/// Longest excerpt of a match shown in the warning
+const MAX_EXCERPT_CHARS: usize = 40;
+
+/// A kind of sensitive text to look for in screenshots
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SensitivePattern {
+    /// Shown in the warning, e.g. "API key"
+    pub name: String,
+    /// Regular expression matched against the screenshot's recognized text
+    pub pattern: String,
+}
+
+impl SensitivePattern {
+    fn new(name: &str, pattern: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+        }
+    }
+}
+
+/// Scan screenshots for passwords, card numbers and keys before they are sent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrivacyGuard {
+    pub enabled: bool,
+    pub patterns: Vec<SensitivePattern>,
+}
+
+impl Default for PrivacyGuard {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            patterns: vec![
+                SensitivePattern::new("Card number", r"\b(?:\d[ -]?){12,18}\d\b"),
+                SensitivePattern::new("Password", r"(?i)\bpass(?:word|wd|code)?\s*[:=]"),
+                SensitivePattern::new(
+                    "API key",
+                    r"\b(?:sk-[A-Za-z0-9_-]{20,}|AKIA[0-9A-Z]{16}|gh[pous]_[A-Za-z0-9]{36})\b",
+                ),
+                SensitivePattern::new("Private key", r"-----BEGIN [A-Z ]*PRIVATE KEY-----"),
+            ],
+        }
+    }
+}
+
+/// One pattern found in a screenshot
+#[derive(Debug, Clone, Serialize)]
+pub struct SensitiveMatch {
+    pub name: String,
+    /// The matched text with all but its ends masked
+    pub excerpt: String,
+}
+
+/// A screenshot looks like it contains sensitive text, or couldn't be checked, and
+/// was not sent
+/// Payload of the `sensitive-content-warning` event
+#[derive(Debug, Clone, Serialize)]
+pub struct SensitiveContent {
+    pub matches: Vec<SensitiveMatch>,
+    /// Images whose text couldn't be recognized (OCR failed or is unavailable on this
+    /// platform, or the image is a remote URL), so they may hide anything
+    pub unscanned: usize,
+}
+
+impl std::fmt::Display for SensitiveContent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.matches.is_empty() {
+            return write!(
+                f,
+                "{} image(s) could not be scanned for sensitive content; confirm to send anyway",
+                self.unscanned
+            );
+        }
+        let names: Vec<&str> = self.matches.iter().map(|m| m.name.as_str()).collect();
+        write!(
+            f,
+            "Screenshot may contain sensitive content ({}); confirm to send it anyway",
+            names.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for SensitiveContent {}
+
+/// Recognize the text of `images` and of the images in the latest user message, and
+/// fail with `SensitiveContent` (emitting `sensitive-content-warning`) if any pattern
+/// matches or an image can't be scanned
+/// Images earlier in the conversation went through the guard when they were sent
+pub(crate) async fn check_images(
+    app: &AppHandle,
+    guard: &PrivacyGuard,
+    messages: &[Message],
+    images: &[String],
+) -> Result<(), SensitiveContent> {
+    let patterns = compile(&guard.patterns);
+    if patterns.is_empty() {
+        return Ok(());
+    }
+
+    let (inline, mut unscanned) = message_images(messages);
+    let mut matches = Vec::new();
+    for image in images.iter().chain(&inline) {
+        match ocr::recognize_base64(image.clone()).await {
+            Ok(text) => matches.extend(scan(&text.text, &patterns)),
+            Err(e) => {
+                eprintln!("Warning: Could not scan image for sensitive content: {}", e);
+                unscanned += 1;
+            }
+        }
+    }
+    if matches.is_empty() && unscanned == 0 {
+        return Ok(());
+    }
+
+    let warning = SensitiveContent { matches, unscanned };
+    let _ = app.emit("sensitive-content-warning", &warning);
+    Err(warning)
+}
+
+/// Base64 data of the `data:` images in the latest user message, and how many of its
+/// images are remote URLs that can't be scanned
+fn message_images(messages: &[Message]) -> (Vec<String>, usize) {
+    let Some(MessageContent::Parts(parts)) =
+        messages.iter().rev().find(|m| m.role == "user").map(|m| &m.content)
+    else {
+        return (Vec::new(), 0);
+    };
+
+    let mut images = Vec::new();
+    let mut remote = 0;
+    for part in parts {
+        if let ContentPart::ImageUrl { image_url } = part {
+            match image_url.url.split_once(";base64,") {
+                Some((prefix, data)) if prefix.starts_with("data:") => {
+                    images.push(data.to_string())
+                }
+                _ => remote += 1,
+            }
+        }
+    }
+    (images, remote)
+}
+
+/// Compile the configured patterns, skipping (and reporting) invalid ones
+fn compile(patterns: &[SensitivePattern]) -> Vec<(&str, Regex)> {
+    patterns
+        .iter()
+        .filter_map(|p| match Regex::new(&p.pattern) {
+            Ok(regex) => Some((p.name.as_str(), regex)),
+            Err(e) => {
+                eprintln!("Warning: Ignoring invalid sensitive pattern {:?}: {}", p.name, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// First match of each pattern in `text`
+fn scan(text: &str, patterns: &[(&str, Regex)]) -> Vec<SensitiveMatch> {
+    patterns
+        .iter()
+        .filter_map(|(name, regex)| {
+            regex.find(text).map(|m| SensitiveMatch {
+                name: name.to_string(),
+                excerpt: mask(m.as_str()),
+            })
+        })
+        .collect()
+}
+
+/// Keep two characters at each end so the user can recognize the match without
+/// the warning itself exposing it
+fn mask(text: &str) -> String {
+    let chars: Vec<char> = text.trim().chars().take(MAX_EXCERPT_CHARS).collect();
+    if chars.len() <= 4 {
+        return "*".repeat(chars.len());
+    }
+    let hidden = chars.len() - 4;
+    let mut masked: String = chars[..2].iter().collect();
+    masked.push_str(&"*".repeat(hidden));
+    masked.extend(&chars[chars.len() - 2..]);
+    masked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::ai::ImageUrl;
+
+    fn image(url: &str) -> ContentPart {
+        ContentPart::ImageUrl {
+            image_url: ImageUrl {
+                url: url.to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn latest_user_message_images_are_collected() {
+        let user = |parts| Message {
+            role: String::from("user"),
+            content: MessageContent::Parts(parts),
+            pinned: false,
+        };
+        let messages = [
+            user(vec![image("data:image/png;base64,b2xk")]),
+            user(vec![
+                image("data:image/jpeg;base64,bmV3"),
+                image("https://example.com/chart.png"),
+            ]),
+        ];
+
+        let (images, remote) = message_images(&messages);
+        assert_eq!(images, ["bmV3"]);
+        assert_eq!(remote, 1);
+    }
+}
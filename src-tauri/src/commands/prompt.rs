@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::commands::ai::{ContentPart, Message, MessageContent};
+use crate::commands::memory;
+use crate::settings::{Settings, SettingsStore};
+
+/// A piece of the system prompt, or of the wrapping around the user message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PromptFragment {
+    /// Who the assistant is, from `system_prompt.persona`
+    Persona,
+    /// The system messages at the start of the conversation
+    Instructions,
+    /// "Always answer in ..." from `system_prompt.response_language`
+    Language,
+    /// Summary of archived turns carried over by conversation memory
+    Summary,
+    /// `message_prefix` / `message_suffix` around the latest user message
+    Wrappers,
+}
+
+impl PromptFragment {
+    /// System fragments in the order used when `system_prompt.order` leaves them out
+    const SYSTEM: [PromptFragment; 4] = [
+        PromptFragment::Persona,
+        PromptFragment::Instructions,
+        PromptFragment::Language,
+        PromptFragment::Summary,
+    ];
+}
+
+/// How the system prompt of every chat request is put together
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SystemPromptSettings {
+    pub persona: Option<String>,
+    /// Language replies should be written in, e.g. "German"
+    pub response_language: Option<String>,
+    /// Order of the system fragments; missing ones follow in the default order
+    pub order: Vec<PromptFragment>,
+    /// Join the fragments into one system message instead of one message each
+    pub merge: bool,
+}
+
+impl Default for SystemPromptSettings {
+    fn default() -> Self {
+        Self {
+            persona: None,
+            response_language: None,
+            order: PromptFragment::SYSTEM.to_vec(),
+            merge: true,
+        }
+    }
+}
+
+impl SystemPromptSettings {
+    /// `order` without duplicates or `Wrappers`, completed with the fragments it omits
+    fn resolved_order(&self) -> Vec<PromptFragment> {
+        let mut order: Vec<PromptFragment> = Vec::new();
+        for fragment in self.order.iter().chain(PromptFragment::SYSTEM.iter()) {
+            if *fragment != PromptFragment::Wrappers && !order.contains(fragment) {
+                order.push(*fragment);
+            }
+        }
+        order
+    }
+}
+
+/// System messages `messages` would be sent with under the current settings
+/// `disabled` drops fragments as `send_message` does
+#[tauri::command]
+pub fn compose_system_prompt(
+    settings: State<'_, SettingsStore>,
+    mut messages: Vec<Message>,
+    disabled: Option<Vec<PromptFragment>>,
+) -> Vec<Message> {
+    compose(&mut messages, &settings.get(), &disabled.unwrap_or_default());
+    messages.into_iter().take_while(|m| m.role == "system").collect()
+}
+
+/// Replace the leading system messages with the composed system prompt and wrap the
+/// latest user message
+/// Fragments in `disabled` are left out, and a fragment repeating an earlier one is
+/// dropped; system messages later in the conversation are not touched
+pub(crate) fn compose(
+    messages: &mut Vec<Message>,
+    settings: &Settings,
+    disabled: &[PromptFragment],
+) {
+    let enabled = |fragment: PromptFragment| !disabled.contains(&fragment);
+    let prompt = &settings.system_prompt;
+
+    let leading = messages.iter().take_while(|m| m.role == "system").count();
+    let (summaries, instructions): (Vec<Message>, Vec<Message>) =
+        messages.drain(..leading).partition(memory::is_summary);
+
+    let mut texts: Vec<String> = Vec::new();
+    for fragment in prompt.resolved_order().into_iter().filter(|f| enabled(*f)) {
+        let parts: Vec<String> = match fragment {
+            PromptFragment::Persona => prompt.persona.iter().cloned().collect(),
+            PromptFragment::Instructions => {
+                instructions.iter().map(|m| m.content.text()).collect()
+            }
+            PromptFragment::Language => prompt
+                .response_language
+                .iter()
+                .map(|language| format!("Always answer in {}.", language.trim()))
+                .collect(),
+            PromptFragment::Summary => summaries.iter().map(|m| m.content.text()).collect(),
+            PromptFragment::Wrappers => Vec::new(),
+        };
+        for text in parts.into_iter().map(|t| t.trim().to_string()) {
+            if !text.is_empty() && !texts.contains(&text) {
+                texts.push(text);
+            }
+        }
+    }
+
+    let system = |text: String| Message {
+        role: String::from("system"),
+        content: MessageContent::Text(text),
+        pinned: false,
+    };
+    let composed: Vec<Message> = if prompt.merge {
+        (!texts.is_empty()).then(|| system(texts.join("\n\n"))).into_iter().collect()
+    } else {
+        texts.into_iter().map(system).collect()
+    };
+    messages.splice(0..0, composed);
+
+    if enabled(PromptFragment::Wrappers) {
+        wrap_user_message(
+            messages,
+            settings.message_prefix.as_deref(),
+            settings.message_suffix.as_deref(),
+        );
+    }
+}
+
+/// Add the configured prefix and suffix to the text of the latest user message
+/// Only this request's copy is changed; the caller's history stays as typed
+/// In multimodal messages the prefix goes on the first text part and the suffix on the last
+fn wrap_user_message(messages: &mut [Message], prefix: Option<&str>, suffix: Option<&str>) {
+    let prefix = prefix.map(str::trim).filter(|p| !p.is_empty());
+    let suffix = suffix.map(str::trim).filter(|s| !s.is_empty());
+    if prefix.is_none() && suffix.is_none() {
+        return;
+    }
+    let Some(message) = messages.iter_mut().rev().find(|m| m.role == "user") else {
+        return;
+    };
+
+    let wrap = |text: &mut String, prefix: Option<&str>, suffix: Option<&str>| {
+        if let Some(prefix) = prefix {
+            *text = format!("{}\n\n{}", prefix, text);
+        }
+        if let Some(suffix) = suffix {
+            text.push_str("\n\n");
+            text.push_str(suffix);
+        }
+    };
+
+    match &mut message.content {
+        MessageContent::Text(text) => wrap(text, prefix, suffix),
+        MessageContent::Parts(parts) => {
+            let mut texts = parts.iter_mut().filter_map(|part| match part {
+                ContentPart::Text { text, .. } => Some(text),
+                _ => None,
+            });
+            match (texts.next(), texts.last()) {
+                (Some(first), Some(last)) => {
+                    wrap(first, prefix, None);
+                    wrap(last, None, suffix);
+                }
+                (Some(only), None) => wrap(only, prefix, suffix),
+                // Image- or audio-only message: the wrapper becomes its text
+                (None, _) => {
+                    let text = [prefix, suffix].into_iter().flatten().collect::<Vec<_>>();
+                    parts.insert(
+                        0,
+                        ContentPart::Text {
+                            text: text.join("\n\n"),
+                            cache_control: None,
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
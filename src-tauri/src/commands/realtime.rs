@@ -0,0 +1,497 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::commands::ai::Message;
+use crate::commands::audio::{self, AudioRecorder};
+use crate::config::ConfigState;
+use crate::settings::SettingsStore;
+
+/// How long a stopped session keeps reading for the transcripts of speech already sent
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Live transcription session state
+/// Only one session runs at a time; the flag tells the session task to stop
+#[derive(Default)]
+pub struct RealtimeSession {
+    active: Arc<AtomicBool>,
+    /// Set until the session task has ended, including its flush after a stop
+    #[cfg_attr(not(feature = "realtime"), allow(dead_code))]
+    running: Arc<AtomicBool>,
+    task: Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    /// Text dictated so far when the session types into the chat input
+    draft: Arc<Mutex<Option<InputDraft>>>,
+}
+
+/// Payload of `input-updated`: everything dictated so far, to put in place of the
+/// previous update rather than append to it
+#[derive(Debug, Clone, Serialize)]
+pub struct InputUpdate {
+    pub text: String,
+    /// End of `text` that is still interim and may change
+    pub interim: String,
+    /// The session has stopped and `text` won't change again
+    pub done: bool,
+}
+
+/// Utterances dictated in a voice-typing session, in the order they were first heard
+/// An utterance shows its partial text until its final transcript replaces it, so
+/// a finished segment is never shown twice
+#[derive(Debug, Default)]
+struct InputDraft {
+    utterances: Vec<Utterance>,
+}
+
+#[derive(Debug)]
+#[cfg_attr(not(feature = "realtime"), allow(dead_code))]
+struct Utterance {
+    item_id: String,
+    text: String,
+    done: bool,
+}
+
+impl InputDraft {
+    #[cfg(feature = "realtime")]
+    fn append_partial(&mut self, item_id: &str, delta: &str) {
+        match self.utterances.iter_mut().find(|u| u.item_id == item_id) {
+            Some(utterance) if !utterance.done => utterance.text.push_str(delta),
+            Some(_) => {}
+            None => self.utterances.push(Utterance {
+                item_id: item_id.to_string(),
+                text: delta.to_string(),
+                done: false,
+            }),
+        }
+    }
+
+    /// Replace the utterance's partial text with its final transcript
+    #[cfg(feature = "realtime")]
+    fn finalize(&mut self, item_id: &str, text: &str) {
+        match self.utterances.iter_mut().find(|u| u.item_id == item_id) {
+            Some(utterance) => {
+                utterance.text = text.to_string();
+                utterance.done = true;
+            }
+            None => self.utterances.push(Utterance {
+                item_id: item_id.to_string(),
+                text: text.to_string(),
+                done: true,
+            }),
+        }
+    }
+
+    fn update(&self, done: bool) -> InputUpdate {
+        InputUpdate {
+            text: joined(self.utterances.iter()),
+            interim: joined(self.utterances.iter().filter(|u| !u.done)),
+            done,
+        }
+    }
+}
+
+fn joined<'a>(utterances: impl Iterator<Item = &'a Utterance>) -> String {
+    utterances
+        .map(|u| u.text.trim())
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Payload of `transcript-partial` and `transcript-final` events
+#[cfg(feature = "realtime")]
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptEvent {
+    /// Identifies the utterance; partials and the final text share it
+    pub item_id: String,
+    pub text: String,
+}
+
+/// Start recording and stream the audio to the Realtime API for live captions
+///
+/// Emits `transcript-partial` with incremental text, `transcript-final` once an
+/// utterance is complete, `realtime-reconnecting` while recovering from a dropped
+/// connection and `realtime-error` if the session gives up
+/// With `voice_typing` the text is also emitted as `input-updated`, the full dictated
+/// text for the chat input with interim words replaced as utterances finalize
+///
+/// Requires the `realtime` feature; `process_audio` remains the default path
+#[tauri::command]
+pub async fn start_realtime_transcription(
+    app: AppHandle,
+    recorder: State<'_, AudioRecorder>,
+    config: State<'_, ConfigState>,
+    session: State<'_, RealtimeSession>,
+    voice_typing: Option<bool>,
+) -> Result<(), String> {
+    config.touch();
+
+    #[cfg(feature = "realtime")]
+    {
+        if session.running.swap(true, Ordering::SeqCst) {
+            return Err("Realtime transcription is already running".to_string());
+        }
+        session.active.store(true, Ordering::SeqCst);
+
+        let recorder = recorder.inner().clone();
+        if let Err(e) =
+            crate::commands::audio::start_microphone_recording(&recorder, None, Some(app.clone()))
+        {
+            session.active.store(false, Ordering::SeqCst);
+            session.running.store(false, Ordering::SeqCst);
+            return Err(format!("Failed to start recording: {}", e));
+        }
+        // Streamed audio is consumed from the buffer as it is sent
+        recorder.clear_buffer();
+        *session.draft.lock().unwrap() = voice_typing.unwrap_or(false).then(InputDraft::default);
+
+        let api_key = config.get().openai_api_key;
+        let active = Arc::clone(&session.active);
+        let running = Arc::clone(&session.running);
+        let draft = Arc::clone(&session.draft);
+        let task = tauri::async_runtime::spawn(async move {
+            live::run_session(app, recorder, api_key, active, draft).await;
+            running.store(false, Ordering::SeqCst);
+        });
+        *session.task.lock().unwrap() = Some(task);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "realtime"))]
+    {
+        let _ = (app, recorder, config, session, voice_typing);
+        Err("Realtime transcription is not enabled in this build".to_string())
+    }
+}
+
+/// Stop the live transcription session and the recording feeding it
+/// Returns once the transcripts of speech already sent have arrived, or after
+/// `FLUSH_TIMEOUT` with whatever is final by then
+/// A voice-typing session emits a last `input-updated` with `done` set; with auto-send
+/// enabled its text is then sent as a new user message after `messages`
+#[tauri::command]
+pub async fn stop_realtime_transcription(
+    app: AppHandle,
+    recorder: State<'_, AudioRecorder>,
+    session: State<'_, RealtimeSession>,
+    settings: State<'_, SettingsStore>,
+    messages: Option<Vec<Message>>,
+) -> Result<(), String> {
+    if session.active.swap(false, Ordering::SeqCst) {
+        recorder.set_recording(false);
+    }
+
+    let task = session.task.lock().unwrap().take();
+    if let Some(task) = task {
+        // The session bounds its own flush; this only guards against it hanging
+        let wait = FLUSH_TIMEOUT + Duration::from_secs(1);
+        if tokio::time::timeout(wait, task).await.is_err() {
+            eprintln!("Warning: Realtime session did not end in time, using its interim text");
+        }
+    }
+
+    let Some(draft) = session.draft.lock().unwrap().take() else {
+        return Ok(());
+    };
+    let update = draft.update(true);
+    app.emit("input-updated", &update)
+        .map_err(|e| format!("Failed to emit event: {}", e))?;
+
+    let auto_send = settings.get().auto_send;
+    if auto_send.enabled && !update.text.is_empty() {
+        let messages = messages.unwrap_or_default();
+        audio::spawn_auto_send(&app, messages, &update.text, auto_send.delay_ms)?;
+    }
+    Ok(())
+}
+
+/// Whether a live transcription session is running
+#[tauri::command]
+pub fn is_realtime_active(session: State<'_, RealtimeSession>) -> bool {
+    session.active.load(Ordering::SeqCst)
+}
+
+#[cfg(feature = "realtime")]
+mod live {
+    use anyhow::{Context, Result};
+    use base64::Engine;
+    use futures_util::{SinkExt, StreamExt};
+    use serde::Deserialize;
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use tauri::{AppHandle, Emitter};
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use tokio_tungstenite::tungstenite::http::HeaderValue;
+    use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+    use super::{InputDraft, TranscriptEvent, FLUSH_TIMEOUT};
+    use crate::commands::audio::{downmix_to_mono, resample_linear, AudioRecorder};
+
+    const REALTIME_URL: &str = "wss://api.openai.com/v1/realtime?intent=transcription";
+    const TRANSCRIPTION_MODEL: &str = "gpt-4o-transcribe";
+
+    /// The Realtime API takes 24kHz mono PCM16
+    const REALTIME_SAMPLE_RATE: u32 = 24_000;
+
+    /// How often captured audio is sent
+    const SEND_INTERVAL: Duration = Duration::from_millis(100);
+
+    const MAX_RECONNECTS: u32 = 5;
+
+    /// A connection that lasted this long resets the reconnect budget
+    const STABLE_CONNECTION: Duration = Duration::from_secs(30);
+
+    #[derive(Debug, Deserialize)]
+    struct ServerEvent {
+        #[serde(rename = "type")]
+        kind: String,
+        #[serde(default)]
+        item_id: String,
+        delta: Option<String>,
+        transcript: Option<String>,
+        error: Option<ServerError>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct ServerError {
+        message: String,
+    }
+
+    /// Utterances committed but not transcribed yet, and whether the commit sent on
+    /// stop has been answered; the session ends once both are settled
+    #[derive(Debug, Default)]
+    struct Pending {
+        items: HashSet<String>,
+        stopping: bool,
+        flushed: bool,
+    }
+
+    impl Pending {
+        fn settled(&self) -> bool {
+            self.flushed && self.items.is_empty()
+        }
+    }
+
+    /// Keep a session alive until stopped, reconnecting with backoff on failures
+    pub(super) async fn run_session(
+        app: AppHandle,
+        recorder: AudioRecorder,
+        api_key: String,
+        active: Arc<AtomicBool>,
+        draft: Arc<Mutex<Option<InputDraft>>>,
+    ) {
+        let mut attempt = 0;
+
+        while active.load(Ordering::SeqCst) {
+            let connected_at = Instant::now();
+            let connection = run_connection(&app, &recorder, &api_key, &active, &draft);
+            let error = match connection.await {
+                Ok(()) => break,
+                Err(e) => e,
+            };
+
+            if connected_at.elapsed() >= STABLE_CONNECTION {
+                attempt = 0;
+            }
+            attempt += 1;
+
+            if attempt > MAX_RECONNECTS {
+                eprintln!("Realtime transcription failed: {}", error);
+                let _ = app.emit("realtime-error", error.to_string());
+                break;
+            }
+
+            eprintln!("Realtime connection lost ({}), reconnecting", error);
+            let _ = app.emit("realtime-reconnecting", attempt);
+            tokio::time::sleep(Duration::from_millis(500 * 2u64.pow(attempt - 1))).await;
+        }
+
+        active.store(false, Ordering::SeqCst);
+        recorder.set_recording(false);
+    }
+
+    /// One WebSocket connection; returns `Ok` only when the session was stopped
+    async fn run_connection(
+        app: &AppHandle,
+        recorder: &AudioRecorder,
+        api_key: &str,
+        active: &AtomicBool,
+        draft: &Mutex<Option<InputDraft>>,
+    ) -> Result<()> {
+        let mut request = REALTIME_URL.into_client_request()?;
+        let headers = request.headers_mut();
+        headers.insert(
+            "Authorization",
+            HeaderValue::from_str(&format!("Bearer {}", crate::config::require_key(api_key)?))?,
+        );
+        headers.insert("OpenAI-Beta", HeaderValue::from_static("realtime=v1"));
+
+        let (socket, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .context("Failed to connect to the Realtime API")?;
+        let (mut write, mut read) = socket.split();
+
+        let session_update = serde_json::json!({
+            "type": "transcription_session.update",
+            "session": {
+                "input_audio_format": "pcm16",
+                "input_audio_transcription": { "model": TRANSCRIPTION_MODEL },
+                "turn_detection": { "type": "server_vad" },
+            },
+        });
+        write.send(WsMessage::Text(session_update.to_string())).await?;
+
+        let mut pending = Pending::default();
+        let mut ticker = tokio::time::interval(SEND_INTERVAL);
+        while active.load(Ordering::SeqCst) {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    if let Some(append) = take_append(recorder) {
+                        write.send(append).await?;
+                    }
+                }
+                message = read.next() => match message {
+                    Some(Ok(WsMessage::Text(text))) => {
+                        handle_event(app, &text, draft, &mut pending)
+                    }
+                    Some(Ok(WsMessage::Close(_))) | None => {
+                        anyhow::bail!("Connection closed by server");
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(e.into()),
+                },
+            }
+        }
+
+        // Stopped: send what was captured since the last tick and commit it, so the
+        // last utterance is transcribed too, then wait for the final transcripts
+        pending.stopping = true;
+        if let Some(append) = take_append(recorder) {
+            let _ = write.send(append).await;
+        }
+        let commit = serde_json::json!({ "type": "input_audio_buffer.commit" });
+        if write.send(WsMessage::Text(commit.to_string())).await.is_err() {
+            pending.flushed = true;
+        }
+        let deadline = tokio::time::sleep(FLUSH_TIMEOUT);
+        tokio::pin!(deadline);
+        while !pending.settled() {
+            tokio::select! {
+                _ = &mut deadline => {
+                    eprintln!("Warning: Final realtime transcripts didn't arrive in time");
+                    break;
+                }
+                message = read.next() => match message {
+                    Some(Ok(WsMessage::Text(text))) => {
+                        handle_event(app, &text, draft, &mut pending)
+                    }
+                    Some(Ok(_)) => {}
+                    _ => break,
+                },
+            }
+        }
+
+        let _ = write.send(WsMessage::Close(None)).await;
+        Ok(())
+    }
+
+    /// An `input_audio_buffer.append` with the audio captured since the last one, if any
+    fn take_append(recorder: &AudioRecorder) -> Option<WsMessage> {
+        let samples = recorder.take_audio();
+        if samples.is_empty() {
+            return None;
+        }
+        let pcm = encode_pcm16(&samples, recorder.sample_rate(), recorder.channels());
+        let append = serde_json::json!({
+            "type": "input_audio_buffer.append",
+            "audio": base64::engine::general_purpose::STANDARD.encode(pcm),
+        });
+        Some(WsMessage::Text(append.to_string()))
+    }
+
+    fn handle_event(
+        app: &AppHandle,
+        text: &str,
+        draft: &Mutex<Option<InputDraft>>,
+        pending: &mut Pending,
+    ) {
+        let event: ServerEvent = match serde_json::from_str(text) {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("Warning: Unparseable realtime event: {}", e);
+                return;
+            }
+        };
+
+        match event.kind.as_str() {
+            "input_audio_buffer.committed" => {
+                pending.items.insert(event.item_id);
+                pending.flushed |= pending.stopping;
+            }
+            "conversation.item.input_audio_transcription.delta" => {
+                if let Some(delta) = event.delta {
+                    if let Some(draft) = draft.lock().unwrap().as_mut() {
+                        draft.append_partial(&event.item_id, &delta);
+                        let _ = app.emit("input-updated", draft.update(false));
+                    }
+                    let _ = app.emit(
+                        "transcript-partial",
+                        TranscriptEvent {
+                            item_id: event.item_id,
+                            text: delta,
+                        },
+                    );
+                }
+            }
+            "conversation.item.input_audio_transcription.completed" => {
+                pending.items.remove(&event.item_id);
+                let text = event.transcript.unwrap_or_default();
+                if let Some(draft) = draft.lock().unwrap().as_mut() {
+                    draft.finalize(&event.item_id, text.trim());
+                    let _ = app.emit("input-updated", draft.update(false));
+                }
+                let _ = app.emit(
+                    "transcript-final",
+                    TranscriptEvent {
+                        item_id: event.item_id,
+                        text: text.trim().to_string(),
+                    },
+                );
+            }
+            // The commit sent on stop fails when nothing was said since the last one
+            "error" if pending.stopping && !pending.flushed => {
+                pending.flushed = true;
+                let message = event.error.map(|e| e.message).unwrap_or_default();
+                println!("Nothing left to transcribe at stop ({})", message);
+            }
+            // Errors about a single utterance don't end the session
+            "error" | "conversation.item.input_audio_transcription.failed" => {
+                pending.items.remove(&event.item_id);
+                let message = event
+                    .error
+                    .map(|e| e.message)
+                    .unwrap_or_else(|| String::from("Unknown realtime error"));
+                eprintln!("Realtime API error: {}", message);
+                let _ = app.emit("realtime-error", message);
+            }
+            _ => {}
+        }
+    }
+
+    /// Convert captured audio to 24kHz mono little-endian PCM16
+    fn encode_pcm16(samples: &[f32], sample_rate: u32, channels: u16) -> Vec<u8> {
+        let mono = downmix_to_mono(samples, channels);
+        let resampled = resample_linear(&mono, sample_rate, REALTIME_SAMPLE_RATE);
+
+        resampled
+            .iter()
+            .flat_map(|&s| {
+                let s = if s.is_finite() { s.clamp(-1.0, 1.0) } else { 0.0 };
+                ((s * i16::MAX as f32) as i16).to_le_bytes()
+            })
+            .collect()
+    }
+}
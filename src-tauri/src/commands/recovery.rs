@@ -0,0 +1,213 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, State};
+
+use crate::commands::ai::{Message, MessageContent, Usage};
+
+/// Write immediately once this many messages were added since the last save
+const AUTOSAVE_EVERY_MESSAGES: usize = 4;
+
+/// Background flush interval for changes below the message threshold
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Minimum time between writes of a reply that is still streaming
+const STREAM_SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RecoveryFile {
+    /// Seconds since the UNIX epoch
+    saved_at: u64,
+    messages: Vec<Message>,
+    /// The last message is a reply that was cut off mid-stream
+    #[serde(default)]
+    partial: bool,
+    /// Token usage of the last reply, once its stream has finished
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    usage: Option<Usage>,
+}
+
+/// What a recovery file contains, shown before offering to restore it
+#[derive(Debug, Serialize)]
+pub struct RecoveryInfo {
+    pub saved_at: u64,
+    pub message_count: usize,
+    /// The last message is an incomplete reply
+    pub partial: bool,
+}
+
+/// Crash-recovery copy of the active conversation
+pub struct RecoveryStore {
+    path: PathBuf,
+    state: Mutex<AutosaveState>,
+}
+
+#[derive(Default)]
+struct AutosaveState {
+    /// Latest conversation not yet written to disk
+    pending: Option<Vec<Message>>,
+    saved_len: usize,
+    /// Last write of a streaming reply, for throttling
+    last_stream_save: Option<Instant>,
+}
+
+impl RecoveryStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            state: Mutex::new(AutosaveState::default()),
+        }
+    }
+
+    /// Record the latest conversation, writing it if enough has changed
+    pub fn update(&self, messages: Vec<Message>) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let changed = messages.len().abs_diff(state.saved_len);
+        state.pending = Some(messages);
+
+        if changed >= AUTOSAVE_EVERY_MESSAGES {
+            self.write(&mut state)?;
+        }
+        Ok(())
+    }
+
+    /// Write any pending changes
+    pub fn flush(&self) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        self.write(&mut state)
+    }
+
+    /// Save `messages` followed by the reply streamed so far, at most once per
+    /// `STREAM_SAVE_INTERVAL`, so a crash mid-stream keeps the partial answer
+    pub fn update_stream(&self, messages: &[Message], reply: &str) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.last_stream_save.is_some_and(|t| t.elapsed() < STREAM_SAVE_INTERVAL) {
+            return Ok(());
+        }
+        state.last_stream_save = Some(Instant::now());
+        self.write_file(&mut state, with_reply(messages, reply), true, None)
+    }
+
+    /// Save the finished reply with its usage, ending the streaming writes
+    pub fn finish_stream(
+        &self,
+        messages: &[Message],
+        reply: &str,
+        usage: Option<Usage>,
+    ) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.last_stream_save = None;
+        self.write_file(&mut state, with_reply(messages, reply), false, usage)
+    }
+
+    fn write(&self, state: &mut AutosaveState) -> Result<()> {
+        let Some(messages) = state.pending.take() else {
+            return Ok(());
+        };
+        self.write_file(state, messages, false, None)
+    }
+
+    fn write_file(
+        &self,
+        state: &mut AutosaveState,
+        messages: Vec<Message>,
+        partial: bool,
+        usage: Option<Usage>,
+    ) -> Result<()> {
+        let saved_len = messages.len();
+        let file = RecoveryFile {
+            saved_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            messages,
+            partial,
+            usage,
+        };
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).context("Failed to create data directory")?;
+        }
+        let json = serde_json::to_string(&file)?;
+        std::fs::write(&self.path, json).context("Failed to write recovery file")?;
+
+        // Whatever was pending is older than what was just written
+        state.pending = None;
+        state.saved_len = saved_len;
+        Ok(())
+    }
+
+    fn read(&self) -> Option<RecoveryFile> {
+        let json = std::fs::read_to_string(&self.path).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    /// Remove the recovery file (clean exit or user declined to restore)
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.pending = None;
+        state.saved_len = 0;
+        state.last_stream_save = None;
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Report the in-progress conversation to autosave
+/// Called by the frontend whenever the conversation changes
+#[tauri::command]
+pub fn autosave_conversation(
+    recovery: State<'_, RecoveryStore>,
+    messages: Vec<Message>,
+) -> Result<(), String> {
+    recovery
+        .update(messages)
+        .map_err(|e| format!("Autosave failed: {}", e))
+}
+
+/// Check for a conversation left behind by a crash
+#[tauri::command]
+pub fn check_recovery(recovery: State<'_, RecoveryStore>) -> Option<RecoveryInfo> {
+    recovery.read().map(|file| RecoveryInfo {
+        saved_at: file.saved_at,
+        message_count: file.messages.len(),
+        partial: file.partial,
+    })
+}
+
+/// Load the recovered conversation
+#[tauri::command]
+pub fn restore_recovery(recovery: State<'_, RecoveryStore>) -> Result<Vec<Message>, String> {
+    recovery
+        .read()
+        .map(|file| file.messages)
+        .ok_or_else(|| "No recoverable conversation found".to_string())
+}
+
+/// Discard the recovery file
+#[tauri::command]
+pub fn discard_recovery(recovery: State<'_, RecoveryStore>) {
+    recovery.clear();
+}
+
+/// `messages` with `reply` appended as the assistant's answer
+fn with_reply(messages: &[Message], reply: &str) -> Vec<Message> {
+    let mut messages = messages.to_vec();
+    messages.push(Message {
+        role: String::from("assistant"),
+        content: MessageContent::Text(reply.to_string()),
+        pinned: false,
+    });
+    messages
+}
+
+/// Periodically flush autosave changes below the message threshold
+pub fn spawn_autosave(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(AUTOSAVE_INTERVAL);
+        if let Err(e) = app.state::<RecoveryStore>().flush() {
+            eprintln!("Autosave failed: {}", e);
+        }
+    });
+}
@@ -0,0 +1,232 @@
+use anyhow::Result;
+use futures_util::future::{AbortHandle, Abortable};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::commands::history;
+use crate::settings::SettingsStore;
+
+/// Returned when a task was aborted through `cancel_request` / `cancel_task`
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Request cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// What a background task is doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TaskKind {
+    Chat,
+    Stream,
+    Transcription,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TaskStatus {
+    Running,
+    /// Aborted, but the future hasn't been dropped yet
+    Cancelling,
+}
+
+/// Entry returned by `list_tasks`
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskInfo {
+    pub id: String,
+    pub kind: TaskKind,
+    pub status: TaskStatus,
+    /// Unix time in milliseconds
+    pub started_at: u64,
+}
+
+struct Task {
+    kind: TaskKind,
+    status: TaskStatus,
+    started_at: u64,
+    handle: AbortHandle,
+}
+
+/// Long-running operations that can be listed and cancelled, keyed by task id
+/// Chat requests use the `request_id` the frontend passed in as their id
+#[derive(Default)]
+pub struct InFlightRequests(Mutex<HashMap<String, Task>>);
+
+impl InFlightRequests {
+    /// Run `future`, registering it under `request_id` so it can be cancelled
+    /// Tasks without an id get a generated one, so they still show up in `list_tasks`
+    /// An id that is already running is refused, so the earlier task stays cancellable
+    pub(crate) async fn run<T>(
+        &self,
+        request_id: Option<String>,
+        kind: TaskKind,
+        future: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let id = request_id.unwrap_or_else(history::new_id);
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        let (handle, registration) = AbortHandle::new_pair();
+        let task = Task {
+            kind,
+            status: TaskStatus::Running,
+            started_at,
+            handle,
+        };
+        {
+            let mut tasks = self.0.lock().unwrap();
+            if tasks.contains_key(&id) {
+                anyhow::bail!("A request with id {} is already running", id);
+            }
+            tasks.insert(id.clone(), task);
+        }
+
+        let result = Abortable::new(future, registration).await;
+        self.0.lock().unwrap().remove(&id);
+
+        result.unwrap_or_else(|_| Err(Cancelled.into()))
+    }
+
+    /// Like `run`, but cancellation yields `Ok(None)` instead of `Cancelled`
+    /// `future` is dropped at its next await point, so anything it wrote to
+    /// borrowed state before then (e.g. partial streamed text) is kept
+    pub(crate) async fn run_until_cancelled<T>(
+        &self,
+        request_id: Option<String>,
+        kind: TaskKind,
+        future: impl Future<Output = Result<T>>,
+    ) -> Result<Option<T>> {
+        match self.run(request_id, kind, future).await {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.is::<Cancelled>() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Abort a running task; returns false if it already finished or was cancelled
+    pub fn cancel(&self, id: &str) -> bool {
+        let mut tasks = self.0.lock().unwrap();
+        match tasks.get_mut(id) {
+            Some(task) if task.status == TaskStatus::Running => {
+                task.handle.abort();
+                task.status = TaskStatus::Cancelling;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Abort every running task, e.g. before the app exits
+    pub fn cancel_all(&self) {
+        for task in self.0.lock().unwrap().values_mut() {
+            if task.status == TaskStatus::Running {
+                task.handle.abort();
+                task.status = TaskStatus::Cancelling;
+            }
+        }
+    }
+
+    /// Snapshot of registered tasks, oldest first
+    pub fn list(&self) -> Vec<TaskInfo> {
+        let mut tasks: Vec<TaskInfo> = self
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, task)| TaskInfo {
+                id: id.clone(),
+                kind: task.kind,
+                status: task.status,
+                started_at: task.started_at,
+            })
+            .collect();
+        tasks.sort_by_key(|t| t.started_at);
+        tasks
+    }
+}
+
+/// Cancel an in-flight `send_message` / `send_message_stream` by its `request_id`
+///
+/// # Returns
+/// Whether a running request was found
+#[tauri::command]
+pub fn cancel_request(requests: State<'_, InFlightRequests>, request_id: String) -> bool {
+    requests.cancel(&request_id)
+}
+
+/// Running background tasks (chat requests, streams and transcriptions)
+#[tauri::command]
+pub fn list_tasks(requests: State<'_, InFlightRequests>) -> Vec<TaskInfo> {
+    requests.list()
+}
+
+/// Cancel any task returned by `list_tasks`
+/// The task fails with a cancellation error, except streams, which return the partial reply
+///
+/// # Returns
+/// Whether a running task was found
+#[tauri::command]
+pub fn cancel_task(requests: State<'_, InFlightRequests>, id: String) -> bool {
+    requests.cancel(&id)
+}
+
+/// Payload of the `response-slow` event
+#[derive(Debug, Clone, Serialize)]
+pub struct SlowResponse {
+    pub request_id: Option<String>,
+    pub elapsed_ms: u64,
+}
+
+/// Emits `response-slow` if not finished within the configured threshold
+/// Finished explicitly (e.g. on the first streamed token) or when dropped
+pub(crate) struct SlowResponseWatch {
+    done: Arc<AtomicBool>,
+}
+
+impl SlowResponseWatch {
+    /// Start watching; does nothing without an app handle or with the warning disabled
+    pub fn start(app: Option<&AppHandle>, request_id: Option<String>) -> Self {
+        let done = Arc::new(AtomicBool::new(false));
+
+        let threshold = app.and_then(|app| app.state::<SettingsStore>().get().slow_response_ms);
+        if let (Some(app), Some(threshold)) = (app.cloned(), threshold) {
+            let done = Arc::clone(&done);
+            tauri::async_runtime::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(threshold)).await;
+                if !done.load(Ordering::Relaxed) {
+                    let _ = app.emit(
+                        "response-slow",
+                        SlowResponse {
+                            request_id,
+                            elapsed_ms: threshold,
+                        },
+                    );
+                }
+            });
+        }
+
+        Self { done }
+    }
+
+    pub fn finish(&self) {
+        self.done.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for SlowResponseWatch {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
@@ -0,0 +1,146 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Duration;
+
+use crate::commands::ai::ApiError;
+
+/// Longest `Retry-After` honored; a longer wait fails instead of hanging the request
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// How transient failures (429, 5xx, timeouts, dropped connections) are retried
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 disables retrying
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each later one
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay_ms: 500,
+            max_delay_ms: 8_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff delay before retry number `retry` (0-based)
+    fn backoff(&self, retry: u32) -> Duration {
+        let delay = self.base_delay_ms.saturating_mul(1 << retry.min(16));
+        Duration::from_millis(delay.min(self.max_delay_ms))
+    }
+}
+
+/// Whether `error` is worth retrying unchanged
+/// Billing errors are `QuotaExceeded`, not `ApiError`, so they never retry
+pub(crate) fn is_retryable(error: &anyhow::Error) -> bool {
+    if let Some(api) = error.downcast_ref::<ApiError>() {
+        return api.status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            || api.status.is_server_error();
+    }
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .any(|e| e.is_timeout() || e.is_connect())
+}
+
+/// Run `attempt` until it succeeds, fails with a non-retryable error or runs out of retries
+/// Waits for the server's `Retry-After` when given, otherwise backs off exponentially;
+/// `on_retry` is told the retry number, the delay and the error before each wait
+pub(crate) async fn with_retry<T, Fut>(
+    policy: &RetryPolicy,
+    mut on_retry: impl FnMut(u32, Duration, &anyhow::Error),
+    mut attempt: impl FnMut() -> Fut,
+) -> Result<T>
+where
+    Fut: Future<Output = Result<T>>,
+{
+    let mut retry = 0;
+    loop {
+        let error = match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+        if retry >= policy.max_retries || !is_retryable(&error) {
+            return Err(error);
+        }
+
+        let retry_after = error.downcast_ref::<ApiError>().and_then(|e| e.retry_after);
+        if retry_after.is_some_and(|d| d > MAX_RETRY_AFTER) {
+            return Err(error);
+        }
+        let delay = retry_after.unwrap_or_else(|| policy.backoff(retry));
+
+        retry += 1;
+        on_retry(retry, delay, &error);
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::ai::{http_client, response_error};
+    use crate::commands::test_server::MockServer;
+
+    const FAST: RetryPolicy = RetryPolicy {
+        max_retries: 2,
+        base_delay_ms: 1,
+        max_delay_ms: 10,
+    };
+
+    /// GET `url`, failing with the API error for non-success statuses
+    async fn fetch(url: &str) -> Result<String> {
+        let response = http_client().get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(response_error(response).await);
+        }
+        Ok(response.text().await?)
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried_until_success() {
+        let server = MockServer::start(vec![
+            (503, r#"{"error": {"message": "overloaded"}}"#),
+            (429, r#"{"error": {"message": "slow down"}}"#),
+            (200, r#"{"text": "done"}"#),
+        ]);
+        let mut retries = Vec::new();
+        let on_retry = |attempt, _: Duration, _: &anyhow::Error| retries.push(attempt);
+
+        let body = with_retry(&FAST, on_retry, || fetch(&server.url)).await.unwrap();
+        assert_eq!(body, r#"{"text": "done"}"#);
+        assert_eq!(retries, [1, 2]);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn client_errors_fail_without_retrying() {
+        let server = MockServer::start(vec![(400, r#"{"error": {"message": "bad audio"}}"#)]);
+
+        let result = with_retry(&FAST, |_, _, _| {}, || fetch(&server.url)).await;
+        assert!(result.is_err());
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let server = MockServer::start(vec![
+            (500, r#"{"error": {"message": "down"}}"#),
+            (500, r#"{"error": {"message": "down"}}"#),
+            (500, r#"{"error": {"message": "down"}}"#),
+            (200, r#"{"text": "too late"}"#),
+        ]);
+
+        let result = with_retry(&FAST, |_, _, _| {}, || fetch(&server.url)).await;
+        let error = result.unwrap_err();
+        assert!(error.downcast_ref::<ApiError>().is_some_and(|e| e.status.is_server_error()));
+        assert_eq!(server.requests().len(), 3);
+    }
+}
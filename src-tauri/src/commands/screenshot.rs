@@ -1,51 +1,586 @@
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::State;
+
+use crate::commands::context::{frontmost_app, CaptureContext};
+use crate::error::GhostError;
+use crate::settings::SettingsStore;
+
+/// Most recent capture, kept as raw PNG bytes so it can be saved later
+#[derive(Default)]
+pub struct LastScreenshot(Mutex<Option<Vec<u8>>>);
+
+/// Output format for `save_last_screenshot`
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFileFormat {
+    Png,
+    Jpeg,
+}
+
+/// What `capture_screenshot` captures
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureMode {
+    /// The whole main display
+    #[default]
+    Full,
+    /// A rectangle dragged out with the mouse (macOS only)
+    Region,
+    /// A window clicked on (macOS only)
+    Window,
+}
+
+/// Why saving a screenshot or generated image failed
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", content = "message")]
+pub enum SaveScreenshotError {
+    NoScreenshot,
+    NoGeneratedImage,
+    PermissionDenied(String),
+    Io(String),
+    Encode(String),
+}
+
+impl std::fmt::Display for SaveScreenshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoScreenshot => write!(f, "No screenshot has been captured yet"),
+            Self::NoGeneratedImage => write!(f, "No image has been generated yet"),
+            Self::PermissionDenied(path) => write!(f, "Permission denied writing {}", path),
+            Self::Io(message) => write!(f, "Failed to write image: {}", message),
+            Self::Encode(message) => write!(f, "Failed to encode image: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SaveScreenshotError {}
+
+/// The display being captured was removed or reconfigured mid-capture
+/// The image would be of the wrong screen (or empty), so the user should pick again
+#[derive(Debug)]
+pub struct DisplayChanged {
+    pub reason: String,
+}
+
+impl std::fmt::Display for DisplayChanged {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Display configuration changed ({}); select a display and try again",
+            self.reason
+        )
+    }
+}
+
+impl std::error::Error for DisplayChanged {}
+
+/// The screen is locked or the display asleep, so a capture would come back black
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub enum DisplayUnavailable {
+    Locked,
+    Asleep,
+}
+
+impl std::fmt::Display for DisplayUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Locked => write!(f, "The screen is locked; unlock it to take a screenshot"),
+            Self::Asleep => write!(f, "The display is asleep; wake it to take a screenshot"),
+        }
+    }
+}
+
+impl std::error::Error for DisplayUnavailable {}
 
 /// Capture screenshot of the main display and return base64-encoded PNG
 /// Returns base64 string suitable for OpenAI Vision API
+/// `mode` picks a region or window interactively instead; dismissing the selection
+/// fails with `GhostError::ScreenshotCancelled`
+/// With `include_app_context` enabled the frontmost app is recorded alongside it
 #[tauri::command]
-pub async fn capture_screenshot() -> Result<String, String> {
-    capture_screenshot_impl()
+pub async fn capture_screenshot(
+    last: State<'_, LastScreenshot>,
+    settings: State<'_, SettingsStore>,
+    capture_context: State<'_, CaptureContext>,
+    mode: Option<CaptureMode>,
+) -> Result<String, GhostError> {
+    // Look before capturing, while the app being asked about is still in front
+    let context = settings
+        .get()
+        .include_app_context
+        .then(frontmost_app)
+        .flatten();
+    capture_context.set(context);
+
+    let png_data = capture_screenshot_impl(mode.unwrap_or_default())
         .await
-        .map_err(|e| format!("Screenshot failed: {}", e))
+        .map_err(|e| {
+            let message = format!("Screenshot failed: {}", e);
+            GhostError::classify(&e, message, GhostError::ScreenshotFailed)
+        })?;
+
+    let base64_data = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &png_data);
+    *last.0.lock().unwrap() = Some(png_data);
+
+    Ok(base64_data)
+}
+
+/// Save the most recent capture to `path` as PNG or JPEG
+/// The extension matching `format` is added if missing
+///
+/// # Returns
+/// The path actually written
+#[tauri::command]
+pub fn save_last_screenshot(
+    last: State<'_, LastScreenshot>,
+    path: PathBuf,
+    format: Option<ImageFileFormat>,
+) -> Result<PathBuf, SaveScreenshotError> {
+    let png_data = last
+        .0
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or(SaveScreenshotError::NoScreenshot)?;
+
+    save_image_file(&png_data, "image/png", path, format)
+}
+
+/// Write an image to `path` as PNG or JPEG, fixing up the extension
+/// `mime` is the format of `data`; it's written unchanged when that's the format asked
+/// for (or `format` is unset and it's PNG or JPEG) and re-encoded otherwise
+pub(crate) fn save_image_file(
+    data: &[u8],
+    mime: &str,
+    path: PathBuf,
+    format: Option<ImageFileFormat>,
+) -> Result<PathBuf, SaveScreenshotError> {
+    let format = format.unwrap_or(match mime {
+        "image/jpeg" => ImageFileFormat::Jpeg,
+        _ => ImageFileFormat::Png,
+    });
+    let extensions: &[&str] = match format {
+        ImageFileFormat::Png => &["png"],
+        ImageFileFormat::Jpeg => &["jpg", "jpeg"],
+    };
+
+    let mut path = path;
+    let has_extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| extensions.iter().any(|ext| e.eq_ignore_ascii_case(ext)));
+    if !has_extension {
+        path.set_extension(extensions[0]);
+    }
+
+    let bytes = match (format, mime) {
+        (ImageFileFormat::Png, "image/png") | (ImageFileFormat::Jpeg, "image/jpeg") => {
+            data.to_vec()
+        }
+        (ImageFileFormat::Png, _) => {
+            let image = image::load_from_memory(data)
+                .map_err(|e| SaveScreenshotError::Encode(e.to_string()))?;
+            let mut bytes = Vec::new();
+            image
+                .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageFormat::Png)
+                .map_err(|e| SaveScreenshotError::Encode(e.to_string()))?;
+            bytes
+        }
+        (ImageFileFormat::Jpeg, _) => {
+            let image = image::load_from_memory(data)
+                .map_err(|e| SaveScreenshotError::Encode(e.to_string()))?;
+            let mut bytes = Vec::new();
+            image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, 90)
+                .encode_image(&image.to_rgb8())
+                .map_err(|e| SaveScreenshotError::Encode(e.to_string()))?;
+            bytes
+        }
+    };
+
+    std::fs::write(&path, bytes).map_err(|e| match e.kind() {
+        std::io::ErrorKind::PermissionDenied => {
+            SaveScreenshotError::PermissionDenied(path.display().to_string())
+        }
+        _ => SaveScreenshotError::Io(e.to_string()),
+    })?;
+
+    Ok(path)
 }
 
 #[cfg(target_os = "macos")]
-async fn capture_screenshot_impl() -> Result<String> {
+pub(crate) async fn capture_screenshot_impl(mode: CaptureMode) -> Result<Vec<u8>> {
     use std::process::Command;
 
+    // Capturing a locked or sleeping screen "succeeds" with a black image
+    ensure_display_available()?;
+
+    // Re-checked after capturing, so a monitor unplugged or a resolution
+    // change mid-capture never yields the wrong or a garbled image
+    let display = DisplaySnapshot::main()?;
+
     // Use native macOS screencapture utility for reliability
     // This avoids complex CoreGraphics API and permission issues
+    // The guard removes the temp file on every exit path, including failures
     let temp_dir = std::env::temp_dir();
-    let timestamp = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-    let temp_path = temp_dir.join(format!("phantom_screenshot_{}.png", timestamp));
+    let temp_file = match TempFile::create(temp_dir.join(temp_screenshot_name())) {
+        Ok(temp_file) => temp_file,
+        // screencapture can only write to a file; capture in memory instead
+        // (the whole display only, there is no in-memory selection)
+        Err(write_error) if mode == CaptureMode::Full => {
+            let png_data = capture_in_memory().map_err(|e| {
+                anyhow::anyhow!(
+                    "Temp directory {} is not writable ({}) and in-memory capture failed: {}",
+                    temp_dir.display(),
+                    write_error,
+                    e
+                )
+            })?;
+            display.revalidate()?;
+            return Ok(png_data);
+        }
+        Err(e) => {
+            return Err(e).with_context(|| {
+                format!("Temp directory {} is not writable", temp_dir.display())
+            })
+        }
+    };
 
     // Capture screenshot using macOS screencapture command
+    let mode_args: &[&str] = match mode {
+        CaptureMode::Full => &["-m"], // Main display only
+        CaptureMode::Region => &["-i"], // Drag out a rectangle
+        CaptureMode::Window => &["-i", "-w"], // Click a window
+    };
     let output = Command::new("screencapture")
         .arg("-x") // Don't play sound
-        .arg("-m") // Main display only
-        .arg(&temp_path)
+        .args(mode_args)
+        .arg(&temp_file.0)
         .output()
         .context("Failed to execute screencapture command")?;
 
+    // Escape during a selection leaves the file as created, empty
+    let selected = std::fs::metadata(&temp_file.0).is_ok_and(|m| m.len() > 0);
+    if mode != CaptureMode::Full && !selected {
+        return Err(GhostError::ScreenshotCancelled(String::from("Screenshot cancelled")).into());
+    }
+
     if !output.status.success() {
+        display.revalidate()?;
         anyhow::bail!("screencapture command failed: {:?}", output.stderr);
     }
+    display.revalidate()?;
 
     // Read the PNG file
-    let png_data = std::fs::read(&temp_path)
+    let png_data = std::fs::read(&temp_file.0)
         .context("Failed to read screenshot file")?;
+    if png_data.is_empty() {
+        return Err(DisplayChanged {
+            reason: String::from("capture was empty"),
+        }
+        .into());
+    }
+
+    Ok(png_data)
+}
+
+/// Fail with `DisplayUnavailable` while the login session's screen is locked or the
+/// main display is asleep
+#[cfg(target_os = "macos")]
+fn ensure_display_available() -> Result<(), DisplayUnavailable> {
+    use cocoa::base::{id, nil};
+    use cocoa::foundation::NSString;
+    use core_graphics::display::CGDisplay;
+    use objc::runtime::{BOOL, YES};
+    use objc::{msg_send, sel, sel_impl};
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGSessionCopyCurrentDictionary() -> id;
+    }
+
+    // The session dictionary is toll-free bridged to NSDictionary
+    let locked = unsafe {
+        let session = CGSessionCopyCurrentDictionary();
+        if session == nil {
+            false
+        } else {
+            let key = NSString::alloc(nil).init_str("CGSSessionScreenIsLocked");
+            let value: id = msg_send![session, objectForKey: key];
+            let locked = value != nil && {
+                let flag: BOOL = msg_send![value, boolValue];
+                flag == YES
+            };
+            let _: () = msg_send![key, release];
+            let _: () = msg_send![session, release];
+            locked
+        }
+    };
+    if locked {
+        return Err(DisplayUnavailable::Locked);
+    }
+
+    let display = CGDisplay::main();
+    if display.is_asleep() || !display.is_active() {
+        return Err(DisplayUnavailable::Asleep);
+    }
+    Ok(())
+}
+
+/// Capture the main display through CoreGraphics and encode it as PNG, without
+/// touching the filesystem
+#[cfg(target_os = "macos")]
+fn capture_in_memory() -> Result<Vec<u8>> {
+    use core_graphics::display::CGDisplay;
+
+    let image = CGDisplay::main()
+        .image()
+        .context("Display could not be captured (is screen recording allowed?)")?;
+    if image.bits_per_pixel() != 32 || image.bits_per_component() != 8 {
+        anyhow::bail!("Unsupported capture format ({} bits per pixel)", image.bits_per_pixel());
+    }
+
+    let (width, height) = (image.width(), image.height());
+    let stride = image.bytes_per_row();
+    let data = image.data();
+    let bytes = data.bytes();
+
+    // Display images are BGRA with rows padded to `stride`
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for row in bytes.chunks(stride).take(height) {
+        for pixel in row[..width * 4].chunks_exact(4) {
+            rgba.extend_from_slice(&[pixel[2], pixel[1], pixel[0], 255]);
+        }
+    }
+
+    let buffer = image::RgbaImage::from_raw(width as u32, height as u32, rgba)
+        .context("Capture was truncated")?;
+    let mut png_data = Vec::new();
+    buffer
+        .write_to(&mut std::io::Cursor::new(&mut png_data), image::ImageFormat::Png)
+        .context("Failed to encode capture")?;
+    Ok(png_data)
+}
+
+/// Identity and resolution of the display about to be captured
+#[cfg(target_os = "macos")]
+#[derive(Debug, PartialEq)]
+struct DisplaySnapshot {
+    id: u32,
+    pixel_width: u64,
+    pixel_height: u64,
+}
+
+#[cfg(target_os = "macos")]
+impl DisplaySnapshot {
+    fn main() -> Result<Self> {
+        use core_graphics::display::CGDisplay;
+
+        let display = CGDisplay::main();
+        let mode = display.display_mode().ok_or_else(|| DisplayChanged {
+            reason: String::from("main display has no active mode"),
+        })?;
+
+        Ok(Self {
+            id: display.id,
+            pixel_width: mode.pixel_width(),
+            pixel_height: mode.pixel_height(),
+        })
+    }
+
+    /// Fail with `DisplayChanged` unless the display is still connected, still the
+    /// main display and still at the same resolution
+    fn revalidate(&self) -> Result<()> {
+        use core_graphics::display::CGDisplay;
+
+        let active = CGDisplay::active_displays()
+            .map_err(|e| anyhow::anyhow!("Failed to list displays (CGError {})", e))?;
+        if !active.contains(&self.id) {
+            return Err(DisplayChanged {
+                reason: String::from("display was disconnected"),
+            }
+            .into());
+        }
+        self.compare(&Self::main()?)
+    }
+
+    /// Fail with `DisplayChanged` unless `current` is the same display at the same
+    /// resolution
+    fn compare(&self, current: &Self) -> Result<()> {
+        if current.id != self.id {
+            return Err(DisplayChanged {
+                reason: String::from("main display changed"),
+            }
+            .into());
+        }
+        if current != self {
+            return Err(DisplayChanged {
+                reason: format!(
+                    "resolution changed from {}x{} to {}x{}",
+                    self.pixel_width, self.pixel_height, current.pixel_width, current.pixel_height
+                ),
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
+/// Temp file that is deleted when dropped
+#[cfg(target_os = "macos")]
+struct TempFile(std::path::PathBuf);
+
+#[cfg(target_os = "macos")]
+impl TempFile {
+    /// Create the file up front, so an unwritable directory is detected before capturing
+    fn create(path: std::path::PathBuf) -> std::io::Result<Self> {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(Self(path))
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
 
-    // Clean up temp file
-    let _ = std::fs::remove_file(&temp_path);
+/// Unique temp filename: nanosecond timestamp, process id and a per-process counter
+/// so rapid or concurrent captures never share a file
+/// A clock set before 1970 yields a zero timestamp; the pid and counter keep names unique
+#[cfg(target_os = "macos")]
+fn temp_screenshot_name() -> String {
+    temp_screenshot_name_at(std::time::SystemTime::now())
+}
+
+/// `temp_screenshot_name` for the time `now`
+#[cfg(target_os = "macos")]
+fn temp_screenshot_name_at(now: std::time::SystemTime) -> String {
+    use std::sync::atomic::{AtomicU64, Ordering};
 
-    // Return base64-encoded PNG
-    Ok(base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &png_data))
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = now
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let seq = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    format!("phantom_screenshot_{}_{}_{}.png", nanos, std::process::id(), seq)
 }
 
+/// Capture the primary display as PNG through `xcap` (Windows GDI / DXGI, X11 or
+/// Wayland via the desktop portal)
 #[cfg(not(target_os = "macos"))]
-async fn capture_screenshot_impl() -> Result<String> {
-    anyhow::bail!("Screenshot capture is only supported on macOS")
+pub(crate) async fn capture_screenshot_impl(mode: CaptureMode) -> Result<Vec<u8>> {
+    if mode != CaptureMode::Full {
+        return Err(GhostError::ScreenshotUnsupported(String::from(
+            "Region and window capture are only available on macOS",
+        ))
+        .into());
+    }
+    tauri::async_runtime::spawn_blocking(capture_primary_monitor)
+        .await
+        .context("Screenshot task failed")?
+}
+
+#[cfg(not(target_os = "macos"))]
+fn capture_primary_monitor() -> Result<Vec<u8>> {
+    let monitors = xcap::Monitor::all().context("Failed to list displays")?;
+    let primary = monitors
+        .iter()
+        .position(|monitor| monitor.is_primary().unwrap_or(false))
+        .unwrap_or(0);
+    let monitor = monitors.get(primary).context("No display to capture")?;
+
+    let captured = monitor.capture_image().context("Failed to capture the screen")?;
+    // Rebuilt from raw pixels so xcap's `image` version doesn't have to match ours
+    let (width, height) = (captured.width(), captured.height());
+    let image = image::RgbaImage::from_raw(width, height, captured.into_raw())
+        .context("Captured image has an unexpected size")?;
+    if width == 0 || height == 0 {
+        return Err(DisplayChanged {
+            reason: String::from("capture was empty"),
+        }
+        .into());
+    }
+
+    let mut png_data = std::io::Cursor::new(Vec::new());
+    image::DynamicImage::ImageRgba8(image)
+        .write_to(&mut png_data, image::ImageFormat::Png)
+        .context("Failed to encode PNG")?;
+    Ok(png_data.into_inner())
+}
+
+#[cfg(all(test, target_os = "macos"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rapid_captures_get_distinct_temp_files() {
+        let dir = std::env::temp_dir();
+        let first = TempFile::create(dir.join(temp_screenshot_name())).unwrap();
+        let second = TempFile::create(dir.join(temp_screenshot_name())).unwrap();
+        assert_ne!(first.0, second.0);
+
+        let paths = [first.0.clone(), second.0.clone()];
+        drop((first, second));
+        assert!(paths.iter().all(|path| !path.exists()));
+    }
+
+    #[test]
+    fn clock_before_1970_still_yields_unique_names() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let before_epoch = UNIX_EPOCH - Duration::from_secs(3600);
+        let first = temp_screenshot_name_at(before_epoch);
+        let second = temp_screenshot_name_at(before_epoch);
+        assert!(first.starts_with("phantom_screenshot_0_"));
+        assert_ne!(first, second);
+
+        let later = temp_screenshot_name_at(UNIX_EPOCH + Duration::from_nanos(42));
+        assert!(later.starts_with("phantom_screenshot_42_"));
+    }
+
+    #[test]
+    fn revalidation_detects_display_changes() {
+        let snapshot = |id, pixel_width, pixel_height| DisplaySnapshot {
+            id,
+            pixel_width,
+            pixel_height,
+        };
+        let before = snapshot(1, 2880, 1800);
+        let reason = |current: DisplaySnapshot| {
+            let error = before.compare(&current).unwrap_err();
+            error.downcast::<DisplayChanged>().unwrap().reason
+        };
+
+        assert!(before.compare(&snapshot(1, 2880, 1800)).is_ok());
+        assert_eq!(reason(snapshot(2, 2880, 1800)), "main display changed");
+        assert_eq!(
+            reason(snapshot(1, 1440, 900)),
+            "resolution changed from 2880x1800 to 1440x900"
+        );
+    }
+
+    #[test]
+    fn unwritable_temp_dir_is_detected_before_capturing() {
+        // A regular file where the directory should be can't hold the temp file
+        let dir = std::env::temp_dir();
+        let not_a_dir = TempFile::create(dir.join(temp_screenshot_name())).unwrap();
+        let inside = not_a_dir.0.join(temp_screenshot_name());
+        assert!(TempFile::create(inside.clone()).is_err());
+        assert!(!inside.exists());
+
+        let missing = dir.join(temp_screenshot_name()).join("capture.png");
+        assert!(TempFile::create(missing).is_err());
+    }
 }
@@ -0,0 +1,212 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use image::{GrayImage, RgbaImage};
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::State;
+
+use crate::commands::image::{decode_base64_image, encode_base64_png};
+use crate::commands::screenshot::{self, CaptureMode};
+
+/// Most frames one scrolling capture may hold
+const MAX_SCROLL_FRAMES: usize = 30;
+
+/// Height of the strip of each new frame searched for in the previous one
+const MATCH_BAND_ROWS: u32 = 48;
+
+/// Columns each row is averaged down to before matching; ignores sub-pixel
+/// rendering differences and keeps the search fast
+const MATCH_COLUMNS: u32 = 96;
+
+/// Largest mean luminance difference (0-255) still accepted as the same content
+const MATCH_TOLERANCE: f32 = 3.0;
+
+/// A band this flat (luminance spread) matches everywhere, e.g. blank background
+const MIN_BAND_CONTRAST: f32 = 8.0;
+
+/// Frames collected for `capture_scrolling`, oldest first, as base64 PNG
+#[derive(Default)]
+pub struct ScrollFrames(Mutex<Vec<String>>);
+
+/// Result of `capture_scrolling`
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum ScrollingCapture {
+    /// One tall image with the overlap between frames removed
+    Stitched { base64: String, frames: usize },
+    /// The frames couldn't be lined up; they are returned as captured
+    Frames { frames: Vec<String>, error: String },
+}
+
+/// Add a frame to the scrolling capture: `frame` as sent by the frontend, or a fresh
+/// screenshot of the main display
+/// Call once per scroll step, keeping some content from the previous frame on screen
+///
+/// # Returns
+/// Number of frames collected so far
+#[tauri::command]
+pub async fn add_scroll_frame(
+    frames: State<'_, ScrollFrames>,
+    frame: Option<String>,
+) -> Result<usize, String> {
+    if frames.0.lock().unwrap().len() >= MAX_SCROLL_FRAMES {
+        return Err(format!("A scrolling capture holds at most {} frames", MAX_SCROLL_FRAMES));
+    }
+
+    let frame = match frame {
+        Some(frame) => frame,
+        None => screenshot::capture_screenshot_impl(CaptureMode::Full)
+            .await
+            .map(|png| base64::engine::general_purpose::STANDARD.encode(png))
+            .map_err(|e| format!("Screenshot failed: {}", e))?,
+    };
+
+    let mut collected = frames.0.lock().unwrap();
+    collected.push(frame);
+    Ok(collected.len())
+}
+
+/// Stitch the frames top to bottom into one image, matching each frame against the
+/// one before it to drop the rows they share
+/// Uses `frames` if given, otherwise the ones collected by `add_scroll_frame`
+/// (which are cleared)
+#[tauri::command]
+pub fn capture_scrolling(
+    collected: State<'_, ScrollFrames>,
+    frames: Option<Vec<String>>,
+) -> Result<ScrollingCapture, String> {
+    let frames = match frames {
+        Some(frames) => frames,
+        None => std::mem::take(&mut *collected.0.lock().unwrap()),
+    };
+    if frames.is_empty() {
+        return Err("No frames captured".to_string());
+    }
+    if frames.len() > MAX_SCROLL_FRAMES {
+        return Err(format!("A scrolling capture holds at most {} frames", MAX_SCROLL_FRAMES));
+    }
+
+    Ok(match stitch(&frames) {
+        Ok(base64) => ScrollingCapture::Stitched {
+            base64,
+            frames: frames.len(),
+        },
+        Err(e) => ScrollingCapture::Frames {
+            frames,
+            error: e.to_string(),
+        },
+    })
+}
+
+/// Drop the frames collected so far
+#[tauri::command]
+pub fn discard_scroll_frames(frames: State<'_, ScrollFrames>) {
+    frames.0.lock().unwrap().clear();
+}
+
+fn stitch(frames: &[String]) -> Result<String> {
+    let images = frames
+        .iter()
+        .enumerate()
+        .map(|(i, f)| {
+            decode_base64_image(f)
+                .map(|image| image.to_rgba8())
+                .with_context(|| format!("Frame {}", i + 1))
+        })
+        .collect::<Result<Vec<RgbaImage>>>()?;
+
+    let width = images[0].width();
+    if let Some(i) = images.iter().position(|image| image.width() != width) {
+        anyhow::bail!("Frame {} has a different width; capture all frames the same way", i + 1);
+    }
+
+    // Rows of each frame that are new relative to the frame before it
+    let mut new_rows = vec![0..images[0].height()];
+    for (i, pair) in images.windows(2).enumerate() {
+        let overlap = find_overlap(&pair[0], &pair[1])
+            .with_context(|| format!("Frames {} and {} don't overlap", i + 1, i + 2))?;
+        new_rows.push(overlap..pair[1].height());
+    }
+
+    let height = new_rows.iter().map(|rows| rows.end - rows.start).sum();
+    let mut stitched = RgbaImage::new(width, height);
+    let mut y = 0;
+    for (image, rows) in images.iter().zip(new_rows) {
+        let rows_height = rows.end - rows.start;
+        let part = image::imageops::crop_imm(image, 0, rows.start, width, rows_height).to_image();
+        image::imageops::replace(&mut stitched, &part, 0, i64::from(y));
+        y += rows_height;
+    }
+
+    encode_base64_png(&image::DynamicImage::ImageRgba8(stitched))
+}
+
+/// Number of rows at the top of `next` that repeat the bottom of `previous`
+///
+/// A high-contrast band from the top third of `next` is searched for in `previous`;
+/// where it matches best fixes how far the content scrolled
+fn find_overlap(previous: &RgbaImage, next: &RgbaImage) -> Result<u32> {
+    let previous = row_profile(&image::imageops::grayscale(previous));
+    let next = row_profile(&image::imageops::grayscale(next));
+    let band = MATCH_BAND_ROWS as usize;
+    if next.len() < band || previous.len() < band {
+        anyhow::bail!("Frames are too short to match");
+    }
+
+    // Band start in `next` with the most contrast, skipping flat areas that match anywhere
+    let band_start = (0..=(next.len() / 3).min(next.len() - band))
+        .step_by(4)
+        .map(|start| (start, contrast(&next[start..start + band])))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .filter(|(_, contrast)| *contrast >= MIN_BAND_CONTRAST)
+        .map(|(start, _)| start)
+        .context("No distinctive content to match")?;
+    let template = &next[band_start..band_start + band];
+
+    let (position, best) = (0..=previous.len() - band)
+        .map(|y| (y, difference(&previous[y..y + band], template)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .context("No match found")?;
+    if best > MATCH_TOLERANCE {
+        anyhow::bail!("No matching content (best difference {:.1})", best);
+    }
+
+    // Row `band_start` of `next` sits at row `position` of `previous`
+    let scrolled = position
+        .checked_sub(band_start)
+        .filter(|&scrolled| scrolled > 0)
+        .context("Content did not scroll down between frames")?;
+    Ok((previous.len() - scrolled).min(next.len()) as u32)
+}
+
+/// Each row averaged down to `MATCH_COLUMNS` luminance values
+fn row_profile(image: &GrayImage) -> Vec<Vec<f32>> {
+    let columns = MATCH_COLUMNS.min(image.width()).max(1);
+    let cell = image.width() / columns;
+    image
+        .rows()
+        .map(|row| {
+            let row: Vec<u8> = row.map(|p| p.0[0]).collect();
+            row.chunks(cell.max(1) as usize)
+                .take(columns as usize)
+                .map(|c| c.iter().map(|&v| f32::from(v)).sum::<f32>() / c.len() as f32)
+                .collect()
+        })
+        .collect()
+}
+
+fn difference(a: &[Vec<f32>], b: &[Vec<f32>]) -> f32 {
+    let (total, count) = a
+        .iter()
+        .zip(b)
+        .flat_map(|(ra, rb)| ra.iter().zip(rb))
+        .fold((0.0, 0usize), |(total, count), (x, y)| (total + (x - y).abs(), count + 1));
+    total / count.max(1) as f32
+}
+
+/// Spread between the darkest and brightest averaged cell
+fn contrast(rows: &[Vec<f32>]) -> f32 {
+    let values = rows.iter().flatten();
+    let (min, max) = values.fold((f32::MAX, f32::MIN), |(min, max), &v| (min.min(v), max.max(v)));
+    max - min
+}
@@ -0,0 +1,237 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, State};
+
+use crate::commands::ai::{send_message_impl, ChatOptions, Message, MessageContent};
+use crate::commands::context::CaptureContext;
+use crate::commands::history::{self, HistoryStore};
+use crate::commands::requests::{InFlightRequests, TaskKind};
+use crate::commands::transfer::{Delivery, Transfers};
+use crate::config::ConfigState;
+
+/// Most attached images kept for follow-up questions; the oldest is dropped first
+const MAX_RETAINED_IMAGES: usize = 16;
+
+/// Most sessions held at once; starting another ends the least recently used
+const MAX_SESSIONS: usize = 32;
+
+struct Session {
+    messages: Vec<Message>,
+    /// Value of `SessionStore::uses` when the session was last read or extended
+    last_used: u64,
+}
+
+/// Conversations held on the backend, so each turn only crosses IPC once
+/// `send_message` still takes the full history for callers that don't use sessions
+#[derive(Default)]
+pub struct SessionStore {
+    sessions: Mutex<HashMap<String, Session>>,
+    /// Recently attached images as `(id, base64)`, oldest first
+    images: Mutex<VecDeque<(String, String)>>,
+    /// Counter ordering session use, for ending the least recently used
+    uses: AtomicU64,
+}
+
+impl SessionStore {
+    /// The image to attach to a request and its id: a newly sent `image` is kept
+    /// under a new id, otherwise `image_id` refers back to one sent earlier
+    pub(crate) fn attach_image(
+        &self,
+        image: Option<String>,
+        image_id: Option<String>,
+    ) -> Result<Option<(String, String)>, String> {
+        let mut images = self.images.lock().unwrap();
+        match (image, image_id) {
+            (Some(image), _) => {
+                let id = history::new_id();
+                if images.len() >= MAX_RETAINED_IMAGES {
+                    images.pop_front();
+                }
+                images.push_back((id.clone(), image.clone()));
+                Ok(Some((id, image)))
+            }
+            (None, Some(id)) => {
+                let image = images
+                    .iter()
+                    .find(|(retained, _)| *retained == id)
+                    .map(|(_, image)| image.clone())
+                    .ok_or_else(|| {
+                        format!("Image {} is no longer available; attach it again", id)
+                    })?;
+                Ok(Some((id, image)))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Hold `messages` under `id`, ending the least recently used session when full
+    fn insert(&self, id: String, messages: Vec<Message>) {
+        let mut sessions = self.sessions.lock().unwrap();
+        if !sessions.contains_key(&id) && sessions.len() >= MAX_SESSIONS {
+            let oldest = sessions
+                .iter()
+                .min_by_key(|(_, session)| session.last_used)
+                .map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                sessions.remove(&oldest);
+            }
+        }
+        let last_used = self.tick();
+        sessions.insert(id, Session { messages, last_used });
+    }
+
+    fn messages(&self, id: &str) -> Option<Vec<Message>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.get_mut(id)?;
+        session.last_used = self.tick();
+        Some(session.messages.clone())
+    }
+
+    /// Add a completed turn; does nothing if the session ended in the meantime
+    fn push_turn(&self, id: &str, turn: [Message; 2]) {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(id) {
+            session.messages.extend(turn);
+            session.last_used = self.tick();
+        }
+    }
+
+    fn tick(&self) -> u64 {
+        self.uses.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+/// Start a backend-held conversation
+/// At most `MAX_SESSIONS` are held; the least recently used one ends to make room
+/// Seeded from `messages` if given, otherwise from the saved conversation
+/// `conversation_id` (whose id the session then shares), otherwise empty
+///
+/// # Returns
+/// The session id for `append_to_session`
+#[tauri::command]
+pub fn start_session(
+    sessions: State<'_, SessionStore>,
+    history: State<'_, HistoryStore>,
+    conversation_id: Option<String>,
+    messages: Option<Vec<Message>>,
+) -> Result<String, String> {
+    let (id, messages) = match (conversation_id, messages) {
+        (id, Some(messages)) => (id.unwrap_or_else(history::new_id), messages),
+        (Some(id), None) => {
+            let saved = history
+                .load(&id)
+                .map_err(|e| format!("Failed to load conversation: {}", e))?;
+            (id, saved.messages)
+        }
+        (None, None) => (history::new_id(), Vec::new()),
+    };
+
+    sessions.insert(id.clone(), messages);
+    Ok(id)
+}
+
+/// Send one new message in a session; the backend supplies the earlier history
+/// The message and the reply are added to the session only if the request succeeds
+/// `screenshot_base64` is attached to this request only, and the reply comes back as
+/// a `Delivery`, as with `send_message`
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn append_to_session(
+    app: AppHandle,
+    config: State<'_, ConfigState>,
+    capture_context: State<'_, CaptureContext>,
+    requests: State<'_, InFlightRequests>,
+    sessions: State<'_, SessionStore>,
+    transfers: State<'_, Transfers>,
+    session_id: String,
+    message: Message,
+    screenshot_base64: Option<String>,
+    model: Option<String>,
+    request_id: Option<String>,
+) -> Result<Delivery, String> {
+    config.touch();
+
+    history::validate_message(&message).map_err(|e| format!("Invalid message: {}", e))?;
+
+    let mut messages = sessions
+        .messages(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+    messages.push(message.clone());
+
+    let options = ChatOptions {
+        app: Some(app),
+        app_context: screenshot_base64
+            .as_ref()
+            .and_then(|_| capture_context.get())
+            .map(|c| c.describe()),
+        model: model.filter(|m| !m.trim().is_empty()),
+        request_id: request_id.clone(),
+        user_turn: true,
+        ..ChatOptions::default()
+    };
+
+    let api_key = config.get().openai_api_key;
+    let images = screenshot_base64.into_iter().collect();
+    let response = requests
+        .run(
+            request_id,
+            TaskKind::Chat,
+            send_message_impl(&api_key, messages, images, &options),
+        )
+        .await
+        .map_err(|e| format!("OpenAI API error: {}", e))?;
+
+    let reply = Message {
+        role: String::from("assistant"),
+        content: MessageContent::Text(response.content.clone()),
+        pinned: false,
+    };
+    sessions.push_turn(&session_id, [message, reply]);
+
+    transfers
+        .deliver(&response)
+        .map_err(|e| format!("Failed to serialize response: {}", e))
+}
+
+/// Full history of a session, e.g. to save it or re-render after a reload
+/// Comes back as a `Delivery`, chunked when too large for one IPC message
+#[tauri::command]
+pub fn get_session(
+    sessions: State<'_, SessionStore>,
+    transfers: State<'_, Transfers>,
+    session_id: String,
+) -> Result<Delivery, String> {
+    let messages = sessions
+        .messages(&session_id)
+        .ok_or_else(|| format!("Session not found: {}", session_id))?;
+    transfers
+        .deliver(&messages)
+        .map_err(|e| format!("Failed to serialize session: {}", e))
+}
+
+/// Drop a session and free its history
+#[tauri::command]
+pub fn end_session(sessions: State<'_, SessionStore>, session_id: String) {
+    sessions.sessions.lock().unwrap().remove(&session_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_store_ends_the_least_recently_used_session() {
+        let store = SessionStore::default();
+        for i in 0..MAX_SESSIONS {
+            store.insert(format!("session-{}", i), Vec::new());
+        }
+        // Touching the oldest makes the second one least recently used
+        assert!(store.messages("session-0").is_some());
+
+        store.insert(String::from("newest"), Vec::new());
+        assert_eq!(store.sessions.lock().unwrap().len(), MAX_SESSIONS);
+        assert!(store.messages("session-0").is_some());
+        assert!(store.messages("session-1").is_none());
+        assert!(store.messages("newest").is_some());
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::commands::ai::{set_extra_headers, validate_extra_headers};
+use crate::commands::audio::AudioRecorder;
+use crate::config::{AppConfig, ConfigState};
+use crate::settings::{Settings, SettingsStore};
+
+/// Version of the `export_settings` file format
+/// Version 0 is a bare `settings.json`, without the wrapper
+const SETTINGS_SCHEMA_VERSION: u64 = 1;
+
+/// Portable settings file written by `export_settings`
+/// Secrets live in `.env`, not in `Settings`, so nothing sensitive is included
+#[derive(Serialize)]
+struct SettingsExport<'a> {
+    schema_version: u64,
+    settings: &'a Settings,
+}
+
+/// Get the current user settings
+#[tauri::command]
+pub fn get_settings(settings: State<'_, SettingsStore>) -> Settings {
+    settings.get()
+}
+
+/// Replace the user settings and persist them
+/// Invalid `extra_headers` are rejected before anything is saved, and nothing is
+/// applied unless the save succeeds
+#[tauri::command]
+pub fn update_settings(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    new_settings: Settings,
+) -> Result<(), String> {
+    validate_extra_headers(&new_settings.extra_headers)
+        .map_err(|e| format!("Invalid extra headers: {}", e))?;
+    settings
+        .update(|s| *s = new_settings.clone())
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    apply_runtime_settings(&app, &new_settings);
+    Ok(())
+}
+
+/// Re-read `.env` and the settings file without restarting
+/// Commands use the new values (e.g. a rotated API key) from their next call
+/// Emits `config-reloaded` on success; on error the previous configuration stays active
+#[tauri::command]
+pub fn reload_config(
+    app: AppHandle,
+    config: State<'_, ConfigState>,
+    settings: State<'_, SettingsStore>,
+) -> Result<(), String> {
+    let new_config = AppConfig::reload().map_err(|e| format!("Failed to reload config: {}", e))?;
+    settings
+        .reload()
+        .map_err(|e| format!("Failed to reload settings: {}", e))?;
+    config.replace(new_config);
+    apply_runtime_settings(&app, &settings.get());
+
+    app.emit("config-reloaded", ())
+        .map_err(|e| format!("Failed to emit event: {}", e))
+}
+
+/// Write the user settings to a portable JSON file for use on another machine
+/// The API key is never included; it stays in `.env`
+///
+/// # Returns
+/// The path written (`.json` is added if missing)
+#[tauri::command]
+pub fn export_settings(
+    settings: State<'_, SettingsStore>,
+    path: PathBuf,
+) -> Result<PathBuf, String> {
+    export_settings_impl(&settings.get(), path).map_err(|e| format!("Export failed: {}", e))
+}
+
+fn export_settings_impl(settings: &Settings, mut path: PathBuf) -> Result<PathBuf> {
+    if path.extension().is_none() {
+        path.set_extension("json");
+    }
+
+    let export = SettingsExport {
+        schema_version: SETTINGS_SCHEMA_VERSION,
+        settings,
+    };
+    let json = serde_json::to_string_pretty(&export)?;
+    std::fs::write(&path, json).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Replace the user settings with a file written by `export_settings`
+/// Older formats are migrated; settings missing from the file keep their defaults
+/// A file with invalid `extra_headers` is rejected
+/// Runtime settings (theme, microphone, login item) apply immediately and
+/// `config-reloaded` is emitted; hotkeys take effect on the next launch
+///
+/// # Returns
+/// The imported settings
+#[tauri::command]
+pub fn import_settings(app: AppHandle, path: PathBuf) -> Result<Settings, String> {
+    let imported = read_settings_export(&path).map_err(|e| format!("Import failed: {}", e))?;
+
+    app.state::<SettingsStore>()
+        .update(|s| *s = imported.clone())
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    apply_runtime_settings(&app, &imported);
+
+    app.emit("config-reloaded", ())
+        .map_err(|e| format!("Failed to emit event: {}", e))?;
+    Ok(imported)
+}
+
+fn read_settings_export(path: &Path) -> Result<Settings> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut value: serde_json::Value =
+        serde_json::from_str(&json).context("Not a valid settings file")?;
+
+    let version = match value.get("schema_version") {
+        Some(version) => version.as_u64().context("Invalid schema_version")?,
+        None => 0,
+    };
+    if version > SETTINGS_SCHEMA_VERSION {
+        anyhow::bail!(
+            "Settings file is from a newer version (schema {}, supported {})",
+            version,
+            SETTINGS_SCHEMA_VERSION
+        );
+    }
+
+    let settings = match version {
+        0 => value,
+        _ => value
+            .get_mut("settings")
+            .map(serde_json::Value::take)
+            .context("Settings file has no settings")?,
+    };
+    let settings: Settings = serde_json::from_value(settings).context("Invalid settings")?;
+    validate_extra_headers(&settings.extra_headers).context("Invalid extra headers")?;
+    Ok(settings)
+}
+
+/// Push settings that are otherwise only read at startup to the running app
+fn apply_runtime_settings(app: &AppHandle, settings: &Settings) {
+    let recorder = app.state::<AudioRecorder>();
+    recorder.set_idle_timeout(settings.mic_idle_timeout_secs);
+    recorder.set_input_gain(settings.input_gain);
+    recorder.set_max_recording(settings.max_recording_secs);
+    recorder.set_silence_auto_stop(settings.silence_auto_stop);
+
+    if let Some(window) = app.get_webview_window("main") {
+        if let Err(e) = crate::commands::window::apply_theme(&window, settings.theme) {
+            eprintln!("Warning: Could not apply theme: {}", e);
+        }
+        if let Err(e) = crate::commands::window::apply_vibrancy(&window, settings.vibrancy) {
+            eprintln!("Warning: Could not apply vibrancy: {}", e);
+        }
+    }
+    if let Err(e) = crate::commands::system::apply_launch_at_login(app, settings.launch_at_login) {
+        eprintln!("Warning: Could not update login item: {}", e);
+    }
+    if let Err(e) = set_extra_headers(&settings.extra_headers) {
+        eprintln!("Warning: Could not apply extra headers: {}", e);
+    }
+}
@@ -0,0 +1,121 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, State};
+
+use crate::commands::history::{Cleanup, HistoryStore, INDEX_FILE};
+
+/// Bytes used in the app data directory, by category
+#[derive(Debug, Default, Serialize)]
+pub struct StorageUsage {
+    /// Saved conversations, including their stored images
+    pub conversations: u64,
+    /// Conversation search index
+    pub search_index: u64,
+    /// Usage ledger (`usage.csv`)
+    pub logs: u64,
+    /// Crash-recovery autosave
+    pub recovery: u64,
+    /// Anything else in the data directory
+    pub other: u64,
+    pub total: u64,
+}
+
+/// Report how much disk space the app's stored data takes
+/// Screenshots are stored with conversations, so they count towards `conversations`
+#[tauri::command]
+pub fn get_storage_usage(app: AppHandle) -> Result<StorageUsage, String> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to locate data directory: {}", e))?;
+    storage_usage(&data_dir).map_err(|e| format!("Failed to measure storage: {}", e))
+}
+
+fn storage_usage(data_dir: &Path) -> Result<StorageUsage> {
+    let mut usage = StorageUsage::default();
+
+    let entries = match std::fs::read_dir(data_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(usage),
+        Err(e) => return Err(e).context("Failed to read data directory"),
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let name = entry.file_name();
+        let path = entry.path();
+
+        match name.to_str() {
+            Some("conversations") => {
+                for file in read_dir_files(&path)? {
+                    let size = dir_size(&file);
+                    if file.file_name().is_some_and(|n| n == INDEX_FILE) {
+                        usage.search_index += size;
+                    } else {
+                        usage.conversations += size;
+                    }
+                }
+            }
+            Some("usage.csv") => usage.logs += file_size(&path),
+            Some("recovery.json") => usage.recovery += file_size(&path),
+            _ => usage.other += dir_size(&path),
+        }
+    }
+
+    usage.total =
+        usage.conversations + usage.search_index + usage.logs + usage.recovery + usage.other;
+    Ok(usage)
+}
+
+/// Delete saved conversations not updated in the last `older_than_days` days
+/// Only files inside the app's conversation directory are touched
+///
+/// # Returns
+/// Ids of the deleted conversations and any that could not be deleted
+#[tauri::command]
+pub fn cleanup_old_conversations(
+    history: State<'_, HistoryStore>,
+    older_than_days: u32,
+) -> Result<Cleanup, String> {
+    if older_than_days == 0 {
+        return Err("older_than_days must be at least 1".to_string());
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let cutoff = now.saturating_sub(older_than_days as u64 * 24 * 60 * 60);
+
+    history
+        .delete_older_than(cutoff)
+        .map_err(|e| format!("Cleanup failed: {}", e))
+}
+
+/// Symlinks are not followed, so nothing outside the data directory is counted
+fn file_size(path: &Path) -> u64 {
+    std::fs::symlink_metadata(path)
+        .map(|m| if m.is_file() { m.len() } else { 0 })
+        .unwrap_or(0)
+}
+
+fn dir_size(path: &Path) -> u64 {
+    match std::fs::symlink_metadata(path) {
+        Ok(m) if m.is_dir() => read_dir_files(path)
+            .unwrap_or_default()
+            .iter()
+            .map(|p| dir_size(p))
+            .sum(),
+        Ok(m) if m.is_file() => m.len(),
+        _ => 0,
+    }
+}
+
+fn read_dir_files(path: &Path) -> Result<Vec<std::path::PathBuf>> {
+    Ok(std::fs::read_dir(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .collect())
+}
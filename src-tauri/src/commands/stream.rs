@@ -0,0 +1,469 @@
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::commands::ai::{
+    chat_completions_url, send_chat, send_message_impl, Annotation, ApiError, ApiUsage,
+    ChatOptions, ChatResponse, ChoiceLogprobs, Citation, Message, SendMessageRequest,
+    TokenLogprob, Usage,
+};
+use crate::commands::code;
+use crate::commands::context::CaptureContext;
+use crate::commands::recovery::RecoveryStore;
+use crate::commands::requests::{InFlightRequests, SlowResponseWatch, TaskKind};
+use crate::commands::session::SessionStore;
+use crate::commands::transfer::{Delivery, Transfers};
+use crate::commands::usage;
+use crate::config::ConfigState;
+use crate::settings::SettingsStore;
+
+/// How streamed deltas are grouped before being emitted as `ai-chunk` events
+/// A batch is emitted as soon as either threshold is reached
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamBatching {
+    /// Emit once this many characters are buffered
+    pub min_chars: Option<usize>,
+    /// Emit once this long has passed since the previous chunk
+    pub min_interval_ms: u64,
+}
+
+impl Default for StreamBatching {
+    fn default() -> Self {
+        Self {
+            min_chars: None,
+            min_interval_ms: 40,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamChunk {
+    #[serde(default)]
+    choices: Vec<StreamChoice>,
+    usage: Option<ApiUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamChoice {
+    delta: Delta,
+    #[serde(default)]
+    logprobs: Option<ChoiceLogprobs>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Delta {
+    content: Option<String>,
+    /// Search models send their citations once the text they cover has streamed
+    #[serde(default)]
+    annotations: Vec<Annotation>,
+}
+
+/// Send message to OpenAI and stream the reply
+///
+/// Emits `ai-chunk` with text deltas (batched per settings) and `ai-done` with the
+/// full response (a `Delivery`, as returned) once the stream ends
+///
+/// `cancel_request` closes the connection immediately and stops `ai-chunk` events;
+/// the response then holds the text received so far with `cancelled` set
+///
+/// The reply is written to the crash-recovery file as it streams (throttled), so a
+/// crash mid-answer still recovers the text generated so far
+///
+/// Takes the same `SendMessageRequest` as `send_message`, without its
+/// `send_message`-only options
+///
+/// # Returns
+/// The complete response, same as `send_message`
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn send_message_stream(
+    app: AppHandle,
+    config: State<'_, ConfigState>,
+    settings: State<'_, SettingsStore>,
+    capture_context: State<'_, CaptureContext>,
+    requests: State<'_, InFlightRequests>,
+    sessions: State<'_, SessionStore>,
+    transfers: State<'_, Transfers>,
+    mut request: SendMessageRequest,
+) -> Result<Delivery, String> {
+    config.touch();
+
+    let attached = request.take_image(&sessions)?;
+    let image_id = attached.as_ref().map(|(id, _)| id.clone());
+    let screenshot_base64 = attached.map(|(_, image)| image);
+
+    let app_context = screenshot_base64
+        .as_ref()
+        .and_then(|_| capture_context.get())
+        .map(|c| c.describe());
+    let options = request.chat_options(app.clone(), app_context)?;
+    let messages = request.messages;
+    let batching = settings.get().stream_batching;
+    let api_key = config.get().openai_api_key;
+
+    let images = screenshot_base64.into_iter().collect();
+    let mut response =
+        send_message_stream_impl(&app, &requests, &api_key, messages, images, &options, &batching)
+            .await
+            .map_err(|e| format!("OpenAI API error: {}", e))?;
+    // Chunks already went out as received; the final text carries the tags
+    code::enrich_reply(&app, &mut response.content);
+    response.image_id = image_id;
+
+    // Emitted and returned as one delivery, so a very long reply can't overflow IPC;
+    // a chunked one is read once, from either
+    let delivery = transfers
+        .deliver(&response)
+        .map_err(|e| format!("Failed to serialize response: {}", e))?;
+    app.emit("ai-done", &delivery)
+        .map_err(|e| format!("Failed to emit event: {}", e))?;
+    if !response.cancelled {
+        code::auto_copy(&app, &response.content);
+    }
+
+    Ok(delivery)
+}
+
+/// Endpoints that rejected `stream: true`; later calls go straight to a plain request
+fn non_streaming_endpoints() -> &'static Mutex<HashSet<String>> {
+    static ENDPOINTS: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    ENDPOINTS.get_or_init(Mutex::default)
+}
+
+fn streaming_unsupported(endpoint: &str) -> bool {
+    non_streaming_endpoints().lock().unwrap().contains(endpoint)
+}
+
+/// What has been received so far
+/// Kept outside the cancellable future so a cancelled stream still has its partial text
+struct StreamProgress {
+    content: String,
+    usage: Option<Usage>,
+    logprobs: Option<Vec<TokenLogprob>>,
+    citations: Vec<Citation>,
+}
+
+async fn send_message_stream_impl(
+    app: &AppHandle,
+    requests: &InFlightRequests,
+    api_key: &str,
+    messages: Vec<Message>,
+    images: Vec<String>,
+    options: &ChatOptions,
+    batching: &StreamBatching,
+) -> Result<ChatResponse> {
+    let fallback = app.state::<SettingsStore>().get().stream_fallback;
+    let endpoint = chat_completions_url();
+    if fallback && streaming_unsupported(&endpoint) {
+        return send_without_stream(app, requests, api_key, messages, images, options).await;
+    }
+
+    let mut progress = StreamProgress {
+        content: String::new(),
+        usage: None,
+        logprobs: options.logprobs.map(|_| Vec::new()),
+        citations: Vec::new(),
+    };
+
+    let result = requests
+        .run_until_cancelled(
+            options.request_id.clone(),
+            TaskKind::Stream,
+            read_stream(app, api_key, &messages, images.clone(), options, batching, &mut progress),
+        )
+        .await;
+    let finished = match result {
+        Err(e) if fallback && rejected_streaming(&endpoint, &e, &progress.content) => {
+            return send_without_stream(app, requests, api_key, messages, images, options).await;
+        }
+        result => result?,
+    };
+    let cancelled = finished.is_none();
+
+    // A cancelled stream never reaches the final chunk that carries usage
+    if !cancelled {
+        usage::record_chat(Some(app), &options.model(), progress.usage.as_ref(), &options.tags);
+    }
+
+    let recovery = app.state::<RecoveryStore>();
+    if let Err(e) = recovery.finish_stream(&messages, &progress.content, progress.usage.clone()) {
+        eprintln!("Autosave failed: {}", e);
+    }
+
+    Ok(ChatResponse {
+        content: progress.content,
+        usage: progress.usage,
+        logprobs: progress.logprobs,
+        cancelled,
+        citations: progress.citations,
+        image_id: None,
+    })
+}
+
+/// Whether a stream that failed with `error` after `received` was rejected because
+/// `endpoint` can't stream; if so the endpoint is remembered so later calls skip it
+fn rejected_streaming(endpoint: &str, error: &anyhow::Error, received: &str) -> bool {
+    let rejected = received.is_empty()
+        && error
+            .downcast_ref::<ApiError>()
+            .is_some_and(ApiError::is_stream_unsupported);
+    if rejected {
+        eprintln!("Streaming not supported by {}, sending without it", endpoint);
+        non_streaming_endpoints().lock().unwrap().insert(endpoint.to_string());
+    }
+    rejected
+}
+
+/// A plain request for backends that can't stream; the whole reply goes out as one
+/// `ai-chunk` so the frontend handles it like a (very fast) stream
+async fn send_without_stream(
+    app: &AppHandle,
+    requests: &InFlightRequests,
+    api_key: &str,
+    messages: Vec<Message>,
+    images: Vec<String>,
+    options: &ChatOptions,
+) -> Result<ChatResponse> {
+    let finished = requests
+        .run_until_cancelled(
+            options.request_id.clone(),
+            TaskKind::Stream,
+            send_message_impl(api_key, messages.clone(), images, options),
+        )
+        .await?;
+
+    let response = match finished {
+        Some(response) => {
+            if !response.content.is_empty() {
+                app.emit("ai-chunk", &response.content)?;
+            }
+            response
+        }
+        None => ChatResponse {
+            content: String::new(),
+            usage: None,
+            logprobs: None,
+            cancelled: true,
+            citations: Vec::new(),
+            image_id: None,
+        },
+    };
+
+    let recovery = app.state::<RecoveryStore>();
+    if let Err(e) = recovery.finish_stream(&messages, &response.content, response.usage.clone()) {
+        eprintln!("Autosave failed: {}", e);
+    }
+    Ok(response)
+}
+
+/// Read the stream into `progress`, emitting batched `ai-chunk` events
+/// Dropping this future (on cancellation) drops the response and closes the connection
+async fn read_stream(
+    app: &AppHandle,
+    api_key: &str,
+    messages: &[Message],
+    images: Vec<String>,
+    options: &ChatOptions,
+    batching: &StreamBatching,
+    progress: &mut StreamProgress,
+) -> Result<()> {
+    // Slow means no first token yet, not a long answer
+    let watch = SlowResponseWatch::start(Some(app), options.request_id.clone());
+    let response = send_chat(api_key, messages.to_vec(), images, options, true).await?;
+    let recovery = app.state::<RecoveryStore>();
+
+    let mut bytes = response.bytes_stream();
+    let mut parser = SseParser::default();
+    let mut batcher = ChunkBatcher::new(batching);
+
+    'read: while let Some(chunk) = bytes.next().await {
+        let chunk = chunk.context("Failed to read stream from OpenAI")?;
+
+        for event in parser.feed(&chunk) {
+            let data = match event {
+                SseEvent::Data(data) => data,
+                SseEvent::Done => break 'read,
+            };
+
+            let parsed: StreamChunk =
+                serde_json::from_str(&data).context("Failed to parse stream chunk")?;
+
+            if let Some(u) = parsed.usage {
+                progress.usage = Some(Usage::from(u));
+            }
+
+            let mut delta = String::new();
+            for choice in parsed.choices {
+                if let (Some(all), Some(tokens)) =
+                    (&mut progress.logprobs, choice.logprobs.and_then(|l| l.content))
+                {
+                    all.extend(tokens);
+                }
+                if let Some(text) = choice.delta.content {
+                    delta.push_str(&text);
+                }
+                progress.citations.extend(
+                    choice.delta.annotations.into_iter().filter_map(Annotation::into_citation),
+                );
+            }
+
+            if !delta.is_empty() {
+                watch.finish();
+                progress.content.push_str(&delta);
+                if let Err(e) = recovery.update_stream(messages, &progress.content) {
+                    eprintln!("Autosave failed: {}", e);
+                }
+                if let Some(batch) = batcher.push(&delta) {
+                    app.emit("ai-chunk", batch)?;
+                }
+            }
+        }
+    }
+
+    // Never drop the tail of the answer
+    if let Some(rest) = batcher.flush() {
+        app.emit("ai-chunk", rest)?;
+    }
+    Ok(())
+}
+
+enum SseEvent {
+    Data(String),
+    Done,
+}
+
+/// Incremental parser for server-sent events
+/// Network chunks can end mid-line (or mid-UTF-8 sequence), so bytes are buffered
+/// until a full line is available
+#[derive(Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    fn feed(&mut self, bytes: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(bytes);
+
+        let mut events = Vec::new();
+        while let Some(newline) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\r', '\n']);
+
+            // Only `data:` lines carry payloads; ignore comments and other fields
+            let Some(data) = line.strip_prefix("data:") else {
+                continue;
+            };
+            let data = data.trim_start();
+
+            if data == "[DONE]" {
+                events.push(SseEvent::Done);
+            } else if !data.is_empty() {
+                events.push(SseEvent::Data(data.to_string()));
+            }
+        }
+
+        events
+    }
+}
+
+/// Groups deltas so the frontend isn't flooded with one IPC event per token
+struct ChunkBatcher {
+    pending: String,
+    last_emit: Instant,
+    min_chars: Option<usize>,
+    min_interval: Duration,
+}
+
+impl ChunkBatcher {
+    fn new(batching: &StreamBatching) -> Self {
+        Self {
+            pending: String::new(),
+            last_emit: Instant::now(),
+            min_chars: batching.min_chars,
+            min_interval: Duration::from_millis(batching.min_interval_ms),
+        }
+    }
+
+    /// Buffer a delta, returning a batch if a threshold was reached
+    fn push(&mut self, delta: &str) -> Option<String> {
+        self.pending.push_str(delta);
+
+        let enough_chars = self
+            .min_chars
+            .is_some_and(|min| self.pending.chars().count() >= min);
+        let enough_time = self.last_emit.elapsed() >= self.min_interval;
+
+        if enough_chars || enough_time {
+            self.flush()
+        } else {
+            None
+        }
+    }
+
+    /// Take whatever is buffered
+    fn flush(&mut self) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        self.last_emit = Instant::now();
+        Some(std::mem::take(&mut self.pending))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::ai::{
+        build_request, http_client, parse_chat_response, response_error, MessageContent,
+        OpenAIRequest,
+    };
+    use crate::commands::test_server::MockServer;
+
+    /// POST `request` to `url`, failing with the API error for non-success statuses
+    /// Posts to the mock server directly, leaving the configured base URL alone
+    async fn post(url: &str, request: &OpenAIRequest) -> Result<reqwest::Response> {
+        let response = http_client().post(url).json(request).send().await?;
+        if !response.status().is_success() {
+            return Err(response_error(response).await);
+        }
+        Ok(response)
+    }
+
+    #[tokio::test]
+    async fn rejected_stream_falls_back_to_a_plain_request() {
+        let server = MockServer::start(vec![
+            (400, r#"{"error": {"message": "Streaming is not supported by this server"}}"#),
+            (200, r#"{"choices": [{"message": {"role": "assistant", "content": "Hello"}}]}"#),
+        ]);
+        let endpoint = server.url.clone();
+        let messages = vec![Message {
+            role: String::from("user"),
+            content: MessageContent::Text(String::from("Hi")),
+            pinned: false,
+        }];
+        let options = ChatOptions::default();
+
+        let streamed = build_request(messages.clone(), Vec::new(), &options, true);
+        let error = post(&endpoint, &streamed).await.unwrap_err();
+        assert!(!rejected_streaming(&endpoint, &error, "partial reply"));
+        assert!(!streaming_unsupported(&endpoint));
+        assert!(rejected_streaming(&endpoint, &error, ""));
+        assert!(streaming_unsupported(&endpoint));
+
+        let plain = build_request(messages, Vec::new(), &options, false);
+        let response = post(&endpoint, &plain).await.unwrap();
+        let response = parse_chat_response(response.json().await.unwrap()).unwrap();
+        assert_eq!(response.content, "Hello");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].contains(r#""stream":true"#));
+        assert!(!requests[1].contains(r#""stream":true"#));
+    }
+}
@@ -0,0 +1,73 @@
+use anyhow::Result;
+use serde::Serialize;
+use tauri::{AppHandle, State};
+use tauri_plugin_autostart::ManagerExt;
+
+use crate::config::ConfigState;
+use crate::settings::SettingsStore;
+
+/// Build and environment details for bug reports (never includes secrets)
+#[derive(Debug, Serialize)]
+pub struct VersionInfo {
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    pub tauri_version: String,
+    pub reqwest_version: String,
+    pub cpal_version: String,
+    pub model: String,
+    pub transcription_provider: String,
+}
+
+/// Enable or disable launching the app at login
+#[tauri::command]
+pub fn set_launch_at_login(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    enabled: bool,
+) -> Result<(), String> {
+    apply_launch_at_login(&app, enabled)
+        .map_err(|e| format!("Failed to update login item: {}", e))?;
+
+    settings
+        .update(|s| s.launch_at_login = enabled)
+        .map_err(|e| format!("Failed to save settings: {}", e))
+}
+
+/// Choose whether the window starts hidden on the next launch
+#[tauri::command]
+pub fn set_start_hidden(settings: State<'_, SettingsStore>, hidden: bool) -> Result<(), String> {
+    settings
+        .update(|s| s.start_hidden = hidden)
+        .map_err(|e| format!("Failed to save settings: {}", e))
+}
+
+/// Sync the OS login item with the setting
+pub(crate) fn apply_launch_at_login(app: &AppHandle, enabled: bool) -> Result<()> {
+    let autolaunch = app.autolaunch();
+    if autolaunch.is_enabled()? != enabled {
+        if enabled {
+            autolaunch.enable()?;
+        } else {
+            autolaunch.disable()?;
+        }
+    }
+    Ok(())
+}
+
+/// Get app, platform and dependency versions
+#[tauri::command]
+pub fn get_version_info(app: AppHandle, config: State<'_, ConfigState>) -> VersionInfo {
+    let config = config.get();
+    VersionInfo {
+        app_version: app.package_info().version.to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        tauri_version: option_env!("DEP_VERSION_TAURI").unwrap_or("unknown").to_string(),
+        reqwest_version: option_env!("DEP_VERSION_REQWEST").unwrap_or("unknown").to_string(),
+        cpal_version: option_env!("DEP_VERSION_CPAL").unwrap_or("unknown").to_string(),
+        // The model requests go to when they name none (GHOSTGPT_MODEL or the default)
+        model: crate::config::default_model(),
+        transcription_provider: format!("{:?}", config.transcription_provider),
+    }
+}
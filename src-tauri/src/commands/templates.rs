@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::{AppHandle, State};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+use crate::commands::ai::{send_message_impl, ChatOptions, Message, MessageContent};
+use crate::config::ConfigState;
+use crate::settings::SettingsStore;
+
+/// Placeholder filled from the clipboard contents
+const SELECTION_PLACEHOLDER: &str = "selection";
+
+/// Named prompt with `{{placeholder}}` variables
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub prompt: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TemplateResponse {
+    /// Prompt after placeholder expansion
+    pub prompt: String,
+    pub response: String,
+}
+
+/// List saved prompt templates
+#[tauri::command]
+pub fn list_templates(settings: State<'_, SettingsStore>) -> Vec<PromptTemplate> {
+    settings.get().templates
+}
+
+/// Create a template, or replace the one with the same name
+#[tauri::command]
+pub fn save_template(
+    settings: State<'_, SettingsStore>,
+    template: PromptTemplate,
+) -> Result<(), String> {
+    if template.name.trim().is_empty() {
+        return Err("Template name cannot be empty".to_string());
+    }
+
+    settings
+        .update(|s| match s.templates.iter_mut().find(|t| t.name == template.name) {
+            Some(existing) => *existing = template,
+            None => s.templates.push(template),
+        })
+        .map_err(|e| format!("Failed to save template: {}", e))
+}
+
+/// Delete a template by name
+#[tauri::command]
+pub fn delete_template(settings: State<'_, SettingsStore>, name: String) -> Result<(), String> {
+    let removed = settings
+        .update(|s| {
+            let before = s.templates.len();
+            s.templates.retain(|t| t.name != name);
+            before != s.templates.len()
+        })
+        .map_err(|e| format!("Failed to delete template: {}", e))?;
+
+    if !removed {
+        return Err(format!("Template not found: {}", name));
+    }
+    Ok(())
+}
+
+/// Expand a saved template and send it as a single-turn conversation
+///
+/// # Arguments
+/// * `name` - Template name
+/// * `variables` - Values for `{{placeholder}}`s; `{{selection}}` defaults to the clipboard
+/// * `images` - Optional base64-encoded images to attach
+#[tauri::command]
+pub async fn send_template(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    config: State<'_, ConfigState>,
+    name: String,
+    mut variables: HashMap<String, String>,
+    images: Option<Vec<String>>,
+) -> Result<TemplateResponse, String> {
+    config.touch();
+
+    let template = settings
+        .get()
+        .templates
+        .into_iter()
+        .find(|t| t.name == name)
+        .ok_or_else(|| format!("Template not found: {}", name))?;
+
+    if template.prompt.contains(&format!("{{{{{}}}}}", SELECTION_PLACEHOLDER))
+        && !variables.contains_key(SELECTION_PLACEHOLDER)
+    {
+        let selection = app
+            .clipboard()
+            .read_text()
+            .map_err(|e| format!("Failed to read clipboard: {}", e))?;
+        variables.insert(SELECTION_PLACEHOLDER.to_string(), selection);
+    }
+
+    let prompt = expand_template(&template.prompt, &variables).map_err(|e| e.to_string())?;
+
+    let messages = vec![Message {
+        role: String::from("user"),
+        content: MessageContent::Text(prompt.clone()),
+        pinned: false,
+    }];
+
+    let options = ChatOptions {
+        app: Some(app),
+        user_turn: true,
+        ..ChatOptions::default()
+    };
+
+    let api_key = config.get().openai_api_key;
+    let response = send_message_impl(&api_key, messages, images.unwrap_or_default(), &options)
+    .await
+    .map_err(|e| format!("OpenAI API error: {}", e))?;
+
+    Ok(TemplateResponse {
+        prompt,
+        response: response.content,
+    })
+}
+
+/// Replace every `{{name}}` in `template` with its value
+/// Fails listing any placeholders without a value
+fn expand_template(template: &str, variables: &HashMap<String, String>) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut missing = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+
+        let end = after
+            .find("}}")
+            .context("Unclosed '{{' in template")?;
+        let key = after[..end].trim();
+
+        match variables.get(key) {
+            Some(value) => output.push_str(value),
+            None => missing.push(key.to_string()),
+        }
+        rest = &after[end + 2..];
+    }
+    output.push_str(rest);
+
+    if !missing.is_empty() {
+        anyhow::bail!("Missing template variables: {}", missing.join(", "));
+    }
+
+    Ok(output)
+}
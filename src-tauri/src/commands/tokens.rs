@@ -0,0 +1,155 @@
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::commands::ai::{ContentPart, Message, MessageContent};
+use crate::commands::models;
+use crate::settings::SettingsStore;
+
+/// Rough English average; real tokenizers vary, so treat estimates as +-20%
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Role and separator tokens the API adds around every message
+const TOKENS_PER_MESSAGE: usize = 4;
+
+/// A high-detail image at the default 1024px tiling (85 base + 170 per tile)
+pub(crate) const TOKENS_PER_IMAGE: usize = 765;
+
+/// Estimated context usage, for a "% of context used" bar
+#[derive(Debug, Clone, Serialize)]
+pub struct ContextUsage {
+    pub model: String,
+    pub estimated_tokens: usize,
+    pub context_window: usize,
+    pub percent_used: f32,
+    /// At or above the configured warning threshold
+    pub warning: bool,
+}
+
+/// Estimate how much of the model's context window `messages` take up
+/// Emits `context-warning` with the same payload when at or above
+/// `context_warning_percent`, so it's time to summarize or start fresh
+#[tauri::command]
+pub fn get_context_usage(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    messages: Vec<Message>,
+    model: Option<String>,
+) -> Result<ContextUsage, String> {
+    let model = model
+        .filter(|m| !m.trim().is_empty())
+        .unwrap_or_else(crate::config::default_model);
+
+    let estimated_tokens = estimate_tokens(&messages);
+    let context_window = context_window(&model);
+    let percent_used = estimated_tokens as f32 / context_window as f32 * 100.0;
+    let warning = settings
+        .get()
+        .context_warning_percent
+        .is_some_and(|threshold| percent_used >= threshold as f32);
+
+    let usage = ContextUsage {
+        model,
+        estimated_tokens,
+        context_window,
+        percent_used,
+        warning,
+    };
+    if warning {
+        app.emit("context-warning", &usage)
+            .map_err(|e| format!("Failed to emit event: {}", e))?;
+    }
+    Ok(usage)
+}
+
+/// Approximate prompt tokens for `messages`, without a tokenizer
+/// Audio parts aren't counted
+pub(crate) fn estimate_tokens(messages: &[Message]) -> usize {
+    messages
+        .iter()
+        .map(|message| {
+            let content = match &message.content {
+                MessageContent::Text(text) => text_tokens(text),
+                MessageContent::Parts(parts) => parts
+                    .iter()
+                    .map(|part| match part {
+                        ContentPart::Text { text, .. } => text_tokens(text),
+                        ContentPart::ImageUrl { .. } => TOKENS_PER_IMAGE,
+                        ContentPart::InputAudio { .. } => 0,
+                    })
+                    .sum(),
+            };
+            TOKENS_PER_MESSAGE + content
+        })
+        .sum()
+}
+
+fn text_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Context window of `model` in tokens
+pub(crate) fn context_window(model: &str) -> usize {
+    models::capabilities(model).max_context
+}
+
+/// Tokens kept free on top of the input estimate, which can be off by about a fifth
+const OUTPUT_BUDGET_MIN_MARGIN: usize = 64;
+
+/// Largest reply `model` has room for after `messages`, for `max_tokens`
+/// Leaves a safety margin for estimation error and never exceeds the model's output limit;
+/// at least 1 even when the input already fills the context (the API then reports the overflow)
+pub(crate) fn output_budget(model: &str, messages: &[Message]) -> u32 {
+    let capabilities = models::capabilities(model);
+    let input = estimate_tokens(messages);
+    let margin = input / 5 + OUTPUT_BUDGET_MIN_MARGIN;
+
+    let available = capabilities.max_context.saturating_sub(input + margin);
+    available.clamp(1, capabilities.max_output) as u32
+}
+
+/// Reply size `auto_max_tokens` would allow for `messages`
+#[tauri::command]
+pub fn get_output_budget(messages: Vec<Message>, model: Option<String>) -> u32 {
+    let model = model
+        .filter(|m| !m.trim().is_empty())
+        .unwrap_or_else(crate::config::default_model);
+    output_budget(&model, &messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One user message estimated at exactly `tokens` tokens
+    fn input(tokens: usize) -> Vec<Message> {
+        let text = "a".repeat((tokens - TOKENS_PER_MESSAGE) * CHARS_PER_TOKEN);
+        let messages = vec![Message {
+            role: String::from("user"),
+            content: MessageContent::Text(text),
+            pinned: false,
+        }];
+        assert_eq!(estimate_tokens(&messages), tokens);
+        messages
+    }
+
+    #[test]
+    fn short_input_gets_the_full_output_limit() {
+        assert_eq!(output_budget("gpt-4o", &input(100)), 16_384);
+    }
+
+    #[test]
+    fn budget_fills_what_the_input_leaves_of_the_context() {
+        // gpt-4: 8192-token context; 6770 tokens of input keep a 1418-token margin
+        let budget = output_budget("gpt-4", &input(6_770));
+        assert_eq!(budget, 4);
+        assert!(6_770 + 1_418 + budget as usize <= context_window("gpt-4"));
+
+        assert_eq!(output_budget("gpt-4", &input(6_773)), 1);
+    }
+
+    #[test]
+    fn full_or_overflowing_context_still_asks_for_one_token() {
+        assert_eq!(output_budget("gpt-4", &input(6_774)), 1);
+        assert_eq!(output_budget("gpt-4", &input(9_000)), 1);
+    }
+}
@@ -0,0 +1,1034 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::commands::ai::{api_error, extra_headers, http_client, response_error};
+use crate::commands::logs;
+use crate::commands::retry;
+use crate::commands::audio::{
+    downmix_to_mono, estimated_wav_size, resample_linear, samples_to_wav, samples_to_wav_i16,
+};
+use crate::config::{api_url, require_key, AppConfig, TranscriptionProvider};
+use crate::settings::SettingsStore;
+
+/// Returned when the transcriber produces no text (silent recording, wrong device)
+#[derive(Debug)]
+pub struct EmptyTranscription;
+
+impl std::fmt::Display for EmptyTranscription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "No speech detected. Check that your microphone is connected, selected and not muted"
+        )
+    }
+}
+
+impl std::error::Error for EmptyTranscription {}
+
+/// Whisper rejects uploads larger than this
+const WHISPER_MAX_UPLOAD_BYTES: usize = 25 * 1024 * 1024;
+
+/// Chunk size used when splitting, leaving headroom for the multipart envelope
+const WHISPER_CHUNK_BYTES: usize = 24 * 1024 * 1024;
+
+/// Per-request overrides for `retranscribe`
+/// Backends reject options they can't honor rather than silently ignoring them
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TranscriptionOptions {
+    /// ISO-639-1 code (BCP-47 for Google) of the spoken language
+    pub language: Option<String>,
+    /// Text the transcript should follow in style and vocabulary (names, jargon)
+    pub prompt: Option<String>,
+    /// Provider model instead of the default (e.g. `whisper-1`, `nova-2`)
+    pub model: Option<String>,
+    /// Translate the speech to English instead of transcribing it
+    pub translate: bool,
+}
+
+impl TranscriptionOptions {
+    fn language(&self) -> Option<&str> {
+        self.language.as_deref().map(str::trim).filter(|l| !l.is_empty())
+    }
+
+    fn prompt(&self) -> Option<&str> {
+        self.prompt.as_deref().map(str::trim).filter(|p| !p.is_empty())
+    }
+
+    fn model(&self) -> Option<&str> {
+        self.model.as_deref().map(str::trim).filter(|m| !m.is_empty())
+    }
+}
+
+/// The selected provider can't honor an option
+fn unsupported(provider: &str, option: &str) -> anyhow::Error {
+    anyhow::anyhow!("{} doesn't support the {} option", provider, option)
+}
+
+/// Payload of the `transcription-progress` event
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptionProgress {
+    /// 1-based index of the chunk just transcribed
+    pub chunk: usize,
+    pub total: usize,
+}
+
+/// Payload of the `transcription-retry` event
+#[derive(Debug, Clone, Serialize)]
+pub struct TranscriptionRetry {
+    /// 1-based retry number
+    pub attempt: u32,
+    pub delay_ms: u64,
+    pub error: String,
+}
+
+/// Captured audio handed to a transcriber
+pub struct AudioClip<'a> {
+    /// Interleaved f32 samples in [-1, 1]
+    pub samples: &'a [f32],
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl AudioClip<'_> {
+    /// Length of the clip in seconds
+    pub fn duration_secs(&self) -> f64 {
+        let samples_per_second = self.sample_rate as f64 * self.channels.max(1) as f64;
+        if samples_per_second == 0.0 {
+            return 0.0;
+        }
+        self.samples.len() as f64 / samples_per_second
+    }
+}
+
+/// A speech-to-text backend
+#[async_trait]
+pub trait Transcriber: Send + Sync {
+    /// Transcribe the clip to plain text
+    async fn transcribe(&self, clip: &AudioClip<'_>) -> Result<String>;
+
+    /// The multipart uploads `transcribe` would send for the clip, one per request
+    fn describe_upload(&self, _clip: &AudioClip<'_>) -> Result<Vec<UploadDescription>> {
+        anyhow::bail!("This transcription backend doesn't upload a multipart form")
+    }
+}
+
+/// One multipart upload as `describe_transcription_upload` reports it; the audio
+/// itself is left out
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadDescription {
+    pub url: String,
+    /// Text fields in the order they are sent, after the file part
+    pub fields: Vec<UploadField>,
+    pub file: UploadFile,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadField {
+    pub name: &'static str,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadFile {
+    /// Form field the audio is sent under
+    pub field: &'static str,
+    pub file_name: &'static str,
+    pub mime: &'static str,
+    pub bytes: usize,
+    /// e.g. `WAV, 16000 Hz, 1 channel, 16-bit PCM`
+    pub format: String,
+}
+
+/// A transcription upload: the audio as the `file` part plus text fields
+/// Built once, then either sent or described, so a dry run shows exactly what is sent
+struct UploadForm {
+    url: String,
+    audio: Vec<u8>,
+    format: String,
+    fields: Vec<(&'static str, String)>,
+}
+
+impl UploadForm {
+    fn new(url: impl Into<String>, audio: Vec<u8>, format: String) -> Self {
+        Self {
+            url: url.into(),
+            audio,
+            format,
+            fields: Vec::new(),
+        }
+    }
+
+    fn text(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.fields.push((name, value.into()));
+        self
+    }
+
+    fn describe(&self) -> UploadDescription {
+        UploadDescription {
+            url: self.url.clone(),
+            fields: self
+                .fields
+                .iter()
+                .map(|(name, value)| UploadField {
+                    name: *name,
+                    value: value.clone(),
+                })
+                .collect(),
+            file: UploadFile {
+                field: "file",
+                file_name: "audio.wav",
+                mime: "audio/wav",
+                bytes: self.audio.len(),
+                format: self.format.clone(),
+            },
+        }
+    }
+
+    /// The request URL and the form to post to it
+    fn into_form(self) -> Result<(String, reqwest::multipart::Form)> {
+        use reqwest::multipart;
+
+        let part = multipart::Part::bytes(self.audio)
+            .file_name("audio.wav")
+            .mime_str("audio/wav")?;
+        let form = self
+            .fields
+            .into_iter()
+            .fold(multipart::Form::new().part("file", part), |form, (name, value)| {
+                form.text(name, value)
+            });
+        Ok((self.url, form))
+    }
+}
+
+/// `UploadFile::format` for a WAV upload
+fn wav_format(sample_rate: u32, channels: u16, encoding: &str) -> String {
+    format!(
+        "WAV, {} Hz, {} channel{}, {}",
+        sample_rate,
+        channels,
+        if channels == 1 { "" } else { "s" },
+        encoding
+    )
+}
+
+/// Build the transcriber selected in the configuration
+/// `app` receives progress events from backends that split long recordings
+pub fn transcriber(config: &AppConfig, app: Option<&AppHandle>) -> Result<Box<dyn Transcriber>> {
+    transcriber_with(config, app, &TranscriptionOptions::default())
+}
+
+/// Like `transcriber`, with per-request overrides
+/// Without a `language` override the `transcription_language` setting is used
+pub fn transcriber_with(
+    config: &AppConfig,
+    app: Option<&AppHandle>,
+    options: &TranscriptionOptions,
+) -> Result<Box<dyn Transcriber>> {
+    let mut options = options.clone();
+    if options.language().is_none() {
+        options.language =
+            app.and_then(|app| app.state::<SettingsStore>().get().transcription_language);
+    }
+    let transcriber: Box<dyn Transcriber> = match config.transcription_provider {
+        TranscriptionProvider::OpenAi => Box::new(OpenAiTranscriber {
+            api_key: config.openai_api_key.clone(),
+            app: app.cloned(),
+            options,
+        }),
+        TranscriptionProvider::Deepgram => {
+            if options.prompt().is_some() {
+                return Err(unsupported("Deepgram", "prompt"));
+            }
+            if options.translate {
+                return Err(unsupported("Deepgram", "translate"));
+            }
+            Box::new(DeepgramTranscriber {
+                api_key: config
+                    .deepgram_api_key
+                    .clone()
+                    .context("DEEPGRAM_API_KEY is required for the Deepgram transcriber")?,
+                options,
+            })
+        }
+        TranscriptionProvider::Google => {
+            if options.prompt().is_some() {
+                return Err(unsupported("Google Speech", "prompt"));
+            }
+            if options.translate {
+                return Err(unsupported("Google Speech", "translate"));
+            }
+            Box::new(GoogleTranscriber {
+                api_key: config
+                    .google_speech_api_key
+                    .clone()
+                    .context("GOOGLE_SPEECH_API_KEY is required for the Google transcriber")?,
+                language: options
+                    .language()
+                    .unwrap_or(&config.google_speech_language)
+                    .to_string(),
+                model: options.model().map(str::to_string),
+            })
+        }
+        TranscriptionProvider::WhisperCpp => {
+            let transcriber = WhisperCppTranscriber::new(&config.whisper_cpp_url, options);
+            if transcriber.style == FormStyle::OpenAi && transcriber.options.translate {
+                return Err(unsupported("An OpenAI-style transcription endpoint", "translate"));
+            }
+            Box::new(transcriber)
+        }
+    };
+    Ok(transcriber)
+}
+
+/// How long `whisper_cpp_reachable` waits for the server to answer
+const WHISPER_CPP_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Whether a server answers at the configured whisper.cpp URL; any HTTP response counts
+pub(crate) async fn whisper_cpp_reachable(config: &AppConfig) -> bool {
+    http_client()
+        .get(config.whisper_cpp_url.trim())
+        .timeout(WHISPER_CPP_PROBE_TIMEOUT)
+        .send()
+        .await
+        .is_ok()
+}
+
+/// Model name of the configured provider, as recorded in the usage log
+pub fn provider_model(config: &AppConfig) -> &'static str {
+    match config.transcription_provider {
+        TranscriptionProvider::OpenAi => "whisper-1",
+        TranscriptionProvider::Deepgram => "deepgram-nova-2",
+        TranscriptionProvider::Google => "google-speech-v1",
+        TranscriptionProvider::WhisperCpp => "whisper-cpp",
+    }
+}
+
+/// Transcribe with the configured provider, normalizing the result
+/// Blank results are reported as `EmptyTranscription` rather than an empty success
+pub async fn transcribe(
+    config: &AppConfig,
+    clip: &AudioClip<'_>,
+    app: Option<&AppHandle>,
+) -> Result<String> {
+    transcribe_with(config, clip, app, &TranscriptionOptions::default()).await
+}
+
+/// Like `transcribe`, with per-request overrides
+pub async fn transcribe_with(
+    config: &AppConfig,
+    clip: &AudioClip<'_>,
+    app: Option<&AppHandle>,
+    options: &TranscriptionOptions,
+) -> Result<String> {
+    let transcriber = transcriber_with(config, app, options)?;
+    let policy = app
+        .map(|app| app.state::<SettingsStore>().get().transcription_retry)
+        .unwrap_or_default();
+
+    let on_retry = |attempt, delay: Duration, error: &anyhow::Error| {
+        eprintln!("Transcription failed ({}), retrying in {:?}", error, delay);
+        if let Some(app) = app {
+            let retrying = TranscriptionRetry {
+                attempt,
+                delay_ms: delay.as_millis() as u64,
+                error: error.to_string(),
+            };
+            let _ = app.emit("transcription-retry", retrying);
+        }
+    };
+    let model = provider_model(config);
+    let text = retry::with_retry(&policy, on_retry, || async {
+        let started = std::time::Instant::now();
+        let result = transcriber.transcribe(clip).await;
+        logs::log_request(app, "transcription", model, started.elapsed(), result.as_ref().err());
+        result
+    })
+    .await?;
+
+    transcript_text(&text)
+}
+
+/// `text` trimmed, or `EmptyTranscription` when nothing is left
+fn transcript_text(text: &str) -> Result<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(EmptyTranscription.into());
+    }
+    Ok(text.to_string())
+}
+
+/// Detect the spoken language using Whisper's `verbose_json` response
+/// Only the OpenAI backend reports a language, so this always uses Whisper
+pub async fn detect_language(config: &AppConfig, clip: &AudioClip<'_>) -> Result<String> {
+    use reqwest::multipart;
+
+    let samples = to_whisper_rate(clip);
+    let audio_data = samples_to_wav(&samples, WHISPER_SAMPLE_RATE, 1)?;
+
+    let part = multipart::Part::bytes(audio_data)
+        .file_name("audio.wav")
+        .mime_str("audio/wav")?;
+
+    let form = multipart::Form::new()
+        .part("file", part)
+        .text("model", "whisper-1")
+        .text("response_format", "verbose_json");
+
+    let client = http_client();
+    let response = client
+        .post(api_url("audio/transcriptions"))
+        .headers(extra_headers())
+        .header(
+            "Authorization",
+            format!("Bearer {}", require_key(&config.openai_api_key)?),
+        )
+        .multipart(form)
+        .send()
+        .await
+        .context("Failed to send request to Whisper API")?;
+
+    if !response.status().is_success() {
+        return Err(response_error(response).await);
+    }
+
+    #[derive(Deserialize)]
+    struct VerboseResponse {
+        language: Option<String>,
+    }
+
+    let verbose: VerboseResponse = response
+        .json()
+        .await
+        .context("Failed to parse Whisper response")?;
+
+    let language = verbose.language.context("Whisper did not report a language")?;
+    Ok(language_code(&language))
+}
+
+/// A stretch of the transcript and where it lies in the recording, in seconds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptSegment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// Transcribe with Whisper's `verbose_json` response to get segment timestamps
+/// Always OpenAI Whisper, whatever the transcription provider; `language` is an
+/// ISO-639-1 hint as for other transcriptions
+/// Clips over the upload limit are split and their times offset to the whole clip
+pub async fn transcribe_segments(
+    config: &AppConfig,
+    clip: &AudioClip<'_>,
+    language: Option<&str>,
+) -> Result<Vec<TranscriptSegment>> {
+    use reqwest::multipart;
+
+    #[derive(Deserialize)]
+    struct VerboseResponse {
+        #[serde(default)]
+        segments: Vec<TranscriptSegment>,
+    }
+
+    let api_key = require_key(&config.openai_api_key).context(
+        "Segment timestamps come from OpenAI Whisper, which needs OPENAI_API_KEY \
+         (or GhostGPT is locked)",
+    )?;
+    let language = language.map(str::trim).filter(|l| !l.is_empty());
+    let samples = to_whisper_rate(clip);
+    let clip = AudioClip {
+        samples: &samples,
+        sample_rate: WHISPER_SAMPLE_RATE,
+        channels: 1,
+    };
+
+    let client = http_client();
+    let mut segments = Vec::new();
+    let mut offset = 0.0;
+    for chunk in split_clip(&clip, WHISPER_CHUNK_BYTES) {
+        let part = multipart::Part::bytes(samples_to_wav(chunk.samples, WHISPER_SAMPLE_RATE, 1)?)
+            .file_name("audio.wav")
+            .mime_str("audio/wav")?;
+        let mut form = multipart::Form::new()
+            .part("file", part)
+            .text("model", "whisper-1")
+            .text("response_format", "verbose_json")
+            .text("timestamp_granularities[]", "segment");
+        if let Some(language) = language {
+            form = form.text("language", language.to_string());
+        }
+
+        let response = client
+            .post(api_url("audio/transcriptions"))
+            .headers(extra_headers())
+            .header("Authorization", format!("Bearer {}", api_key))
+            .multipart(form)
+            .send()
+            .await
+            .context("Failed to send request to Whisper API")?;
+        if !response.status().is_success() {
+            return Err(response_error(response).await);
+        }
+
+        let verbose: VerboseResponse = response
+            .json()
+            .await
+            .context("Failed to parse Whisper response")?;
+        segments.extend(verbose.segments.into_iter().map(|s| TranscriptSegment {
+            start: s.start + offset,
+            end: s.end + offset,
+            text: s.text.trim().to_string(),
+        }));
+        offset += chunk.duration_secs();
+    }
+
+    segments.retain(|s| !s.text.is_empty());
+    if segments.is_empty() {
+        return Err(EmptyTranscription.into());
+    }
+    Ok(segments)
+}
+
+/// Whisper reports languages by English name ("german"); map common ones to ISO-639-1
+fn language_code(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    let code = match name.as_str() {
+        "english" => "en",
+        "german" => "de",
+        "french" => "fr",
+        "spanish" => "es",
+        "italian" => "it",
+        "portuguese" => "pt",
+        "dutch" => "nl",
+        "russian" => "ru",
+        "ukrainian" => "uk",
+        "polish" => "pl",
+        "czech" => "cs",
+        "swedish" => "sv",
+        "norwegian" => "no",
+        "danish" => "da",
+        "finnish" => "fi",
+        "turkish" => "tr",
+        "greek" => "el",
+        "arabic" => "ar",
+        "hebrew" => "he",
+        "hindi" => "hi",
+        "chinese" => "zh",
+        "japanese" => "ja",
+        "korean" => "ko",
+        "vietnamese" => "vi",
+        "indonesian" => "id",
+        // Already a code, or a language without a mapping
+        _ => return name,
+    };
+    code.to_string()
+}
+
+/// Sample rate Whisper models run at
+/// Capture rate is a separate setting, so audio is resampled to this before upload
+const WHISPER_SAMPLE_RATE: u32 = 16_000;
+
+/// Downmix and resample a clip to 16 kHz mono
+/// Whisper resamples everything to this anyway, so uploading more is wasted bandwidth
+fn to_whisper_rate(clip: &AudioClip<'_>) -> Vec<f32> {
+    let mono = downmix_to_mono(clip.samples, clip.channels);
+    resample_linear(&mono, clip.sample_rate, WHISPER_SAMPLE_RATE)
+}
+
+/// Split a clip into frame-aligned pieces whose WAV encoding stays under `max_bytes`
+fn split_clip<'a>(clip: &AudioClip<'a>, max_bytes: usize) -> Vec<AudioClip<'a>> {
+    let channels = clip.channels.max(1) as usize;
+    let max_samples = (max_bytes - estimated_wav_size(0)) / std::mem::size_of::<f32>();
+    let chunk_samples = (max_samples / channels).max(1) * channels;
+
+    clip.samples
+        .chunks(chunk_samples)
+        .map(|samples| AudioClip {
+            samples,
+            sample_rate: clip.sample_rate,
+            channels: clip.channels,
+        })
+        .collect()
+}
+
+/// OpenAI Whisper API
+pub struct OpenAiTranscriber {
+    api_key: String,
+    app: Option<AppHandle>,
+    options: TranscriptionOptions,
+}
+
+#[async_trait]
+impl Transcriber for OpenAiTranscriber {
+    async fn transcribe(&self, clip: &AudioClip<'_>) -> Result<String> {
+        let samples = to_whisper_rate(clip);
+        let clip = &AudioClip {
+            samples: &samples,
+            sample_rate: WHISPER_SAMPLE_RATE,
+            channels: 1,
+        };
+
+        if estimated_wav_size(clip.samples.len()) <= WHISPER_MAX_UPLOAD_BYTES {
+            return self.transcribe_chunk(clip).await;
+        }
+
+        let chunks = split_clip(clip, WHISPER_CHUNK_BYTES);
+        let total = chunks.len();
+        println!("Recording exceeds Whisper's upload limit, splitting into {} chunks", total);
+
+        let mut texts = Vec::with_capacity(total);
+        for (i, chunk) in chunks.iter().enumerate() {
+            let text = self
+                .transcribe_chunk(chunk)
+                .await
+                .with_context(|| format!("Chunk {} of {} failed", i + 1, total))?;
+            texts.push(text.trim().to_string());
+
+            if let Some(app) = &self.app {
+                let _ = app.emit(
+                    "transcription-progress",
+                    TranscriptionProgress { chunk: i + 1, total },
+                );
+            }
+        }
+
+        Ok(texts
+            .into_iter()
+            .filter(|t| !t.is_empty())
+            .collect::<Vec<_>>()
+            .join(" "))
+    }
+
+    fn describe_upload(&self, clip: &AudioClip<'_>) -> Result<Vec<UploadDescription>> {
+        let samples = to_whisper_rate(clip);
+        let clip = AudioClip {
+            samples: &samples,
+            sample_rate: WHISPER_SAMPLE_RATE,
+            channels: 1,
+        };
+        let chunks = if estimated_wav_size(clip.samples.len()) <= WHISPER_MAX_UPLOAD_BYTES {
+            vec![clip]
+        } else {
+            split_clip(&clip, WHISPER_CHUNK_BYTES)
+        };
+        chunks
+            .iter()
+            .map(|chunk| Ok(self.upload_form(chunk)?.describe()))
+            .collect()
+    }
+}
+
+impl OpenAiTranscriber {
+    /// Form for one clip that fits within the size limit
+    fn upload_form(&self, clip: &AudioClip<'_>) -> Result<UploadForm> {
+        let audio_data = samples_to_wav(clip.samples, clip.sample_rate, clip.channels)?;
+        let format = wav_format(clip.sample_rate, clip.channels, "32-bit float");
+
+        // Translations always produce English, so they take no language
+        let options = &self.options;
+        let url = if options.translate {
+            api_url("audio/translations")
+        } else {
+            api_url("audio/transcriptions")
+        };
+        let mut form = UploadForm::new(url, audio_data, format)
+            .text("model", options.model().unwrap_or("whisper-1"));
+        if let Some(prompt) = options.prompt() {
+            form = form.text("prompt", prompt);
+        }
+        if let Some(language) = options.language().filter(|_| !options.translate) {
+            form = form.text("language", language);
+        }
+        Ok(form)
+    }
+
+    /// Upload one clip that fits within the size limit
+    async fn transcribe_chunk(&self, clip: &AudioClip<'_>) -> Result<String> {
+        let upload = self.upload_form(clip)?;
+        println!("Encoded {} bytes of WAV data", upload.audio.len());
+        let (url, form) = upload.into_form()?;
+
+        let client = http_client();
+        let response = client
+            .post(url)
+            .headers(extra_headers())
+            .header("Authorization", format!("Bearer {}", require_key(&self.api_key)?))
+            .multipart(form)
+            .send()
+            .await
+            .context("Failed to send request to Whisper API")?;
+
+        if !response.status().is_success() {
+            return Err(response_error(response).await);
+        }
+
+        let whisper_response: WhisperResponse = response
+            .json()
+            .await
+            .context("Failed to parse Whisper response")?;
+
+        Ok(whisper_response.text)
+    }
+}
+
+#[derive(Deserialize)]
+struct WhisperResponse {
+    text: String,
+}
+
+/// Local whisper.cpp `whisper-server`
+pub struct WhisperCppTranscriber {
+    url: String,
+    style: FormStyle,
+    options: TranscriptionOptions,
+}
+
+/// Multipart convention of the server at the configured URL
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FormStyle {
+    /// `/inference`: `file`, `response_format`, `temperature`
+    WhisperCpp,
+    /// `/v1/audio/transcriptions` (whisper.cpp with `--inference-path`, or other
+    /// OpenAI-compatible servers): `file`, `model`
+    OpenAi,
+}
+
+impl WhisperCppTranscriber {
+    fn upload_form(&self, clip: &AudioClip<'_>) -> Result<UploadForm> {
+        // whisper.cpp reads 16 kHz 16-bit mono WAV unless the server runs with --convert
+        let samples = to_whisper_rate(clip);
+        let audio_data = samples_to_wav_i16(&samples, WHISPER_SAMPLE_RATE, 1)?;
+        let format = wav_format(WHISPER_SAMPLE_RATE, 1, "16-bit PCM");
+
+        let options = &self.options;
+        let form = UploadForm::new(self.url.as_str(), audio_data, format);
+        let mut form = match self.style {
+            FormStyle::WhisperCpp => form
+                .text("response_format", "json")
+                .text("temperature", "0.0")
+                .text("translate", options.translate.to_string()),
+            FormStyle::OpenAi => form
+                .text("model", options.model().unwrap_or("whisper-1"))
+                .text("response_format", "json"),
+        };
+        // The server has one model loaded; `/inference` takes no model field
+        if let Some(language) = options.language() {
+            form = form.text("language", language);
+        }
+        if let Some(prompt) = options.prompt() {
+            form = form.text("prompt", prompt);
+        }
+        Ok(form)
+    }
+
+    /// The form style is picked from the URL path; a bare host gets `/inference`
+    fn new(url: &str, options: TranscriptionOptions) -> Self {
+        let url = url.trim().trim_end_matches('/');
+        let path = url
+            .split_once("://")
+            .map_or(url, |(_, rest)| rest)
+            .split_once('/')
+            .map(|(_, path)| path)
+            .unwrap_or("");
+
+        let (url, style) = if path.is_empty() {
+            (format!("{}/inference", url), FormStyle::WhisperCpp)
+        } else if path.ends_with("audio/transcriptions") {
+            (url.to_string(), FormStyle::OpenAi)
+        } else {
+            (url.to_string(), FormStyle::WhisperCpp)
+        };
+        Self {
+            url,
+            style,
+            options,
+        }
+    }
+}
+
+#[async_trait]
+impl Transcriber for WhisperCppTranscriber {
+    async fn transcribe(&self, clip: &AudioClip<'_>) -> Result<String> {
+        let (url, form) = self.upload_form(clip)?.into_form()?;
+
+        let client = http_client();
+        let response = client
+            .post(&url)
+            .multipart(form)
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach whisper.cpp server at {}", url))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            // Typed as `ApiError` so 429s and 5xx are retried like the OpenAI paths
+            let message = format!("whisper.cpp returned error {}: {}", status, error_text);
+            return Err(api_error(status, error_text).context(message));
+        }
+
+        // Both styles answer `{"text": ...}`; whisper.cpp reports failures as `{"error": ...}`
+        #[derive(Deserialize)]
+        struct WhisperCppResponse {
+            text: Option<String>,
+            error: Option<String>,
+        }
+
+        let parsed: WhisperCppResponse = response
+            .json()
+            .await
+            .context("Failed to parse whisper.cpp response")?;
+        match (parsed.text, parsed.error) {
+            (_, Some(error)) => anyhow::bail!("whisper.cpp error: {}", error),
+            (Some(text), None) => Ok(text),
+            (None, None) => anyhow::bail!("whisper.cpp response contained no text"),
+        }
+    }
+
+    fn describe_upload(&self, clip: &AudioClip<'_>) -> Result<Vec<UploadDescription>> {
+        Ok(vec![self.upload_form(clip)?.describe()])
+    }
+}
+
+/// Deepgram pre-recorded transcription
+pub struct DeepgramTranscriber {
+    api_key: String,
+    options: TranscriptionOptions,
+}
+
+#[async_trait]
+impl Transcriber for DeepgramTranscriber {
+    async fn transcribe(&self, clip: &AudioClip<'_>) -> Result<String> {
+        let audio_data = samples_to_wav(clip.samples, clip.sample_rate, clip.channels)?;
+
+        let mut query = vec![
+            ("model", self.options.model().unwrap_or("nova-2")),
+            ("smart_format", "true"),
+        ];
+        if let Some(language) = self.options.language() {
+            query.push(("language", language));
+        }
+
+        let client = http_client();
+        let response = client
+            .post("https://api.deepgram.com/v1/listen")
+            .query(&query)
+            .header("Authorization", format!("Token {}", require_key(&self.api_key)?))
+            .header("Content-Type", "audio/wav")
+            .body(audio_data)
+            .send()
+            .await
+            .context("Failed to send request to Deepgram")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let message = format!("Deepgram returned error {}: {}", status, error_text);
+            return Err(api_error(status, error_text).context(message));
+        }
+
+        #[derive(Deserialize)]
+        struct DeepgramResponse {
+            results: DeepgramResults,
+        }
+        #[derive(Deserialize)]
+        struct DeepgramResults {
+            channels: Vec<DeepgramChannel>,
+        }
+        #[derive(Deserialize)]
+        struct DeepgramChannel {
+            alternatives: Vec<DeepgramAlternative>,
+        }
+        #[derive(Deserialize)]
+        struct DeepgramAlternative {
+            transcript: String,
+        }
+
+        let deepgram_response: DeepgramResponse = response
+            .json()
+            .await
+            .context("Failed to parse Deepgram response")?;
+
+        Ok(deepgram_response
+            .results
+            .channels
+            .first()
+            .and_then(|c| c.alternatives.first())
+            .map(|a| a.transcript.clone())
+            .unwrap_or_default())
+    }
+}
+
+/// Google Cloud Speech-to-Text (v1 `speech:recognize`)
+pub struct GoogleTranscriber {
+    api_key: String,
+    language: String,
+    /// e.g. `latest_long`; Google picks one when unset
+    model: Option<String>,
+}
+
+#[async_trait]
+impl Transcriber for GoogleTranscriber {
+    async fn transcribe(&self, clip: &AudioClip<'_>) -> Result<String> {
+        use base64::Engine;
+
+        // Google only accepts integer PCM for LINEAR16
+        let audio_data = samples_to_wav_i16(clip.samples, clip.sample_rate, clip.channels)?;
+
+        let mut body = serde_json::json!({
+            "config": {
+                "encoding": "LINEAR16",
+                "sampleRateHertz": clip.sample_rate,
+                "audioChannelCount": clip.channels,
+                "languageCode": self.language,
+            },
+            "audio": {
+                "content": base64::engine::general_purpose::STANDARD.encode(&audio_data),
+            },
+        });
+        if let Some(model) = &self.model {
+            body["config"]["model"] = serde_json::json!(model);
+        }
+
+        let client = http_client();
+        let response = client
+            .post("https://speech.googleapis.com/v1/speech:recognize")
+            .query(&[("key", require_key(&self.api_key)?)])
+            .json(&body)
+            .send()
+            .await
+            .context("Failed to send request to Google Speech")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            let message = format!("Google Speech returned error {}: {}", status, error_text);
+            return Err(api_error(status, error_text).context(message));
+        }
+
+        #[derive(Deserialize)]
+        struct GoogleResponse {
+            #[serde(default)]
+            results: Vec<GoogleResult>,
+        }
+        #[derive(Deserialize)]
+        struct GoogleResult {
+            alternatives: Vec<GoogleAlternative>,
+        }
+        #[derive(Deserialize)]
+        struct GoogleAlternative {
+            transcript: String,
+        }
+
+        let google_response: GoogleResponse = response
+            .json()
+            .await
+            .context("Failed to parse Google Speech response")?;
+
+        // Each result covers a consecutive portion of the audio
+        Ok(google_response
+            .results
+            .iter()
+            .filter_map(|r| r.alternatives.first())
+            .map(|a| a.transcript.trim())
+            .collect::<Vec<_>>()
+            .join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_whisper_text_is_empty_transcription() {
+        for body in [r#"{"text": ""}"#, r#"{"text": "  \n "}"#] {
+            let response: WhisperResponse = serde_json::from_str(body).unwrap();
+            let error = transcript_text(&response.text).unwrap_err();
+            assert!(error.is::<EmptyTranscription>(), "{}", body);
+        }
+    }
+
+    #[test]
+    fn transcript_text_is_trimmed() {
+        let response: WhisperResponse = serde_json::from_str(r#"{"text": " Hello. "}"#).unwrap();
+        assert_eq!(transcript_text(&response.text).unwrap(), "Hello.");
+    }
+
+    #[test]
+    fn wav_size_estimate_never_undercounts() {
+        for len in [0, 1, 1_000, 16_000] {
+            let encoded = samples_to_wav(&vec![0.0; len], WHISPER_SAMPLE_RATE, 1).unwrap().len();
+            let estimated = estimated_wav_size(len);
+            assert!(estimated >= encoded, "{} samples: {} < {}", len, estimated, encoded);
+            assert!(estimated - encoded <= 80, "{} samples: estimate far too high", len);
+        }
+    }
+
+    #[test]
+    fn clips_are_split_right_at_the_size_limit() {
+        let samples = vec![0.0; 2_001];
+        let max_bytes = estimated_wav_size(1_000);
+        let clip = |len, channels| AudioClip {
+            samples: &samples[..len],
+            sample_rate: WHISPER_SAMPLE_RATE,
+            channels,
+        };
+
+        assert_eq!(split_clip(&clip(1_000, 1), max_bytes).len(), 1);
+        let chunks = split_clip(&clip(1_001, 1), max_bytes);
+        assert_eq!(chunks.iter().map(|c| c.samples.len()).collect::<Vec<_>>(), [1_000, 1]);
+        for chunk in &chunks {
+            let encoded = samples_to_wav(chunk.samples, chunk.sample_rate, 1).unwrap();
+            assert!(encoded.len() <= max_bytes);
+        }
+
+        // Stereo chunks hold whole frames
+        let stereo = split_clip(&clip(2_000, 2), estimated_wav_size(999));
+        assert!(stereo.iter().all(|c| c.samples.len() % 2 == 0));
+    }
+
+    #[test]
+    fn stereo_48k_recording_is_uploaded_as_16k_mono() {
+        // One second with a tone on the left channel and silence on the right
+        let samples: Vec<f32> = (0..48_000)
+            .flat_map(|i| {
+                let t = i as f32 / 48_000.0;
+                [0.5 * (t * 440.0 * std::f32::consts::TAU).sin(), 0.0]
+            })
+            .collect();
+        let recording = AudioClip {
+            samples: &samples,
+            sample_rate: 48_000,
+            channels: 2,
+        };
+        let transcriber = OpenAiTranscriber {
+            api_key: String::new(),
+            app: None,
+            options: TranscriptionOptions::default(),
+        };
+
+        let uploads = transcriber.describe_upload(&recording).unwrap();
+        assert_eq!(uploads.len(), 1);
+        assert_eq!(uploads[0].file.format, "WAV, 16000 Hz, 1 channel, 32-bit float");
+
+        let mono = to_whisper_rate(&recording);
+        let upload = transcriber
+            .upload_form(&AudioClip {
+                samples: &mono,
+                sample_rate: WHISPER_SAMPLE_RATE,
+                channels: 1,
+            })
+            .unwrap();
+        assert_eq!(upload.audio.len(), uploads[0].file.bytes);
+        let reader = hound::WavReader::new(std::io::Cursor::new(&upload.audio)).unwrap();
+        assert_eq!(reader.spec().sample_rate, 16_000);
+        assert_eq!(reader.spec().channels, 1);
+        assert_eq!(reader.duration(), 16_000);
+    }
+}
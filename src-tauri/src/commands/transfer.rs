@@ -0,0 +1,208 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::value::RawValue;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::State;
+
+use crate::commands::history;
+
+/// Results whose JSON is larger than this are delivered in chunks
+/// Well under the size at which webview IPC starts failing or stalling
+const MAX_INLINE_BYTES: usize = 4 * 1024 * 1024;
+
+const CHUNK_BYTES: usize = 1024 * 1024;
+
+/// Unread transfers kept; older ones are dropped so abandoned ones can't pile up
+/// Transfers already being read don't count, so a new one never cuts off a read
+const MAX_PENDING_TRANSFERS: usize = 4;
+
+/// A transfer is abandoned once no chunk of it was read for this long
+const TRANSFER_IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// A command result or event payload, either inline or staged for `read_transfer_chunk`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "delivery", rename_all = "kebab-case")]
+pub enum Delivery {
+    /// The value's JSON, serialized once and passed through as is
+    Inline { value: Box<RawValue> },
+    /// Read chunks `0..chunks` with `read_transfer_chunk`, concatenate them and
+    /// parse the result as JSON
+    Chunked {
+        transfer_id: String,
+        chunks: usize,
+        bytes: usize,
+    },
+}
+
+struct PendingTransfer {
+    id: String,
+    chunks: Vec<String>,
+    /// When the transfer was staged or a chunk of it last read
+    last_used: Instant,
+    /// At least one chunk was read
+    started: bool,
+}
+
+/// Oversized results waiting to be read, oldest first
+#[derive(Default)]
+pub struct Transfers(Mutex<VecDeque<PendingTransfer>>);
+
+impl Transfers {
+    /// `value` inline, or its JSON staged in chunks if it's too large for one IPC message
+    pub(crate) fn deliver<T: Serialize + ?Sized>(&self, value: &T) -> Result<Delivery> {
+        let json = serde_json::to_string(value)?;
+        if json.len() <= MAX_INLINE_BYTES {
+            return Ok(Delivery::Inline {
+                value: RawValue::from_string(json)?,
+            });
+        }
+
+        let chunks = split_chunks(&json, CHUNK_BYTES);
+        let transfer_id = history::new_id();
+        let delivery = Delivery::Chunked {
+            transfer_id: transfer_id.clone(),
+            chunks: chunks.len(),
+            bytes: json.len(),
+        };
+
+        let mut pending = self.0.lock().unwrap();
+        pending.retain(|transfer| transfer.last_used.elapsed() < TRANSFER_IDLE_TIMEOUT);
+        let mut unread = pending.iter().filter(|transfer| !transfer.started).count();
+        while unread >= MAX_PENDING_TRANSFERS {
+            if let Some(oldest) = pending.iter().position(|transfer| !transfer.started) {
+                pending.remove(oldest);
+            }
+            unread -= 1;
+        }
+        pending.push_back(PendingTransfer {
+            id: transfer_id,
+            chunks,
+            last_used: Instant::now(),
+            started: false,
+        });
+        Ok(delivery)
+    }
+
+    /// Chunk `index` of a transfer; the transfer is released once its last chunk is read
+    pub fn read_chunk(&self, transfer_id: &str, index: usize) -> Option<String> {
+        let mut pending = self.0.lock().unwrap();
+        let position = pending.iter().position(|transfer| transfer.id == transfer_id)?;
+
+        let transfer = &mut pending[position];
+        let chunk = transfer.chunks.get(index)?.clone();
+        transfer.last_used = Instant::now();
+        transfer.started = true;
+        if index + 1 == transfer.chunks.len() {
+            pending.remove(position);
+        }
+        Some(chunk)
+    }
+}
+
+/// Split into pieces of at most `max` bytes without cutting a UTF-8 sequence
+fn split_chunks(text: &str, max: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut end = rest.len().min(max);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk.to_string());
+        rest = tail;
+    }
+    chunks
+}
+
+/// Read one chunk of a result returned as `Delivery::Chunked`
+#[tauri::command]
+pub fn read_transfer_chunk(
+    transfers: State<'_, Transfers>,
+    transfer_id: String,
+    index: usize,
+) -> Result<String, String> {
+    transfers
+        .read_chunk(&transfer_id, index)
+        .ok_or_else(|| format!("Transfer {} has no chunk {}", transfer_id, index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::ai::ChatResponse;
+
+    /// Read a delivery back the way the frontend does
+    fn receive(transfers: &Transfers, delivery: Delivery) -> String {
+        match delivery {
+            Delivery::Inline { value } => value.get().to_string(),
+            Delivery::Chunked {
+                transfer_id,
+                chunks,
+                bytes,
+            } => {
+                let json: String = (0..chunks)
+                    .map(|index| transfers.read_chunk(&transfer_id, index).unwrap())
+                    .collect();
+                assert_eq!(json.len(), bytes);
+                json
+            }
+        }
+    }
+
+    fn response(content: String) -> ChatResponse {
+        ChatResponse {
+            content,
+            usage: None,
+            logprobs: None,
+            cancelled: false,
+            citations: Vec::new(),
+            image_id: None,
+        }
+    }
+
+    #[test]
+    fn multi_megabyte_response_arrives_intact() {
+        let transfers = Transfers::default();
+        // Multi-byte characters make chunk boundaries fall inside UTF-8 sequences
+        let content = "Grüße, 世界! ".repeat(1_000_000);
+        assert!(content.len() > 3 * MAX_INLINE_BYTES);
+
+        let delivery = transfers.deliver(&response(content.clone())).unwrap();
+        assert!(matches!(delivery, Delivery::Chunked { .. }));
+
+        let json = receive(&transfers, delivery);
+        let received: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(received["content"], content);
+        assert!(transfers.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn small_response_is_inline() {
+        let transfers = Transfers::default();
+        let delivery = transfers.deliver(&response(String::from("Hi"))).unwrap();
+        assert!(matches!(delivery, Delivery::Inline { .. }));
+
+        let received: serde_json::Value =
+            serde_json::from_str(&receive(&transfers, delivery)).unwrap();
+        assert_eq!(received["content"], "Hi");
+    }
+
+    #[test]
+    fn transfer_being_read_is_not_evicted() {
+        let transfers = Transfers::default();
+        let large = "x".repeat(MAX_INLINE_BYTES + 1);
+        let Delivery::Chunked { transfer_id, .. } = transfers.deliver(&large).unwrap() else {
+            panic!("expected a chunked delivery");
+        };
+        assert!(transfers.read_chunk(&transfer_id, 0).is_some());
+
+        for _ in 0..MAX_PENDING_TRANSFERS + 2 {
+            transfers.deliver(&large).unwrap();
+        }
+        assert!(transfers.read_chunk(&transfer_id, 1).is_some());
+        assert_eq!(transfers.0.lock().unwrap().len(), MAX_PENDING_TRANSFERS + 1);
+    }
+}
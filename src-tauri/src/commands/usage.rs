@@ -0,0 +1,252 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, State};
+
+use crate::commands::ai::Usage;
+use crate::settings::SettingsStore;
+
+const CSV_HEADER: &str =
+    "timestamp,kind,model,prompt_tokens,completion_tokens,audio_seconds,cost_usd,tags";
+
+/// Separator between tags in the `tags` column
+const TAG_SEPARATOR: char = ';';
+
+/// USD per million tokens (prompt, completion)
+fn chat_pricing(model: &str) -> (f64, f64) {
+    match model {
+        "gpt-4o-mini" => (0.15, 0.60),
+        m if m.starts_with("gpt-4o") => (2.50, 10.00),
+        "gpt-4-turbo" => (10.00, 30.00),
+        // Unknown models are logged with zero cost rather than a guess
+        _ => (0.0, 0.0),
+    }
+}
+
+/// USD per minute of audio
+fn transcription_pricing(model: &str) -> f64 {
+    match model {
+        "whisper-1" => 0.006,
+        _ => 0.0,
+    }
+}
+
+/// Running totals for a period
+#[derive(Debug, Default, Serialize)]
+pub struct UsageTotals {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub audio_seconds: f64,
+    pub cost_usd: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageSummary {
+    pub today: UsageTotals,
+    pub this_month: UsageTotals,
+}
+
+/// Append-only CSV of API spend in the app data directory
+/// The mutex serializes appends so concurrent requests never interleave rows
+pub struct UsageLedger {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+struct LedgerRow<'a> {
+    kind: &'a str,
+    model: &'a str,
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    audio_seconds: f64,
+    cost_usd: f64,
+    tags: &'a [String],
+}
+
+impl UsageLedger {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn append(&self, row: LedgerRow<'_>) -> Result<()> {
+        let _guard = self.lock.lock().unwrap();
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).context("Failed to create data directory")?;
+        }
+        let is_new = !self.path.exists();
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .context("Failed to open usage log")?;
+
+        if is_new {
+            writeln!(file, "{}", CSV_HEADER)?;
+        }
+        writeln!(
+            file,
+            "{},{},{},{},{},{:.2},{:.6},{}",
+            Local::now().to_rfc3339(),
+            row.kind,
+            row.model,
+            row.prompt_tokens,
+            row.completion_tokens,
+            row.audio_seconds,
+            row.cost_usd,
+            row.tags.join(&TAG_SEPARATOR.to_string())
+        )
+        .context("Failed to write usage log")?;
+        Ok(())
+    }
+
+    /// Totals for today and this month, counting only rows tagged `tag` if given
+    fn summary(&self, tag: Option<&str>) -> Result<UsageSummary> {
+        let _guard = self.lock.lock().unwrap();
+
+        let mut summary = UsageSummary {
+            today: UsageTotals::default(),
+            this_month: UsageTotals::default(),
+        };
+
+        let contents = match std::fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(summary),
+            Err(e) => return Err(e).context("Failed to read usage log"),
+        };
+
+        let now = Local::now();
+        let today = now.format("%Y-%m-%d").to_string();
+        let month = now.format("%Y-%m").to_string();
+
+        for line in contents.lines().skip(1) {
+            let fields: Vec<&str> = line.split(',').collect();
+            // Rows written before tags were added have no tags column
+            let (timestamp, prompt, completion, audio, cost, tags) = match fields[..] {
+                [timestamp, _kind, _model, prompt, completion, audio, cost] => {
+                    (timestamp, prompt, completion, audio, cost, "")
+                }
+                [timestamp, _kind, _model, prompt, completion, audio, cost, tags] => {
+                    (timestamp, prompt, completion, audio, cost, tags)
+                }
+                _ => continue,
+            };
+            if let Some(tag) = tag {
+                if !tags.split(TAG_SEPARATOR).any(|t| t.eq_ignore_ascii_case(tag)) {
+                    continue;
+                }
+            }
+
+            let add = |totals: &mut UsageTotals| {
+                totals.requests += 1;
+                totals.prompt_tokens += prompt.parse::<u64>().unwrap_or(0);
+                totals.completion_tokens += completion.parse::<u64>().unwrap_or(0);
+                totals.audio_seconds += audio.parse::<f64>().unwrap_or(0.0);
+                totals.cost_usd += cost.parse::<f64>().unwrap_or(0.0);
+            };
+
+            // Timestamps are local RFC 3339, so date prefixes match calendar periods
+            if timestamp.starts_with(&month) {
+                add(&mut summary.this_month);
+                if timestamp.starts_with(&today) {
+                    add(&mut summary.today);
+                }
+            }
+        }
+
+        Ok(summary)
+    }
+}
+
+/// Trim tags and drop empty or duplicate ones, removing characters that would
+/// break the CSV columns
+pub(crate) fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag: String = tag
+            .chars()
+            .filter(|c| *c != ',' && *c != TAG_SEPARATOR && !c.is_control())
+            .collect();
+        let tag = tag.trim();
+        if !tag.is_empty() && !normalized.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            normalized.push(tag.to_string());
+        }
+    }
+    normalized
+}
+
+/// USD cost of a chat completion; 0 for models without known pricing
+pub(crate) fn chat_cost(model: &str, usage: &Usage) -> f64 {
+    let (prompt_price, completion_price) = chat_pricing(model);
+    (usage.prompt_tokens as f64 * prompt_price + usage.completion_tokens as f64 * completion_price)
+        / 1_000_000.0
+}
+
+/// Log a chat completion if usage logging is enabled
+pub(crate) fn record_chat(
+    app: Option<&AppHandle>,
+    model: &str,
+    usage: Option<&Usage>,
+    tags: &[String],
+) {
+    let (Some(app), Some(usage)) = (app, usage) else {
+        return;
+    };
+    if !app.state::<SettingsStore>().get().log_usage {
+        return;
+    }
+
+    let row = LedgerRow {
+        kind: "chat",
+        model,
+        prompt_tokens: usage.prompt_tokens,
+        completion_tokens: usage.completion_tokens,
+        audio_seconds: 0.0,
+        cost_usd: chat_cost(model, usage),
+        tags,
+    };
+
+    if let Err(e) = app.state::<UsageLedger>().append(row) {
+        eprintln!("Failed to log usage: {}", e);
+    }
+}
+
+/// Log a transcription by recording duration if usage logging is enabled
+pub(crate) fn record_transcription(app: &AppHandle, model: &str, audio_seconds: f64) {
+    if !app.state::<SettingsStore>().get().log_usage {
+        return;
+    }
+
+    let row = LedgerRow {
+        kind: "transcription",
+        model,
+        prompt_tokens: 0,
+        completion_tokens: 0,
+        audio_seconds,
+        cost_usd: audio_seconds / 60.0 * transcription_pricing(model),
+        tags: &[],
+    };
+
+    if let Err(e) = app.state::<UsageLedger>().append(row) {
+        eprintln!("Failed to log usage: {}", e);
+    }
+}
+
+/// Totals from the usage log for today and the current month (local time)
+/// With `tag` only requests carrying that tag are counted
+#[tauri::command]
+pub fn get_usage_summary(
+    ledger: State<'_, UsageLedger>,
+    tag: Option<String>,
+) -> Result<UsageSummary, String> {
+    ledger
+        .summary(tag.as_deref().map(str::trim).filter(|t| !t.is_empty()))
+        .map_err(|e| format!("Failed to read usage: {}", e))
+}
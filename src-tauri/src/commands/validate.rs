@@ -0,0 +1,183 @@
+use serde::Serialize;
+use tauri::State;
+
+use crate::commands::ai::{normalize_role, ContentPart, Message, MessageContent, CHAT_ROLES};
+use crate::commands::image::{decoded_len, exceeds_image_limit, supported_mime, MAX_IMAGE_BYTES};
+use crate::commands::models;
+use crate::commands::tokens::{self, TOKENS_PER_IMAGE};
+use crate::config::ConfigState;
+
+/// Whether an issue stops the request or only deserves a look
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The API would reject the request
+    Error,
+    /// The request can go out, but probably not as intended
+    Warning,
+}
+
+/// A problem found by `validate_request`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum RequestIssue {
+    MissingApiKey,
+    InvalidRole { index: usize, role: String },
+    EmptyMessage { index: usize },
+    /// Images are attached but the model can't see them
+    ImagesUnsupported { model: String },
+    /// Not PNG, JPEG, GIF or WebP, or not valid base64
+    UnsupportedImageFormat { image: usize },
+    /// Over the API limit; `send_message` downscales it before sending, at lower quality
+    ImageTooLarge { image: usize, bytes: usize },
+    ContextExceeded { estimated_tokens: usize, context_window: usize },
+}
+
+impl RequestIssue {
+    fn severity(&self) -> Severity {
+        match self {
+            RequestIssue::EmptyMessage { .. } | RequestIssue::ImageTooLarge { .. } => {
+                Severity::Warning
+            }
+            _ => Severity::Error,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            RequestIssue::MissingApiKey => {
+                String::from("No OpenAI API key is set, or GhostGPT is locked")
+            }
+            RequestIssue::InvalidRole { index, role } => format!(
+                "Message {} has invalid role {:?}, expected one of {}",
+                index,
+                role,
+                CHAT_ROLES.join(", ")
+            ),
+            RequestIssue::EmptyMessage { index } => format!("Message {} is empty", index),
+            RequestIssue::ImagesUnsupported { model } => {
+                format!("Model {} does not accept images", model)
+            }
+            RequestIssue::UnsupportedImageFormat { image } => {
+                format!("Image {} is not a PNG, JPEG, GIF or WebP image", image)
+            }
+            RequestIssue::ImageTooLarge { image, bytes } => format!(
+                "Image {} is {:.1} MB, over the {} MB limit; it will be downscaled",
+                image,
+                *bytes as f64 / (1024.0 * 1024.0),
+                MAX_IMAGE_BYTES / (1024 * 1024)
+            ),
+            RequestIssue::ContextExceeded {
+                estimated_tokens,
+                context_window,
+            } => format!(
+                "About {} tokens, more than the model's {}-token context",
+                estimated_tokens, context_window
+            ),
+        }
+    }
+}
+
+/// `RequestIssue` with its severity and a readable message
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    #[serde(flatten)]
+    pub issue: RequestIssue,
+    pub message: String,
+}
+
+impl From<RequestIssue> for ValidationIssue {
+    fn from(issue: RequestIssue) -> Self {
+        Self {
+            severity: issue.severity(),
+            message: issue.message(),
+            issue,
+        }
+    }
+}
+
+/// Check a request before `send_message` for what the API would reject: missing key,
+/// bad roles, empty messages, images the model can't take and context overflow
+/// `images` are base64 images to be attached to the last user message
+///
+/// # Returns
+/// Every issue found, errors first; empty when the request looks sendable
+#[tauri::command]
+pub fn validate_request(
+    config: State<'_, ConfigState>,
+    messages: Vec<Message>,
+    model: Option<String>,
+    images: Option<Vec<String>>,
+) -> Vec<ValidationIssue> {
+    let model = model
+        .filter(|m| !m.trim().is_empty())
+        .unwrap_or_else(crate::config::default_model);
+    let images = images.unwrap_or_default();
+    let mut issues: Vec<RequestIssue> = Vec::new();
+
+    if config.get().openai_api_key.is_empty() {
+        issues.push(RequestIssue::MissingApiKey);
+    }
+
+    for (index, message) in messages.iter().enumerate() {
+        let role = normalize_role(&message.role);
+        if role.is_none() {
+            issues.push(RequestIssue::InvalidRole {
+                index,
+                role: message.role.clone(),
+            });
+        }
+        let empty = match &message.content {
+            MessageContent::Text(text) => text.trim().is_empty(),
+            MessageContent::Parts(parts) => parts.iter().all(|part| match part {
+                ContentPart::Text { text, .. } => text.trim().is_empty(),
+                _ => false,
+            }),
+        };
+        if empty && !(role.as_deref() == Some("user") && !images.is_empty()) {
+            issues.push(RequestIssue::EmptyMessage { index });
+        }
+    }
+
+    let inline_images = messages
+        .iter()
+        .filter_map(|message| match &message.content {
+            MessageContent::Parts(parts) => Some(parts),
+            MessageContent::Text(_) => None,
+        })
+        .flatten()
+        .filter(|part| matches!(part, ContentPart::ImageUrl { .. }))
+        .count();
+    if (inline_images > 0 || !images.is_empty()) && !models::capabilities(&model).vision {
+        issues.push(RequestIssue::ImagesUnsupported {
+            model: model.clone(),
+        });
+    }
+
+    for (image, data) in images.iter().enumerate() {
+        if supported_mime(data).is_none() {
+            issues.push(RequestIssue::UnsupportedImageFormat { image });
+            continue;
+        }
+        if exceeds_image_limit(data) {
+            issues.push(RequestIssue::ImageTooLarge {
+                image,
+                bytes: decoded_len(data),
+            });
+        }
+    }
+
+    let estimated_tokens = tokens::estimate_tokens(&messages) + images.len() * TOKENS_PER_IMAGE;
+    let context_window = tokens::context_window(&model);
+    if estimated_tokens > context_window {
+        issues.push(RequestIssue::ContextExceeded {
+            estimated_tokens,
+            context_window,
+        });
+    }
+
+    let mut issues: Vec<ValidationIssue> = issues.into_iter().map(ValidationIssue::from).collect();
+    issues.sort_by_key(|issue| issue.severity == Severity::Warning);
+    issues
+}
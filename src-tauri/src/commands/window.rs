@@ -0,0 +1,582 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tauri::{
+    AppHandle, Emitter, Manager, Monitor, PhysicalPosition, PhysicalSize, State, WebviewWindow,
+};
+
+use crate::settings::SettingsStore;
+
+/// Target position for `snap_window`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+/// Color scheme for the frontend and native window chrome
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Light,
+    Dark,
+    #[default]
+    System,
+}
+
+/// macOS visual-effect material shown behind the content when vibrancy is on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VibrancyMaterial {
+    #[default]
+    HudWindow,
+    Sidebar,
+    Popover,
+    Menu,
+    Sheet,
+    WindowBackground,
+    UnderWindowBackground,
+}
+
+/// Blurred translucent window background
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VibrancySettings {
+    pub enabled: bool,
+    pub material: VibrancyMaterial,
+}
+
+/// Where the show/hide hotkey places the window before showing it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SummonPosition {
+    /// Wherever the window was last
+    #[default]
+    RememberLast,
+    /// Just below and right of the mouse, kept inside the work area
+    NearCursor,
+    /// Centered on the monitor the mouse is on
+    ActiveMonitorCenter,
+}
+
+/// Monitor picked by `move_to_monitor`: its name, or its index in `list_monitors`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum MonitorTarget {
+    Index(usize),
+    Name(String),
+}
+
+/// A connected display, for a monitor picker
+#[derive(Debug, Clone, Serialize)]
+pub struct MonitorInfo {
+    pub index: usize,
+    pub name: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f64,
+    pub primary: bool,
+}
+
+/// Gap between the cursor and the window for `SummonPosition::NearCursor`, in logical pixels
+const CURSOR_OFFSET: f64 = 12.0;
+
+/// File in the app data directory holding the window's last position and size
+const GEOMETRY_FILE: &str = "window-state.json";
+
+/// Outer position and inner size (what `set_size` takes) of the main window in
+/// physical pixels, kept between runs
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct WindowGeometry {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+/// Window size in logical pixels
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct WindowSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Get the persisted theme
+#[tauri::command]
+pub fn get_theme(settings: State<'_, SettingsStore>) -> Theme {
+    settings.get().theme
+}
+
+/// Persist the theme, restyle the native window and notify the frontend
+#[tauri::command]
+pub fn set_theme(
+    app: AppHandle,
+    window: WebviewWindow,
+    settings: State<'_, SettingsStore>,
+    theme: Theme,
+) -> Result<(), String> {
+    settings
+        .update(|s| s.theme = theme)
+        .map_err(|e| format!("Failed to save theme: {}", e))?;
+
+    apply_theme(&window, theme).map_err(|e| format!("Failed to apply theme: {}", e))?;
+
+    app.emit("theme-changed", theme)
+        .map_err(|e| format!("Failed to emit theme change: {}", e))
+}
+
+/// Apply the theme to the native window
+/// On macOS this sets the `NSWindow` appearance (Aqua / DarkAqua)
+pub(crate) fn apply_theme(window: &WebviewWindow, theme: Theme) -> Result<()> {
+    let native = match theme {
+        Theme::Light => Some(tauri::Theme::Light),
+        Theme::Dark => Some(tauri::Theme::Dark),
+        Theme::System => None,
+    };
+    window.set_theme(native)?;
+    Ok(())
+}
+
+/// Turn the blurred background on or off, persist it and apply it to the window
+/// `material` defaults to the one last chosen
+///
+/// # Returns
+/// Whether vibrancy is now showing; `false` means a solid background should be drawn
+#[tauri::command]
+pub fn set_vibrancy(
+    window: WebviewWindow,
+    settings: State<'_, SettingsStore>,
+    enabled: bool,
+    material: Option<VibrancyMaterial>,
+) -> Result<bool, String> {
+    let mut vibrancy = settings.get().vibrancy;
+    vibrancy.enabled = enabled;
+    if let Some(material) = material {
+        vibrancy.material = material;
+    }
+
+    settings
+        .update(|s| s.vibrancy = vibrancy)
+        .map_err(|e| format!("Failed to save vibrancy: {}", e))?;
+
+    apply_vibrancy(&window, vibrancy).map_err(|e| format!("Failed to apply vibrancy: {}", e))
+}
+
+/// Apply the vibrancy setting to the native window
+/// On macOS this places an `NSVisualEffectView` behind the webview; elsewhere
+/// any effect is cleared and the frontend keeps its solid background
+///
+/// # Returns
+/// Whether vibrancy is showing
+pub(crate) fn apply_vibrancy(window: &WebviewWindow, vibrancy: VibrancySettings) -> Result<bool> {
+    use tauri::utils::config::WindowEffectsConfig;
+
+    #[cfg(target_os = "macos")]
+    if vibrancy.enabled {
+        use tauri::window::{Effect, EffectState, EffectsBuilder};
+
+        let effect = match vibrancy.material {
+            VibrancyMaterial::HudWindow => Effect::HudWindow,
+            VibrancyMaterial::Sidebar => Effect::Sidebar,
+            VibrancyMaterial::Popover => Effect::Popover,
+            VibrancyMaterial::Menu => Effect::Menu,
+            VibrancyMaterial::Sheet => Effect::Sheet,
+            VibrancyMaterial::WindowBackground => Effect::WindowBackground,
+            VibrancyMaterial::UnderWindowBackground => Effect::UnderWindowBackground,
+        };
+        window.set_effects(
+            EffectsBuilder::new()
+                .effect(effect)
+                // Stay blurred while the overlay is unfocused
+                .state(EffectState::Active)
+                .build(),
+        )?;
+        return Ok(true);
+    }
+
+    window.set_effects(None::<WindowEffectsConfig>)?;
+    Ok(false)
+}
+
+/// Snap the overlay to a corner (or the center) of the monitor it is on
+/// Uses the monitor's work area so the window never lands under the menu bar or dock
+#[tauri::command]
+pub fn snap_window(
+    window: WebviewWindow,
+    settings: State<'_, SettingsStore>,
+    corner: Corner,
+) -> Result<(), String> {
+    let settings = settings.get();
+    snap_window_impl(&window, corner, settings.snap_margin, settings.snap_size)
+        .map_err(|e| format!("Failed to snap window: {}", e))
+}
+
+fn snap_window_impl(
+    window: &WebviewWindow,
+    corner: Corner,
+    margin: f64,
+    size: Option<WindowSize>,
+) -> Result<()> {
+    let monitor = current_monitor(window)?;
+    let scale = monitor.scale_factor();
+
+    if let Some(size) = size {
+        window.set_size(PhysicalSize::new(
+            (size.width * scale).round() as u32,
+            (size.height * scale).round() as u32,
+        ))?;
+    }
+
+    let window_size = window.outer_size()?;
+    let position = corner_position(&monitor, window_size, corner, (margin * scale).round() as i32);
+    window.set_position(position)?;
+
+    Ok(())
+}
+
+/// Smallest size a preset may shrink the window to, in logical pixels
+const MIN_WINDOW_SIZE: WindowSize = WindowSize {
+    width: 280.0,
+    height: 200.0,
+};
+
+/// Resize the window to the next size preset, keeping its nearest corner anchored
+///
+/// # Returns
+/// Index of the preset now applied
+#[tauri::command]
+pub fn cycle_window_size(
+    window: WebviewWindow,
+    settings: State<'_, SettingsStore>,
+) -> Result<usize, String> {
+    let current = settings.get();
+    if current.size_presets.is_empty() {
+        return Err("No window size presets configured".to_string());
+    }
+
+    let index = (current.size_preset + 1) % current.size_presets.len();
+    resize_anchored(&window, current.size_presets[index])
+        .map_err(|e| format!("Failed to resize window: {}", e))?;
+
+    settings
+        .update(|s| s.size_preset = index)
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    Ok(index)
+}
+
+/// Resize the window, keeping the corner nearest to a work-area edge fixed
+fn resize_anchored(window: &WebviewWindow, size: WindowSize) -> Result<()> {
+    let monitor = current_monitor(window)?;
+    let scale = monitor.scale_factor();
+    let area = monitor.work_area();
+
+    let old_pos = window.outer_position()?;
+    let old_size = window.outer_size()?;
+
+    // Respect the minimum size and never exceed the work area
+    let width = (size.width.max(MIN_WINDOW_SIZE.width) * scale).round() as u32;
+    let height = (size.height.max(MIN_WINDOW_SIZE.height) * scale).round() as u32;
+    let width = width.min(area.size.width);
+    let height = height.min(area.size.height);
+
+    // Anchor to whichever half of the work area the window's center is in
+    let center_x = old_pos.x + old_size.width as i32 / 2;
+    let center_y = old_pos.y + old_size.height as i32 / 2;
+    let anchor_right = center_x > area.position.x + area.size.width as i32 / 2;
+    let anchor_bottom = center_y > area.position.y + area.size.height as i32 / 2;
+
+    let x = if anchor_right {
+        old_pos.x + old_size.width as i32 - width as i32
+    } else {
+        old_pos.x
+    };
+    let y = if anchor_bottom {
+        old_pos.y + old_size.height as i32 - height as i32
+    } else {
+        old_pos.y
+    };
+
+    let new_size = PhysicalSize::new(width, height);
+    let position = clamp_to_area(&monitor, PhysicalPosition::new(x, y), new_size);
+
+    window.set_size(new_size)?;
+    window.set_position(position)?;
+    Ok(())
+}
+
+/// Connected monitors, in the order `move_to_monitor` indexes them
+#[tauri::command]
+pub fn list_monitors(window: WebviewWindow) -> Result<Vec<MonitorInfo>, String> {
+    let monitors = window
+        .available_monitors()
+        .map_err(|e| format!("Failed to list monitors: {}", e))?;
+    let primary = window.primary_monitor().ok().flatten().and_then(|m| m.name().cloned());
+
+    Ok(monitors
+        .into_iter()
+        .enumerate()
+        .map(|(index, m)| MonitorInfo {
+            index,
+            name: m.name().cloned(),
+            width: m.size().width,
+            height: m.size().height,
+            scale_factor: m.scale_factor(),
+            primary: m.name().is_some() && m.name() == primary.as_ref(),
+        })
+        .collect())
+}
+
+/// Move the window onto another monitor, keeping its relative place on screen
+/// The choice is saved, so the window opens on that monitor at the next launch
+///
+/// # Returns
+/// Name of the monitor the window is now on
+#[tauri::command]
+pub fn move_to_monitor(
+    window: WebviewWindow,
+    settings: State<'_, SettingsStore>,
+    monitor: MonitorTarget,
+) -> Result<String, String> {
+    let monitors = window
+        .available_monitors()
+        .map_err(|e| format!("Failed to list monitors: {}", e))?;
+    let target = match &monitor {
+        MonitorTarget::Index(index) => monitors.get(*index),
+        MonitorTarget::Name(name) => monitors
+            .iter()
+            .find(|m| m.name().is_some_and(|n| n.eq_ignore_ascii_case(name.trim()))),
+    }
+    .ok_or_else(|| format!("No monitor {:?}", monitor))?;
+
+    move_to(&window, target).map_err(|e| format!("Failed to move window: {}", e))?;
+
+    let name = target.name().cloned().unwrap_or_default();
+    settings
+        .update(|s| s.preferred_monitor = Some(name.clone()).filter(|n| !n.is_empty()))
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+    Ok(name)
+}
+
+/// Put the window on the saved monitor at launch
+/// If that monitor is disconnected the window goes to the primary one instead
+pub(crate) fn restore_monitor(window: &WebviewWindow, name: &str) -> Result<()> {
+    let monitors = window.available_monitors()?;
+    let target = match monitors.iter().find(|m| m.name().is_some_and(|n| n == name)) {
+        Some(monitor) => monitor.clone(),
+        None => {
+            println!("Monitor {} not connected, using the primary monitor", name);
+            window.primary_monitor()?.context("No monitor available")?
+        }
+    };
+
+    if current_monitor(window)?.name() == target.name() {
+        return Ok(());
+    }
+    move_to(window, &target)
+}
+
+/// Remember where the window is and how big, for `restore_geometry` at the next launch
+/// Called when the window is hidden or closed; failures are only logged
+pub(crate) fn save_geometry(window: &WebviewWindow) {
+    let result = (|| -> Result<()> {
+        let position = window.outer_position()?;
+        let size = window.inner_size()?;
+        let geometry = WindowGeometry {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+        };
+        let dir = window.path().app_data_dir()?;
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join(GEOMETRY_FILE), serde_json::to_string(&geometry)?)?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        eprintln!("Warning: Could not save window position: {}", e);
+    }
+}
+
+/// Put the window back where `save_geometry` last saw it; call before it is first shown
+/// The saved rectangle is fitted into the work area of the monitor it overlaps most,
+/// or the primary monitor if none (e.g. it was on a display that is now unplugged)
+pub(crate) fn restore_geometry(window: &WebviewWindow) -> Result<()> {
+    let path = window.path().app_data_dir()?.join(GEOMETRY_FILE);
+    let json = match std::fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).context("Failed to read saved window position"),
+    };
+    let geometry: WindowGeometry =
+        serde_json::from_str(&json).context("Corrupt saved window position")?;
+
+    let position = PhysicalPosition::new(geometry.x, geometry.y);
+    let size = PhysicalSize::new(geometry.width, geometry.height);
+    let monitor = window
+        .available_monitors()?
+        .into_iter()
+        .map(|m| (overlap_area(&m, position, size), m))
+        .filter(|(area, _)| *area > 0)
+        .max_by_key(|(area, _)| *area)
+        .map(|(_, m)| m);
+    let monitor = match monitor {
+        Some(monitor) => monitor,
+        None => window.primary_monitor()?.context("No monitor available")?,
+    };
+
+    // A window bigger than the work area couldn't be moved fully onto it
+    let area = monitor.work_area();
+    let scale = monitor.scale_factor();
+    let size = PhysicalSize::new(
+        size.width.min(area.size.width).max((MIN_WINDOW_SIZE.width * scale) as u32),
+        size.height.min(area.size.height).max((MIN_WINDOW_SIZE.height * scale) as u32),
+    );
+    window.set_size(size)?;
+    window.set_position(clamp_to_area(&monitor, position, window.outer_size()?))?;
+    Ok(())
+}
+
+/// Move the window to `target`, at the same relative spot of its work area
+fn move_to(window: &WebviewWindow, target: &Monitor) -> Result<()> {
+    let current = current_monitor(window)?;
+    let from = current.work_area();
+    let to = target.work_area();
+    let position = window.outer_position()?;
+    let size = window.outer_size()?;
+
+    // Fraction of the free space to the left of / above the window
+    let fraction = |offset: i32, free: i64| {
+        if free <= 0 {
+            0.0
+        } else {
+            (offset as f64 / free as f64).clamp(0.0, 1.0)
+        }
+    };
+    let fx = fraction(position.x - from.position.x, from.size.width as i64 - size.width as i64);
+    let fy = fraction(position.y - from.position.y, from.size.height as i64 - size.height as i64);
+
+    let free_x = (to.size.width as i64 - size.width as i64).max(0) as f64;
+    let free_y = (to.size.height as i64 - size.height as i64).max(0) as f64;
+    let position = PhysicalPosition::new(
+        to.position.x + (fx * free_x).round() as i32,
+        to.position.y + (fy * free_y).round() as i32,
+    );
+
+    window.set_position(clamp_to_area(target, position, size))?;
+    Ok(())
+}
+
+/// Move the window as configured for a hotkey summon; call before showing it
+pub(crate) fn position_for_summon(window: &WebviewWindow, mode: SummonPosition) -> Result<()> {
+    if mode == SummonPosition::RememberLast {
+        return Ok(());
+    }
+
+    let cursor = window.cursor_position()?;
+    let monitor = match window.monitor_from_point(cursor.x, cursor.y)? {
+        Some(monitor) => monitor,
+        None => current_monitor(window)?,
+    };
+    let size = window.outer_size()?;
+
+    let position = match mode {
+        SummonPosition::NearCursor => {
+            let offset = CURSOR_OFFSET * monitor.scale_factor();
+            let position = PhysicalPosition::new(
+                (cursor.x + offset).round() as i32,
+                (cursor.y + offset).round() as i32,
+            );
+            clamp_to_area(&monitor, position, size)
+        }
+        SummonPosition::ActiveMonitorCenter | SummonPosition::RememberLast => {
+            corner_position(&monitor, size, Corner::Center, 0)
+        }
+    };
+    window.set_position(position)?;
+    Ok(())
+}
+
+/// Move a window rectangle so it lies fully inside the monitor's work area
+pub(crate) fn clamp_to_area(
+    monitor: &Monitor,
+    position: PhysicalPosition<i32>,
+    size: PhysicalSize<u32>,
+) -> PhysicalPosition<i32> {
+    let area = monitor.work_area();
+    let max_x = area.position.x + area.size.width as i32 - size.width as i32;
+    let max_y = area.position.y + area.size.height as i32 - size.height as i32;
+
+    PhysicalPosition::new(
+        position.x.min(max_x).max(area.position.x),
+        position.y.min(max_y).max(area.position.y),
+    )
+}
+
+/// Monitor the window overlaps the most, falling back to the primary monitor
+pub(crate) fn current_monitor(window: &WebviewWindow) -> Result<Monitor> {
+    let position = window.outer_position()?;
+    let size = window.outer_size()?;
+
+    let best = window
+        .available_monitors()?
+        .into_iter()
+        .map(|m| (overlap_area(&m, position, size), m))
+        .filter(|(area, _)| *area > 0)
+        .max_by_key(|(area, _)| *area)
+        .map(|(_, m)| m);
+
+    match best {
+        Some(monitor) => Ok(monitor),
+        None => window
+            .primary_monitor()?
+            .context("No monitor available"),
+    }
+}
+
+/// Top-left position that places a window of `size` at `corner` of the work area
+pub(crate) fn corner_position(
+    monitor: &Monitor,
+    size: PhysicalSize<u32>,
+    corner: Corner,
+    margin: i32,
+) -> PhysicalPosition<i32> {
+    let area = monitor.work_area();
+    let left = area.position.x + margin;
+    let top = area.position.y + margin;
+    let right = area.position.x + area.size.width as i32 - size.width as i32 - margin;
+    let bottom = area.position.y + area.size.height as i32 - size.height as i32 - margin;
+
+    let (x, y) = match corner {
+        Corner::TopLeft => (left, top),
+        Corner::TopRight => (right, top),
+        Corner::BottomLeft => (left, bottom),
+        Corner::BottomRight => (right, bottom),
+        Corner::Center => (
+            area.position.x + (area.size.width as i32 - size.width as i32) / 2,
+            area.position.y + (area.size.height as i32 - size.height as i32) / 2,
+        ),
+    };
+
+    // Keep the window on-screen even if it is larger than the work area
+    PhysicalPosition::new(x.max(area.position.x), y.max(area.position.y))
+}
+
+fn overlap_area(
+    monitor: &Monitor,
+    position: PhysicalPosition<i32>,
+    size: PhysicalSize<u32>,
+) -> i64 {
+    let m_pos = monitor.position();
+    let m_size = monitor.size();
+
+    let x1 = position.x.max(m_pos.x) as i64;
+    let y1 = position.y.max(m_pos.y) as i64;
+    let x2 = (position.x + size.width as i32).min(m_pos.x + m_size.width as i32) as i64;
+    let y2 = (position.y + size.height as i32).min(m_pos.y + m_size.height as i32) as i64;
+
+    (x2 - x1).max(0) * (y2 - y1).max(0)
+}
@@ -1,9 +1,60 @@
+use anyhow::{Context, Result};
 use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::commands::ai::DEFAULT_MODEL;
+
+/// Speech-to-text backend used by `process_audio`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptionProvider {
+    /// OpenAI Whisper, uses OPENAI_API_KEY
+    OpenAi,
+    /// Deepgram, requires DEEPGRAM_API_KEY
+    Deepgram,
+    /// Google Cloud Speech-to-Text, requires GOOGLE_SPEECH_API_KEY
+    /// (an API key with the Speech-to-Text API enabled)
+    Google,
+    /// Local whisper.cpp server at WHISPER_CPP_URL, no key needed
+    WhisperCpp,
+}
+
+impl TranscriptionProvider {
+    pub const ALL: [Self; 4] = [Self::OpenAi, Self::Deepgram, Self::Google, Self::WhisperCpp];
+}
+
+/// Default address of `whisper-server` from whisper.cpp
+const DEFAULT_WHISPER_CPP_URL: &str = "http://127.0.0.1:8080/inference";
+
+/// Default OPENAI_BASE_URL
+const DEFAULT_OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
 
 /// Application configuration loaded from environment variables
 #[derive(Debug, Clone)]
 pub struct AppConfig {
     pub openai_api_key: String,
+    /// TRANSCRIPTION_PROVIDER: `openai` (default), `deepgram`, `google` or `whisper-cpp`
+    pub transcription_provider: TranscriptionProvider,
+    pub deepgram_api_key: Option<String>,
+    pub google_speech_api_key: Option<String>,
+    /// GOOGLE_SPEECH_LANGUAGE: BCP-47 code, Google requires one (default `en-US`)
+    pub google_speech_language: String,
+    /// WHISPER_CPP_URL: whisper.cpp `/inference` endpoint, or an OpenAI-compatible
+    /// `/v1/audio/transcriptions` one (default `http://127.0.0.1:8080/inference`)
+    pub whisper_cpp_url: String,
+    /// OPENAI_BASE_URL: root of the OpenAI-compatible API the chat, transcription,
+    /// image and batch endpoints are appended to, e.g. `http://localhost:1234/v1` for
+    /// LM Studio (default `https://api.openai.com/v1`); trailing slashes are dropped
+    pub base_url: String,
+    /// GHOSTGPT_MODEL: chat model used when a request names none (default `gpt-4o`)
+    pub model: Option<String>,
+    /// GHOSTGPT_MAX_TOKENS: reply length limit for requests that set none, in place of
+    /// the `max_tokens` / `auto_max_tokens` settings
+    pub max_tokens: Option<u32>,
+    /// GHOSTGPT_HOTKEY: show/hide shortcut, e.g. `CommandOrControl+Shift+K`
+    /// (default `CommandOrControl+Shift+Space`, then `CommandOrControl+Option+G`)
+    pub hotkey: Option<String>,
 }
 
 impl AppConfig {
@@ -14,9 +65,253 @@ impl AppConfig {
         // Try to load .env file (silently ignore if not found)
         let _ = dotenvy::dotenv();
 
+        Self::read_env().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Re-read .env, letting its values replace ones loaded earlier
+    pub fn reload() -> Result<Self> {
+        let _ = dotenvy::dotenv_override();
+        Self::read_env()
+    }
+
+    fn read_env() -> Result<Self> {
         let openai_api_key = env::var("OPENAI_API_KEY")
-            .expect("OPENAI_API_KEY not found. Create a .env file with: OPENAI_API_KEY=sk-your-key");
+            .context("OPENAI_API_KEY not found. Create a .env file with: OPENAI_API_KEY=sk-your-key")?;
+
+        let transcription_provider = match env::var("TRANSCRIPTION_PROVIDER")
+            .unwrap_or_default()
+            .to_lowercase()
+            .as_str()
+        {
+            "deepgram" => TranscriptionProvider::Deepgram,
+            "google" => TranscriptionProvider::Google,
+            "whisper-cpp" | "whispercpp" => TranscriptionProvider::WhisperCpp,
+            "" | "openai" => TranscriptionProvider::OpenAi,
+            other => {
+                eprintln!("Warning: Unknown TRANSCRIPTION_PROVIDER '{}', using openai", other);
+                TranscriptionProvider::OpenAi
+            }
+        };
+
+        let max_tokens = env::var("GHOSTGPT_MAX_TOKENS").ok();
+        let max_tokens = match max_tokens.filter(|v| !v.trim().is_empty()) {
+            Some(value) => match value.trim().parse::<u32>() {
+                Ok(max_tokens) if max_tokens > 0 => Some(max_tokens),
+                _ => {
+                    eprintln!("Warning: Invalid GHOSTGPT_MAX_TOKENS '{}', ignoring it", value);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        Ok(Self {
+            openai_api_key,
+            transcription_provider,
+            deepgram_api_key: env::var("DEEPGRAM_API_KEY").ok(),
+            google_speech_api_key: env::var("GOOGLE_SPEECH_API_KEY").ok(),
+            google_speech_language: env::var("GOOGLE_SPEECH_LANGUAGE")
+                .unwrap_or_else(|_| String::from("en-US")),
+            whisper_cpp_url: env::var("WHISPER_CPP_URL")
+                .ok()
+                .filter(|url| !url.trim().is_empty())
+                .unwrap_or_else(|| String::from(DEFAULT_WHISPER_CPP_URL)),
+            base_url: normalize_base_url(env::var("OPENAI_BASE_URL").ok().as_deref()),
+            model: env::var("GHOSTGPT_MODEL")
+                .ok()
+                .map(|model| model.trim().to_string())
+                .filter(|model| !model.is_empty()),
+            max_tokens,
+            hotkey: env::var("GHOSTGPT_HOTKEY")
+                .ok()
+                .map(|hotkey| hotkey.trim().to_string())
+                .filter(|hotkey| !hotkey.is_empty()),
+        })
+    }
+}
+
+impl AppConfig {
+    /// Point `api_url` at this configuration's `base_url` and `default_model` /
+    /// `default_max_tokens` at its chat defaults
+    fn apply_globals(&self) {
+        *api_base().write().unwrap() = self.base_url.clone();
+        *chat_defaults().write().unwrap() = (self.model.clone(), self.max_tokens);
+    }
+
+    /// Copy with every API key blanked, held while the app is locked
+    /// Optional keys stay `Some` so a locked request reports `Locked`, not a missing key
+    fn without_keys(mut self) -> Self {
+        self.openai_api_key.clear();
+        for key in [&mut self.deepgram_api_key, &mut self.google_speech_api_key]
+            .into_iter()
+            .flatten()
+        {
+            key.clear();
+        }
+        self
+    }
+}
+
+/// `url` without surrounding whitespace or trailing slashes, or the OpenAI default
+fn normalize_base_url(url: Option<&str>) -> String {
+    let url = url.unwrap_or_default().trim().trim_end_matches('/');
+    if url.is_empty() {
+        String::from(DEFAULT_OPENAI_BASE_URL)
+    } else {
+        url.to_string()
+    }
+}
+
+/// Base URL of the current configuration, for building endpoint URLs without a config
+/// at hand; kept in step with `ConfigState`
+fn api_base() -> &'static RwLock<String> {
+    static BASE: std::sync::OnceLock<RwLock<String>> = std::sync::OnceLock::new();
+    BASE.get_or_init(|| RwLock::new(String::from(DEFAULT_OPENAI_BASE_URL)))
+}
+
+/// Full URL of the API endpoint at `path`, e.g. `chat/completions`
+pub(crate) fn api_url(path: &str) -> String {
+    format!("{}/{}", api_base().read().unwrap(), path.trim_start_matches('/'))
+}
+
+/// GHOSTGPT_MODEL and GHOSTGPT_MAX_TOKENS of the current configuration, for requests
+/// built without a config at hand; kept in step with `ConfigState`
+fn chat_defaults() -> &'static RwLock<(Option<String>, Option<u32>)> {
+    static DEFAULTS: std::sync::OnceLock<RwLock<(Option<String>, Option<u32>)>> =
+        std::sync::OnceLock::new();
+    DEFAULTS.get_or_init(RwLock::default)
+}
+
+/// Chat model for requests that name none: GHOSTGPT_MODEL, else `DEFAULT_MODEL`
+pub(crate) fn default_model() -> String {
+    chat_defaults()
+        .read()
+        .unwrap()
+        .0
+        .clone()
+        .unwrap_or_else(|| DEFAULT_MODEL.to_string())
+}
+
+/// GHOSTGPT_MAX_TOKENS, the reply length limit for requests that set none
+pub(crate) fn default_max_tokens() -> Option<u32> {
+    chat_defaults().read().unwrap().1
+}
+
+/// The app was locked after inactivity and the API keys dropped from memory
+#[derive(Debug)]
+pub struct Locked;
+
+impl std::fmt::Display for Locked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GhostGPT is locked; unlock it to send requests")
+    }
+}
+
+impl std::error::Error for Locked {}
+
+/// A request needs an API key that isn't configured
+#[derive(Debug)]
+pub struct MissingApiKey;
+
+impl std::fmt::Display for MissingApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No API key is set for this service")
+    }
+}
+
+impl std::error::Error for MissingApiKey {}
+
+/// Whether the keys are blanked by `ConfigState::lock`, for `require_key`; kept in
+/// step with `ConfigState`
+fn keys_locked() -> &'static AtomicBool {
+    static LOCKED: AtomicBool = AtomicBool::new(false);
+    &LOCKED
+}
+
+/// Fail instead of sending a request without a key: with `Locked` while the app is
+/// locked, otherwise with `MissingApiKey`
+pub(crate) fn require_key(api_key: &str) -> Result<&str> {
+    if api_key.is_empty() {
+        if keys_locked().load(Ordering::Relaxed) {
+            return Err(Locked.into());
+        }
+        return Err(MissingApiKey.into());
+    }
+    Ok(api_key)
+}
+
+/// Managed configuration, replaceable at runtime by `reload_config`
+/// Commands take a snapshot so the lock is never held across an await
+pub struct ConfigState {
+    config: RwLock<AppConfig>,
+    locked: AtomicBool,
+    /// Last user-facing command or reported UI activity, for the auto-lock timer
+    last_activity: Mutex<Instant>,
+}
+
+impl ConfigState {
+    pub fn new(config: AppConfig) -> Self {
+        config.apply_globals();
+        Self {
+            config: RwLock::new(config),
+            locked: AtomicBool::new(false),
+            last_activity: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Snapshot of the current configuration; while locked the API keys are blank
+    /// Not activity by itself, since background work reads it too; see `touch`
+    pub fn get(&self) -> AppConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Swap in a new configuration; while locked its keys are dropped until `unlock`
+    pub fn replace(&self, config: AppConfig) {
+        config.apply_globals();
+        let mut current = self.config.write().unwrap();
+        *current = if self.is_locked() {
+            config.without_keys()
+        } else {
+            config
+        };
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    /// Drop the API keys from memory
+    /// The flag is set under the same write lock that blanks the keys, so no reader
+    /// sees the app locked with its keys still present
+    /// # Returns
+    /// `false` if already locked
+    pub fn lock(&self) -> bool {
+        let mut config = self.config.write().unwrap();
+        if self.locked.swap(true, Ordering::Relaxed) {
+            return false;
+        }
+        *config = config.clone().without_keys();
+        keys_locked().store(true, Ordering::Relaxed);
+        true
+    }
+
+    /// Restore the keys from a freshly read configuration
+    pub fn unlock(&self, config: AppConfig) {
+        config.apply_globals();
+        let mut current = self.config.write().unwrap();
+        *current = config;
+        self.locked.store(false, Ordering::Relaxed);
+        keys_locked().store(false, Ordering::Relaxed);
+        drop(current);
+        self.touch();
+    }
+
+    /// Reset the auto-lock timer; called by commands the user invokes
+    pub fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
 
-        Self { openai_api_key }
+    pub fn idle_for(&self) -> Duration {
+        self.last_activity.lock().unwrap().elapsed()
     }
 }
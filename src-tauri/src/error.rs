@@ -0,0 +1,144 @@
+use serde::Serialize;
+
+use crate::commands::ai::{ApiError, QuotaExceeded};
+use crate::commands::audio::NoInputDevice;
+use crate::commands::requests::Cancelled;
+use crate::commands::screenshot::DisplayUnavailable;
+use crate::commands::transcription::EmptyTranscription;
+use crate::config::{Locked, MissingApiKey};
+
+/// Error returned by `send_message`, `process_audio`, `start_audio_recording` and
+/// `capture_screenshot`, tagged with its `kind` so the frontend can react to the cause
+/// `message` is the text these commands returned before, also used by `Display`
+#[derive(Debug, Clone, Serialize, thiserror::Error)]
+#[serde(tag = "kind", content = "message")]
+pub enum GhostError {
+    /// The app was locked after inactivity and the API keys dropped from memory
+    #[error("{0}")]
+    Locked(String),
+    /// The service needs an API key that isn't configured
+    #[error("{0}")]
+    MissingApiKey(String),
+    /// The API rejected the key (401)
+    #[error("{0}")]
+    InvalidApiKey(String),
+    /// Still rate-limited (429) after the configured retries
+    #[error("{0}")]
+    RateLimited(String),
+    /// The account is out of credit or hit its billing limit
+    #[error("{0}")]
+    QuotaExceeded(String),
+    /// Any other API or network failure
+    #[error("{0}")]
+    Api(String),
+    /// Aborted through `cancel_request` / `cancel_task`
+    #[error("{0}")]
+    Cancelled(String),
+    /// The same question was sent moments ago; resend with `allow_duplicate`
+    #[error("{0}")]
+    DuplicateMessage(String),
+    /// Refused before anything was sent, e.g. an invalid option or image id
+    #[error("{0}")]
+    InvalidRequest(String),
+    #[error("{0}")]
+    NoInputDevice(String),
+    #[error("{0}")]
+    RecordingFailed(String),
+    /// Nothing was recorded
+    #[error("{0}")]
+    NoAudio(String),
+    /// The recording came back without any speech
+    #[error("{0}")]
+    NoSpeech(String),
+    #[error("{0}")]
+    TranscriptionFailed(String),
+    /// Transcription failed and the recording was saved for `transcribe_saved`
+    #[error("{0}")]
+    TranscriptionDeferred(String),
+    /// The region or window selection was dismissed
+    #[error("{0}")]
+    ScreenshotCancelled(String),
+    /// The capture mode isn't available on this platform
+    #[error("{0}")]
+    ScreenshotUnsupported(String),
+    /// The screen is locked or the display asleep, so a capture would come back black
+    #[error("{0}")]
+    DisplayUnavailable(String),
+    #[error("{0}")]
+    ScreenshotFailed(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl GhostError {
+    /// Kind matching the cause of `error`, carrying `message`; `fallback` when no
+    /// known cause is found
+    /// A `GhostError` already inside `error` is returned unchanged
+    pub(crate) fn classify(
+        error: &anyhow::Error,
+        message: String,
+        fallback: fn(String) -> Self,
+    ) -> Self {
+        if let Some(error) = error.chain().find_map(|e| e.downcast_ref::<GhostError>()) {
+            return error.clone();
+        }
+        let caused_by = |is: fn(&(dyn std::error::Error + 'static)) -> bool| error.chain().any(is);
+
+        if caused_by(|e| e.is::<Locked>()) {
+            Self::Locked(message)
+        } else if caused_by(|e| e.is::<MissingApiKey>()) {
+            Self::MissingApiKey(message)
+        } else if caused_by(|e| e.is::<Cancelled>()) {
+            Self::Cancelled(message)
+        } else if caused_by(|e| e.is::<QuotaExceeded>()) {
+            Self::QuotaExceeded(message)
+        } else if caused_by(|e| e.is::<NoInputDevice>()) {
+            Self::NoInputDevice(message)
+        } else if caused_by(|e| e.is::<EmptyTranscription>()) {
+            Self::NoSpeech(message)
+        } else if caused_by(|e| e.is::<DisplayUnavailable>()) {
+            Self::DisplayUnavailable(message)
+        } else {
+            match error.chain().find_map(|e| e.downcast_ref::<ApiError>()) {
+                Some(api) if api.status == reqwest::StatusCode::UNAUTHORIZED => {
+                    Self::InvalidApiKey(message)
+                }
+                Some(api) if api.status == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                    Self::RateLimited(message)
+                }
+                _ => fallback(message),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn locked_screen_is_display_unavailable() {
+        let error = Err::<(), _>(DisplayUnavailable::Locked)
+            .context("Capture failed")
+            .unwrap_err();
+        let classified = GhostError::classify(
+            &error,
+            String::from("Screenshot failed"),
+            GhostError::ScreenshotFailed,
+        );
+        assert!(matches!(classified, GhostError::DisplayUnavailable(_)));
+    }
+
+    #[test]
+    fn missing_key_is_its_own_kind() {
+        let error = crate::config::require_key("").unwrap_err();
+        let classified =
+            GhostError::classify(&error, String::from("OpenAI API error"), GhostError::Api);
+        assert!(matches!(classified, GhostError::MissingApiKey(_)));
+        assert_eq!(classified.to_string(), "OpenAI API error");
+
+        let json = serde_json::to_value(&classified).unwrap();
+        assert_eq!(json["kind"], "MissingApiKey");
+    }
+}
@@ -1,17 +1,247 @@
 // Prevents additional console window on Windows in release builds
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use tauri::{Manager};
-use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
+mod commands;
+mod config;
+mod error;
+mod settings;
+
+use std::io::Write;
+use tauri::{DragDropEvent, Manager, RunEvent, WindowEvent};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+use commands::audio::{AudioRecorder, Playback};
+use commands::context::CaptureContext;
+use commands::files::DroppedPaths;
+use commands::history::HistoryStore;
+use commands::hotkeys::ToggleHotkey;
+use commands::imagegen::LastGeneratedImage;
+use commands::logs::RequestLog;
+use commands::realtime::RealtimeSession;
+use commands::requests::InFlightRequests;
+use commands::recovery::RecoveryStore;
+use commands::screenshot::LastScreenshot;
+use commands::scrolling::ScrollFrames;
+use commands::session::SessionStore;
+use commands::transfer::Transfers;
+use commands::usage::UsageLedger;
+use config::{AppConfig, ConfigState};
+use settings::SettingsStore;
 
 fn main() {
+    let config = AppConfig::from_env();
+
     tauri::Builder::default()
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_autostart::init(
+            tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+            None,
+        ))
+        .manage(ConfigState::new(config))
+        .manage(AudioRecorder::new())
+        .manage(Playback::default())
+        .manage(LastScreenshot::default())
+        .manage(LastGeneratedImage::default())
+        .manage(ScrollFrames::default())
+        .manage(CaptureContext::default())
+        .manage(RealtimeSession::default())
+        .manage(InFlightRequests::default())
+        .manage(SessionStore::default())
+        .manage(ToggleHotkey::default())
+        .manage(Transfers::default())
+        .manage(DroppedPaths::default())
+        .invoke_handler(tauri::generate_handler![
+            commands::screenshot::capture_screenshot,
+            commands::screenshot::save_last_screenshot,
+            commands::scrolling::add_scroll_frame,
+            commands::scrolling::capture_scrolling,
+            commands::scrolling::discard_scroll_frames,
+            commands::context::list_windows,
+            commands::ocr::capture_and_ocr,
+            commands::ai::send_message,
+            commands::duplicates::check_duplicate,
+            commands::ai::quick_ask,
+            commands::ai::summarize_screen,
+            commands::ai::edit_and_resend,
+            commands::ai::warmup,
+            commands::compare::compare_models,
+            commands::stream::send_message_stream,
+            commands::requests::cancel_request,
+            commands::requests::list_tasks,
+            commands::requests::cancel_task,
+            commands::memory::compress_conversation,
+            commands::tokens::get_context_usage,
+            commands::tokens::get_output_budget,
+            commands::validate::validate_request,
+            commands::models::get_model_capabilities,
+            commands::audio::start_audio_recording,
+            commands::audio::stop_audio_recording,
+            commands::audio::is_recording,
+            commands::audio::get_recording_duration,
+            commands::audio::discard_recording,
+            commands::audio::save_recording,
+            commands::audio::process_audio,
+            commands::audio::get_waveform,
+            commands::audio::analyze_audio,
+            commands::audio::retry_transcription,
+            commands::audio::transcribe_saved,
+            commands::audio::retranscribe,
+            commands::audio::describe_transcription_upload,
+            commands::audio::benchmark_transcription,
+            commands::audio::set_retention_window,
+            commands::audio::set_mic_idle_timeout,
+            commands::audio::set_input_gain,
+            commands::audio::set_typing_noise_gate,
+            commands::audio::rename_device_settings,
+            commands::audio::process_recent,
+            commands::audio::list_input_configs,
+            commands::audio::detect_language,
+            commands::chapters::chapterize_transcript,
+            commands::audio::send_audio_message,
+            commands::audio::test_microphone,
+            commands::audio::play_recording,
+            commands::audio::set_playback_speed,
+            commands::audio::stop_playback,
+            commands::app_audio::list_capturable_apps,
+            commands::app_audio::start_app_audio_recording,
+            commands::realtime::start_realtime_transcription,
+            commands::realtime::stop_realtime_transcription,
+            commands::realtime::is_realtime_active,
+            commands::recovery::autosave_conversation,
+            commands::recovery::check_recovery,
+            commands::recovery::restore_recovery,
+            commands::recovery::discard_recovery,
+            commands::history::save_conversation,
+            commands::history::load_conversation,
+            commands::transfer::read_transfer_chunk,
+            commands::history::list_conversations,
+            commands::history::search_conversations,
+            commands::history::get_conversation_model,
+            commands::history::set_conversation_model,
+            commands::history::merge_conversations,
+            commands::history::append_message,
+            commands::history::pin_message,
+            commands::history::unpin_message,
+            commands::batch::submit_batch,
+            commands::batch::poll_batch,
+            commands::session::start_session,
+            commands::session::append_to_session,
+            commands::session::get_session,
+            commands::session::end_session,
+            commands::history::next_conversation,
+            commands::history::previous_conversation,
+            commands::templates::list_templates,
+            commands::templates::save_template,
+            commands::templates::delete_template,
+            commands::templates::send_template,
+            commands::settings::get_settings,
+            commands::settings::update_settings,
+            commands::settings::reload_config,
+            commands::settings::export_settings,
+            commands::settings::import_settings,
+            commands::window::snap_window,
+            commands::window::cycle_window_size,
+            commands::window::list_monitors,
+            commands::window::move_to_monitor,
+            commands::window::get_theme,
+            commands::window::set_theme,
+            commands::window::set_vibrancy,
+            commands::system::get_version_info,
+            commands::diagnostics::run_diagnostics,
+            commands::logs::get_log_path,
+            commands::logs::clear_logs,
+            commands::permissions::check_permissions,
+            commands::hotkeys::set_hotkey_enabled,
+            commands::hotkeys::set_global_hotkey,
+            commands::hotkeys::set_quiet_hours,
+            commands::hotkeys::is_hotkey_active,
+            commands::lock::lock_app,
+            commands::lock::unlock_app,
+            commands::lock::is_locked,
+            commands::lock::report_activity,
+            commands::permissions::request_permissions,
+            commands::system::set_launch_at_login,
+            commands::system::set_start_hidden,
+            commands::image::crop_image,
+            commands::image::annotate_image,
+            commands::image::diff_images,
+            commands::image::prepare_image,
+            commands::imagegen::generate_image,
+            commands::imagegen::save_generated_image,
+            commands::export::export_html,
+            commands::export::export_session_bundle,
+            commands::prompt::compose_system_prompt,
+            commands::files::read_dropped_files,
+            commands::code::extract_code_blocks,
+            commands::code::tag_code_blocks,
+            commands::usage::get_usage_summary,
+            commands::storage::get_storage_usage,
+            commands::storage::cleanup_old_conversations,
+        ])
+        .on_window_event(|window, event| {
+            match event {
+                WindowEvent::DragDrop(DragDropEvent::Drop { paths, .. }) => {
+                    commands::files::handle_drop(window.app_handle(), paths.clone());
+                }
+                WindowEvent::CloseRequested { .. } if window.label() == "main" => {
+                    if let Some(window) = window.app_handle().get_webview_window("main") {
+                        commands::window::save_geometry(&window);
+                    }
+                }
+                _ => {}
+            }
+        })
         .setup(|app| {
+            // Load persisted user settings
+            let settings_path = app.path().app_config_dir()?.join("settings.json");
+            app.manage(SettingsStore::load(settings_path));
+
+            // Crash-recovery autosave of the active conversation
+            let data_dir = app.path().app_data_dir()?;
+            app.manage(RecoveryStore::new(data_dir.join("recovery.json")));
+            commands::recovery::spawn_autosave(app.handle().clone());
+            commands::lock::spawn_auto_lock(app.handle().clone());
+            app.manage(UsageLedger::new(data_dir.join("usage.csv")));
+            app.manage(HistoryStore::new(data_dir.join("conversations")));
+            app.manage(RequestLog::new(app.path().app_log_dir()?));
+
             // Get main window
             let window = app.get_webview_window("main")
                 .expect("Failed to get main window");
 
+            let settings = app.state::<SettingsStore>().get();
+            if let Err(e) = commands::ai::set_extra_headers(&settings.extra_headers) {
+                eprintln!("Warning: Could not apply extra headers: {}", e);
+            }
+            let recorder = app.state::<AudioRecorder>();
+            recorder.set_idle_timeout(settings.mic_idle_timeout_secs);
+            recorder.set_input_gain(settings.input_gain);
+            recorder.set_max_recording(settings.max_recording_secs);
+            recorder.set_silence_auto_stop(settings.silence_auto_stop);
+
+            // Restore the persisted theme on the native window chrome
+            if let Err(e) = commands::window::apply_theme(&window, settings.theme) {
+                eprintln!("Warning: Could not apply theme: {}", e);
+            }
+            if let Err(e) = commands::window::apply_vibrancy(&window, settings.vibrancy) {
+                eprintln!("Warning: Could not apply vibrancy: {}", e);
+            }
+
+            // Last position and size first; a monitor picked with `move_to_monitor` wins
+            if let Err(e) = commands::window::restore_geometry(&window) {
+                eprintln!("Warning: Could not restore window position: {}", e);
+            }
+            if let Some(monitor) = &settings.preferred_monitor {
+                if let Err(e) = commands::window::restore_monitor(&window, monitor) {
+                    eprintln!("Warning: Could not restore monitor: {}", e);
+                }
+            }
+
+            // Apply startup behavior; the hotkey below is registered either way
+            if let Err(e) = commands::system::apply_launch_at_login(app.handle(), settings.launch_at_login) {
+                eprintln!("Warning: Could not update login item: {}", e);
+            }
             // Platform-specific window setup for macOS overlay
             #[cfg(target_os = "macos")]
             {
@@ -42,41 +272,82 @@ fn main() {
                 }
             }
 
+            // Hidden until now (`visible: false`), so it first appears where it was left
+            if !settings.start_hidden {
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+
             // Register global hotkey to show/hide window
-            let hotkey_options = vec![
-                "CommandOrControl+Shift+Space",
-                "CommandOrControl+Option+G",
-            ];
-
-            let mut registered = false;
-            for hotkey_str in hotkey_options {
-                if let Ok(shortcut) = hotkey_str.parse::<Shortcut>() {
-                    if app.global_shortcut().register(shortcut).is_ok() {
-                        app.global_shortcut().on_shortcut(shortcut, {
-                            let window = window.clone();
-                            move |_app, _shortcut, _event| {
-                                if window.is_visible().unwrap_or(false) {
-                                    let _ = window.hide();
-                                } else {
-                                    let _ = window.show();
-                                    let _ = window.set_focus();
+            commands::hotkeys::register_toggle_hotkey(app.handle());
+            commands::hotkeys::spawn_quiet_hours(app.handle().clone());
+
+            // Shortcuts for flipping between saved conversations
+            let conversation_hotkeys = &settings.conversation_hotkeys;
+            for (hotkey, offset) in [
+                (&conversation_hotkeys.next, 1),
+                (&conversation_hotkeys.previous, -1),
+            ] {
+                let Some(hotkey_str) = hotkey else {
+                    continue;
+                };
+
+                let registered = hotkey_str.parse::<Shortcut>().is_ok_and(|shortcut| {
+                    app.global_shortcut()
+                        .on_shortcut(shortcut, move |app, _shortcut, event| {
+                            if event.state == ShortcutState::Pressed {
+                                if let Err(e) = commands::history::cycle_conversation(app, offset) {
+                                    eprintln!("{}", e);
                                 }
                             }
-                        }).ok();
+                        })
+                        .is_ok()
+                });
 
-                        println!("✓ Registered global hotkey: {}", hotkey_str);
-                        registered = true;
-                        break;
-                    }
+                if registered {
+                    println!("✓ Registered conversation hotkey: {}", hotkey_str);
+                } else {
+                    eprintln!("Warning: Could not register conversation hotkey {}", hotkey_str);
                 }
             }
 
-            if !registered {
-                eprintln!("Warning: Could not register any global hotkey");
-            }
-
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("Error running Tauri application");
+        .build(tauri::generate_context!())
+        .expect("Error building Tauri application")
+        .run(|app, event| {
+            if let RunEvent::Exit = event {
+                shutdown(app);
+            }
+        });
+}
+
+/// Release resources before the process exits
+fn shutdown(app: &tauri::AppHandle) {
+    // Keep a recording that was never processed, then stop capture so the microphone
+    // is released and the OS indicator clears
+    let recorder = app.state::<AudioRecorder>();
+    recorder.set_recording(false);
+    match commands::audio::save_unprocessed(app, &recorder) {
+        Ok(Some(path)) => println!("Saved unprocessed recording to {}", path.display()),
+        Ok(None) => {}
+        Err(e) => eprintln!("Warning: Could not save the unprocessed recording: {}", e),
+    }
+    recorder.shutdown();
+
+    // Abort requests and transcriptions so none is left half-finished
+    app.state::<InFlightRequests>().cancel_all();
+
+    // Quitting while the window is shown skips the save done on hide
+    if let Some(window) = app.get_webview_window("main") {
+        if window.is_visible().unwrap_or(false) {
+            commands::window::save_geometry(&window);
+        }
+    }
+
+    // A clean exit leaves nothing to recover
+    app.state::<RecoveryStore>().clear();
+
+    let _ = std::io::stdout().flush();
+    let _ = std::io::stderr().flush();
 }
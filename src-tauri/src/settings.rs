@@ -0,0 +1,289 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::commands::ai::{ReasoningEffort, DEFAULT_MAX_TOKENS};
+use crate::commands::audio::{AutoSendSettings, DeviceAudioSettings, SilenceAutoStop};
+use crate::commands::code::AutoCopyCode;
+use crate::commands::duplicates::DuplicateCheck;
+use crate::commands::history::{ArchiveSettings, ConversationHotkeys};
+use crate::commands::hotkeys::QuietHours;
+use crate::commands::image::ImagePrepSettings;
+use crate::commands::imagegen::ImageGenerationSettings;
+use crate::commands::logs::RequestLogSettings;
+use crate::commands::memory::MemorySettings;
+use crate::commands::retry::RetryPolicy;
+use crate::commands::ocr::OcrSettings;
+use crate::commands::privacy::PrivacyGuard;
+use crate::commands::prompt::SystemPromptSettings;
+use crate::commands::stream::StreamBatching;
+use crate::commands::templates::PromptTemplate;
+use crate::commands::window::{SummonPosition, Theme, VibrancySettings, WindowSize};
+
+/// User settings persisted as JSON in the app config directory
+/// Unlike `AppConfig` these can be changed at runtime from the UI
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub templates: Vec<PromptTemplate>,
+    /// Distance from the work-area edge when snapping, in logical pixels
+    pub snap_margin: f64,
+    /// Size applied when snapping so the overlay is consistently compact
+    pub snap_size: Option<WindowSize>,
+    pub theme: Theme,
+    /// Blurred background behind the content (macOS only)
+    pub vibrancy: VibrancySettings,
+    pub stream_batching: StreamBatching,
+    pub memory: MemorySettings,
+    /// Register the app to start when the user logs in
+    pub launch_at_login: bool,
+    /// Keep the window hidden on launch; the global hotkey still summons it
+    pub start_hidden: bool,
+    /// Sizes rotated through by `cycle_window_size`
+    pub size_presets: Vec<WindowSize>,
+    /// Index into `size_presets` of the last-used preset
+    pub size_preset: usize,
+    /// Thresholds for sending recognized screen text instead of the screenshot
+    pub ocr: OcrSettings,
+    /// Re-encode attached images to drop EXIF, GPS and device metadata before sending
+    pub strip_image_metadata: bool,
+    /// Text added before / after the latest user message in every chat request
+    /// Applied to the request only, never to the saved conversation
+    pub message_prefix: Option<String>,
+    pub message_suffix: Option<String>,
+    /// Send the frontmost app name and window title along with screenshots
+    /// Off by default since titles can contain private information
+    pub include_app_context: bool,
+    /// Append each request's tokens and estimated cost to `usage.csv`
+    pub log_usage: bool,
+    /// Global shortcuts for `next_conversation` / `previous_conversation`
+    pub conversation_hotkeys: ConversationHotkeys,
+    /// Name saved conversations with a short model-written title after the first reply
+    /// Off by default since it makes one extra request per conversation
+    pub auto_title: bool,
+    /// Archive long conversations on save and continue in a fresh one
+    pub auto_archive: ArchiveSettings,
+    /// Release the microphone after this many seconds without recording
+    /// `None` keeps it open until exit
+    pub mic_idle_timeout_secs: Option<u64>,
+    /// Gain multiplier applied to captured samples
+    /// Default for devices without a remembered gain in `device_audio`
+    pub input_gain: f32,
+    /// Capture rate requested from the input device, e.g. 96000 for archival
+    /// Used when no format was requested or remembered for the device; devices that
+    /// can't honor it record at their default. Transcription always resamples to 16 kHz
+    pub preferred_sample_rate: Option<u32>,
+    /// Sensitivity (0-1) of the gate that mutes key clicks while dictating; `None` is off
+    pub typing_noise_gate: Option<f32>,
+    /// Stop a recording at this length so a forgotten one can't fill memory
+    /// `None` records until stopped
+    pub max_recording_secs: Option<u64>,
+    /// Stop recording once the input stays quiet after speech, for hands-free dictation
+    pub silence_auto_stop: Option<SilenceAutoStop>,
+    /// Save recordings whose transcription keeps failing, for `transcribe_saved`
+    pub defer_failed_transcriptions: bool,
+    /// Transcribe once more, louder, when a quiet recording comes back empty
+    pub retry_empty_transcriptions: bool,
+    /// Try the device's other supported formats when its chosen one won't open
+    pub input_config_fallback: bool,
+    /// Audio preferences remembered per input device, keyed by device name
+    pub device_audio: BTreeMap<String, DeviceAudioSettings>,
+    /// Emit `response-slow` when no reply (or first token) arrives within this many ms
+    pub slow_response_ms: Option<u64>,
+    /// Show/hide hotkey set by `set_global_hotkey`, in place of GHOSTGPT_HOTKEY
+    pub toggle_hotkey: Option<String>,
+    /// Repeat presses of the show/hide hotkey within this many ms are ignored
+    pub hotkey_cooldown_ms: u64,
+    /// Whether the show/hide hotkey is registered (e.g. off while presenting)
+    pub hotkey_enabled: bool,
+    /// Daily window during which the show/hide hotkey is off
+    pub quiet_hours: Option<QuietHours>,
+    /// Lock and drop the API keys from memory after this many idle minutes
+    /// `None` never locks
+    pub auto_lock_minutes: Option<u64>,
+    /// Send transcripts from `process_audio` without a manual send press
+    pub auto_send: AutoSendSettings,
+    /// Copy this code block of each completed reply to the clipboard
+    pub auto_copy_code: Option<AutoCopyCode>,
+    /// Add detected languages to unlabeled code fences in replies, for highlighting
+    pub tag_code_languages: bool,
+    /// `get_context_usage` emits `context-warning` at this % of the context window
+    pub context_warning_percent: Option<u8>,
+    /// Extra HTTP headers sent with every OpenAI-compatible API request (e.g. for
+    /// observability proxies); other transcription backends never get them
+    pub extra_headers: BTreeMap<String, String>,
+    /// Name of the monitor the window opens on, set by `move_to_monitor`
+    pub preferred_monitor: Option<String>,
+    /// Where the show/hide hotkey places the window
+    pub summon_position: SummonPosition,
+    /// Retries for transient transcription failures (429, 5xx, network)
+    pub transcription_retry: RetryPolicy,
+    /// ISO-639-1 code of the language usually spoken (e.g. `de`), sent to the transcriber
+    /// unless a request names its own; `None` lets the provider detect it
+    pub transcription_language: Option<String>,
+    /// Retries for transient chat request failures (429, 5xx, network)
+    pub chat_retry: RetryPolicy,
+    /// `send_message` refuses to resend a recent question unless confirmed
+    pub duplicate_check: DuplicateCheck,
+    /// Hold back screenshots whose text looks like passwords, card numbers or keys
+    pub privacy_guard: PrivacyGuard,
+    /// One line per API request in the app log directory, rotated by size and day
+    pub request_log: RequestLogSettings,
+    /// Default model and size for `generate_image`
+    pub image_generation: ImageGenerationSettings,
+    /// Size limit and crop shape used by `prepare_image`
+    pub image_prep: ImagePrepSettings,
+    /// Retry without streaming (and remember that) when a backend rejects `stream: true`
+    pub stream_fallback: bool,
+    /// Reply length limit sent as `max_tokens`
+    pub max_tokens: u32,
+    /// Size `max_tokens` to the context left after the input instead of using `max_tokens`
+    pub auto_max_tokens: bool,
+    /// Persona, response language and the order system-prompt fragments are sent in
+    pub system_prompt: SystemPromptSettings,
+    /// Sent to reasoning models only; other models ignore it
+    pub reasoning_effort: Option<ReasoningEffort>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            templates: Vec::new(),
+            snap_margin: 16.0,
+            snap_size: Some(WindowSize {
+                width: 400.0,
+                height: 500.0,
+            }),
+            theme: Theme::System,
+            vibrancy: VibrancySettings::default(),
+            stream_batching: StreamBatching::default(),
+            memory: MemorySettings::default(),
+            launch_at_login: false,
+            start_hidden: false,
+            size_presets: vec![
+                WindowSize {
+                    width: 400.0,
+                    height: 500.0,
+                },
+                WindowSize {
+                    width: 720.0,
+                    height: 860.0,
+                },
+            ],
+            size_preset: 0,
+            ocr: OcrSettings::default(),
+            strip_image_metadata: true,
+            message_prefix: None,
+            message_suffix: None,
+            include_app_context: false,
+            log_usage: false,
+            conversation_hotkeys: ConversationHotkeys::default(),
+            auto_title: false,
+            auto_archive: ArchiveSettings::default(),
+            mic_idle_timeout_secs: Some(120),
+            input_gain: 1.0,
+            typing_noise_gate: None,
+            max_recording_secs: Some(300),
+            silence_auto_stop: None,
+            defer_failed_transcriptions: true,
+            retry_empty_transcriptions: false,
+            input_config_fallback: true,
+            preferred_sample_rate: None,
+            device_audio: BTreeMap::new(),
+            slow_response_ms: Some(10_000),
+            toggle_hotkey: None,
+            hotkey_cooldown_ms: 150,
+            hotkey_enabled: true,
+            quiet_hours: None,
+            auto_lock_minutes: None,
+            auto_send: AutoSendSettings::default(),
+            auto_copy_code: None,
+            tag_code_languages: false,
+            context_warning_percent: Some(80),
+            extra_headers: BTreeMap::new(),
+            preferred_monitor: None,
+            summon_position: SummonPosition::RememberLast,
+            transcription_retry: RetryPolicy::default(),
+            transcription_language: None,
+            chat_retry: RetryPolicy::default(),
+            duplicate_check: DuplicateCheck::default(),
+            privacy_guard: PrivacyGuard::default(),
+            request_log: RequestLogSettings::default(),
+            image_generation: ImageGenerationSettings::default(),
+            image_prep: ImagePrepSettings::default(),
+            stream_fallback: true,
+            max_tokens: DEFAULT_MAX_TOKENS,
+            auto_max_tokens: false,
+            system_prompt: SystemPromptSettings::default(),
+            reasoning_effort: None,
+        }
+    }
+}
+
+/// Managed state holding the current settings and where they are stored
+pub struct SettingsStore {
+    path: PathBuf,
+    settings: Mutex<Settings>,
+}
+
+impl SettingsStore {
+    /// Load settings from `path`
+    /// A missing or corrupt file falls back to defaults so the app still starts
+    pub fn load(path: PathBuf) -> Self {
+        let settings = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                eprintln!("Warning: Ignoring invalid settings file {:?}: {}", path, e);
+                Settings::default()
+            }),
+            Err(_) => Settings::default(),
+        };
+
+        Self {
+            path,
+            settings: Mutex::new(settings),
+        }
+    }
+
+    /// Re-read the settings file, keeping the current settings if it is invalid
+    /// A missing file resets to defaults
+    pub fn reload(&self) -> Result<()> {
+        let settings = match std::fs::read_to_string(&self.path) {
+            Ok(json) => serde_json::from_str(&json)
+                .with_context(|| format!("Invalid settings file {:?}", self.path))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Settings::default(),
+            Err(e) => return Err(e).context("Failed to read settings file"),
+        };
+
+        *self.settings.lock().unwrap() = settings;
+        Ok(())
+    }
+
+    /// Snapshot of the current settings
+    pub fn get(&self) -> Settings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    /// Apply a change to the settings and write them to disk
+    /// The change is made on a copy that replaces the settings only once it is saved,
+    /// so a failed write leaves them untouched
+    pub fn update<R>(&self, f: impl FnOnce(&mut Settings) -> R) -> Result<R> {
+        let mut settings = self.settings.lock().unwrap();
+        let mut updated = settings.clone();
+        let result = f(&mut updated);
+        Self::save(&self.path, &updated)?;
+        *settings = updated;
+        Ok(result)
+    }
+
+    fn save(path: &PathBuf, settings: &Settings) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).context("Failed to create settings directory")?;
+        }
+
+        let json = serde_json::to_string_pretty(settings)?;
+        std::fs::write(path, json).context("Failed to write settings file")?;
+        Ok(())
+    }
+}
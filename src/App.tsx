@@ -1,5 +1,6 @@
 import { useState, useRef, useEffect, useCallback } from 'react';
 import MessageList from './components/MessageList';
+import AboutPanel from './components/AboutPanel';
 
 interface Message {
   role: 'user' | 'assistant';
@@ -17,6 +18,7 @@ function App() {
   const [isLoading, setIsLoading] = useState(false);
   const [isRecording, setIsRecording] = useState(false);
   const [error, setError] = useState<string | null>(null);
+  const [showAbout, setShowAbout] = useState(false);
   const inputRef = useRef<HTMLTextAreaElement>(null);
   const abortControllerRef = useRef<AbortController | null>(null);
 
@@ -344,12 +346,21 @@ function App() {
     <div className="app-container">
       <div className="window-header" data-tauri-drag-region>
         <h1>Phantom</h1>
-        <div className="status">
-          {isLoading ? 'Thinking...' : isRecording ? 'Recording...' : 'Ready'}
+        <div className="header-actions">
+          <div className="status">
+            {isLoading ? 'Thinking...' : isRecording ? 'Recording...' : 'Ready'}
+          </div>
+          <button onClick={() => setShowAbout(!showAbout)} className="btn-about">
+            About
+          </button>
         </div>
       </div>
 
-      <MessageList messages={messages} />
+      {showAbout ? (
+        <AboutPanel onClose={() => setShowAbout(false)} />
+      ) : (
+        <MessageList messages={messages} />
+      )}
 
       {error && (
         <div className="error-banner">
@@ -0,0 +1,82 @@
+import { useEffect, useState } from 'react';
+import { invoke } from '@tauri-apps/api/core';
+
+interface VersionInfo {
+  app_version: string;
+  os: string;
+  arch: string;
+  tauri_version: string;
+  reqwest_version: string;
+  cpal_version: string;
+  model: string;
+  transcription_provider: string;
+}
+
+interface AboutPanelProps {
+  onClose: () => void;
+}
+
+const FIELDS: [keyof VersionInfo, string][] = [
+  ['app_version', 'Version'],
+  ['os', 'OS'],
+  ['arch', 'Architecture'],
+  ['model', 'Model'],
+  ['transcription_provider', 'Transcription'],
+  ['tauri_version', 'Tauri'],
+  ['reqwest_version', 'reqwest'],
+  ['cpal_version', 'cpal'],
+];
+
+function AboutPanel({ onClose }: AboutPanelProps) {
+  const [info, setInfo] = useState<VersionInfo | null>(null);
+  const [error, setError] = useState<string | null>(null);
+  const [copied, setCopied] = useState(false);
+
+  useEffect(() => {
+    invoke<VersionInfo>('get_version_info')
+      .then(setInfo)
+      .catch((err) => setError(String(err)));
+  }, []);
+
+  const copyInfo = async () => {
+    if (!info) return;
+    const text = FIELDS.map(([key, label]) => `${label}: ${info[key]}`).join('\n');
+    try {
+      await navigator.clipboard.writeText(text);
+      setCopied(true);
+      window.setTimeout(() => setCopied(false), 1500);
+    } catch (err: any) {
+      setError('Failed to copy: ' + err.message);
+    }
+  };
+
+  return (
+    <div className="about-panel">
+      <div className="about-header">
+        <h2>About Phantom</h2>
+        <button onClick={onClose} className="btn-secondary">
+          Close
+        </button>
+      </div>
+
+      {error && <div className="about-error">{error}</div>}
+
+      {info && (
+        <dl className="about-details">
+          {FIELDS.map(([key, label]) => (
+            <div key={key} className="about-row">
+              <dt>{label}</dt>
+              <dd>{info[key]}</dd>
+            </div>
+          ))}
+        </dl>
+      )}
+
+      <button onClick={copyInfo} disabled={!info} className="btn-primary">
+        {copied ? 'Copied' : 'Copy for bug report'}
+      </button>
+    </div>
+  );
+}
+
+export default AboutPanel;